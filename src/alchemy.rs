@@ -0,0 +1,201 @@
+//! Alchemy `GasPriceEstimating` implementation.
+//!
+//! The priority fee comes from Alchemy's `eth_maxPriorityFeePerGas` and the base fee from the
+//! pending block, both through the JSON-RPC api of the chain. Documentation at
+//! https://docs.alchemy.com/reference/eth-maxpriorityfeepergas .
+
+use super::{
+    chain, error::Result, json_rpc, ChainConfig, EstimatedGasPrice, GasEstimationError,
+    GasPrice1559, GasPriceEstimating, GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+// Subdomain of the api endpoint of every supported chain.
+pub fn network_from_chain_id(chain_id: u64) -> Option<&'static str> {
+    match chain_id {
+        1 => Some("eth-mainnet"),
+        10 => Some("opt-mainnet"),
+        56 => Some("bnb-mainnet"),
+        100 => Some("gnosis-mainnet"),
+        137 => Some("polygon-mainnet"),
+        250 => Some("fantom-mainnet"),
+        8453 => Some("base-mainnet"),
+        42161 => Some("arb-mainnet"),
+        43114 => Some("avax-mainnet"),
+        _ => None,
+    }
+}
+
+/// Parameters for the Alchemy estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // a coefficient to multiply the pending base fee with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            base_fee_multiplier: 2.0,
+        }
+    }
+}
+
+/// Requires a transport that supports `Transport::post_json`.
+pub struct AlchemyGasEstimator<T> {
+    transport: T,
+    url: String,
+    block_time: Duration,
+    params: Params,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Block {
+    base_fee_per_gas: Option<String>,
+}
+
+impl<T: Transport> AlchemyGasEstimator<T> {
+    /// The api key is part of the url.
+    pub fn new(chain_id: u64, transport: T, api_key: &str, params: Option<Params>) -> Result<Self> {
+        let network = network_from_chain_id(chain_id).ok_or_else(|| {
+            GasEstimationError::Unsupported(format!("unsupported chain id {}", chain_id))
+        })?;
+        Ok(Self {
+            transport,
+            url: format!("https://{}.g.alchemy.com/v2/{}", network, api_key),
+            block_time: ChainConfig::from_chain_id(chain_id)
+                .unwrap_or(chain::ETHEREUM)
+                .block_time,
+            params: params.unwrap_or_default(),
+        })
+    }
+
+    /// For chains without a known network or a proxy, the api key has to be part of the url.
+    pub fn with_url(self, url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..self
+        }
+    }
+
+    async fn base_fee(&self) -> Result<f64> {
+        let block: Block = json_rpc::call(
+            &self.transport,
+            &self.url,
+            "eth_getBlockByNumber",
+            json!(["pending", false]),
+        )
+        .await
+        .map_err(|err| err.context("failed to get pending block"))?;
+        let base_fee = block
+            .base_fee_per_gas
+            .ok_or_else(|| GasEstimationError::Decode(anyhow!("pending block has no base fee")))?;
+        json_rpc::quantity_to_f64(&base_fee)
+    }
+
+    async fn priority_fee(&self) -> Result<f64> {
+        let priority_fee: String = json_rpc::call(
+            &self.transport,
+            &self.url,
+            "eth_maxPriorityFeePerGas",
+            json!([]),
+        )
+        .await
+        .map_err(|err| err.context("failed to get max priority fee"))?;
+        json_rpc::quantity_to_f64(&priority_fee)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for AlchemyGasEstimator<T> {
+    fn source(&self) -> &'static str {
+        "alchemy"
+    }
+
+    fn block_time(&self) -> Duration {
+        self.block_time
+    }
+
+    // Alchemy suggests a single priority fee so the time limit is ignored.
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let (base_fee, priority_fee) = futures::try_join!(self.base_fee(), self.priority_fee())?;
+        estimate(base_fee, priority_fee, &self.params)
+    }
+}
+
+fn estimate(base_fee: f64, priority_fee: f64, params: &Params) -> Result<EstimatedGasPrice> {
+    let max_fee_per_gas = base_fee * params.base_fee_multiplier + priority_fee;
+    EstimatedGasPrice {
+        legacy: max_fee_per_gas,
+        eip1559: Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(base_fee),
+            max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+            max_priority_fee_per_gas: GasPriceWei(priority_fee),
+        }),
+    }
+    .validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+
+    #[test]
+    fn estimates_with_pending_base_fee() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({ "baseFeePerGas": "0x64" }))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"));
+        let estimator = AlchemyGasEstimator::new(1, node, "key", None).unwrap();
+        assert_eq!(estimator.url, "https://eth-mainnet.g.alchemy.com/v2/key");
+        let estimate = estimator.estimate().wait().unwrap();
+        assert_eq!(estimate.base_fee(), 100.0);
+        assert_eq!(estimate.tip(), 10.0);
+        assert_eq!(estimate.cap(), 210.0);
+    }
+
+    #[test]
+    fn unknown_chain_is_unsupported() {
+        assert!(matches!(
+            AlchemyGasEstimator::new(2, FakeNode::default(), "key", None),
+            Err(GasEstimationError::Unsupported(_))
+        ));
+        let estimator = AlchemyGasEstimator::new(137, FakeNode::default(), "key", None).unwrap();
+        assert_eq!(estimator.block_time(), chain::POLYGON.block_time);
+    }
+
+    #[test]
+    fn fails_without_base_fee() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({}))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"));
+        let estimator = AlchemyGasEstimator::new(1, node, "key", None).unwrap();
+        assert!(matches!(
+            estimator.estimate().wait(),
+            Err(GasEstimationError::Decode(_))
+        ));
+    }
+
+    // ALCHEMY_API_KEY=... cargo test alchemy -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = AlchemyGasEstimator::new(
+            1,
+            TestTransport::default(),
+            &std::env::var("ALCHEMY_API_KEY").unwrap(),
+            None,
+        )
+        .unwrap();
+        println!("{:?}", estimator.estimate().await);
+    }
+}
@@ -0,0 +1,375 @@
+//! Detection of gas price sources that disagree. When one oracle returns prices far away from the
+//! others it is usually broken, for example because it stopped updating or changed its units.
+
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasPriceEstimating,
+    TransactionCost, TxSpec,
+};
+use anyhow::anyhow;
+use futures::future::join_all;
+use std::{future::Future, time::Duration};
+
+/// Two sources whose estimates differ by more than the configured factor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    pub sources: (&'static str, &'static str),
+    // effective gas prices of the two estimates, the first is the higher one
+    pub prices: (f64, f64),
+    // `prices.0 / prices.1`
+    pub ratio: f64,
+}
+
+type Callback = Box<dyn Fn(&[Divergence]) + Send + Sync>;
+
+// Queries all estimators concurrently and returns the median estimate by effective gas price like
+// `MedianGasPriceEstimating`. Every pair of successful estimates whose effective gas prices differ
+// by more than `max_ratio` is reported to the callback. `estimate_verbose` also reports the
+// largest ratio in the metadata. Hints and transactions are passed to all estimators.
+pub struct DivergenceDetectingEstimator {
+    estimators: Vec<Box<dyn GasPriceEstimating>>,
+    max_ratio: f64,
+    on_divergence: Option<Callback>,
+}
+
+impl DivergenceDetectingEstimator {
+    // `max_ratio` of 2.0 reports sources of which one is more than twice as expensive as the other.
+    pub fn new(estimators: Vec<Box<dyn GasPriceEstimating>>, max_ratio: f64) -> Self {
+        Self {
+            estimators,
+            max_ratio,
+            on_divergence: None,
+        }
+    }
+
+    /// Called with all diverging pairs of sources whenever there is at least one.
+    pub fn with_callback(
+        self,
+        on_divergence: impl Fn(&[Divergence]) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            on_divergence: Some(Box::new(on_divergence)),
+            ..self
+        }
+    }
+
+    // Calls `operation` on all estimators, reports the divergences of the successful results and
+    // returns the median result with them. `metadata` is the source and the gas price of a result.
+    async fn combine<'a, T, F, R>(
+        &'a self,
+        operation: T,
+        metadata: impl Fn(&'a dyn GasPriceEstimating, &R) -> (&'static str, EstimatedGasPrice),
+    ) -> Result<(R, Vec<Divergence>)>
+    where
+        T: Fn(&'a dyn GasPriceEstimating) -> F,
+        F: Future<Output = Result<R>>,
+        R: Copy,
+    {
+        let results = join_all(
+            self.estimators
+                .iter()
+                .map(|estimator| operation(estimator.as_ref())),
+        )
+        .await;
+        let mut estimates = self
+            .estimators
+            .iter()
+            .zip(results)
+            .enumerate()
+            .filter_map(|(i, (estimator, result))| match result {
+                Ok(result) => {
+                    let (source, price) = metadata(estimator.as_ref(), &result);
+                    Some((source, price, result))
+                }
+                Err(err) => {
+                    tracing::warn!("gas estimator {} failed: {:?}", i, err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let divergences = self.detect(
+            &estimates
+                .iter()
+                .map(|(source, price, _)| (*source, *price))
+                .collect::<Vec<_>>(),
+        );
+        if !divergences.is_empty() {
+            tracing::warn!(?divergences, "gas price sources disagree");
+            if let Some(on_divergence) = &self.on_divergence {
+                on_divergence(&divergences);
+            }
+        }
+
+        estimates.sort_by(|(_, a, _), (_, b, _)| {
+            a.effective_gas_price()
+                .partial_cmp(&b.effective_gas_price())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let (_, _, median) = estimates
+            .get(estimates.len().saturating_sub(1) / 2)
+            .copied()
+            .ok_or_else(|| anyhow!("all gas estimators failed"))?;
+        Ok((median, divergences))
+    }
+
+    fn detect(&self, estimates: &[(&'static str, EstimatedGasPrice)]) -> Vec<Divergence> {
+        let mut divergences = Vec::new();
+        for (i, (a_source, a)) in estimates.iter().enumerate() {
+            for (b_source, b) in &estimates[i + 1..] {
+                let (a_price, b_price) = (a.effective_gas_price(), b.effective_gas_price());
+                let (sources, prices) = if a_price >= b_price {
+                    ((*a_source, *b_source), (a_price, b_price))
+                } else {
+                    ((*b_source, *a_source), (b_price, a_price))
+                };
+                let ratio = ratio(prices.0, prices.1);
+                if ratio > self.max_ratio {
+                    divergences.push(Divergence {
+                        sources,
+                        prices,
+                        ratio,
+                    });
+                }
+            }
+        }
+        divergences
+    }
+}
+
+// Sources that estimate zero diverge from all sources that don't.
+fn ratio(high: f64, low: f64) -> f64 {
+    if low > 0.0 {
+        high / low
+    } else if high > 0.0 {
+        f64::INFINITY
+    } else {
+        1.0
+    }
+}
+
+#[async_trait::async_trait]
+impl GasPriceEstimating for DivergenceDetectingEstimator {
+    fn source(&self) -> &'static str {
+        "divergence"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        Ok(self.estimate_verbose(gas_limit, time_limit).await?.price)
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        let (price, _) = self
+            .combine(
+                |estimator| estimator.estimate_with_hints(gas_limit, time_limit, hints),
+                |estimator, price| (estimator.source(), *price),
+            )
+            .await?;
+        Ok(price)
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let (median, divergences) = self
+            .combine(
+                |estimator| estimator.estimate_verbose(gas_limit, time_limit),
+                |_, estimate| (estimate.source, estimate.price),
+            )
+            .await?;
+        Ok(EstimateWithMetadata {
+            divergence: divergences
+                .iter()
+                .map(|divergence| divergence.ratio)
+                .reduce(f64::max),
+            ..median
+        })
+    }
+
+    // Ordered by gas price, the l1 fee is the one of the picked estimator.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let (cost, _) = self
+            .combine(
+                |estimator| estimator.estimate_for_tx(tx),
+                |estimator, cost| (estimator.source(), cost.gas_price),
+            )
+            .await?;
+        Ok(cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::GasEstimationError;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Named(&'static str, FixedGasPriceEstimator);
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for Named {
+        fn source(&self) -> &'static str {
+            self.0
+        }
+
+        async fn estimate_with_limits(
+            &self,
+            gas_limit: f64,
+            time_limit: Duration,
+        ) -> Result<EstimatedGasPrice> {
+            self.1.estimate_with_limits(gas_limit, time_limit).await
+        }
+    }
+
+    fn estimator(source: &'static str, gas_price: f64) -> Box<dyn GasPriceEstimating> {
+        Box::new(Named(source, FixedGasPriceEstimator::legacy(gas_price)))
+    }
+
+    #[test]
+    fn reports_diverging_sources() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let estimator = DivergenceDetectingEstimator::new(
+            vec![
+                estimator("a", 10.0),
+                estimator("broken", 100.0),
+                estimator("b", 12.0),
+            ],
+            2.0,
+        )
+        .with_callback(move |divergences| {
+            reported_clone
+                .lock()
+                .unwrap()
+                .extend_from_slice(divergences)
+        });
+        let estimate = estimator
+            .estimate_verbose(21000.0, Duration::from_secs(30))
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.price.legacy, 12.0);
+        assert_eq!(estimate.source, "b");
+        assert_eq!(estimate.divergence, Some(10.0));
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                Divergence {
+                    sources: ("broken", "a"),
+                    prices: (100.0, 10.0),
+                    ratio: 10.0,
+                },
+                Divergence {
+                    sources: ("broken", "b"),
+                    prices: (100.0, 12.0),
+                    ratio: 100.0 / 12.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn agreeing_sources_are_not_reported() {
+        let estimator = DivergenceDetectingEstimator::new(
+            vec![estimator("a", 10.0), estimator("b", 15.0)],
+            2.0,
+        )
+        .with_callback(|_| panic!("sources agree"));
+        let estimate = estimator
+            .estimate_verbose(21000.0, Duration::from_secs(30))
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.price.legacy, 10.0);
+        assert_eq!(estimate.divergence, None);
+        assert_eq!(ratio(1.0, 0.0), f64::INFINITY);
+        assert_eq!(ratio(0.0, 0.0), 1.0);
+    }
+
+    // Like an L2 source that charges an l1 fee and a mempool source that skips private
+    // transactions.
+    struct Rollup;
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for Rollup {
+        fn source(&self) -> &'static str {
+            "rollup"
+        }
+
+        async fn estimate_with_limits(
+            &self,
+            _gas_limit: f64,
+            _time_limit: Duration,
+        ) -> Result<EstimatedGasPrice> {
+            Ok(EstimatedGasPrice {
+                legacy: 11.0,
+                eip1559: None,
+            })
+        }
+
+        async fn estimate_with_hints(
+            &self,
+            gas_limit: f64,
+            time_limit: Duration,
+            hints: &EstimationHints,
+        ) -> Result<EstimatedGasPrice> {
+            if hints.is_private() {
+                return Err(GasEstimationError::Unsupported("private".into()));
+            }
+            self.estimate_with_limits(gas_limit, time_limit).await
+        }
+
+        async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+            Ok(TransactionCost {
+                gas_price: self
+                    .estimate_with_limits(tx.gas_limit, tx.time_limit)
+                    .await?,
+                gas_limit: tx.gas_limit,
+                l1_fee: 1e6,
+            })
+        }
+    }
+
+    #[test]
+    fn passes_hints_and_transactions() {
+        let estimator = DivergenceDetectingEstimator::new(
+            vec![estimator("a", 10.0), Box::new(Rollup), estimator("b", 12.0)],
+            2.0,
+        );
+        let estimate = |hints| {
+            estimator
+                .estimate_with_hints(21000.0, Duration::from_secs(30), &hints)
+                .wait()
+                .unwrap()
+                .legacy
+        };
+        assert_eq!(estimate(EstimationHints::default()), 11.0);
+        // the median of the two remaining estimates is the cheaper one
+        assert_eq!(estimate(EstimationHints::private()), 10.0);
+
+        let cost = estimator
+            .estimate_for_tx(&TxSpec::default())
+            .wait()
+            .unwrap();
+        assert_eq!(cost.gas_price.legacy, 11.0);
+        assert_eq!(cost.l1_fee, 1e6);
+    }
+
+    #[test]
+    fn fails_if_all_estimators_fail() {
+        let failing = FixedGasPriceEstimator::legacy(1.0);
+        failing.push_result(Err(anyhow!("down").into()));
+        let estimator = DivergenceDetectingEstimator::new(vec![Box::new(Named("a", failing))], 2.0);
+        assert!(estimator.estimate().wait().is_err());
+    }
+}
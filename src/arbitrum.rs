@@ -0,0 +1,251 @@
+//! Arbitrum gas estimation.
+//!
+//! Arbitrum transactions pay for L2 execution and for posting their calldata to L1. Both are
+//! charged in L2 gas at the L2 gas price: the node inflates the gas limit by the amount of L2 gas
+//! needed to cover the L1 calldata cost. `NodeInterface.gasEstimateComponents` returns the split.
+//! Documentation at https://docs.arbitrum.io/build-decentralized-apps/how-to-estimate-gas .
+
+use super::{
+    chain,
+    cost::{TransactionCost, TxSpec},
+    error::Result,
+    json_rpc::{self, encode_bytes, uint_word, word_to_f64},
+    op_stack::incompressible_calldata,
+    EstimatedGasPrice, GasPriceEstimating, Transport,
+};
+use serde_json::json;
+use std::time::Duration;
+
+/// Address of the NodeInterface virtual contract. It only exists for `eth_call` and
+/// `eth_estimateGas`.
+pub const NODE_INTERFACE: &str = "0x00000000000000000000000000000000000000c8";
+// gasEstimateComponents(address,bool,bytes)
+const GAS_ESTIMATE_COMPONENTS: [u8; 4] = [0xc9, 0x4e, 0x6e, 0xeb];
+
+/// Estimate for a specific Arbitrum transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ArbitrumGasEstimate {
+    // Price of one unit of L2 gas. Arbitrum does not pay out priority fees so this is the L2 base
+    // fee.
+    pub gas_price: EstimatedGasPrice,
+    // Total gas limit of the transaction including `l1_gas`.
+    pub gas_limit: f64,
+    // L2 gas charged for posting the calldata to L1.
+    pub l1_gas: f64,
+    // Estimated L1 base fee per gas the L1 component is based on.
+    pub l1_base_fee_estimate: f64,
+}
+
+impl ArbitrumGasEstimate {
+    // L2 gas used by the execution of the transaction.
+    pub fn l2_gas(&self) -> f64 {
+        self.gas_limit - self.l1_gas
+    }
+
+    // Part of the fee that pays for L1 calldata.
+    pub fn l1_fee(&self) -> f64 {
+        self.l1_gas * self.gas_price.effective_gas_price()
+    }
+
+    // Part of the fee that pays for L2 execution.
+    pub fn l2_fee(&self) -> f64 {
+        self.l2_gas() * self.gas_price.effective_gas_price()
+    }
+
+    pub fn total_fee(&self) -> f64 {
+        self.gas_limit * self.gas_price.effective_gas_price()
+    }
+}
+
+/// Estimates gas prices through an Arbitrum node's JSON-RPC api. Requires a transport that
+/// supports `Transport::post_json`.
+pub struct ArbitrumGasEstimator<T> {
+    transport: T,
+    node_url: String,
+}
+
+impl<T: Transport> ArbitrumGasEstimator<T> {
+    pub fn new(transport: T, node_url: String) -> Self {
+        Self {
+            transport,
+            node_url,
+        }
+    }
+
+    /// Estimate both fee components for sending `data` to `to`. For contract creations `to` is
+    /// ignored and `data` is the init code.
+    pub async fn estimate_components(
+        &self,
+        to: [u8; 20],
+        contract_creation: bool,
+        data: &[u8],
+    ) -> Result<ArbitrumGasEstimate> {
+        let result = json_rpc::eth_call(
+            &self.transport,
+            &self.node_url,
+            NODE_INTERFACE,
+            &gas_estimate_components_call(to, contract_creation, data),
+        )
+        .await
+        .map_err(|err| err.context("failed to call gasEstimateComponents"))?;
+        decode_gas_estimate_components(&result)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for ArbitrumGasEstimator<T> {
+    fn source(&self) -> &'static str {
+        "arbitrum"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::ARBITRUM.block_time
+    }
+
+    // Arbitrum has no fee market for inclusion time so the time limit is ignored. `gas_limit` is
+    // only the L2 part, use `estimate_components` to include the L1 calldata cost.
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let gas_price: String =
+            json_rpc::call(&self.transport, &self.node_url, "eth_gasPrice", json!([]))
+                .await
+                .map_err(|err| err.context("failed to get arbitrum gas price"))?;
+        Ok(EstimatedGasPrice {
+            legacy: json_rpc::quantity_to_f64(&gas_price)?,
+            eip1559: None,
+        })
+    }
+
+    // The L1 gas is estimated for a call of the zero address with calldata of the same size
+    // because only the size of the transaction data is known. It is added to the gas limit since
+    // Arbitrum charges it in L2 gas.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let components = self
+            .estimate_components([0; 20], false, &incompressible_calldata(tx.data_len()))
+            .await?;
+        Ok(TransactionCost {
+            gas_price: components.gas_price,
+            gas_limit: tx.gas_limit + components.l1_gas,
+            l1_fee: 0.0,
+        })
+    }
+}
+
+fn gas_estimate_components_call(to: [u8; 20], contract_creation: bool, data: &[u8]) -> Vec<u8> {
+    let mut call = GAS_ESTIMATE_COMPONENTS.to_vec();
+    let mut address = [0u8; 32];
+    address[12..].copy_from_slice(&to);
+    call.extend_from_slice(&address);
+    call.extend_from_slice(&uint_word(contract_creation as u64));
+    // offset of the dynamic bytes argument after the three head words
+    call.extend_from_slice(&uint_word(3 * 32));
+    call.extend_from_slice(&encode_bytes(data));
+    call
+}
+
+// Returns (uint64 gasEstimate, uint64 gasEstimateForL1, uint256 baseFee, uint256 l1BaseFeeEstimate).
+fn decode_gas_estimate_components(result: &[u8]) -> Result<ArbitrumGasEstimate> {
+    Ok(ArbitrumGasEstimate {
+        gas_price: EstimatedGasPrice {
+            legacy: word_to_f64(result, 2)?,
+            eip1559: None,
+        },
+        gas_limit: word_to_f64(result, 0)?,
+        l1_gas: word_to_f64(result, 1)?,
+        l1_base_fee_estimate: word_to_f64(result, 3)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::{tests::FakeNode, to_hex};
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+
+    fn components() -> Vec<u8> {
+        [
+            uint_word(600_000),
+            uint_word(500_000),
+            uint_word(100_000_000),
+            uint_word(30_000_000_000),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn encodes_call() {
+        let call = gas_estimate_components_call([0x11; 20], false, &[0xaa, 0xbb]);
+        assert_eq!(&call[..4], &GAS_ESTIMATE_COMPONENTS);
+        assert_eq!(&call[4..16], &[0u8; 12]);
+        assert_eq!(&call[16..36], &[0x11; 20]);
+        assert_eq!(&call[36..68], &uint_word(0));
+        assert_eq!(&call[68..100], &uint_word(96));
+        assert_eq!(&call[100..132], &uint_word(2));
+        assert_eq!(&call[132..134], &[0xaa, 0xbb]);
+        assert_eq!(call.len(), 4 + 5 * 32);
+    }
+
+    #[test]
+    fn splits_fee_components() {
+        let estimate = decode_gas_estimate_components(&components()).unwrap();
+        assert_eq!(estimate.gas_limit, 600_000.0);
+        assert_eq!(estimate.l1_gas, 500_000.0);
+        assert_eq!(estimate.l2_gas(), 100_000.0);
+        assert_eq!(estimate.gas_price.legacy, 1e8);
+        assert_eq!(estimate.l1_base_fee_estimate, 3e10);
+        assert_eq!(estimate.l1_fee(), 5e13);
+        assert_eq!(estimate.l2_fee(), 1e13);
+        assert_eq!(estimate.total_fee(), 6e13);
+    }
+
+    #[test]
+    fn short_result_fails() {
+        assert!(decode_gas_estimate_components(&components()[..96]).is_err());
+    }
+
+    #[test]
+    fn estimates_through_node() {
+        let node = FakeNode::default()
+            .with_result("eth_gasPrice", json!("0x5f5e100"))
+            .with_result("eth_call:0xc94e6eeb", json!(to_hex(&components())));
+        let estimator = ArbitrumGasEstimator::new(node, String::new());
+        assert_eq!(estimator.estimate().wait().unwrap().legacy, 1e8);
+        let estimate = estimator
+            .estimate_components([0; 20], false, &[])
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.l1_gas, 500_000.0);
+
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                gas_limit: 100_000.0,
+                calldata_len: 100,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.gas_limit, 600_000.0);
+        assert_eq!(cost.l1_fee, 0.0);
+        assert_eq!(cost.expected_cost(), 6e13);
+    }
+
+    // ARBITRUM_NODE_URL=... cargo test arbitrum -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = ArbitrumGasEstimator::new(
+            TestTransport::default(),
+            std::env::var("ARBITRUM_NODE_URL").unwrap(),
+        );
+        println!("{:?}", estimator.estimate().await);
+        println!(
+            "{:?}",
+            estimator
+                .estimate_components([0x11; 20], false, &[0; 100])
+                .await
+        );
+    }
+}
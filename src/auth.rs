@@ -0,0 +1,149 @@
+//! Credentials for the paid tiers of gas price APIs. Every HTTP estimator accepts them with
+//! `with_credentials` so the shape of the authorization is configuration rather than code.
+
+use super::error::Result;
+use anyhow::anyhow;
+use http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use std::fmt;
+
+/// How an API key is sent with requests.
+#[derive(Clone, PartialEq, Eq)]
+pub enum ApiCredentials {
+    /// `name: value` header, for example `x-api-key: <key>`.
+    Header { name: String, value: String },
+    /// `Authorization: Bearer <token>`.
+    Bearer(String),
+    /// `Authorization: Basic <base64 of username:password>`.
+    Basic { username: String, password: String },
+    /// `?name=value` query parameter, for example `apikey=<key>` for etherscan.
+    QueryParam { name: String, value: String },
+}
+
+// Keeps secrets out of logs.
+impl fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header { name, .. } => write!(f, "Header({}: <redacted>)", name),
+            Self::Bearer(_) => write!(f, "Bearer(<redacted>)"),
+            Self::Basic { username, .. } => write!(f, "Basic({}:<redacted>)", username),
+            Self::QueryParam { name, .. } => write!(f, "QueryParam({}=<redacted>)", name),
+        }
+    }
+}
+
+impl ApiCredentials {
+    pub fn header(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::Header {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn query_param(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::QueryParam {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Adds the credentials to a request for `url`. Returns the url to request, which only
+    /// differs from `url` for query parameters.
+    pub fn apply(&self, url: &str, header: &mut HeaderMap) -> Result<String> {
+        match self {
+            Self::Header { name, value } => {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|err| anyhow!(err).context("invalid credentials header name"))?;
+                header.insert(name, header_value(value)?);
+            }
+            Self::Bearer(token) => {
+                header.insert(AUTHORIZATION, header_value(&format!("Bearer {}", token))?);
+            }
+            Self::Basic { username, password } => {
+                let encoded = base64::encode(format!("{}:{}", username, password));
+                header.insert(AUTHORIZATION, header_value(&format!("Basic {}", encoded))?);
+            }
+            Self::QueryParam { name, value } => {
+                let mut url = url::Url::parse(url)
+                    .map_err(|err| anyhow!(err).context("invalid url for credentials"))?;
+                url.query_pairs_mut().append_pair(name, value);
+                return Ok(url.into());
+            }
+        }
+        Ok(url.to_string())
+    }
+}
+
+// The url and header of a request with optional credentials.
+pub(crate) fn authorize(
+    credentials: Option<&ApiCredentials>,
+    url: &str,
+    mut header: HeaderMap,
+) -> Result<(String, HeaderMap)> {
+    let url = match credentials {
+        Some(credentials) => credentials.apply(url, &mut header)?,
+        None => url.to_string(),
+    };
+    Ok((url, header))
+}
+
+fn header_value(value: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|err| anyhow!(err).context("invalid credentials header value"))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(credentials: &ApiCredentials) -> (String, HeaderMap) {
+        authorize(
+            Some(credentials),
+            "https://example.com/api?a=1",
+            HeaderMap::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn applies_credentials() {
+        let (url, header) = apply(&ApiCredentials::header("x-api-key", "secret"));
+        assert_eq!(url, "https://example.com/api?a=1");
+        assert_eq!(header["x-api-key"], "secret");
+
+        let (_, header) = apply(&ApiCredentials::Bearer("token".to_string()));
+        assert_eq!(header[AUTHORIZATION], "Bearer token");
+
+        let (_, header) = apply(&ApiCredentials::Basic {
+            username: "user".to_string(),
+            password: "pass".to_string(),
+        });
+        assert_eq!(header[AUTHORIZATION], "Basic dXNlcjpwYXNz");
+
+        let (url, header) = apply(&ApiCredentials::query_param("apikey", "a b"));
+        assert_eq!(url, "https://example.com/api?a=1&apikey=a+b");
+        assert!(header.is_empty());
+    }
+
+    #[test]
+    fn invalid_credentials_fail() {
+        let credentials = ApiCredentials::header("invalid name", "secret");
+        assert!(credentials
+            .apply("https://example.com", &mut HeaderMap::new())
+            .is_err());
+        let credentials = ApiCredentials::query_param("apikey", "secret");
+        assert!(credentials
+            .apply("not a url", &mut HeaderMap::new())
+            .is_err());
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let credentials = ApiCredentials::Basic {
+            username: "user".to_string(),
+            password: "pass".to_string(),
+        };
+        assert_eq!(format!("{:?}", credentials), "Basic(user:<redacted>)");
+    }
+}
@@ -0,0 +1,162 @@
+//! Base fee prediction for transactions that may be included several blocks in the future.
+//!
+//! EIP-1559 changes the base fee by at most 12.5% per block depending on how full the parent block
+//! is relative to the gas target (half of the gas limit). Projecting the recent gas used ratios
+//! forward gives the expected base fee and assuming full blocks the highest possible one.
+
+use super::{
+    error::Result,
+    json_rpc::{self, FeeHistory},
+    GasEstimationError, Transport,
+};
+use anyhow::anyhow;
+
+// 1 / BASE_FEE_MAX_CHANGE_DENOMINATOR from EIP-1559
+const MAX_CHANGE: f64 = 0.125;
+
+/// Parameters for the base fee predictor.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // number of most recent blocks whose gas used ratios are averaged
+    pub fee_history_blocks: u64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            fee_history_blocks: 20,
+        }
+    }
+}
+
+/// Predicted base fee in wei.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaseFeePrediction {
+    // If future blocks are as full as the recent ones on average.
+    pub expected: f64,
+    // If all future blocks are full. A max_fee_per_gas of at least this guarantees that the
+    // transaction stays includable until the block.
+    pub worst_case: f64,
+}
+
+pub struct BaseFeePredictor<T> {
+    transport: T,
+    node_url: String,
+    params: Params,
+}
+
+impl<T: Transport> BaseFeePredictor<T> {
+    pub fn new(transport: T, node_url: String, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    /// Base fee of the block `blocks_ahead` blocks after the latest one. 1 is the pending block
+    /// whose base fee is already known.
+    pub async fn predict_base_fee(&self, blocks_ahead: u32) -> Result<BaseFeePrediction> {
+        let fee_history = json_rpc::fee_history(
+            &self.transport,
+            &self.node_url,
+            self.params.fee_history_blocks,
+            &[],
+        )
+        .await?;
+        predict_base_fee(&fee_history, blocks_ahead)
+    }
+}
+
+/// Base fee after `blocks` blocks that all have the given gas used ratio.
+pub fn project_base_fee(base_fee: f64, gas_used_ratio: f64, blocks: u32) -> f64 {
+    let change = (gas_used_ratio.clamp(0.0, 1.0) * 2.0 - 1.0) * MAX_CHANGE;
+    base_fee * (1.0 + change).powi(blocks as i32)
+}
+
+fn predict_base_fee(fee_history: &FeeHistory, blocks_ahead: u32) -> Result<BaseFeePrediction> {
+    let pending = fee_history
+        .base_fee_per_gas
+        .last()
+        .ok_or_else(|| GasEstimationError::Decode(anyhow!("fee history is missing base fee")))
+        .and_then(|base_fee| json_rpc::quantity_to_f64(base_fee))?;
+    let ratios = &fee_history.gas_used_ratio;
+    let average_ratio = match ratios.len() {
+        0 => 0.5,
+        len => ratios.iter().sum::<f64>() / len as f64,
+    };
+    let blocks = blocks_ahead.saturating_sub(1);
+    Ok(BaseFeePrediction {
+        expected: project_base_fee(pending, average_ratio, blocks),
+        worst_case: project_base_fee(pending, 1.0, blocks),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use serde_json::json;
+
+    #[test]
+    fn projects_with_eip1559_formula() {
+        assert_approx_eq!(project_base_fee(100.0, 1.0, 1), 112.5);
+        assert_approx_eq!(project_base_fee(100.0, 1.0, 2), 126.5625);
+        assert_approx_eq!(project_base_fee(100.0, 0.0, 1), 87.5);
+        assert_approx_eq!(project_base_fee(100.0, 0.5, 10), 100.0);
+        assert_approx_eq!(project_base_fee(100.0, 0.75, 1), 106.25);
+    }
+
+    #[test]
+    fn predicts_from_fee_history() {
+        let fee_history = FeeHistory {
+            base_fee_per_gas: vec!["0x5a".into(), "0x64".into()],
+            gas_used_ratio: vec![0.75, 0.75],
+            reward: None,
+        };
+        let pending = predict_base_fee(&fee_history, 1).unwrap();
+        assert_approx_eq!(pending.expected, 100.0);
+        assert_approx_eq!(pending.worst_case, 100.0);
+        let later = predict_base_fee(&fee_history, 3).unwrap();
+        assert_approx_eq!(later.expected, 100.0 * 1.0625 * 1.0625);
+        assert_approx_eq!(later.worst_case, 126.5625);
+
+        assert!(predict_base_fee(&Default::default(), 1).is_err());
+    }
+
+    #[test]
+    fn requests_fee_history() {
+        let node = FakeNode::default().with_result(
+            "eth_feeHistory",
+            json!({
+                "oldestBlock": "0x1",
+                "baseFeePerGas": ["0x5a", "0x64"],
+                "gasUsedRatio": [0.0],
+            }),
+        );
+        let predictor = BaseFeePredictor::new(node, String::new(), None);
+        let prediction = predictor.predict_base_fee(2).wait().unwrap();
+        assert_approx_eq!(prediction.expected, 87.5);
+        assert_approx_eq!(prediction.worst_case, 112.5);
+    }
+
+    // NODE_URL=... cargo test base_fee -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let predictor = BaseFeePredictor::new(
+            TestTransport::default(),
+            std::env::var("NODE_URL").unwrap(),
+            None,
+        );
+        for blocks_ahead in [1, 5, 10, 25] {
+            println!(
+                "{}: {:?}",
+                blocks_ahead,
+                predictor.predict_base_fee(blocks_ahead).await
+            );
+        }
+    }
+}
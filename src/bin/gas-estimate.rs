@@ -0,0 +1,302 @@
+//! Prints the current estimates of gas price providers, for debugging estimates that look wrong
+//! in production. Run `gas-estimate --help` for the flags.
+
+use gas_estimation::{
+    registry, BlockingGasPriceEstimator, EstimatedGasPrice, GasPriceWei, ReqwestTransport,
+    DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT,
+};
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: gas-estimate [OPTIONS]
+
+Options:
+  --chain <ID>          chain id [default: 1]
+  --provider <NAME>     provider out of GasNow, EthGasStation, Etherscan, GnosisSafe, Polygon,
+                        GnosisChain, Node and BlockNative, repeatable or comma separated
+                        [default: the default estimator of the chain]
+  --time-limit <SECS>   time until the transaction should be included [default: 30]
+  --gas-limit <GAS>     gas limit of the transaction [default: 21000]
+  --node-url <URL>      JSON-RPC node for the node based providers [env: NODE_URL]
+  --json                print the estimates as json instead of a table
+  -h, --help            print this help
+
+BLOCKNATIVE_API_KEY is used for the BlockNative provider.";
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    chain_id: u64,
+    // empty for the default estimator of the chain
+    providers: Vec<String>,
+    time_limit: Duration,
+    gas_limit: f64,
+    node_url: Option<String>,
+    blocknative_api_key: Option<String>,
+    json: bool,
+}
+
+// `None` if the help was requested.
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        chain_id: 1,
+        providers: Vec::new(),
+        time_limit: DEFAULT_TIME_LIMIT,
+        gas_limit: DEFAULT_GAS_LIMIT,
+        node_url: env("NODE_URL"),
+        blocknative_api_key: env("BLOCKNATIVE_API_KEY"),
+        json: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--chain" => {
+                let value = value()?;
+                parsed.chain_id = value
+                    .parse()
+                    .map_err(|_| format!("invalid chain id {}", value))?;
+            }
+            "--provider" => parsed.providers.extend(
+                value()?
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|provider| !provider.is_empty())
+                    .map(String::from),
+            ),
+            "--time-limit" => {
+                let value = value()?;
+                parsed.time_limit = value
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("invalid time limit {}", value))?;
+            }
+            "--gas-limit" => {
+                let value = value()?;
+                parsed.gas_limit = value
+                    .parse()
+                    .ok()
+                    .filter(|gas_limit: &f64| *gas_limit >= 0.0)
+                    .ok_or_else(|| format!("invalid gas limit {}", value))?;
+            }
+            "--node-url" => parsed.node_url = Some(value()?),
+            "--json" => parsed.json = true,
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(Some(parsed))
+}
+
+fn estimate(
+    args: &Args,
+    provider: Option<&str>,
+) -> gas_estimation::error::Result<EstimatedGasPrice> {
+    let transport = ReqwestTransport::new(None)?;
+    let estimator = BlockingGasPriceEstimator::build(async {
+        match provider {
+            Some(provider) => {
+                registry::estimator_by_name(
+                    provider,
+                    args.chain_id,
+                    transport,
+                    args.node_url.as_deref(),
+                    args.blocknative_api_key.as_deref(),
+                )
+                .await
+            }
+            None => registry::default_estimator(args.chain_id, transport, args.node_url.as_deref())
+                .ok_or_else(|| {
+                    gas_estimation::GasEstimationError::Unsupported(format!(
+                        "no default estimator for chain {}, pass --provider or --node-url",
+                        args.chain_id
+                    ))
+                }),
+        }
+    })?;
+    estimator.estimate_with_limits(args.gas_limit, args.time_limit)
+}
+
+type Row = (String, Result<EstimatedGasPrice, String>);
+
+fn gwei(wei: f64) -> String {
+    format!("{:.3}", GasPriceWei(wei).to_gwei().0)
+}
+
+fn table(rows: &[Row]) -> String {
+    let width = rows
+        .iter()
+        .map(|(provider, _)| provider.len())
+        .chain(["provider".len()])
+        .max()
+        .unwrap_or_default();
+    let mut table = format!(
+        "{:width$}  {:>12}  {:>12}  {:>12}  {:>12}\n",
+        "provider", "legacy", "max fee", "priority fee", "base fee"
+    );
+    for (provider, estimate) in rows {
+        let line = match estimate {
+            Ok(estimate) => {
+                let (max_fee, priority_fee, base_fee) = match &estimate.eip1559 {
+                    Some(eip1559) => (
+                        gwei(eip1559.max_fee_per_gas.0),
+                        gwei(eip1559.max_priority_fee_per_gas.0),
+                        gwei(eip1559.base_fee_per_gas.0),
+                    ),
+                    None => ("-".into(), "-".into(), "-".into()),
+                };
+                format!(
+                    "{:width$}  {:>12}  {:>12}  {:>12}  {:>12}",
+                    provider,
+                    gwei(estimate.legacy),
+                    max_fee,
+                    priority_fee,
+                    base_fee
+                )
+            }
+            Err(err) => format!("{:width$}  error: {}", provider, err),
+        };
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table.push_str("(gwei)");
+    table
+}
+
+fn json(rows: &[Row]) -> serde_json::Value {
+    rows.iter()
+        .map(|(provider, estimate)| match estimate {
+            Ok(estimate) => serde_json::json!({ "provider": provider, "estimate": estimate }),
+            Err(err) => serde_json::json!({ "provider": provider, "error": err }),
+        })
+        .collect()
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1), |name| {
+        std::env::var(name).ok().filter(|value| !value.is_empty())
+    }) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let providers = match args.providers.is_empty() {
+        true => vec![None],
+        false => args
+            .providers
+            .iter()
+            .map(|provider| Some(provider.as_str()))
+            .collect(),
+    };
+    let rows = providers
+        .into_iter()
+        .map(|provider| {
+            let estimate = estimate(&args, provider).map_err(|err| format!("{:#}", err));
+            (provider.unwrap_or("default").to_string(), estimate)
+        })
+        .collect::<Vec<Row>>();
+    match args.json {
+        true => println!("{:#}", json(&rows)),
+        false => println!("{}", table(&rows)),
+    }
+    if rows.iter().any(|(_, estimate)| estimate.is_err()) {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gas_estimation::GasPrice1559;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), |name| {
+            (name == "NODE_URL").then(|| "http://node".to_string())
+        })
+    }
+
+    #[test]
+    fn parses_flags() {
+        let args = parse(&[
+            "--chain",
+            "100",
+            "--provider",
+            "GnosisChain,node",
+            "--provider",
+            "Etherscan",
+            "--time-limit",
+            "60",
+            "--gas-limit",
+            "100000",
+            "--json",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            args,
+            Args {
+                chain_id: 100,
+                providers: vec!["GnosisChain".into(), "node".into(), "Etherscan".into()],
+                time_limit: Duration::from_secs(60),
+                gas_limit: 100_000.0,
+                node_url: Some("http://node".into()),
+                blocknative_api_key: None,
+                json: true,
+            }
+        );
+        let args = parse(&[]).unwrap().unwrap();
+        assert_eq!(args.chain_id, 1);
+        assert!(args.providers.is_empty());
+        assert_eq!(args.time_limit, DEFAULT_TIME_LIMIT);
+        assert_eq!(parse(&["--help"]).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_flags() {
+        assert!(parse(&["--chain"]).is_err());
+        assert!(parse(&["--chain", "mainnet"]).is_err());
+        assert!(parse(&["--time-limit", "-1"]).is_err());
+        assert!(parse(&["--gas-limit", "lots"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+
+    #[test]
+    fn formats_estimates() {
+        let rows = vec![
+            (
+                "gasnow".to_string(),
+                Ok(EstimatedGasPrice {
+                    legacy: 30e9,
+                    eip1559: Some(GasPrice1559 {
+                        base_fee_per_gas: GasPriceWei(20e9),
+                        max_fee_per_gas: GasPriceWei(42e9),
+                        max_priority_fee_per_gas: GasPriceWei(1.5e9),
+                    }),
+                }),
+            ),
+            ("etherscan".to_string(), Err("rate limited".to_string())),
+        ];
+        assert_eq!(
+            table(&rows),
+            "\
+provider         legacy       max fee  priority fee      base fee
+gasnow           30.000        42.000         1.500        20.000
+etherscan  error: rate limited
+(gwei)"
+        );
+        let json = json(&rows);
+        assert_eq!(json[0]["estimate"]["legacy"], 30e9);
+        assert_eq!(json[1]["error"], "rate limited");
+    }
+}
@@ -0,0 +1,94 @@
+//! Synchronous estimates for CLI tools and scripts that don't run an async runtime.
+
+use super::{error::Result, EstimatedGasPrice, GasPriceEstimating, GasPriceSchedule};
+use anyhow::Context;
+use std::{future::Future, time::Duration};
+
+/// Runs the estimates of the wrapped estimator on an internal tokio runtime with one worker
+/// thread, which also keeps background tasks of the estimator running between calls. The methods
+/// block the calling thread and panic when called from within an async runtime.
+pub struct BlockingGasPriceEstimator<T> {
+    inner: T,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl<T: GasPriceEstimating> BlockingGasPriceEstimator<T> {
+    pub fn new(inner: T) -> Result<Self> {
+        Ok(Self {
+            inner,
+            runtime: runtime()?,
+        })
+    }
+
+    /// Creates the estimator with an async constructor on the internal runtime, for estimators
+    /// like `BlockNative` that spawn their background task on creation.
+    pub fn build(constructor: impl Future<Output = Result<T>>) -> Result<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(constructor)?;
+        Ok(Self { inner, runtime })
+    }
+
+    pub fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.runtime.block_on(self.inner.estimate())
+    }
+
+    pub fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.runtime
+            .block_on(self.inner.estimate_with_limits(gas_limit, time_limit))
+    }
+
+    pub fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.runtime.block_on(self.inner.estimate_schedule())
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .context("failed to build tokio runtime")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::super::TimeLimitedEstimator;
+    use super::*;
+
+    #[test]
+    fn estimates_without_runtime() {
+        let estimator =
+            BlockingGasPriceEstimator::new(FixedGasPriceEstimator::legacy(100.0)).unwrap();
+        assert_eq!(estimator.estimate().unwrap().legacy, 100.0);
+        assert_eq!(
+            estimator
+                .estimate_with_limits(21000.0, Duration::from_secs(30))
+                .unwrap()
+                .legacy,
+            100.0
+        );
+        assert!(estimator.estimate_schedule().is_ok());
+        assert!(estimator.inner().calls() >= 3);
+    }
+
+    #[test]
+    fn runs_timers() {
+        let estimator = BlockingGasPriceEstimator::build(async {
+            Ok(TimeLimitedEstimator::new(
+                FixedGasPriceEstimator::legacy(100.0),
+                Duration::from_secs(1),
+            ))
+        })
+        .unwrap();
+        assert_eq!(estimator.estimate().unwrap().legacy, 100.0);
+    }
+}
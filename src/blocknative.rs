@@ -1,52 +1,77 @@
-use super::{linear_interpolation, EstimatedGasPrice, GasPrice1559, GasPriceEstimating, Transport};
-use anyhow::{anyhow, Context, Result};
-use serde::Deserialize;
-use std::{
-    convert::TryInto,
-    sync::{Arc, Mutex},
-    time::{Duration, Instant},
+use super::{
+    auth, error::Result, interpolation, time::Instant, trace, ApiCredentials, EstimateRange,
+    EstimateWithMetadata, EstimatedGasPrice, GasPrice1559, GasPriceEstimating, GasPriceSchedule,
+    GasPriceWei, Transport,
+};
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+use super::{
+    runtime::{self, Runtime},
+    task::BackgroundTask,
+    Readiness,
 };
-use tokio::task::{self, JoinHandle};
+#[cfg(feature = "tokio_")]
+use super::{time::Clock, ws_util, WebSocketConnection, WebSocketTransport, DEFAULT_TIME_LIMIT};
+use anyhow::anyhow;
+use serde::Deserialize;
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+use std::sync::{Arc, Mutex};
+use std::{convert::TryInto, time::Duration};
+#[cfg(feature = "tokio_")]
+use tokio::sync::watch;
 
 // Gas price estimation with https://www.blocknative.com/gas-estimator , api https://docs.blocknative.com/gas-platform#example-request .
+//
+// `BlocknativeGasStation` requests the http api on every estimate. `BlockNative` polls it in a
+// background task on a `Runtime`, with the `tokio_` feature `BlocknativeWebSocketGasStation`
+// subscribes to the gas stream instead.
 
 const API_URI: &str = "https://api.blocknative.com/gasprices/blockprices";
+#[cfg(feature = "tokio_")]
+const WEBSOCKET_URI: &str = "wss://api.blocknative.com/v0";
 
 const TIME_PER_BLOCK: Duration = Duration::from_secs(15);
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 const RATE_LIMIT: Duration = Duration::from_secs(10);
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 const CACHED_RESPONSE_VALIDITY: Duration = Duration::from_secs(60);
 
+/// The prices of one confidence level. Prices are in gwei as sent by Blocknative and in wei in the
+/// responses returned by `fetch_raw`.
 #[derive(Debug, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
-struct EstimatedPrice {
-    confidence: f64,
-    price: f64,
-    max_priority_fee_per_gas: f64,
-    max_fee_per_gas: f64,
+pub struct EstimatedPrice {
+    /// Probability in percent that a transaction with these prices is included in the next block.
+    pub confidence: f64,
+    /// Legacy gas price.
+    pub price: f64,
+    pub max_priority_fee_per_gas: f64,
+    pub max_fee_per_gas: f64,
 }
 
 impl EstimatedPrice {
     fn gwei_to_wei(self) -> Self {
         Self {
-            price: self.price * 1_000_000_000.0,
-            max_fee_per_gas: self.max_fee_per_gas * 1_000_000_000.0,
-            max_priority_fee_per_gas: self.max_priority_fee_per_gas * 1_000_000_000.0,
+            price: GasPriceWei::from_gwei(self.price).0,
+            max_fee_per_gas: GasPriceWei::from_gwei(self.max_fee_per_gas).0,
+            max_priority_fee_per_gas: GasPriceWei::from_gwei(self.max_priority_fee_per_gas).0,
             ..self
         }
     }
 }
 
+/// Estimates for inclusion in the next block, base fee in the same unit as the prices.
 #[derive(Debug, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
-struct BlockPrice {
-    estimated_prices: Vec<EstimatedPrice>,
-    base_fee_per_gas: f64,
+pub struct BlockPrice {
+    /// Not sorted by confidence.
+    pub estimated_prices: Vec<EstimatedPrice>,
+    pub base_fee_per_gas: f64,
 }
 
 impl BlockPrice {
     fn gwei_to_wei(self) -> Self {
         Self {
-            base_fee_per_gas: self.base_fee_per_gas * 1_000_000_000.0,
+            base_fee_per_gas: GasPriceWei::from_gwei(self.base_fee_per_gas).0,
             estimated_prices: self
                 .estimated_prices
                 .into_iter()
@@ -56,10 +81,12 @@ impl BlockPrice {
     }
 }
 
+/// The blockprices response of the http api and the gas stream. Only the first block is used for
+/// estimates.
 #[derive(Debug, serde::Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
-struct Response {
-    block_prices: Vec<BlockPrice>,
+pub struct Response {
+    pub block_prices: Vec<BlockPrice>,
 }
 
 impl Response {
@@ -93,42 +120,271 @@ impl Default for CachedResponse {
     }
 }
 
+/// Maps time limits to the Blocknative confidence level (the probability in percent that a
+/// transaction with that price is included in the next block) whose prices are used. Prices for
+/// time limits between two entries are interpolated, time limits outside of the table are clamped
+/// to the first or last entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfidenceTable(Vec<(f64, f64)>);
+
+impl ConfidenceTable {
+    /// The entries must be sorted by strictly increasing time limit and have confidences in
+    /// (0, 100].
+    pub fn new(entries: Vec<(Duration, f64)>) -> Result<Self> {
+        if entries.is_empty() {
+            return Err(anyhow!("confidence table is empty").into());
+        }
+        if !entries.windows(2).all(|window| window[0].0 < window[1].0) {
+            return Err(anyhow!("confidence table is not sorted by time limit").into());
+        }
+        if !entries
+            .iter()
+            .all(|(_, confidence)| *confidence > 0.0 && *confidence <= 100.0)
+        {
+            return Err(anyhow!("confidence table contains invalid confidence").into());
+        }
+        Ok(Self(
+            entries
+                .into_iter()
+                .map(|(time, confidence)| (time.as_secs_f64(), confidence))
+                .collect(),
+        ))
+    }
+
+    /// The interpolated confidence used for `time_limit`.
+    pub fn confidence(&self, time_limit: Duration) -> f64 {
+        // Validated in the constructor.
+        interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            self.0.as_slice().try_into().unwrap(),
+        )
+    }
+}
+
+impl Default for ConfidenceTable {
+    // The confidence levels Blocknative returns. The time limit of a level is calculated as
+    // 15s / (confidence / 100%), so 90% confidence maps to 16.67s.
+    fn default() -> Self {
+        Self(
+            [99.0, 95.0, 90.0, 80.0, 70.0]
+                .iter()
+                .map(|confidence| {
+                    (
+                        TIME_PER_BLOCK.as_secs_f64() / (confidence / 100.0),
+                        *confidence,
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
 struct Request<T> {
     transport: T,
+    url: String,
     header: http::header::HeaderMap,
 }
 
 impl<T: Transport> Request<T> {
     async fn gas_price(&self) -> Result<Response> {
         self.transport
-            .get_json(API_URI, self.header.clone())
+            .get_json(&self.url, self.header.clone())
             .await
-            .context("failed to get blocknative gas price")
+            .map_err(|err| err.context("failed to get blocknative gas price"))
+            .inspect(|response| trace::response("blocknative", response))
+    }
+}
+
+/// Requests the http api on every estimate, without a background task. Blocknative allows one
+/// request every 10 seconds on the free plan so this is usually wrapped in
+/// `CachedGasPriceEstimating`.
+pub struct BlocknativeGasStation<T> {
+    request: Request<T>,
+    confidence_table: ConfidenceTable,
+}
+
+impl<T: Transport> BlocknativeGasStation<T> {
+    pub fn new(transport: T, header: http::header::HeaderMap) -> Self {
+        Self::with_confidence_table(transport, header, Default::default())
+    }
+
+    pub fn with_confidence_table(
+        transport: T,
+        header: http::header::HeaderMap,
+        confidence_table: ConfidenceTable,
+    ) -> Self {
+        Self {
+            request: Request {
+                transport,
+                url: API_URI.to_string(),
+                header,
+            },
+            confidence_table,
+        }
+    }
+
+    /// Blocknative expects the api key as `ApiCredentials::header("Authorization", api_key)`.
+    pub fn with_credentials(
+        transport: T,
+        credentials: ApiCredentials,
+        confidence_table: ConfidenceTable,
+    ) -> Result<Self> {
+        let (url, header) = auth::authorize(Some(&credentials), API_URI, Default::default())?;
+        Ok(Self {
+            request: Request {
+                transport,
+                url,
+                header,
+            },
+            confidence_table,
+        })
+    }
+
+    /// The current response with prices in wei, for custom interpolation of the confidence
+    /// levels.
+    pub async fn fetch_raw(&self) -> Result<Response> {
+        Ok(self.request.gas_price().await?.gwei_to_wei())
+    }
+
+    async fn response(&self) -> Result<CachedResponse> {
+        Ok(CachedResponse {
+            time: Instant::now(),
+            data: self.fetch_raw().await?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for BlocknativeGasStation<T> {
+    fn source(&self) -> &'static str {
+        "blocknative"
+    }
+
+    fn block_time(&self) -> Duration {
+        TIME_PER_BLOCK
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        trace::estimate(self.source(), Some(API_URI), async {
+            estimate_with_limits(time_limit, self.response().await?, &self.confidence_table)
+        })
+        .await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let response = self.response().await?;
+        GasPriceSchedule::from_estimates(|time_limit| {
+            estimate_with_limits(time_limit, response.clone(), &self.confidence_table)
+        })
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let response = self.response().await?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| {
+                estimate_with_limits(*time_limit, response.clone(), &self.confidence_table)
+            })
+            .collect()
+    }
+
+    async fn estimate_verbose(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let response = self.response().await?;
+        Ok(EstimateWithMetadata {
+            price: estimate_with_limits(time_limit, response.clone(), &self.confidence_table)?,
+            source: self.source(),
+            observed_at: response.time,
+            confidence: Some(self.confidence_table.confidence(time_limit) / 100.0),
+            divergence: None,
+            range: confidence_band(time_limit, &response, &self.confidence_table)?,
+        })
     }
 }
 
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 pub struct BlockNative {
     cached_response: Arc<Mutex<CachedResponse>>,
-    handle: JoinHandle<()>,
+    confidence_table: ConfidenceTable,
+    task: BackgroundTask,
 }
 
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 impl Drop for BlockNative {
     fn drop(&mut self) {
-        self.handle.abort();
         self.cached_response = Default::default();
     }
 }
 
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 impl BlockNative {
     pub async fn new<T: Transport + 'static>(
         transport: T,
         header: http::header::HeaderMap,
+    ) -> Result<Self> {
+        Self::with_confidence_table(transport, header, Default::default()).await
+    }
+
+    pub async fn with_confidence_table<T: Transport + 'static>(
+        transport: T,
+        header: http::header::HeaderMap,
+        confidence_table: ConfidenceTable,
+    ) -> Result<Self> {
+        let request = Request {
+            transport,
+            url: API_URI.to_string(),
+            header,
+        };
+        Self::with_request(request, confidence_table, runtime::default_runtime()).await
+    }
+
+    /// Blocknative expects the api key as `ApiCredentials::header("Authorization", api_key)`.
+    pub async fn with_credentials<T: Transport + 'static>(
+        transport: T,
+        credentials: ApiCredentials,
+        confidence_table: ConfidenceTable,
+    ) -> Result<Self> {
+        let (url, header) = auth::authorize(Some(&credentials), API_URI, Default::default())?;
+        let request = Request {
+            transport,
+            url,
+            header,
+        };
+        Self::with_request(request, confidence_table, runtime::default_runtime()).await
+    }
+
+    /// Like `with_credentials` but polls on `runtime` instead of the default runtime.
+    pub async fn with_runtime<T: Transport + 'static>(
+        transport: T,
+        credentials: ApiCredentials,
+        confidence_table: ConfidenceTable,
+        runtime: Arc<dyn Runtime>,
+    ) -> Result<Self> {
+        let (url, header) = auth::authorize(Some(&credentials), API_URI, Default::default())?;
+        let request = Request {
+            transport,
+            url,
+            header,
+        };
+        Self::with_request(request, confidence_table, runtime).await
+    }
+
+    async fn with_request<T: Transport + 'static>(
+        request: Request<T>,
+        confidence_table: ConfidenceTable,
+        runtime: Arc<dyn Runtime>,
     ) -> Result<Self> {
         let cached_response: Arc<Mutex<CachedResponse>> = Default::default();
         let cached_response_clone = cached_response.clone();
 
         //send one request to initially populate the cached response
-        let request = Request { transport, header };
         match request.gas_price().await {
             Ok(response) => {
                 *cached_response_clone.lock().unwrap() = CachedResponse {
@@ -138,14 +394,15 @@ impl BlockNative {
             }
             Err(err) => {
                 tracing::warn!(?err, "failed to get initial response from blocknative");
-                return Err(anyhow!("failed to get initial response from blocknative"));
+                return Err(anyhow!("failed to get initial response from blocknative").into());
             }
         }
 
         //spawn task for updating the cached response every RATE_LIMIT seconds
-        let handle = task::spawn(async move {
+        let task_runtime = runtime.clone();
+        let task = BackgroundTask::spawn_on(runtime.as_ref(), async move {
             loop {
-                tokio::time::sleep(RATE_LIMIT).await;
+                task_runtime.sleep(RATE_LIMIT).await;
                 match request.gas_price().await {
                     Ok(response) => {
                         *cached_response_clone.lock().unwrap() = CachedResponse {
@@ -160,106 +417,569 @@ impl BlockNative {
 
         Ok(Self {
             cached_response,
-            handle,
+            confidence_table,
+            task,
+        })
+    }
+
+    /// Stops the background task and waits until it has finished. Estimates fail once the last
+    /// response is stale.
+    pub async fn shutdown(&mut self) {
+        self.task.shutdown().await;
+    }
+
+    /// The last response with prices in wei, for custom interpolation of the confidence levels.
+    /// Fails like the estimates if it is stale.
+    pub async fn fetch_raw(&self) -> Result<Response> {
+        Ok(self.fresh_response()?.data)
+    }
+
+    fn fresh_response(&self) -> Result<CachedResponse> {
+        let cached_response = self.cached_response.lock().unwrap().clone();
+        check_staleness(
+            cached_response.time,
+            CACHED_RESPONSE_VALIDITY,
+            Instant::now(),
+        )?;
+        Ok(cached_response)
+    }
+}
+
+/// Keeps the cached response up to date by subscribing to the gas stream of the Blocknative
+/// websocket api instead of polling the http api.
+#[cfg(feature = "tokio_")]
+pub struct BlocknativeWebSocketGasStation {
+    // `None` until the first message has been received.
+    cached_response: Arc<Mutex<Option<CachedResponse>>>,
+    confidence_table: ConfidenceTable,
+    max_staleness: Duration,
+    // Estimate for the default time limit, updated on every message.
+    prices: watch::Receiver<EstimatedGasPrice>,
+    clock: Arc<dyn Clock>,
+    task: BackgroundTask,
+}
+
+// Wraps the blockprices response that is pushed on the gas subscription.
+#[cfg(feature = "tokio_")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamMessage {
+    event: StreamEvent,
+}
+
+#[cfg(feature = "tokio_")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamEvent {
+    gas_price: Response,
+}
+
+#[cfg(feature = "tokio_")]
+impl BlocknativeWebSocketGasStation {
+    /// Unlike `BlockNative::new` this does not wait for the first response. Estimates fail until
+    /// the first message has been received.
+    pub fn new<T: WebSocketTransport + 'static>(transport: T, api_key: String) -> Self {
+        Self::with_confidence_table(transport, api_key, Default::default())
+    }
+
+    pub fn with_confidence_table<T: WebSocketTransport + 'static>(
+        transport: T,
+        api_key: String,
+        confidence_table: ConfidenceTable,
+    ) -> Self {
+        Self::with_params(transport, api_key, confidence_table, Default::default())
+    }
+
+    /// `connection` configures reconnecting, keep alive pings and connection state callbacks.
+    pub fn with_params<T: WebSocketTransport + 'static>(
+        transport: T,
+        api_key: String,
+        confidence_table: ConfidenceTable,
+        connection: ws_util::Params,
+    ) -> Self {
+        Self::with_urls(
+            transport,
+            api_key,
+            confidence_table,
+            connection,
+            vec![WEBSOCKET_URI.to_string()],
+        )
+    }
+
+    /// Connects to the first of `urls` that is reachable, for example a relay in front of
+    /// Blocknative with the public endpoint as fallback. Every url backs off on its own so a
+    /// failing endpoint doesn't delay connecting to the next one.
+    pub fn with_urls<T: WebSocketTransport + 'static>(
+        transport: T,
+        api_key: String,
+        confidence_table: ConfidenceTable,
+        connection: ws_util::Params,
+        urls: Vec<String>,
+    ) -> Self {
+        let cached_response: Arc<Mutex<Option<CachedResponse>>> = Default::default();
+        let cached_response_clone = cached_response.clone();
+        let (sender, prices) = watch::channel(EstimatedGasPrice::default());
+        let confidence_table_clone = confidence_table.clone();
+        let clock = connection.clock.clone();
+        let task = BackgroundTask::spawn(async move {
+            let updates = Updates {
+                cached_response: &cached_response_clone,
+                prices: &sender,
+                confidence_table: &confidence_table_clone,
+                clock: connection.clock.as_ref(),
+            };
+            let urls = urls.iter().map(String::as_str).collect::<Vec<_>>();
+            ws_util::run_with_failover(
+                &transport,
+                &urls,
+                Default::default(),
+                &connection,
+                |connection| stream_gas_prices(connection, &api_key, &updates),
+            )
+            .await
+        });
+        Self {
+            cached_response,
+            confidence_table,
+            max_staleness: CACHED_RESPONSE_VALIDITY,
+            prices,
+            clock,
+            task,
+        }
+    }
+
+    /// Estimates fail if the last message was received longer than `max_staleness` ago, which
+    /// happens when the connection silently stops delivering updates. Defaults to 60 seconds.
+    pub fn with_max_staleness(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = max_staleness;
+        self
+    }
+
+    /// The time at which the last gas price update was received.
+    pub fn last_update(&self) -> Option<Instant> {
+        self.cached_response
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|cached_response| cached_response.time)
+    }
+
+    /// Receives the estimate for the default time limit whenever a message changes it. The value
+    /// is the default estimate until the first message has been received, use `changed` to wait
+    /// for updates.
+    pub fn subscribe(&self) -> watch::Receiver<EstimatedGasPrice> {
+        self.prices.clone()
+    }
+
+    /// Stops the background task and waits until it has finished. Estimates fail once the last
+    /// message is stale.
+    pub async fn shutdown(&mut self) {
+        self.task.shutdown().await;
+    }
+
+    /// The last received response with prices in wei, for custom interpolation of the confidence
+    /// levels. Fails like the estimates before the first message or if it is stale.
+    pub async fn fetch_raw(&self) -> Result<Response> {
+        Ok(self.fresh_response()?.data)
+    }
+
+    fn fresh_response(&self) -> Result<CachedResponse> {
+        let cached_response = self
+            .cached_response
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("no message received from blocknative websocket"))?;
+        check_staleness(cached_response.time, self.max_staleness, self.clock.now())?;
+        Ok(cached_response)
+    }
+}
+
+#[cfg(feature = "tokio_")]
+fn initialize_message(api_key: &str) -> String {
+    serde_json::json!({
+        "categoryCode": "initialize",
+        "eventCode": "checkDappId",
+        "dappId": api_key,
+        "timeStamp": timestamp(),
+        "version": "1",
+        "blockchain": { "system": "ethereum", "network": "main" },
+    })
+    .to_string()
+}
+
+#[cfg(feature = "tokio_")]
+fn subscribe_message(api_key: &str) -> String {
+    serde_json::json!({
+        "categoryCode": "configs",
+        "eventCode": "put",
+        "dappId": api_key,
+        "timeStamp": timestamp(),
+        "version": "1",
+        "blockchain": { "system": "ethereum", "network": "main" },
+        "config": { "scope": "gas", "filters": [], "watchAddress": false },
+    })
+    .to_string()
+}
+
+// The current time as an ISO 8601 UTC timestamp for the `timeStamp` field of messages.
+#[cfg(feature = "tokio_")]
+fn timestamp() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    iso8601(now.as_millis() as u64)
+}
+
+// Formats milliseconds since the epoch without a date time dependency, the date is computed with
+// the days from civil algorithm of http://howardhinnant.github.io/date_algorithms.html.
+#[cfg(feature = "tokio_")]
+fn iso8601(millis: u64) -> String {
+    let (days, millis_of_day) = (millis / 86_400_000, millis % 86_400_000);
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let seconds = millis_of_day / 1000;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        millis_of_day % 1000
+    )
+}
+
+// Where the stream publishes received gas prices.
+#[cfg(feature = "tokio_")]
+struct Updates<'a> {
+    cached_response: &'a Mutex<Option<CachedResponse>>,
+    prices: &'a watch::Sender<EstimatedGasPrice>,
+    confidence_table: &'a ConfidenceTable,
+    clock: &'a dyn Clock,
+}
+
+// Returns when the connection is closed.
+#[cfg(feature = "tokio_")]
+async fn stream_gas_prices(
+    mut connection: impl WebSocketConnection,
+    api_key: &str,
+    updates: &Updates<'_>,
+) -> Result<()> {
+    connection.send(initialize_message(api_key)).await?;
+    connection.send(subscribe_message(api_key)).await?;
+    while let Some(message) = connection.receive().await {
+        // Other messages like acknowledgements of the subscription are ignored.
+        if let Ok(message) = serde_json::from_str::<StreamMessage>(&message?) {
+            trace::response("blocknative", &message.event.gas_price);
+            let cached_response = CachedResponse {
+                time: updates.clock.now(),
+                data: message.event.gas_price.gwei_to_wei(),
+            };
+            *updates.cached_response.lock().unwrap() = Some(cached_response.clone());
+            // Published after the cached response so that subscribers see the same estimate.
+            match estimate_with_limits(
+                DEFAULT_TIME_LIMIT,
+                cached_response,
+                updates.confidence_table,
+            ) {
+                Ok(price) => {
+                    updates.prices.send_if_modified(|current| {
+                        let modified = *current != price;
+                        *current = price;
+                        modified
+                    });
+                }
+                Err(err) => tracing::warn!(?err, "failed to estimate from blocknative message"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tokio_")]
+#[async_trait::async_trait]
+impl GasPriceEstimating for BlocknativeWebSocketGasStation {
+    fn source(&self) -> &'static str {
+        "blocknative"
+    }
+
+    fn block_time(&self) -> Duration {
+        TIME_PER_BLOCK
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        trace::estimate(self.source(), Some(WEBSOCKET_URI), async {
+            let cached_response = self.fresh_response()?;
+            estimate_with_limits(time_limit, cached_response, &self.confidence_table)
+        })
+        .await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let cached_response = self.fresh_response()?;
+
+        GasPriceSchedule::from_estimates(|time_limit| {
+            estimate_with_limits(time_limit, cached_response.clone(), &self.confidence_table)
+        })
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let cached_response = self.fresh_response()?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| {
+                estimate_with_limits(*time_limit, cached_response.clone(), &self.confidence_table)
+            })
+            .collect()
+    }
+
+    async fn estimate_verbose(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        trace::estimate(self.source(), Some(WEBSOCKET_URI), async {
+            // One snapshot so that the price and the range come from the same message.
+            let cached_response = self.fresh_response()?;
+            Ok(EstimateWithMetadata {
+                price: estimate_with_limits(
+                    time_limit,
+                    cached_response.clone(),
+                    &self.confidence_table,
+                )?,
+                source: self.source(),
+                observed_at: cached_response.time,
+                confidence: Some(self.confidence_table.confidence(time_limit) / 100.0),
+                divergence: None,
+                range: confidence_band(time_limit, &cached_response, &self.confidence_table)?,
+            })
         })
+        .await
     }
 }
 
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 #[async_trait::async_trait]
 impl GasPriceEstimating for BlockNative {
+    fn source(&self) -> &'static str {
+        "blocknative"
+    }
+
+    fn block_time(&self) -> Duration {
+        TIME_PER_BLOCK
+    }
+
     async fn estimate_with_limits(
         &self,
         _gas_limit: f64,
         time_limit: Duration,
     ) -> Result<EstimatedGasPrice> {
-        let cached_response = self.cached_response.lock().unwrap().clone();
+        trace::estimate(self.source(), Some(API_URI), async {
+            let cached_response = self.fresh_response()?;
+            estimate_with_limits(time_limit, cached_response, &self.confidence_table)
+        })
+        .await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let cached_response = self.fresh_response()?;
+
+        GasPriceSchedule::from_estimates(|time_limit| {
+            estimate_with_limits(time_limit, cached_response.clone(), &self.confidence_table)
+        })
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let cached_response = self.fresh_response()?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| {
+                estimate_with_limits(*time_limit, cached_response.clone(), &self.confidence_table)
+            })
+            .collect()
+    }
+
+    async fn estimate_verbose(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        trace::estimate(self.source(), Some(API_URI), async {
+            // One snapshot so that the price and the range come from the same response.
+            let cached_response = self.fresh_response()?;
+            Ok(EstimateWithMetadata {
+                price: estimate_with_limits(
+                    time_limit,
+                    cached_response.clone(),
+                    &self.confidence_table,
+                )?,
+                source: self.source(),
+                observed_at: cached_response.time,
+                confidence: Some(self.confidence_table.confidence(time_limit) / 100.0),
+                divergence: None,
+                range: confidence_band(time_limit, &cached_response, &self.confidence_table)?,
+            })
+        })
+        .await
+    }
+}
+
+// The prices of the confidence levels of the response below and above the confidence used for
+// `time_limit`. `None` if the response has fewer than two levels.
+fn confidence_band(
+    time_limit: Duration,
+    cached_response: &CachedResponse,
+    confidence_table: &ConfidenceTable,
+) -> Result<Option<EstimateRange>> {
+    let block = match cached_response.data.block_prices.first() {
+        Some(block) if block.estimated_prices.len() >= 2 => block,
+        _ => return Ok(None),
+    };
+    let mut prices = block.estimated_prices.clone();
+    prices.sort_by(|a, b| {
+        a.confidence
+            .partial_cmp(&b.confidence)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let confidence = confidence_table.confidence(time_limit);
+    let low = prices
+        .iter()
+        .rev()
+        .find(|price| price.confidence < confidence)
+        .unwrap_or(&prices[0]);
+    let high = prices
+        .iter()
+        .find(|price| price.confidence > confidence)
+        .unwrap_or(&prices[prices.len() - 1]);
+    let estimate = |price: &EstimatedPrice| {
+        EstimatedGasPrice {
+            legacy: price.price,
+            eip1559: Some(GasPrice1559 {
+                max_fee_per_gas: GasPriceWei(price.max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(price.max_priority_fee_per_gas),
+                base_fee_per_gas: GasPriceWei(block.base_fee_per_gas),
+            }),
+        }
+        .validate()
+    };
+    Ok(Some(EstimateRange {
+        low: estimate(low)?,
+        high: estimate(high)?,
+    }))
+}
+
+#[cfg(feature = "tokio_")]
+#[async_trait::async_trait]
+impl Readiness for BlocknativeWebSocketGasStation {
+    async fn ready(&self) -> bool {
+        self.cached_response
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|cached_response| {
+                check_staleness(cached_response.time, self.max_staleness, self.clock.now()).is_ok()
+            })
+    }
+}
+
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+#[async_trait::async_trait]
+impl Readiness for BlockNative {
+    async fn ready(&self) -> bool {
+        let time = self.cached_response.lock().unwrap().time;
+        check_staleness(time, CACHED_RESPONSE_VALIDITY, Instant::now()).is_ok()
+    }
+}
 
-        estimate_with_limits(time_limit, cached_response)
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+fn check_staleness(time: Instant, max_staleness: Duration, now: Instant) -> Result<()> {
+    let age = now.saturating_duration_since(time);
+    if age > max_staleness {
+        return Err(anyhow!("cached response is stale, last update {:?} ago", age).into());
     }
+    Ok(())
 }
 
 fn estimate_with_limits(
     time_limit: Duration,
     mut cached_response: CachedResponse,
+    confidence_table: &ConfidenceTable,
 ) -> Result<EstimatedGasPrice> {
-    if Instant::now().saturating_duration_since(cached_response.time) > CACHED_RESPONSE_VALIDITY {
-        return Err(anyhow!("cached response is stale"));
-    }
-
     if let Some(block) = cached_response.data.block_prices.first_mut() {
         //need to sort by confidence since Blocknative API does not guarantee sorted response
         block
             .estimated_prices
-            .sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap()); //change to total_cmp when stable
-
-        //if confidence is 90%, point is calculated as 15s / (90% / 100%)
-        let points = block
-            .estimated_prices
-            .iter()
-            .map(|estimated_price| {
-                (
-                    TIME_PER_BLOCK.as_secs_f64() / (estimated_price.confidence / 100.0),
-                    estimated_price.price,
-                    estimated_price.max_fee_per_gas,
-                    estimated_price.max_priority_fee_per_gas,
-                )
-            })
-            .collect::<Vec<(f64, f64, f64, f64)>>();
+            .sort_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap()); //change to total_cmp when stable
 
-        let gas_price_points = points
-            .iter()
-            .map(
-                |(duration, gas_price, _max_fee_per_gas, _max_priority_fee_per_gas)| {
-                    (*duration, *gas_price)
-                },
-            )
-            .collect::<Vec<(f64, f64)>>();
-        let max_fee_per_gas_points = points
-            .iter()
-            .map(
-                |(duration, _gas_price, max_fee_per_gas, _max_priority_fee_per_gas)| {
-                    (*duration, *max_fee_per_gas)
-                },
-            )
-            .collect::<Vec<(f64, f64)>>();
-        let max_priority_fee_per_gas_points = points
-            .iter()
-            .map(
-                |(duration, _gas_price, _max_fee_per_gas, max_priority_fee_per_gas)| {
-                    (*duration, *max_priority_fee_per_gas)
-                },
-            )
-            .collect::<Vec<(f64, f64)>>();
+        //the price of each configured confidence level, interpolated between the confidence levels
+        //of the response, becomes the point at the level's time limit
+        let interpolate = |field: fn(&EstimatedPrice) -> f64| -> Result<f64> {
+            let confidence_points = block
+                .estimated_prices
+                .iter()
+                .map(|estimated_price| (estimated_price.confidence, field(estimated_price)))
+                .collect::<Vec<(f64, f64)>>();
+            let confidence_points = confidence_points.as_slice().try_into()?;
+            let time_points = confidence_table
+                .0
+                .iter()
+                .map(|(time, confidence)| {
+                    (
+                        *time,
+                        interpolation::interpolate(*confidence, confidence_points),
+                    )
+                })
+                .collect::<Vec<(f64, f64)>>();
+            Ok(interpolation::interpolate(
+                time_limit.as_secs_f64(),
+                time_points.as_slice().try_into()?,
+            ))
+        };
 
         return EstimatedGasPrice {
-            legacy: linear_interpolation::interpolate(
-                time_limit.as_secs_f64(),
-                gas_price_points.as_slice().try_into()?,
-            ),
+            legacy: interpolate(|estimated_price| estimated_price.price)?,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: linear_interpolation::interpolate(
-                    time_limit.as_secs_f64(),
-                    max_fee_per_gas_points.as_slice().try_into()?,
-                ),
-                max_priority_fee_per_gas: linear_interpolation::interpolate(
-                    time_limit.as_secs_f64(),
-                    max_priority_fee_per_gas_points.as_slice().try_into()?,
-                ),
-                base_fee_per_gas: block.base_fee_per_gas,
+                max_fee_per_gas: GasPriceWei(interpolate(|estimated_price| {
+                    estimated_price.max_fee_per_gas
+                })?),
+                max_priority_fee_per_gas: GasPriceWei(interpolate(|estimated_price| {
+                    estimated_price.max_priority_fee_per_gas
+                })?),
+                base_fee_per_gas: GasPriceWei(block.base_fee_per_gas),
             }),
         }
         .validate();
     }
 
-    Err(anyhow!("no valid response exist"))
+    Err(anyhow!("no valid response exist").into())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::tests::TestTransport;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
     use super::*;
+    use serde::de::DeserializeOwned;
     use serde_json::json;
 
+    #[cfg(feature = "tokio_")]
     #[tokio::test]
     #[ignore]
     async fn real_request() {
@@ -296,6 +1016,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "tokio_")]
     #[tokio::test]
     #[ignore]
     async fn expect_constructor_fail() {
@@ -308,6 +1029,316 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "tokio_")]
+    struct TestConnection {
+        sent: Vec<String>,
+        messages: std::vec::IntoIter<String>,
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[async_trait::async_trait]
+    impl WebSocketConnection for TestConnection {
+        async fn send(&mut self, message: String) -> Result<()> {
+            self.sent.push(message);
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Option<Result<String>> {
+            self.messages.next().map(Ok)
+        }
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[test]
+    fn formats_iso8601_timestamps() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso8601(951_782_400_000), "2000-02-29T00:00:00.000Z");
+        assert_eq!(iso8601(1_767_225_599_999), "2025-12-31T23:59:59.999Z");
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[test]
+    fn stream_updates_cached_response() {
+        let message = json!({
+            "event": {
+                "gasPrice": {
+                    "blockPrices": [{
+                        "baseFeePerGas": 1.0,
+                        "estimatedPrices": [{
+                            "confidence": 99,
+                            "price": 3,
+                            "maxPriorityFeePerGas": 1.5,
+                            "maxFeePerGas": 4
+                        }]
+                    }]
+                }
+            }
+        });
+        let connection = TestConnection {
+            sent: Vec::new(),
+            messages: vec![r#"{"status":"ok"}"#.to_string(), message.to_string()].into_iter(),
+        };
+        let cached_response = Mutex::new(None);
+        let (sender, prices) = watch::channel(EstimatedGasPrice::default());
+        let updates = Updates {
+            cached_response: &cached_response,
+            prices: &sender,
+            confidence_table: &Default::default(),
+            clock: &crate::time::SystemClock,
+        };
+        futures::executor::block_on(stream_gas_prices(connection, "key", &updates)).unwrap();
+        assert!(prices.has_changed().unwrap());
+        assert_eq!(prices.borrow().legacy, 3e9);
+
+        let cached_response = cached_response.lock().unwrap().clone().unwrap();
+        let price = estimate_with_limits(
+            Duration::from_secs(15),
+            cached_response,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            price,
+            EstimatedGasPrice {
+                legacy: 3e9,
+                eip1559: Some(GasPrice1559 {
+                    max_fee_per_gas: GasPriceWei(4e9),
+                    max_priority_fee_per_gas: GasPriceWei(1.5e9),
+                    base_fee_per_gas: GasPriceWei(1e9),
+                })
+            }
+        );
+    }
+
+    // Serves a single message on every connection, then stays idle.
+    #[cfg(feature = "tokio_")]
+    struct SingleMessage(String);
+
+    #[cfg(feature = "tokio_")]
+    struct SingleMessageConnection(Option<String>);
+
+    #[cfg(feature = "tokio_")]
+    #[async_trait::async_trait]
+    impl WebSocketConnection for SingleMessageConnection {
+        async fn send(&mut self, _message: String) -> Result<()> {
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Option<Result<String>> {
+            match self.0.take() {
+                Some(message) => Some(Ok(message)),
+                None => std::future::pending().await,
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[async_trait::async_trait]
+    impl WebSocketTransport for SingleMessage {
+        type Connection = SingleMessageConnection;
+
+        async fn connect(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<Self::Connection> {
+            Ok(SingleMessageConnection(Some(self.0.clone())))
+        }
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn websocket_staleness_uses_clock() {
+        let message = json!({
+            "event": {
+                "gasPrice": {
+                    "blockPrices": [{
+                        "baseFeePerGas": 1.0,
+                        "estimatedPrices": [{
+                            "confidence": 99,
+                            "price": 3,
+                            "maxPriorityFeePerGas": 1.5,
+                            "maxFeePerGas": 4
+                        }]
+                    }]
+                }
+            }
+        });
+        let clock = crate::time::tests::ManualClock::default();
+        let station = BlocknativeWebSocketGasStation::with_params(
+            SingleMessage(message.to_string()),
+            "key".to_string(),
+            Default::default(),
+            ws_util::Params {
+                ping_interval: None,
+                idle_timeout: None,
+                clock: Arc::new(clock.clone()),
+                ..Default::default()
+            },
+        );
+        station.subscribe().changed().await.unwrap();
+        assert_eq!(station.last_update(), Some(clock.now()));
+        assert!(station.estimate().await.is_ok());
+        assert!(station.ready().await);
+        // verbose estimates are observed when the message was received
+        let received = clock.now();
+        clock.advance(Duration::from_secs(1));
+        let verbose = station
+            .estimate_verbose(21000.0, Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(verbose.observed_at, received);
+        let response = station.fetch_raw().await.unwrap();
+        assert_eq!(response.block_prices[0].base_fee_per_gas, 1e9);
+        assert_eq!(response.block_prices[0].estimated_prices[0].price, 3e9);
+        clock.advance(CACHED_RESPONSE_VALIDITY * 2);
+        assert!(station.estimate().await.is_err());
+        assert!(station.fetch_raw().await.is_err());
+        assert!(!station.ready().await);
+    }
+
+    struct Fixed(serde_json::Value);
+
+    #[async_trait::async_trait]
+    impl Transport for Fixed {
+        async fn get_json<R: DeserializeOwned>(
+            &self,
+            url: &str,
+            header: http::header::HeaderMap,
+        ) -> Result<R> {
+            assert_eq!(url, API_URI);
+            assert_eq!(header.get("Authorization").unwrap(), "key");
+            Ok(serde_json::from_value(self.0.clone())?)
+        }
+    }
+
+    fn response() -> serde_json::Value {
+        json!({
+            "blockPrices": [{
+                "baseFeePerGas": 1.0,
+                "estimatedPrices": [{
+                    "confidence": 99,
+                    "price": 3,
+                    "maxPriorityFeePerGas": 1.5,
+                    "maxFeePerGas": 4
+                }]
+            }]
+        })
+    }
+
+    #[test]
+    fn gas_station_requests_on_every_estimate() {
+        let station = BlocknativeGasStation::with_credentials(
+            Fixed(response()),
+            ApiCredentials::header("Authorization", "key"),
+            Default::default(),
+        )
+        .unwrap();
+        let price = station.estimate().wait().unwrap();
+        assert_eq!(
+            price,
+            EstimatedGasPrice {
+                legacy: 3e9,
+                eip1559: Some(GasPrice1559 {
+                    max_fee_per_gas: GasPriceWei(4e9),
+                    max_priority_fee_per_gas: GasPriceWei(1.5e9),
+                    base_fee_per_gas: GasPriceWei(1e9),
+                })
+            }
+        );
+        let response = station.fetch_raw().wait().unwrap();
+        assert_eq!(response.block_prices[0].base_fee_per_gas, 1e9);
+        assert!(station.estimate_schedule().wait().is_ok());
+    }
+
+    #[cfg(feature = "async-std_")]
+    #[test]
+    fn polls_on_async_std() {
+        async_std::task::block_on(async {
+            let blocknative = BlockNative::with_runtime(
+                Fixed(response()),
+                ApiCredentials::header("Authorization", "key"),
+                Default::default(),
+                Arc::new(runtime::AsyncStdRuntime),
+            )
+            .await
+            .unwrap();
+            assert_eq!(blocknative.estimate().await.unwrap().legacy, 3e9);
+            assert!(blocknative.ready().await);
+        });
+    }
+
+    // BLOCKNATIVE_API_KEY=... cargo test blocknative -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn gas_station_real_request() {
+        let station = BlocknativeGasStation::with_credentials(
+            TestTransport::default(),
+            ApiCredentials::header(
+                "Authorization",
+                std::env::var("BLOCKNATIVE_API_KEY").unwrap(),
+            ),
+            Default::default(),
+        )
+        .unwrap();
+        println!("{:?}", station.estimate().await);
+    }
+
+    #[test]
+    fn confidence_band_uses_neighbouring_levels() {
+        let level = |confidence: f64, price: f64| {
+            json!({
+                "confidence": confidence,
+                "price": price,
+                "maxPriorityFeePerGas": price - 1.0,
+                "maxFeePerGas": price + 10.0
+            })
+        };
+        let response: Response = serde_json::from_value(json!({
+            "blockPrices": [{
+                "baseFeePerGas": 1.0,
+                "estimatedPrices": [level(80.0, 3.0), level(99.0, 5.0), level(90.0, 4.0)]
+            }]
+        }))
+        .unwrap();
+        let cached_response = CachedResponse {
+            time: Instant::now(),
+            data: response,
+        };
+        let table = ConfidenceTable::default();
+        // between the 95% and 90% levels of the table
+        let range = confidence_band(Duration::from_secs(16), &cached_response, &table)
+            .unwrap()
+            .unwrap();
+        assert_eq!(range.low.legacy, 4.0);
+        assert_eq!(range.high.legacy, 5.0);
+        assert_eq!(
+            range.high.eip1559.unwrap().max_fee_per_gas,
+            GasPriceWei(15.0)
+        );
+        // above and below all levels of the response
+        let range = confidence_band(Duration::from_secs(1), &cached_response, &table)
+            .unwrap()
+            .unwrap();
+        assert_eq!((range.low.legacy, range.high.legacy), (4.0, 5.0));
+        let range = confidence_band(Duration::from_secs(600), &cached_response, &table)
+            .unwrap()
+            .unwrap();
+        assert_eq!((range.low.legacy, range.high.legacy), (3.0, 3.0));
+
+        let single = CachedResponse {
+            time: Instant::now(),
+            data: serde_json::from_value(json!({
+                "blockPrices": [{"baseFeePerGas": 1.0, "estimatedPrices": [level(99.0, 5.0)]}]
+            }))
+            .unwrap(),
+        };
+        assert_eq!(
+            confidence_band(Duration::from_secs(15), &single, &table).unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn estimate_with_limits_test() {
         let json = json!({
@@ -363,65 +1394,185 @@ mod tests {
             data: response,
         };
 
-        let price = estimate_with_limits(Duration::from_secs(10), cached_response.clone()).unwrap();
+        let price = estimate_with_limits(
+            Duration::from_secs(10),
+            cached_response.clone(),
+            &Default::default(),
+        )
+        .unwrap();
         assert_eq!(
             price,
             EstimatedGasPrice {
                 legacy: 104.0,
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 199.16,
-                    max_priority_fee_per_gas: 9.86,
-                    base_fee_per_gas: 94.647990462,
+                    max_fee_per_gas: GasPriceWei(199.16),
+                    max_priority_fee_per_gas: GasPriceWei(9.86),
+                    base_fee_per_gas: GasPriceWei(94.647990462),
                 })
             }
         );
-        let price = estimate_with_limits(Duration::from_secs(16), cached_response.clone()).unwrap();
+        let price = estimate_with_limits(
+            Duration::from_secs(16),
+            cached_response.clone(),
+            &Default::default(),
+        )
+        .unwrap();
         assert_eq!(
             price,
             EstimatedGasPrice {
                 legacy: 98.76,
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 194.134,
-                    max_priority_fee_per_gas: 4.844000000000001,
-                    base_fee_per_gas: 94.647990462,
+                    max_fee_per_gas: GasPriceWei(194.134),
+                    max_priority_fee_per_gas: GasPriceWei(4.844000000000001),
+                    base_fee_per_gas: GasPriceWei(94.647990462),
                 })
             }
         );
-        let price = estimate_with_limits(Duration::from_secs(17), cached_response.clone()).unwrap();
+        let price = estimate_with_limits(
+            Duration::from_secs(17),
+            cached_response.clone(),
+            &Default::default(),
+        )
+        .unwrap();
         assert_eq!(
             price,
             EstimatedGasPrice {
                 legacy: 97.84,
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 193.2612,
-                    max_priority_fee_per_gas: 3.9696000000000007,
-                    base_fee_per_gas: 94.647990462,
+                    max_fee_per_gas: GasPriceWei(193.2612),
+                    max_priority_fee_per_gas: GasPriceWei(3.9696000000000007),
+                    base_fee_per_gas: GasPriceWei(94.647990462),
                 })
             }
         );
-        let price = estimate_with_limits(Duration::from_secs(19), cached_response.clone()).unwrap();
+        let price = estimate_with_limits(
+            Duration::from_secs(19),
+            cached_response.clone(),
+            &Default::default(),
+        )
+        .unwrap();
         assert_eq!(
             price,
             EstimatedGasPrice {
                 legacy: 96.90666666666667,
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 192.1552,
-                    max_priority_fee_per_gas: 2.8552000000000004,
-                    base_fee_per_gas: 94.647990462,
+                    max_fee_per_gas: GasPriceWei(192.1552),
+                    max_priority_fee_per_gas: GasPriceWei(2.8552000000000004),
+                    base_fee_per_gas: GasPriceWei(94.647990462),
                 })
             }
         );
-        let price = estimate_with_limits(Duration::from_secs(25), cached_response).unwrap();
+        let price = estimate_with_limits(
+            Duration::from_secs(25),
+            cached_response,
+            &Default::default(),
+        )
+        .unwrap();
         assert_eq!(
             price,
             EstimatedGasPrice {
                 legacy: 96.0,
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 191.04,
-                    max_priority_fee_per_gas: 1.74,
-                    base_fee_per_gas: 94.647990462,
+                    max_fee_per_gas: GasPriceWei(191.04),
+                    max_priority_fee_per_gas: GasPriceWei(1.74),
+                    base_fee_per_gas: GasPriceWei(94.647990462),
                 })
             }
         );
     }
+
+    #[test]
+    fn custom_confidence_table() {
+        let cached_response = CachedResponse {
+            time: Instant::now(),
+            data: Response {
+                block_prices: vec![BlockPrice {
+                    base_fee_per_gas: 1.0,
+                    estimated_prices: vec![
+                        EstimatedPrice {
+                            confidence: 70.0,
+                            price: 10.0,
+                            max_priority_fee_per_gas: 1.0,
+                            max_fee_per_gas: 11.0,
+                        },
+                        EstimatedPrice {
+                            confidence: 99.0,
+                            price: 20.0,
+                            max_priority_fee_per_gas: 2.0,
+                            max_fee_per_gas: 22.0,
+                        },
+                    ],
+                }],
+            },
+        };
+        let table = ConfidenceTable::new(vec![
+            (Duration::from_secs(30), 99.0),
+            (Duration::from_secs(120), 70.0),
+        ])
+        .unwrap();
+
+        let price =
+            estimate_with_limits(Duration::from_secs(30), cached_response.clone(), &table).unwrap();
+        assert_eq!(price.legacy, 20.0);
+        let price =
+            estimate_with_limits(Duration::from_secs(75), cached_response.clone(), &table).unwrap();
+        assert_eq!(price.legacy, 15.0);
+        assert_eq!(price.eip1559.unwrap().max_fee_per_gas, GasPriceWei(16.5));
+        let price =
+            estimate_with_limits(Duration::from_secs(600), cached_response, &table).unwrap();
+        assert_eq!(price.legacy, 10.0);
+        assert_eq!(table.confidence(Duration::from_secs(75)), 84.5);
+    }
+
+    #[test]
+    fn confidence_level_missing_from_response_is_interpolated() {
+        let cached_response = CachedResponse {
+            time: Instant::now(),
+            data: Response {
+                block_prices: vec![BlockPrice {
+                    base_fee_per_gas: 1.0,
+                    estimated_prices: vec![
+                        EstimatedPrice {
+                            confidence: 99.0,
+                            price: 20.0,
+                            max_priority_fee_per_gas: 2.0,
+                            max_fee_per_gas: 22.0,
+                        },
+                        EstimatedPrice {
+                            confidence: 70.0,
+                            price: 10.0,
+                            max_priority_fee_per_gas: 1.0,
+                            max_fee_per_gas: 11.0,
+                        },
+                    ],
+                }],
+            },
+        };
+        let table = ConfidenceTable::new(vec![(Duration::from_secs(60), 84.5)]).unwrap();
+        let price = estimate_with_limits(Duration::from_secs(1), cached_response, &table).unwrap();
+        assert_eq!(price.legacy, 15.0);
+    }
+
+    #[test]
+    fn invalid_confidence_table() {
+        assert!(ConfidenceTable::new(vec![]).is_err());
+        assert!(ConfidenceTable::new(vec![
+            (Duration::from_secs(20), 90.0),
+            (Duration::from_secs(10), 99.0),
+        ])
+        .is_err());
+        assert!(ConfidenceTable::new(vec![(Duration::from_secs(10), 0.0)]).is_err());
+        assert!(ConfidenceTable::new(vec![(Duration::from_secs(10), 101.0)]).is_err());
+    }
+
+    #[cfg(any(feature = "tokio_", feature = "async-std_"))]
+    #[test]
+    fn stale_response_is_rejected() {
+        let now = Instant::now();
+        let max_staleness = Duration::from_secs(60);
+        assert!(check_staleness(now, max_staleness, now + max_staleness).is_ok());
+        assert!(check_staleness(now, max_staleness, now + max_staleness * 2).is_err());
+        // updates received after `now` are fresh
+        assert!(check_staleness(now + max_staleness, max_staleness, now).is_ok());
+    }
 }
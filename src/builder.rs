@@ -0,0 +1,320 @@
+use super::{
+    error::Result, validate, CachedGasPriceEstimating, GasPriceEstimating,
+    PriorityGasPriceEstimating, SanitizingEstimator,
+};
+use anyhow::anyhow;
+use std::time::Duration;
+
+// Composes sources and decorators into a single estimator without spelling out the nested types.
+// Sources are tried in the order they are added. Regardless of the order of the builder calls the
+// decorators are applied as cache(max cap(retry(timeout(sources)))) so that cache hits don't wait
+// for retries and every attempt gets its own timeout.
+#[derive(Default)]
+pub struct EstimatorBuilder {
+    sources: Vec<Box<dyn GasPriceEstimating>>,
+    cache_ttl: Option<Duration>,
+    #[cfg(feature = "tokio_")]
+    max_retries: Option<usize>,
+    #[cfg(feature = "tokio_")]
+    timeout: Option<Duration>,
+    max_cap: Option<f64>,
+}
+
+impl EstimatorBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn source(mut self, source: impl GasPriceEstimating + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    pub fn with_cache(self, ttl: Duration) -> Self {
+        Self {
+            cache_ttl: Some(ttl),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tokio_")]
+    pub fn with_retry(self, max_retries: usize) -> Self {
+        Self {
+            max_retries: Some(max_retries),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tokio_")]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    // Clamps the legacy gas price and max_fee_per_gas to at most `max_cap` wei. Estimates with a
+    // base fee above the cap fail with `GasEstimationError::InvalidEstimate`.
+    pub fn with_max_cap(self, max_cap: f64) -> Self {
+        Self {
+            max_cap: Some(max_cap),
+            ..self
+        }
+    }
+
+    /// Fails if no source has been added.
+    pub fn build(mut self) -> Result<Box<dyn GasPriceEstimating>> {
+        let mut estimator: Box<dyn GasPriceEstimating> = match self.sources.len() {
+            0 => return Err(anyhow!("no gas price source").into()),
+            1 => self.sources.pop().unwrap(),
+            _ => Box::new(PriorityGasPriceEstimating::new(self.sources)),
+        };
+        #[cfg(feature = "tokio_")]
+        {
+            if let Some(timeout) = self.timeout {
+                estimator = Box::new(super::TimeLimitedEstimator::new(estimator, timeout));
+            }
+            if let Some(max_retries) = self.max_retries {
+                estimator = Box::new(super::RetryGasPriceEstimating::new(
+                    estimator,
+                    Some(super::retry::Params {
+                        max_retries,
+                        ..Default::default()
+                    }),
+                ));
+            }
+        }
+        if let Some(max_cap) = self.max_cap {
+            estimator = Box::new(SanitizingEstimator::new(
+                estimator,
+                Some(validate::Params {
+                    max_gas_price: max_cap,
+                    ..Default::default()
+                }),
+            ));
+        }
+        if let Some(ttl) = self.cache_ttl {
+            estimator = Box::new(CachedGasPriceEstimating::new(estimator, ttl));
+        }
+        Ok(estimator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::{tests::FakeNode, to_hex, uint_word};
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::{
+        time::Instant, EstimateWithMetadata, EstimatedGasPrice, GasPriceSchedule,
+        MockGasPriceEstimating, OpStackGasEstimator, TxSpec,
+    };
+    use super::*;
+    use serde_json::json;
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fails_without_sources() {
+        assert!(EstimatorBuilder::new().build().is_err());
+    }
+
+    #[test]
+    fn falls_back_to_later_sources() {
+        let mut first = MockGasPriceEstimating::new();
+        first
+            .expect_estimate()
+            .times(1)
+            .returning(|| Err(anyhow!("").into()));
+        let mut second = MockGasPriceEstimating::new();
+        second
+            .expect_estimate()
+            .times(1)
+            .returning(|| Ok(price(1.0)));
+        let estimator = EstimatorBuilder::new()
+            .source(first)
+            .source(second)
+            .build()
+            .unwrap();
+        assert_eq!(estimator.estimate().wait().unwrap(), price(1.0));
+    }
+
+    #[test]
+    fn caches_capped_estimates() {
+        let mut source = MockGasPriceEstimating::new();
+        source
+            .expect_estimate_with_limits()
+            .times(1)
+            .returning(|_, _| Ok(price(1000e9)));
+        let estimator = EstimatorBuilder::new()
+            .source(source)
+            .with_max_cap(500e9)
+            .with_cache(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            assert_eq!(
+                estimator
+                    .estimate_with_limits(21000.0, Duration::from_secs(30))
+                    .wait()
+                    .unwrap(),
+                price(500e9)
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_l1_fee_of_l2_sources() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({ "baseFeePerGas": "0x64" }))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"))
+            .with_result("eth_call:0x49948e0e", json!(to_hex(&uint_word(1_000_000))));
+        let estimator = EstimatorBuilder::new()
+            .source(OpStackGasEstimator::new(node, String::new(), None))
+            .source(MockGasPriceEstimating::new())
+            .with_max_cap(500e9)
+            .with_cache(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                calldata_len: 200,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.l1_fee, 1e6);
+        assert_eq!(cost.gas_price.eip1559.unwrap().max_fee_per_gas.0, 210.0);
+    }
+
+    #[test]
+    fn keeps_metadata_of_the_source() {
+        let mut source = MockGasPriceEstimating::new();
+        source.expect_estimate_verbose().times(1).returning(|_, _| {
+            Ok(EstimateWithMetadata {
+                confidence: Some(0.9),
+                ..EstimateWithMetadata::new(price(1000e9), "source", Instant::now())
+            })
+        });
+        let estimator = EstimatorBuilder::new()
+            .source(source)
+            .with_max_cap(500e9)
+            .with_cache(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            let estimate = estimator
+                .estimate_verbose(21000.0, Duration::from_secs(30))
+                .wait()
+                .unwrap();
+            assert_eq!(estimate.price, price(500e9));
+            assert_eq!(estimate.source, "source");
+            assert_eq!(estimate.confidence, Some(0.9));
+        }
+    }
+
+    fn schedule() -> GasPriceSchedule {
+        GasPriceSchedule {
+            slow: price(1e9),
+            standard: price(2e9),
+            fast: price(3e9),
+            instant: price(4e9),
+        }
+    }
+
+    #[test]
+    fn schedule_is_one_request() {
+        let mut source = MockGasPriceEstimating::new();
+        source
+            .expect_estimate_schedule()
+            .times(1)
+            .returning(|| Ok(schedule()));
+        let estimator = EstimatorBuilder::new()
+            .source(source)
+            .with_max_cap(3e9)
+            .with_cache(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            let schedule = estimator.estimate_schedule().wait().unwrap();
+            assert_eq!(schedule.slow, price(1e9));
+            assert_eq!(schedule.instant, price(3e9));
+        }
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn decorators_forward_schedule() {
+        use super::super::{
+            circuit_breaker::CircuitBreakerEstimator,
+            history::{GasPriceHistory, RecordingGasPriceEstimating},
+            hysteresis::HysteresisEstimator,
+            instrumented::{InstrumentedGasPriceEstimating, MockMetrics},
+            limits::{ClampedEstimator, Limits},
+            rate_limit::{RateLimitedEstimator, RateLimiter},
+            retry::RetryGasPriceEstimating,
+            smoothing::EwmaEstimator,
+            timeout::TimeLimitedEstimator,
+            validate::SanitizingEstimator,
+        };
+        use std::sync::Arc;
+
+        // any other call of the source panics
+        let mut source = MockGasPriceEstimating::new();
+        source
+            .expect_estimate_schedule()
+            .times(1)
+            .returning(|| Ok(schedule()));
+        let mut metrics = MockMetrics::new();
+        metrics
+            .expect_estimate_succeeded()
+            .times(4)
+            .return_const(());
+        let history = Arc::new(GasPriceHistory::new(10));
+        let estimator = RetryGasPriceEstimating::new(source, None);
+        let estimator = TimeLimitedEstimator::new(estimator, Duration::from_secs(1));
+        let estimator = ClampedEstimator::new(estimator, Limits::new(3e9, 0.0));
+        let estimator = SanitizingEstimator::new(estimator, None);
+        let estimator = EwmaEstimator::new(estimator, None);
+        let estimator = HysteresisEstimator::new(estimator, None);
+        let estimator = RecordingGasPriceEstimating::new(estimator, history.clone())
+            .with_time_limit(GasPriceSchedule::FAST);
+        let estimator = CircuitBreakerEstimator::new(estimator, None);
+        let estimator = InstrumentedGasPriceEstimating::new(estimator, "test", Arc::new(metrics));
+        let estimator =
+            RateLimitedEstimator::new(estimator, Arc::new(RateLimiter::new(1, Duration::MAX)));
+        let estimator = CachedGasPriceEstimating::new(estimator, Duration::from_secs(60));
+        for _ in 0..2 {
+            let schedule = estimator.estimate_schedule().await.unwrap();
+            assert_eq!(schedule.standard, price(2e9));
+            assert_eq!(schedule.instant, price(3e9));
+        }
+        assert_eq!(history.max_over(Duration::from_secs(60)), Some(price(3e9)));
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn retries() {
+        let mut source = MockGasPriceEstimating::new();
+        let mut calls = 0;
+        source.expect_estimate().times(2).returning(move || {
+            calls += 1;
+            if calls == 1 {
+                Err(anyhow!("").into())
+            } else {
+                Ok(price(1.0))
+            }
+        });
+        let estimator = EstimatorBuilder::new()
+            .source(source)
+            .with_retry(3)
+            .with_timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        assert_eq!(estimator.estimate().await.unwrap(), price(1.0));
+    }
+}
@@ -0,0 +1,117 @@
+//! Gas prices for resubmitting a pending transaction.
+
+use super::{EstimatedGasPrice, GasPrice1559, GasPriceWei};
+
+/// Factor by which nodes require every fee of a replacement transaction to be higher than the fee
+/// of the pending transaction (geth and openethereum default to 10% and 12.5%).
+pub const MIN_REPLACEMENT_BUMP: f64 = 1.125;
+
+pub trait BumpStrategy: Send + Sync {
+    /// Gas price for resubmitting a transaction that is pending with `previous`. Implementations
+    /// return a gas price that satisfies the node replacement rules, see
+    /// `EstimatedGasPrice::bump_for_replacement`.
+    fn bump(&self, previous: &EstimatedGasPrice) -> EstimatedGasPrice;
+}
+
+// Multiplies all fees by a factor.
+#[derive(Debug, Clone, Copy)]
+pub struct GeometricBump {
+    pub factor: f64,
+}
+
+impl BumpStrategy for GeometricBump {
+    fn bump(&self, previous: &EstimatedGasPrice) -> EstimatedGasPrice {
+        previous.bump(self.factor).bump_for_replacement(previous)
+    }
+}
+
+// Adds a fixed amount in wei to all fees.
+#[derive(Debug, Clone, Copy)]
+pub struct AdditiveBump {
+    pub step: f64,
+}
+
+impl AdditiveBump {
+    pub fn from_gwei(step: f64) -> Self {
+        Self {
+            step: GasPriceWei::from_gwei(step).0,
+        }
+    }
+}
+
+impl BumpStrategy for AdditiveBump {
+    fn bump(&self, previous: &EstimatedGasPrice) -> EstimatedGasPrice {
+        let step = GasPriceWei(self.step);
+        EstimatedGasPrice {
+            legacy: previous.legacy + self.step,
+            eip1559: previous.eip1559.map(|x| GasPrice1559 {
+                max_fee_per_gas: x.max_fee_per_gas + step,
+                max_priority_fee_per_gas: x.max_priority_fee_per_gas + step,
+                ..x
+            }),
+        }
+        .bump_for_replacement(previous)
+    }
+}
+
+// The smallest bump that nodes accept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinimumReplacementBump;
+
+impl BumpStrategy for MinimumReplacementBump {
+    fn bump(&self, previous: &EstimatedGasPrice) -> EstimatedGasPrice {
+        previous.bump_for_replacement(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eip1559(max_fee: f64, tip: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy: max_fee,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(10.0),
+                max_fee_per_gas: GasPriceWei(max_fee),
+                max_priority_fee_per_gas: GasPriceWei(tip),
+            }),
+        }
+    }
+
+    #[test]
+    fn geometric() {
+        let strategy = GeometricBump { factor: 2.0 };
+        assert_eq!(strategy.bump(&eip1559(100.0, 10.0)), eip1559(200.0, 20.0));
+        // too small factors are raised to the replacement rule
+        let strategy = GeometricBump { factor: 1.01 };
+        assert_eq!(strategy.bump(&eip1559(100.0, 10.0)), eip1559(113.0, 12.0));
+    }
+
+    #[test]
+    fn additive() {
+        let strategy = AdditiveBump::from_gwei(1.0);
+        assert_eq!(
+            strategy.bump(&eip1559(10e9, 2e9)),
+            EstimatedGasPrice {
+                legacy: 11.25e9,
+                ..eip1559(11.25e9, 3e9)
+            }
+        );
+    }
+
+    #[test]
+    fn minimum_replacement() {
+        let previous = EstimatedGasPrice {
+            legacy: 8.0,
+            eip1559: None,
+        };
+        assert_eq!(
+            MinimumReplacementBump.bump(&previous),
+            EstimatedGasPrice {
+                legacy: 9.0,
+                eip1559: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,688 @@
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+use super::runtime::Runtime;
+use super::{
+    error::Result,
+    gas_price::StoredEstimate,
+    time::{Clock, Instant, SystemClock},
+    EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasEstimationError,
+    GasPriceEstimating, GasPriceSchedule, TransactionCost, TxSpec, DEFAULT_GAS_LIMIT,
+};
+use anyhow::anyhow;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// Caches estimates of the inner estimator for `ttl`. Estimates are cached per time limit and per
+// gas limit bucket so that similar requests share a cache entry.
+// When an entry has expired the first caller refreshes it. Other callers for the same entry get
+// the expired estimate while that refresh is in flight instead of sending more requests.
+// With `with_stale_while_revalidate` the first caller also gets the expired estimate and the
+// refresh runs in the background, until the estimate is older than the max staleness.
+pub struct CachedGasPriceEstimating<T> {
+    inner: Arc<T>,
+    ttl: Duration,
+    gas_limit_bucket: f64,
+    cache: Arc<Mutex<HashMap<Key, Entry>>>,
+    clock: Box<dyn Clock>,
+    // Expired estimates up to this age are returned while they are refreshed by `refresh`.
+    max_staleness: Option<Duration>,
+    refresh: Option<Box<Refresh>>,
+}
+
+// Callers usually use a few fixed limits so the cache only grows past this if the limits vary
+// between requests, for example with a time limit computed by the caller.
+const MAX_CACHED_ESTIMATES: usize = 64;
+
+// `None` is used for `estimate` because estimators can implement it differently from
+// `estimate_with_limits`.
+type Key = Option<(u64, Duration)>;
+
+// The gas limit and time limit of `estimate_with_limits`, `None` for `estimate`.
+type Limits = Option<(f64, Duration)>;
+
+// Spawns the refresh of the entry for the limits that was started at the instant. Verbose entries
+// are refreshed with `estimate_verbose` so they keep their metadata.
+type Refresh = dyn Fn(Key, Limits, bool, Instant) + Send + Sync;
+
+#[derive(Default)]
+struct Entry {
+    // The time at which the estimate was fetched.
+    time: Option<Instant>,
+    estimate: Option<StoredEstimate>,
+    // The time at which the refresh that is currently in flight was started. Refreshes older than
+    // the ttl are considered abandoned (for example because the caller dropped the future).
+    refreshing_since: Option<Instant>,
+}
+
+impl<T: GasPriceEstimating> CachedGasPriceEstimating<T> {
+    pub fn new(inner: T, ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            ttl,
+            gas_limit_bucket: DEFAULT_GAS_LIMIT,
+            cache: Default::default(),
+            clock: Box::new(SystemClock),
+            max_staleness: None,
+            refresh: None,
+        }
+    }
+
+    // Gas limits are rounded up to a multiple of this value to form the cache key.
+    pub fn with_gas_limit_bucket(self, gas_limit_bucket: f64) -> Self {
+        Self {
+            gas_limit_bucket,
+            ..self
+        }
+    }
+
+    // The time used to expire entries, the system clock by default.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            ..self
+        }
+    }
+
+    // Returns expired estimates right away and refreshes them in a task on `runtime`, so that a
+    // slow inner estimator only delays callers once an estimate is older than `max_staleness`.
+    // Then callers wait for the refresh and get its error if it fails.
+    #[cfg(any(feature = "tokio_", feature = "async-std_"))]
+    pub fn with_stale_while_revalidate(
+        self,
+        max_staleness: Duration,
+        runtime: Arc<dyn Runtime>,
+    ) -> Self
+    where
+        T: 'static,
+    {
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let refresh = move |key: Key, limits: Limits, verbose: bool, now: Instant| {
+            let inner = inner.clone();
+            let cache = cache.clone();
+            runtime.spawn(Box::pin(async move {
+                let plain = |price| StoredEstimate::Plain {
+                    price,
+                    observed_at: now,
+                };
+                let result = match limits {
+                    Some((gas_limit, time_limit)) if verbose => inner
+                        .estimate_verbose(gas_limit, time_limit)
+                        .await
+                        .map(StoredEstimate::Verbose),
+                    Some((gas_limit, time_limit)) => inner
+                        .estimate_with_limits(gas_limit, time_limit)
+                        .await
+                        .map(plain),
+                    None => inner.estimate().await.map(plain),
+                };
+                if let Err(err) = &result {
+                    tracing::warn!(?err, "failed to refresh cached estimate");
+                }
+                store(&cache, key, now, result.as_ref().ok());
+            }));
+        };
+        Self {
+            max_staleness: Some(max_staleness),
+            refresh: Some(Box::new(refresh)),
+            ..self
+        }
+    }
+
+    fn key(&self, limits: Limits) -> Key {
+        let (gas_limit, time_limit) = limits?;
+        let bucket = if self.gas_limit_bucket > 0.0 {
+            (gas_limit / self.gas_limit_bucket).ceil()
+        } else {
+            gas_limit
+        };
+        Some((bucket as u64, time_limit))
+    }
+
+    async fn estimate_with_cache<Fut>(
+        &self,
+        now: Instant,
+        limits: Limits,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<EstimatedGasPrice>
+    where
+        Fut: Future<Output = Result<EstimatedGasPrice>>,
+    {
+        let fetch = || async {
+            let price = fetch().await?;
+            Ok(StoredEstimate::Plain {
+                price,
+                observed_at: now,
+            })
+        };
+        self.stored_with_cache(now, limits, fetch)
+            .await
+            .map(|estimate| estimate.price())
+    }
+
+    // Serves `estimate_verbose`, plain and verbose calls for the same limits share the entry.
+    async fn verbose_with_cache<Fut>(
+        &self,
+        now: Instant,
+        limits: Limits,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<EstimateWithMetadata>
+    where
+        Fut: Future<Output = Result<EstimateWithMetadata>>,
+    {
+        let fetch = || async { Ok(StoredEstimate::Verbose(fetch().await?)) };
+        self.stored_with_cache(now, limits, fetch)
+            .await
+            .map(|estimate| estimate.with_metadata(|| self.inner.source()))
+    }
+
+    async fn stored_with_cache<Fut>(
+        &self,
+        now: Instant,
+        limits: Limits,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<StoredEstimate>
+    where
+        Fut: Future<Output = Result<StoredEstimate>>,
+    {
+        let key = self.key(limits);
+        match self.lookup(now, key) {
+            Lookup::Fresh(estimate) => return Ok(estimate),
+            Lookup::Stale(estimate) => {
+                let verbose = matches!(estimate, StoredEstimate::Verbose(_));
+                (self.refresh.as_ref().unwrap())(key, limits, verbose, now);
+                return Ok(estimate);
+            }
+            Lookup::Missing => (),
+        }
+
+        let result = fetch().await;
+        store(&self.cache, key, now, result.as_ref().ok());
+        result
+    }
+
+    // Serves several limits with one call of the inner estimator. `fetch` is called with the
+    // limits that are not cached and returns estimates for them, and possibly for other limits
+    // that came with the same response. Expired entries are fetched right away instead of being
+    // refreshed in the background.
+    async fn many_with_cache<Fut>(
+        &self,
+        now: Instant,
+        requests: &[(f64, Duration)],
+        fetch: impl FnOnce(Vec<(f64, Duration)>) -> Fut,
+    ) -> Result<Vec<EstimatedGasPrice>>
+    where
+        Fut: Future<Output = Result<Vec<((f64, Duration), EstimatedGasPrice)>>>,
+    {
+        let mut estimates = requests
+            .iter()
+            .map(|limits| match self.lookup(now, self.key(Some(*limits))) {
+                Lookup::Fresh(estimate) => Some(estimate.price()),
+                Lookup::Stale(_) | Lookup::Missing => None,
+            })
+            .collect::<Vec<_>>();
+        let missing = requests
+            .iter()
+            .zip(&estimates)
+            .filter(|(_, estimate)| estimate.is_none())
+            .map(|(limits, _)| *limits)
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(estimates.into_iter().flatten().collect());
+        }
+
+        let fetched = fetch(missing.clone()).await;
+        let fetched = match fetched {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                for limits in missing {
+                    store(&self.cache, self.key(Some(limits)), now, None);
+                }
+                return Err(err);
+            }
+        };
+        let fetched = fetched
+            .into_iter()
+            .map(|(limits, price)| (self.key(Some(limits)), price))
+            .collect::<HashMap<_, _>>();
+        for (key, price) in &fetched {
+            let estimate = StoredEstimate::Plain {
+                price: *price,
+                observed_at: now,
+            };
+            store(&self.cache, *key, now, Some(&estimate));
+        }
+        for (limits, estimate) in requests.iter().zip(&mut estimates) {
+            if estimate.is_none() {
+                let price = fetched.get(&self.key(Some(*limits))).ok_or_else(|| {
+                    GasEstimationError::Decode(anyhow!("no estimate for {:?}", limits))
+                })?;
+                *estimate = Some(*price);
+            }
+        }
+        Ok(estimates.into_iter().flatten().collect())
+    }
+
+    // Looks up the entry and marks it as refreshing if the caller has to fetch it, or has to start
+    // the background refresh for `Stale`.
+    fn lookup(&self, now: Instant, key: Key) -> Lookup {
+        let mut cache = self.cache.lock().unwrap();
+        let entry = entry_bounded(&mut cache, key);
+        let is_younger = |time: Option<Instant>, age: Duration| {
+            time.is_some_and(|time| now.saturating_duration_since(time) < age)
+        };
+        let is_fresh = |time: Option<Instant>| is_younger(time, self.ttl);
+        let is_usable = |time: Option<Instant>| match self.max_staleness {
+            Some(max_staleness) => is_younger(time, max_staleness.max(self.ttl)),
+            None => true,
+        };
+        match entry.estimate {
+            Some(estimate) if is_fresh(entry.time) => Lookup::Fresh(estimate),
+            Some(estimate) if is_usable(entry.time) && is_fresh(entry.refreshing_since) => {
+                Lookup::Fresh(estimate)
+            }
+            Some(estimate) if is_usable(entry.time) && self.refresh.is_some() => {
+                entry.refreshing_since = Some(now);
+                Lookup::Stale(estimate)
+            }
+            _ => {
+                entry.refreshing_since = Some(now);
+                Lookup::Missing
+            }
+        }
+    }
+}
+
+enum Lookup {
+    // Fresh, or expired while another caller refreshes it.
+    Fresh(StoredEstimate),
+    // Expired and to be refreshed in the background.
+    Stale(StoredEstimate),
+    Missing,
+}
+
+// Stores the result of a refresh started at `now`, failed refreshes (`None`) keep the previous
+// estimate.
+fn store(
+    cache: &Mutex<HashMap<Key, Entry>>,
+    key: Key,
+    now: Instant,
+    estimate: Option<&StoredEstimate>,
+) {
+    let mut cache = cache.lock().unwrap();
+    let entry = entry_bounded(&mut cache, key);
+    entry.refreshing_since = None;
+    if let Some(estimate) = estimate {
+        entry.time = Some(now);
+        entry.estimate = Some(*estimate);
+    }
+}
+
+// Returns the entry for the key, evicting the least recently used entry if a new entry would grow
+// the cache past `MAX_CACHED_ESTIMATES`.
+fn entry_bounded(cache: &mut HashMap<Key, Entry>, key: Key) -> &mut Entry {
+    if cache.len() >= MAX_CACHED_ESTIMATES && !cache.contains_key(&key) {
+        let oldest = cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.time.max(entry.refreshing_since))
+            .map(|(key, _)| *key);
+        if let Some(oldest) = oldest {
+            cache.remove(&oldest);
+        }
+    }
+    cache.entry(key).or_default()
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for CachedGasPriceEstimating<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.estimate_with_cache(self.clock.now(), Some((gas_limit, time_limit)), || {
+            self.inner.estimate_with_limits(gas_limit, time_limit)
+        })
+        .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.estimate_with_cache(self.clock.now(), None, || self.inner.estimate())
+            .await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.verbose_with_cache(self.clock.now(), Some((gas_limit, time_limit)), || {
+            self.inner.estimate_verbose(gas_limit, time_limit)
+        })
+        .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        // Hinted estimates are for one transaction and are not cached.
+        self.inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await
+    }
+
+    // Transaction costs depend on the transaction data and are not cached.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.inner.estimate_for_tx(tx).await
+    }
+
+    // Shares the entries of `estimate_with_limits` for the urgencies with the default gas limit.
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let requests = GasPriceSchedule::requests();
+        let estimates = self
+            .many_with_cache(self.clock.now(), &requests, |_| async {
+                let schedule = self.inner.estimate_schedule().await?;
+                Ok(GasPriceSchedule::requests()
+                    .into_iter()
+                    .zip(schedule.entries().map(|(_, estimate)| estimate))
+                    .collect())
+            })
+            .await?;
+        GasPriceSchedule::from_requested(estimates)
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.many_with_cache(self.clock.now(), requests, |missing| async move {
+            let estimates = self.inner.estimate_many(&missing).await?;
+            Ok(missing.into_iter().zip(estimates).collect())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    #[cfg(feature = "tokio_")]
+    use super::super::{testing::FixedGasPriceEstimator, GasEstimationError};
+    use super::*;
+    use anyhow::anyhow;
+    use futures::FutureExt;
+    use std::future::{ready, Pending};
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    fn panic_future() -> Pending<Result<EstimatedGasPrice>> {
+        panic!()
+    }
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    fn cached() -> CachedGasPriceEstimating<MockGasPriceEstimating> {
+        CachedGasPriceEstimating::new(MockGasPriceEstimating::new(), TTL)
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entry() {
+        let cached = cached();
+        let now = Instant::now();
+        let limits = |i: usize| Some((21000.0, Duration::from_millis(i as u64)));
+        for i in 0..=MAX_CACHED_ESTIMATES {
+            cached
+                .estimate_with_cache(now + Duration::from_millis(i as u64), limits(i), || {
+                    ready(Ok(price(i as f64)))
+                })
+                .wait()
+                .unwrap();
+        }
+        let cache = cached.cache.lock().unwrap();
+        assert_eq!(cache.len(), MAX_CACHED_ESTIMATES);
+        assert!(!cache.contains_key(&cached.key(limits(0))));
+        assert!(cache.contains_key(&cached.key(limits(1))));
+    }
+
+    #[test]
+    fn cache_works_ok() {
+        let cached = cached();
+        let now = Instant::now();
+        let key = Some((21000.0, TTL));
+
+        cached
+            .estimate_with_cache(now, key, || ready(Ok(price(1.0))))
+            .wait()
+            .unwrap();
+        // panic_future isn't called
+        assert_eq!(
+            cached
+                .estimate_with_cache(now + TTL / 2, key, panic_future)
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+
+        // cache gets updated after expiry
+        let now = now + TTL;
+        assert_eq!(
+            cached
+                .estimate_with_cache(now, key, || ready(Ok(price(2.0))))
+                .wait()
+                .unwrap(),
+            price(2.0)
+        );
+    }
+
+    #[test]
+    fn ttl_uses_clock() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(2)
+            .returning(|| Ok(price(1.0)));
+        let clock = crate::time::tests::ManualClock::default();
+        let cached = CachedGasPriceEstimating::new(inner, TTL).with_clock(clock.clone());
+        cached.estimate().wait().unwrap();
+        clock.advance(TTL / 2);
+        cached.estimate().wait().unwrap();
+        clock.advance(TTL);
+        cached.estimate().wait().unwrap();
+    }
+
+    #[test]
+    fn keys_are_bucketed() {
+        let cached = cached().with_gas_limit_bucket(10_000.0);
+        assert_eq!(
+            cached.key(Some((1.0, TTL))),
+            cached.key(Some((10_000.0, TTL)))
+        );
+        assert_ne!(
+            cached.key(Some((10_000.0, TTL))),
+            cached.key(Some((10_001.0, TTL)))
+        );
+        assert_ne!(
+            cached.key(Some((1.0, TTL))),
+            cached.key(Some((1.0, 2 * TTL)))
+        );
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let cached = cached();
+        let now = Instant::now();
+        let key = Some((21000.0, TTL));
+
+        assert!(cached
+            .estimate_with_cache(now, key, || ready(Err(anyhow!("").into())))
+            .wait()
+            .is_err());
+        assert_eq!(
+            cached
+                .estimate_with_cache(now, key, || ready(Ok(price(1.0))))
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+    }
+
+    #[test]
+    fn serves_stale_while_refreshing() {
+        let cached = cached();
+        let now = Instant::now();
+        let key = Some((21000.0, TTL));
+        cached
+            .estimate_with_cache(now, key, || ready(Ok(price(1.0))))
+            .wait()
+            .unwrap();
+
+        // start a refresh that never completes
+        let now = now + TTL;
+        let pending = cached.estimate_with_cache(now, key, futures::future::pending);
+        futures::pin_mut!(pending);
+        assert!(pending.as_mut().now_or_never().is_none());
+
+        // panic_future isn't called
+        assert_eq!(
+            cached
+                .estimate_with_cache(now, key, panic_future)
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+
+        // abandoned refreshes expire
+        assert_eq!(
+            cached
+                .estimate_with_cache(now + TTL, key, || ready(Ok(price(2.0))))
+                .wait()
+                .unwrap(),
+            price(2.0)
+        );
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn revalidates_in_background() {
+        let clock = crate::time::tests::ManualClock::default();
+        let cached = CachedGasPriceEstimating::new(FixedGasPriceEstimator::legacy(1.0), TTL)
+            .with_clock(clock.clone())
+            .with_stale_while_revalidate(3 * TTL, Arc::new(crate::runtime::TokioRuntime));
+        assert_eq!(cached.estimate().await.unwrap(), price(1.0));
+
+        // the expired estimate is returned and refreshed once
+        cached.inner.set(price(2.0));
+        clock.advance(TTL);
+        assert_eq!(cached.estimate().await.unwrap(), price(1.0));
+        assert_eq!(cached.estimate().await.unwrap(), price(1.0));
+        while cached.inner.calls() < 2 {
+            tokio::task::yield_now().await;
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(cached.estimate().await.unwrap(), price(2.0));
+        assert_eq!(cached.inner.calls(), 2);
+
+        // estimates older than the max staleness aren't returned
+        clock.advance(3 * TTL);
+        cached.inner.push_result(Err(GasEstimationError::Timeout));
+        assert!(cached.estimate().await.is_err());
+        assert_eq!(cached.estimate().await.unwrap(), price(2.0));
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn revalidates_verbose_estimates_with_metadata() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let clock = crate::time::tests::ManualClock::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut inner = MockGasPriceEstimating::new();
+        inner.expect_estimate_verbose().times(2).returning({
+            let calls = calls.clone();
+            move |_, _| {
+                let legacy = (calls.fetch_add(1, Ordering::SeqCst) + 1) as f64;
+                Ok(EstimateWithMetadata {
+                    confidence: Some(0.9),
+                    ..EstimateWithMetadata::new(price(legacy), "mock", Instant::now())
+                })
+            }
+        });
+        let cached = CachedGasPriceEstimating::new(inner, TTL)
+            .with_clock(clock.clone())
+            .with_stale_while_revalidate(3 * TTL, Arc::new(crate::runtime::TokioRuntime));
+        let estimate = || cached.estimate_verbose(21000.0, TTL);
+        assert_eq!(estimate().await.unwrap().price, price(1.0));
+
+        clock.advance(TTL);
+        assert_eq!(estimate().await.unwrap().price, price(1.0));
+        while calls.load(Ordering::SeqCst) < 2 {
+            tokio::task::yield_now().await;
+        }
+        tokio::task::yield_now().await;
+        let refreshed = estimate().await.unwrap();
+        assert_eq!(refreshed.price, price(2.0));
+        assert_eq!(refreshed.confidence, Some(0.9));
+    }
+
+    #[test]
+    fn uses_inner_estimator() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(1)
+            .returning(|| Ok(price(1.0)));
+        let cached = CachedGasPriceEstimating::new(inner, TTL);
+        assert_eq!(cached.estimate().wait().unwrap(), price(1.0));
+        assert_eq!(cached.estimate().wait().unwrap(), price(1.0));
+    }
+
+    #[test]
+    fn estimate_many_fetches_missing_entries_in_one_call() {
+        let mut inner = MockGasPriceEstimating::new();
+        let fast = (21000.0, Duration::from_secs(15));
+        let slow = (21000.0, Duration::from_secs(600));
+        inner
+            .expect_estimate_many()
+            .withf(move |requests| requests == [fast, slow])
+            .times(1)
+            .returning(|_| Ok(vec![price(2.0), price(1.0)]));
+        inner
+            .expect_estimate_many()
+            .withf(|requests| requests == [(100_000.0, Duration::from_secs(15))])
+            .times(1)
+            .returning(|_| Ok(vec![price(3.0)]));
+        let cached = CachedGasPriceEstimating::new(inner, TTL);
+        assert_eq!(
+            cached.estimate_many(&[fast, slow]).wait().unwrap(),
+            vec![price(2.0), price(1.0)]
+        );
+        assert_eq!(
+            cached
+                .estimate_many(&[slow, (100_000.0, Duration::from_secs(15)), fast])
+                .wait()
+                .unwrap(),
+            vec![price(1.0), price(3.0), price(2.0)]
+        );
+        // shares the entries with estimate_with_limits
+        assert_eq!(
+            cached.estimate_with_limits(21000.0, slow.1).wait().unwrap(),
+            price(1.0)
+        );
+    }
+}
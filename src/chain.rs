@@ -0,0 +1,157 @@
+//! Properties of the fee markets of the supported chains that estimators need to turn time limits
+//! into urgency.
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    // average time between blocks
+    pub block_time: Duration,
+    // lowest priority fee (or gas price on chains without base fee) in wei the chain accepts
+    pub min_priority_fee: f64,
+    // Whether blocks have an EIP-1559 base fee. Without one only legacy prices are estimated.
+    pub supports_eip1559: bool,
+}
+
+pub const ETHEREUM: ChainConfig = ChainConfig {
+    chain_id: 1,
+    block_time: Duration::from_secs(12),
+    // Fee history rewards can be close to zero in quiet periods but transactions with such tips
+    // often are not included.
+    min_priority_fee: 1e9,
+    supports_eip1559: true,
+};
+
+pub const RINKEBY: ChainConfig = ChainConfig {
+    chain_id: 4,
+    block_time: Duration::from_secs(15),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+pub const OPTIMISM: ChainConfig = ChainConfig {
+    chain_id: 10,
+    block_time: Duration::from_secs(2),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+// The base fee is always zero and validators enforce a minimum gas price instead.
+pub const BSC: ChainConfig = ChainConfig {
+    chain_id: 56,
+    block_time: Duration::from_secs(3),
+    min_priority_fee: 1e9,
+    supports_eip1559: false,
+};
+
+pub const GNOSIS_CHAIN: ChainConfig = ChainConfig {
+    chain_id: 100,
+    block_time: Duration::from_secs(5),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+// Validators reject transactions with a priority fee below 30 gwei.
+pub const POLYGON: ChainConfig = ChainConfig {
+    chain_id: 137,
+    block_time: Duration::from_secs(2),
+    min_priority_fee: 30e9,
+    supports_eip1559: true,
+};
+
+pub const FANTOM: ChainConfig = ChainConfig {
+    chain_id: 250,
+    block_time: Duration::from_secs(1),
+    min_priority_fee: 1e9,
+    supports_eip1559: true,
+};
+
+pub const ZKSYNC_ERA: ChainConfig = ChainConfig {
+    chain_id: 324,
+    block_time: Duration::from_secs(1),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+pub const BASE: ChainConfig = ChainConfig {
+    chain_id: 8453,
+    ..OPTIMISM
+};
+
+pub const ARBITRUM: ChainConfig = ChainConfig {
+    chain_id: 42161,
+    block_time: Duration::from_millis(250),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+// Blocks in quiet periods often contain only transactions without tip, which would make the
+// estimate zero.
+pub const AVALANCHE: ChainConfig = ChainConfig {
+    chain_id: 43114,
+    block_time: Duration::from_secs(2),
+    min_priority_fee: 1e9,
+    supports_eip1559: true,
+};
+
+pub const LINEA: ChainConfig = ChainConfig {
+    chain_id: 59144,
+    block_time: Duration::from_secs(2),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+pub const POLYGON_MUMBAI: ChainConfig = ChainConfig {
+    chain_id: 80001,
+    ..POLYGON
+};
+
+pub const SCROLL: ChainConfig = ChainConfig {
+    chain_id: 534352,
+    block_time: Duration::from_secs(3),
+    min_priority_fee: 0.0,
+    supports_eip1559: true,
+};
+
+const ALL: &[ChainConfig] = &[
+    ETHEREUM,
+    RINKEBY,
+    OPTIMISM,
+    BSC,
+    GNOSIS_CHAIN,
+    POLYGON,
+    FANTOM,
+    ZKSYNC_ERA,
+    BASE,
+    ARBITRUM,
+    AVALANCHE,
+    LINEA,
+    POLYGON_MUMBAI,
+    SCROLL,
+];
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        ETHEREUM
+    }
+}
+
+impl ChainConfig {
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        ALL.iter().find(|chain| chain.chain_id == chain_id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_ids_are_unique() {
+        for chain in ALL {
+            assert_eq!(ChainConfig::from_chain_id(chain.chain_id), Some(*chain));
+        }
+        assert_eq!(ChainConfig::from_chain_id(2), None);
+    }
+}
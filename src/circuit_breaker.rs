@@ -0,0 +1,351 @@
+use super::{
+    error::Result, time::Instant, EstimateWithMetadata, EstimatedGasPrice, EstimationHints,
+    GasEstimationError, GasPriceEstimating, GasPriceSchedule, TransactionCost, TxSpec,
+};
+use std::{future::Future, sync::Mutex, time::Duration};
+
+/// Parameters for the circuit breaker.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // number of consecutive failures after which the circuit opens
+    pub failure_threshold: usize,
+    // time the circuit stays open before a single probe request is let through
+    pub cool_down: Duration,
+    // Returned instead of an error while the circuit is open. Not used for `estimate_for_tx`
+    // because the l1 fee of the transaction is unknown.
+    pub fallback: Option<EstimatedGasPrice>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+            fallback: None,
+        }
+    }
+}
+
+// Stops calling the inner estimator after it failed `failure_threshold` times in a row. While the
+// circuit is open calls fail immediately with `GasEstimationError::CircuitOpen` (or return the
+// fallback) so that for example
+// `PriorityGasPriceEstimating` moves on to the next estimator without waiting for a timeout.
+// After the cool down one call is let through to probe whether the estimator has recovered.
+pub struct CircuitBreakerEstimator<T> {
+    inner: T,
+    params: Params,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    failures_in_a_row: usize,
+    // Set while the circuit is open. After this time the circuit is half open.
+    open_until: Option<Instant>,
+    // The time at which the probe that is currently in flight was started. Probes older than the
+    // cool down are considered abandoned (for example because the caller dropped the future).
+    probing_since: Option<Instant>,
+}
+
+impl<T: GasPriceEstimating> CircuitBreakerEstimator<T> {
+    pub fn new(inner: T, params: Option<Params>) -> Self {
+        Self {
+            inner,
+            params: params.unwrap_or_default(),
+            state: Default::default(),
+        }
+    }
+
+    async fn estimate_with_breaker<Fut>(
+        &self,
+        now: Instant,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<EstimatedGasPrice>
+    where
+        Fut: Future<Output = Result<EstimatedGasPrice>>,
+    {
+        self.call_with_breaker(now, fetch, Some).await
+    }
+
+    // Like `estimate_with_breaker` for any call of the inner estimator, `from_fallback` turns the
+    // fallback estimate into the result of the call or returns `None` if it can't be used.
+    async fn call_with_breaker<Fut, R>(
+        &self,
+        now: Instant,
+        fetch: impl FnOnce() -> Fut,
+        from_fallback: impl FnOnce(EstimatedGasPrice) -> Option<R>,
+    ) -> Result<R>
+    where
+        Fut: Future<Output = Result<R>>,
+    {
+        let probing = {
+            let mut state = self.state.lock().unwrap();
+            match state.open_until {
+                Some(open_until) if now < open_until => {
+                    return self.short_circuit(Some(open_until - now), from_fallback)
+                }
+                Some(_) => {
+                    let probe_in_flight = state.probing_since.is_some_and(|probing_since| {
+                        now.saturating_duration_since(probing_since) < self.params.cool_down
+                    });
+                    if probe_in_flight {
+                        return self.short_circuit(None, from_fallback);
+                    }
+                    state.probing_since = Some(now);
+                    true
+                }
+                None => false,
+            }
+        };
+
+        let result = fetch().await;
+        let mut state = self.state.lock().unwrap();
+        match &result {
+            Ok(_) => *state = State::default(),
+            Err(_) => {
+                state.failures_in_a_row += 1;
+                state.probing_since = None;
+                if probing || state.failures_in_a_row >= self.params.failure_threshold {
+                    if !probing {
+                        tracing::warn!(
+                            "opening circuit breaker after {} failures",
+                            state.failures_in_a_row
+                        );
+                    }
+                    state.open_until = Some(now + self.params.cool_down);
+                }
+            }
+        }
+        result
+    }
+
+    fn short_circuit<R>(
+        &self,
+        retry_after: Option<Duration>,
+        from_fallback: impl FnOnce(EstimatedGasPrice) -> Option<R>,
+    ) -> Result<R> {
+        self.params
+            .fallback
+            .and_then(from_fallback)
+            .ok_or(GasEstimationError::CircuitOpen { retry_after })
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for CircuitBreakerEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.estimate_with_breaker(Instant::now(), || {
+            self.inner.estimate_with_limits(gas_limit, time_limit)
+        })
+        .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.estimate_with_breaker(Instant::now(), || self.inner.estimate())
+            .await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.call_with_breaker(
+            Instant::now(),
+            || self.inner.estimate_verbose(gas_limit, time_limit),
+            |fallback| {
+                Some(EstimateWithMetadata::new(
+                    fallback,
+                    "fallback",
+                    Instant::now(),
+                ))
+            },
+        )
+        .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.call_with_breaker(
+            Instant::now(),
+            || self.inner.estimate_with_hints(gas_limit, time_limit, hints),
+            |fallback| Some(hints.adjust(fallback)),
+        )
+        .await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.call_with_breaker(Instant::now(), || self.inner.estimate_for_tx(tx), |_| None)
+            .await
+    }
+
+    // While the circuit is open the fallback is used for all urgencies.
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.call_with_breaker(
+            Instant::now(),
+            || self.inner.estimate_schedule(),
+            |fallback| Some(GasPriceSchedule::default().map(|_, _| fallback)),
+        )
+        .await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.call_with_breaker(
+            Instant::now(),
+            || self.inner.estimate_many(requests),
+            |fallback| Some(vec![fallback; requests.len()]),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+    use anyhow::anyhow;
+    use std::future::{ready, Pending};
+
+    const COOL_DOWN: Duration = Duration::from_secs(10);
+
+    fn panic_future() -> Pending<Result<EstimatedGasPrice>> {
+        panic!()
+    }
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    fn breaker(
+        fallback: Option<EstimatedGasPrice>,
+    ) -> CircuitBreakerEstimator<MockGasPriceEstimating> {
+        CircuitBreakerEstimator::new(
+            MockGasPriceEstimating::new(),
+            Some(Params {
+                failure_threshold: 2,
+                cool_down: COOL_DOWN,
+                fallback,
+            }),
+        )
+    }
+
+    fn fail(breaker: &CircuitBreakerEstimator<MockGasPriceEstimating>, now: Instant) {
+        assert!(breaker
+            .estimate_with_breaker(now, || ready(Err(anyhow!("").into())))
+            .wait()
+            .is_err());
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker(None);
+        let now = Instant::now();
+        fail(&breaker, now);
+        // a success resets the count
+        breaker
+            .estimate_with_breaker(now, || ready(Ok(price(1.0))))
+            .wait()
+            .unwrap();
+        fail(&breaker, now);
+        fail(&breaker, now);
+        // panic_future isn't called
+        assert!(matches!(
+            breaker
+                .estimate_with_breaker(now + COOL_DOWN / 2, panic_future)
+                .wait(),
+            Err(GasEstimationError::CircuitOpen {
+                retry_after: Some(retry_after)
+            }) if retry_after == COOL_DOWN / 2
+        ));
+    }
+
+    #[test]
+    fn returns_fallback_while_open() {
+        let breaker = breaker(Some(price(2.0)));
+        let now = Instant::now();
+        fail(&breaker, now);
+        fail(&breaker, now);
+        assert_eq!(
+            breaker
+                .estimate_with_breaker(now, panic_future)
+                .wait()
+                .unwrap(),
+            price(2.0)
+        );
+    }
+
+    #[test]
+    fn half_open_probe_closes_on_success() {
+        let breaker = breaker(None);
+        let now = Instant::now();
+        fail(&breaker, now);
+        fail(&breaker, now);
+        let now = now + COOL_DOWN;
+        assert_eq!(
+            breaker
+                .estimate_with_breaker(now, || ready(Ok(price(1.0))))
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+        // closed again, a single failure does not open it
+        fail(&breaker, now);
+        breaker
+            .estimate_with_breaker(now, || ready(Ok(price(1.0))))
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn half_open_probe_reopens_on_failure() {
+        let breaker = breaker(None);
+        let now = Instant::now();
+        fail(&breaker, now);
+        fail(&breaker, now);
+        let now = now + COOL_DOWN;
+        fail(&breaker, now);
+        assert!(breaker
+            .estimate_with_breaker(now + COOL_DOWN / 2, panic_future)
+            .wait()
+            .is_err());
+    }
+
+    #[test]
+    fn only_one_probe_at_a_time() {
+        let breaker = breaker(None);
+        let now = Instant::now();
+        fail(&breaker, now);
+        fail(&breaker, now);
+        let now = now + COOL_DOWN;
+        breaker.state.lock().unwrap().probing_since = Some(now);
+        assert!(matches!(
+            breaker.estimate_with_breaker(now, panic_future).wait(),
+            Err(GasEstimationError::CircuitOpen { retry_after: None })
+        ));
+        // abandoned probes expire
+        breaker
+            .estimate_with_breaker(now + COOL_DOWN, || ready(Ok(price(1.0))))
+            .wait()
+            .unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+//! Native token prices from the CoinGecko simple price api, documentation at
+//! https://docs.coingecko.com/reference/simple-price .
+
+use super::{
+    auth,
+    error::Result,
+    fiat::{Currency, NativeTokenPriceEstimating},
+    ApiCredentials, GasEstimationError, Transport,
+};
+use anyhow::anyhow;
+use std::collections::HashMap;
+
+const API_URI: &str = "https://api.coingecko.com/api/v3/simple/price";
+
+// coin id -> currency code -> price
+type Response = HashMap<String, HashMap<String, f64>>;
+
+pub struct CoinGeckoPriceEstimator<T> {
+    transport: T,
+    coin_id: String,
+    credentials: Option<ApiCredentials>,
+}
+
+impl<T: Transport> CoinGeckoPriceEstimator<T> {
+    /// `coin_id` is the CoinGecko id of the native token, for example "ethereum" or "xdai".
+    pub fn new(transport: T, coin_id: impl Into<String>) -> Self {
+        Self {
+            transport,
+            coin_id: coin_id.into(),
+            credentials: None,
+        }
+    }
+
+    /// Credentials sent with every request, for example the `x-cg-pro-api-key` header of the pro api.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    fn url(&self, currency: Currency) -> String {
+        format!(
+            "{}?ids={}&vs_currencies={}",
+            API_URI,
+            self.coin_id,
+            currency.code()
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> NativeTokenPriceEstimating for CoinGeckoPriceEstimator<T> {
+    async fn native_token_price(&self, currency: Currency) -> Result<f64> {
+        let (url, header) = auth::authorize(
+            self.credentials.as_ref(),
+            &self.url(currency),
+            Default::default(),
+        )?;
+        let response: Response = self
+            .transport
+            .get_json(&url, header)
+            .await
+            .map_err(|err| err.context("failed to get coingecko price"))?;
+        price(&response, &self.coin_id, currency)
+    }
+}
+
+fn price(response: &Response, coin_id: &str, currency: Currency) -> Result<f64> {
+    response
+        .get(coin_id)
+        .and_then(|prices| prices.get(currency.code()))
+        .copied()
+        .ok_or_else(|| {
+            GasEstimationError::Decode(anyhow!(
+                "no {} price for {} in coingecko response",
+                currency.code(),
+                coin_id
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::TestTransport;
+    use super::*;
+
+    #[test]
+    fn decodes_price() {
+        let response: Response = serde_json::from_str(r#"{"ethereum":{"usd":1234.5}}"#).unwrap();
+        assert_eq!(price(&response, "ethereum", Currency::Usd).unwrap(), 1234.5);
+        assert!(price(&response, "ethereum", Currency::Eur).is_err());
+        assert!(price(&response, "xdai", Currency::Usd).is_err());
+    }
+
+    #[test]
+    fn builds_url() {
+        let estimator = CoinGeckoPriceEstimator::new(TestTransport::default(), "xdai");
+        assert_eq!(
+            estimator.url(Currency::Eur),
+            "https://api.coingecko.com/api/v3/simple/price?ids=xdai&vs_currencies=eur"
+        );
+    }
+
+    // cargo test coingecko --features coingecko -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = CoinGeckoPriceEstimator::new(TestTransport::default(), "ethereum");
+        println!("{:?}", estimator.native_token_price(Currency::Usd).await);
+    }
+}
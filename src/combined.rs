@@ -0,0 +1,254 @@
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasPriceEstimating,
+    TransactionCost, TxSpec,
+};
+use anyhow::anyhow;
+use futures::future::join_all;
+use std::{future::Future, time::Duration};
+
+// Queries all estimators concurrently and picks the estimate at the configured quantile of the
+// successful results ordered by effective gas price. Picking an existing estimate instead of
+// combining the individual fields keeps the result consistent with what one estimator returned.
+pub struct MedianGasPriceEstimating {
+    estimators: Vec<Box<dyn GasPriceEstimating>>,
+    min_successes: usize,
+    quantile: f64,
+}
+
+impl MedianGasPriceEstimating {
+    // Errors if fewer than `min_successes` estimators succeed.
+    pub fn new(estimators: Vec<Box<dyn GasPriceEstimating>>, min_successes: usize) -> Self {
+        Self {
+            estimators,
+            min_successes: min_successes.max(1),
+            quantile: 0.5,
+        }
+    }
+
+    // Use a quantile other than the median. 0.0 picks the cheapest and 1.0 the most expensive
+    // estimate.
+    pub fn with_quantile(self, quantile: f64) -> Self {
+        Self {
+            quantile: quantile.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    async fn combine<'a, T, F, R>(
+        &'a self,
+        operation: T,
+        price: impl Fn(&R) -> EstimatedGasPrice,
+    ) -> Result<R>
+    where
+        T: Fn(&'a dyn GasPriceEstimating) -> F,
+        F: Future<Output = Result<R>>,
+        R: Copy,
+    {
+        let results = join_all(
+            self.estimators
+                .iter()
+                .map(|estimator| operation(estimator.as_ref())),
+        )
+        .await;
+        let mut estimates = results
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, result)| match result {
+                Ok(estimate) => Some(estimate),
+                Err(err) => {
+                    tracing::warn!("gas estimator {} failed: {:?}", i, err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if estimates.len() < self.min_successes {
+            return Err(anyhow!(
+                "only {} of {} required gas estimators succeeded",
+                estimates.len(),
+                self.min_successes
+            )
+            .into());
+        }
+        estimates.sort_by(|a, b| {
+            price(a)
+                .effective_gas_price()
+                .partial_cmp(&price(b).effective_gas_price())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let index = ((estimates.len() - 1) as f64 * self.quantile).round() as usize;
+        estimates
+            .get(index)
+            .copied()
+            .ok_or_else(|| anyhow!("no gas estimate").into())
+    }
+}
+
+#[async_trait::async_trait]
+impl GasPriceEstimating for MedianGasPriceEstimating {
+    fn source(&self) -> &'static str {
+        "median"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.combine(
+            |estimator| estimator.estimate_with_limits(gas_limit, time_limit),
+            |estimate| *estimate,
+        )
+        .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.combine(|estimator| estimator.estimate(), |estimate| *estimate)
+            .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.combine(
+            |estimator| estimator.estimate_with_hints(gas_limit, time_limit, hints),
+            |estimate| *estimate,
+        )
+        .await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.combine(
+            |estimator| estimator.estimate_verbose(gas_limit, time_limit),
+            |estimate| estimate.price,
+        )
+        .await
+    }
+
+    // Ordered by gas price, the l1 fee is the one of the picked estimator.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.combine(
+            |estimator| estimator.estimate_for_tx(tx),
+            |cost| cost.gas_price,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use futures::future::FutureExt;
+
+    fn estimator(result: Option<f64>) -> Box<dyn GasPriceEstimating> {
+        let mut estimator = MockGasPriceEstimating::new();
+        estimator
+            .expect_estimate()
+            .times(1)
+            .returning(move || match result {
+                Some(legacy) => Ok(EstimatedGasPrice {
+                    legacy,
+                    ..Default::default()
+                }),
+                None => Err(anyhow!("").into()),
+            });
+        Box::new(estimator)
+    }
+
+    #[test]
+    fn picks_median() {
+        let median = MedianGasPriceEstimating::new(
+            vec![
+                estimator(Some(3.0)),
+                estimator(Some(100.0)),
+                estimator(Some(1.0)),
+            ],
+            1,
+        );
+        let result = median.estimate().now_or_never().unwrap().unwrap();
+        assert_approx_eq!(result.legacy, 3.0);
+    }
+
+    #[test]
+    fn ignores_failures() {
+        let median = MedianGasPriceEstimating::new(
+            vec![estimator(None), estimator(Some(2.0)), estimator(Some(2.0))],
+            2,
+        );
+        let result = median.estimate().now_or_never().unwrap().unwrap();
+        assert_approx_eq!(result.legacy, 2.0);
+    }
+
+    #[test]
+    fn picks_quantile() {
+        let median = MedianGasPriceEstimating::new(
+            vec![
+                estimator(Some(3.0)),
+                estimator(Some(1.0)),
+                estimator(Some(2.0)),
+            ],
+            1,
+        )
+        .with_quantile(1.0);
+        let result = median.estimate().now_or_never().unwrap().unwrap();
+        assert_approx_eq!(result.legacy, 3.0);
+    }
+
+    fn verbose_estimator(legacy: f64, source: &'static str) -> Box<dyn GasPriceEstimating> {
+        let mut estimator = MockGasPriceEstimating::new();
+        estimator
+            .expect_estimate_verbose()
+            .times(1)
+            .returning(move |_, _| {
+                Ok(EstimateWithMetadata {
+                    price: EstimatedGasPrice {
+                        legacy,
+                        ..Default::default()
+                    },
+                    source,
+                    observed_at: crate::time::Instant::now(),
+                    confidence: None,
+                    divergence: None,
+                    range: None,
+                })
+            });
+        Box::new(estimator)
+    }
+
+    #[test]
+    fn verbose_reports_source_of_picked_estimate() {
+        let median = MedianGasPriceEstimating::new(
+            vec![
+                verbose_estimator(3.0, "a"),
+                verbose_estimator(1.0, "b"),
+                verbose_estimator(2.0, "c"),
+            ],
+            1,
+        );
+        let result = median
+            .estimate_verbose(0.0, Duration::ZERO)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.source, "c");
+        assert_approx_eq!(result.price.legacy, 2.0);
+    }
+
+    #[test]
+    fn fails_if_not_enough_succeed() {
+        let median = MedianGasPriceEstimating::new(
+            vec![estimator(None), estimator(Some(1.0)), estimator(None)],
+            2,
+        );
+        let result = median.estimate().now_or_never().unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Estimator stacks described in TOML or YAML so that operators can change gas sources without a
+//! new release. For example
+//!
+//! ```toml
+//! cache_ttl = 10
+//! max_gas_price_gwei = 1500
+//!
+//! [[sources]]
+//! type = "etherscan"
+//! api_key = { env = "ETHERSCAN_API_KEY" }
+//!
+//! [[sources]]
+//! type = "gas_now"
+//! ```
+//!
+//! Sources are tried in the order they are listed, the decorators are applied like
+//! `EstimatorBuilder` applies them.
+
+use super::{
+    error::Result, generic_http, ApiCredentials, ChainConfig, ConfigurableEstimator,
+    EstimatorBuilder, EthGasStation, EtherscanGasStation, GasEstimationError, GasNowGasStation,
+    GasPriceEstimating, GasPriceWei, GnosisChainGasStation, PolygonGasStation,
+    RpcPercentileEstimator, Transport,
+};
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use serde_with::{serde_as, DurationSecondsWithFrac};
+use std::{fmt, path::Path, time::Duration};
+
+/// A secret in the config file or, preferably, the name of the environment variable holding it:
+/// `api_key = "secret"` or `api_key = { env = "API_KEY" }`.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Value(String),
+    Env { env: String },
+}
+
+// Keeps secrets out of logs. The name of the environment variable is not secret.
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(_) => write!(f, "Value(<redacted>)"),
+            Self::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+        }
+    }
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Env { env } => std::env::var(env)
+                .with_context(|| format!("environment variable {} is not set", env))
+                .map_err(Into::into),
+        }
+    }
+}
+
+/// Prices of one tier of a `generic_http` source, see `generic_http::Tier`.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TierConfig {
+    // in seconds
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub time_limit: Duration,
+    pub price: String,
+    #[serde(default)]
+    pub max_fee_per_gas: Option<String>,
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<String>,
+}
+
+impl From<&TierConfig> for generic_http::Tier {
+    fn from(tier: &TierConfig) -> Self {
+        Self {
+            time_limit: tier.time_limit,
+            price: tier.price.clone(),
+            max_fee_per_gas: tier.max_fee_per_gas.clone(),
+            max_priority_fee_per_gas: tier.max_priority_fee_per_gas.clone(),
+        }
+    }
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// See `generic_http::Config`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenericHttpConfig {
+    pub url: String,
+    pub fast: TierConfig,
+    pub standard: TierConfig,
+    pub slow: TierConfig,
+    #[serde(default)]
+    pub base_fee_per_gas: Option<String>,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SourceConfig {
+    GasNow {
+        #[serde(default)]
+        url: Option<String>,
+    },
+    EthGasStation {
+        #[serde(default)]
+        api_key: Option<Secret>,
+    },
+    Etherscan {
+        #[serde(default)]
+        api_key: Option<Secret>,
+    },
+    GnosisChain {
+        #[serde(default)]
+        url: Option<String>,
+    },
+    Polygon {
+        network_id: String,
+    },
+    /// Percentiles of the priority fees of recent blocks of the node of a supported chain.
+    RpcPercentile {
+        node_url: String,
+        chain_id: u64,
+    },
+    /// The Blocknative http api. Polled in a background task with the `tokio_` feature, requested
+    /// on every estimate without it.
+    Blocknative {
+        api_key: Secret,
+    },
+    GenericHttp(Box<GenericHttpConfig>),
+}
+
+impl SourceConfig {
+    async fn build<T: Transport + Clone + 'static>(
+        &self,
+        transport: T,
+    ) -> Result<Box<dyn GasPriceEstimating>> {
+        Ok(match self {
+            Self::GasNow { url } => match url {
+                Some(url) => Box::new(GasNowGasStation::with_url(transport, url)),
+                None => Box::new(GasNowGasStation::new(transport)),
+            },
+            Self::EthGasStation { api_key } => {
+                let estimator = EthGasStation::new(transport);
+                match api_key {
+                    Some(api_key) => Box::new(estimator.with_credentials(
+                        ApiCredentials::query_param("api-key", api_key.resolve()?),
+                    )),
+                    None => Box::new(estimator),
+                }
+            }
+            Self::Etherscan { api_key } => match api_key {
+                Some(api_key) => Box::new(EtherscanGasStation::with_api_key(
+                    transport,
+                    api_key.resolve()?,
+                )),
+                None => Box::new(EtherscanGasStation::new(transport)),
+            },
+            Self::GnosisChain { url } => match url {
+                Some(url) => Box::new(GnosisChainGasStation::with_url(transport, url)),
+                None => Box::new(GnosisChainGasStation::new(transport)),
+            },
+            Self::Polygon { network_id } => {
+                Box::new(PolygonGasStation::with_network_id(network_id, transport)?)
+            }
+            Self::RpcPercentile { node_url, chain_id } => {
+                let chain = ChainConfig::from_chain_id(*chain_id).ok_or_else(|| {
+                    GasEstimationError::Unsupported(format!("unsupported chain {}", chain_id))
+                })?;
+                Box::new(RpcPercentileEstimator::new(
+                    transport,
+                    node_url.clone(),
+                    chain,
+                    None,
+                ))
+            }
+            #[cfg(feature = "tokio_")]
+            Self::Blocknative { api_key } => Box::new(
+                super::BlockNative::with_credentials(
+                    transport,
+                    ApiCredentials::header("Authorization", api_key.resolve()?),
+                    Default::default(),
+                )
+                .await?,
+            ),
+            #[cfg(not(feature = "tokio_"))]
+            Self::Blocknative { api_key } => {
+                Box::new(super::BlocknativeGasStation::with_credentials(
+                    transport,
+                    ApiCredentials::header("Authorization", api_key.resolve()?),
+                    Default::default(),
+                )?)
+            }
+            Self::GenericHttp(config) => {
+                let config = generic_http::Config {
+                    url: config.url.clone(),
+                    fast: (&config.fast).into(),
+                    standard: (&config.standard).into(),
+                    slow: (&config.slow).into(),
+                    base_fee_per_gas: config.base_fee_per_gas.clone(),
+                    scale: config.scale,
+                };
+                Box::new(ConfigurableEstimator::new(transport, config)?)
+            }
+        })
+    }
+}
+
+/// An estimator stack. Durations are in seconds.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StackConfig {
+    /// In order of priority.
+    pub sources: Vec<SourceConfig>,
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    #[serde(default)]
+    pub cache_ttl: Option<Duration>,
+    #[serde(default)]
+    pub max_gas_price_gwei: Option<f64>,
+    /// Retries and timeouts need the `tokio_` feature.
+    #[serde(default)]
+    pub max_retries: Option<usize>,
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+}
+
+impl StackConfig {
+    pub fn from_toml(config: &str) -> Result<Self> {
+        toml::from_str(config).map_err(|err| GasEstimationError::Decode(err.into()))
+    }
+
+    pub fn from_yaml(config: &str) -> Result<Self> {
+        serde_yaml::from_str(config).map_err(|err| GasEstimationError::Decode(err.into()))
+    }
+
+    /// Reads a `.toml`, `.yaml` or `.yml` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&config),
+            Some("yaml" | "yml") => Self::from_yaml(&config),
+            _ => Err(anyhow!("unknown config format of {}", path.display()).into()),
+        }
+    }
+
+    /// Builds the stack, resolving api keys from the environment. Async because some sources
+    /// request their first estimate on construction.
+    pub async fn build<T: Transport + Clone + 'static>(
+        &self,
+        transport: T,
+    ) -> Result<Box<dyn GasPriceEstimating>> {
+        let mut builder = EstimatorBuilder::new();
+        for source in &self.sources {
+            builder = builder.source(source.build(transport.clone()).await?);
+        }
+        if let Some(ttl) = self.cache_ttl {
+            builder = builder.with_cache(ttl);
+        }
+        if let Some(max_gas_price_gwei) = self.max_gas_price_gwei {
+            builder = builder.with_max_cap(GasPriceWei::from_gwei(max_gas_price_gwei).0);
+        }
+        #[cfg(feature = "tokio_")]
+        {
+            if let Some(max_retries) = self.max_retries {
+                builder = builder.with_retry(max_retries);
+            }
+            if let Some(timeout) = self.timeout {
+                builder = builder.with_timeout(timeout);
+            }
+        }
+        #[cfg(not(feature = "tokio_"))]
+        if self.max_retries.is_some() || self.timeout.is_some() {
+            return Err(GasEstimationError::Unsupported(
+                "retries and timeouts require the tokio_ feature".into(),
+            ));
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    #[derive(Clone)]
+    struct GasNowTransport;
+
+    #[async_trait::async_trait]
+    impl Transport for GasNowTransport {
+        async fn get_json<T: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<T> {
+            Ok(serde_json::from_str(
+                r#"{"code":200,"data":{"rapid":4e9,"fast":3e9,"standard":2e9,"slow":1e9}}"#,
+            )?)
+        }
+    }
+
+    #[test]
+    fn parses_toml_and_yaml() {
+        let toml = StackConfig::from_toml(
+            r#"
+            cache_ttl = 2.5
+            max_gas_price_gwei = 500
+
+            [[sources]]
+            type = "etherscan"
+            api_key = { env = "ETHERSCAN_API_KEY" }
+
+            [[sources]]
+            type = "generic_http"
+            url = "http://localhost"
+            scale = 1e9
+            fast = { time_limit = 15, price = "fast" }
+            standard = { time_limit = 60, price = "standard" }
+            slow = { time_limit = 300, price = "slow" }
+            "#,
+        )
+        .unwrap();
+        let yaml = StackConfig::from_yaml(
+            r#"
+            cache_ttl: 2.5
+            max_gas_price_gwei: 500
+            sources:
+              - type: etherscan
+                api_key:
+                  env: ETHERSCAN_API_KEY
+              - type: generic_http
+                url: http://localhost
+                scale: 1e9
+                fast: { time_limit: 15, price: fast }
+                standard: { time_limit: 60, price: standard }
+                slow: { time_limit: 300, price: slow }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(toml, yaml);
+        assert_eq!(toml.cache_ttl, Some(Duration::from_millis(2500)));
+        assert_eq!(
+            toml.sources[0],
+            SourceConfig::Etherscan {
+                api_key: Some(Secret::Env {
+                    env: "ETHERSCAN_API_KEY".to_string()
+                })
+            }
+        );
+        assert!(StackConfig::from_toml("[[sources]]\ntype = \"unknown\"").is_err());
+        assert!(StackConfig::from_toml("sources = []\ncache = 1").is_err());
+    }
+
+    #[test]
+    fn builds_stack() {
+        let config = StackConfig::from_toml(
+            r#"
+            max_gas_price_gwei = 2.5
+
+            [[sources]]
+            type = "gas_now"
+            "#,
+        )
+        .unwrap();
+        let estimator = config.build(GasNowTransport).wait().unwrap();
+        let estimate = |time_limit| {
+            estimator
+                .estimate_with_limits(21000., Duration::from_secs(time_limit))
+                .wait()
+                .unwrap()
+                .legacy
+        };
+        assert_eq!(estimate(600), 1e9);
+        // capped
+        assert_eq!(estimate(15), 2.5e9);
+
+        let missing_key = StackConfig::from_toml(
+            r#"
+            [[sources]]
+            type = "etherscan"
+            api_key = { env = "GAS_ESTIMATION_TEST_UNSET_VARIABLE" }
+            "#,
+        )
+        .unwrap();
+        assert!(missing_key.build(GasNowTransport).wait().is_err());
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let secret = Secret::Value("key".to_string());
+        assert_eq!(format!("{:?}", secret), "Value(<redacted>)");
+        let env = Secret::Env {
+            env: "API_KEY".to_string(),
+        };
+        assert_eq!(format!("{:?}", env), "Env { env: \"API_KEY\" }");
+    }
+}
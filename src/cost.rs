@@ -0,0 +1,233 @@
+//! Transaction costs from gas price estimates.
+//!
+//! For EIP-1559 estimates the expected cost uses the effective gas price (base fee plus priority
+//! fee, at most max_fee_per_gas) while the maximum cost uses max_fee_per_gas, which is what the
+//! sender has to be able to pay.
+
+use super::{
+    error::Result, EstimatedGasPrice, GasPriceEstimating, OpStackGasEstimator, ScrollGasEstimator,
+    Transport, DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT,
+};
+use std::time::Duration;
+
+const WEI_PER_NATIVE_TOKEN: f64 = 1e18;
+
+/// Source of the fee for posting a transaction's data to L1 which rollups charge in addition to
+/// the L2 execution fee.
+#[async_trait::async_trait]
+pub trait L1DataFeeEstimating: Send + Sync {
+    /// The L1 data fee in wei for a transaction with `calldata_size` bytes of calldata.
+    async fn l1_data_fee(&self, calldata_size: usize) -> Result<f64>;
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> L1DataFeeEstimating for OpStackGasEstimator<T> {
+    async fn l1_data_fee(&self, calldata_size: usize) -> Result<f64> {
+        self.l1_fee(calldata_size).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> L1DataFeeEstimating for ScrollGasEstimator<T> {
+    async fn l1_data_fee(&self, calldata_size: usize) -> Result<f64> {
+        self.l1_fee(calldata_size).await
+    }
+}
+
+/// Transaction to estimate with `GasPriceEstimating::estimate_for_tx`. Estimators for chains where
+/// the fee depends on the size of the transaction use the data fields, others only the limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxSpec {
+    pub gas_limit: f64,
+    pub time_limit: Duration,
+    // size of the transaction calldata in bytes
+    pub calldata_len: usize,
+    // (address, storage keys) pairs of the EIP-2930 access list
+    pub access_list: Vec<([u8; 20], Vec<[u8; 32]>)>,
+    // number of EIP-4844 blobs, not priced by any estimator yet
+    pub blob_count: usize,
+}
+
+impl Default for TxSpec {
+    fn default() -> Self {
+        Self {
+            gas_limit: DEFAULT_GAS_LIMIT,
+            time_limit: DEFAULT_TIME_LIMIT,
+            calldata_len: 0,
+            access_list: Vec::new(),
+            blob_count: 0,
+        }
+    }
+}
+
+impl TxSpec {
+    // Bytes of transaction data that have to be posted to L1 on rollups: the calldata and the
+    // addresses and storage keys of the access list.
+    pub fn data_len(&self) -> usize {
+        self.calldata_len
+            + self
+                .access_list
+                .iter()
+                .map(|(_, storage_keys)| 20 + 32 * storage_keys.len())
+                .sum::<usize>()
+    }
+}
+
+/// Estimated cost of a transaction. All values in wei.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TransactionCost {
+    pub gas_price: EstimatedGasPrice,
+    pub gas_limit: f64,
+    pub l1_fee: f64,
+}
+
+impl TransactionCost {
+    // Cost if the transaction uses the whole gas limit and is mined at the estimated base fee.
+    pub fn expected_cost(&self) -> f64 {
+        self.gas_limit * self.gas_price.effective_gas_price() + self.l1_fee
+    }
+
+    // Highest possible cost of the transaction.
+    pub fn max_cost(&self) -> f64 {
+        self.gas_limit * self.gas_price.cap() + self.l1_fee
+    }
+
+    pub fn expected_cost_in_native_token(&self) -> f64 {
+        wei_to_native_token(self.expected_cost())
+    }
+
+    pub fn max_cost_in_native_token(&self) -> f64 {
+        wei_to_native_token(self.max_cost())
+    }
+}
+
+pub fn wei_to_native_token(wei: f64) -> f64 {
+    wei / WEI_PER_NATIVE_TOKEN
+}
+
+// Estimates transaction costs with the gas prices of the inner estimator and, on rollups, the L1
+// data fee.
+pub struct TransactionCostEstimator<T> {
+    estimator: T,
+    l1_fee: Option<Box<dyn L1DataFeeEstimating>>,
+    calldata_size: usize,
+}
+
+impl<T: GasPriceEstimating> TransactionCostEstimator<T> {
+    pub fn new(estimator: T) -> Self {
+        Self {
+            estimator,
+            l1_fee: None,
+            calldata_size: 0,
+        }
+    }
+
+    // Include the L1 data fee for transactions with `calldata_size` bytes of calldata.
+    pub fn with_l1_fee(
+        self,
+        l1_fee: impl L1DataFeeEstimating + 'static,
+        calldata_size: usize,
+    ) -> Self {
+        Self {
+            l1_fee: Some(Box::new(l1_fee)),
+            calldata_size,
+            ..self
+        }
+    }
+
+    pub async fn cost(&self, gas_limit: f64, time_limit: Duration) -> Result<TransactionCost> {
+        let gas_price = self.estimator.estimate_with_limits(gas_limit, time_limit);
+        let l1_fee = async {
+            match &self.l1_fee {
+                Some(l1_fee) => l1_fee.l1_data_fee(self.calldata_size).await,
+                None => Ok(0.0),
+            }
+        };
+        let (gas_price, l1_fee) = futures::try_join!(gas_price, l1_fee)?;
+        Ok(TransactionCost {
+            gas_price,
+            gas_limit,
+            l1_fee,
+        })
+    }
+
+    /// Expected total cost in wei.
+    pub async fn cost_in_wei(&self, gas_limit: f64, time_limit: Duration) -> Result<f64> {
+        Ok(self.cost(gas_limit, time_limit).await?.expected_cost())
+    }
+
+    /// Expected total cost in the native token of the chain (ETH on mainnet).
+    pub async fn cost_in_native_token(&self, gas_limit: f64, time_limit: Duration) -> Result<f64> {
+        Ok(self
+            .cost(gas_limit, time_limit)
+            .await?
+            .expected_cost_in_native_token())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    struct FixedL1Fee;
+
+    #[async_trait::async_trait]
+    impl L1DataFeeEstimating for FixedL1Fee {
+        async fn l1_data_fee(&self, calldata_size: usize) -> Result<f64> {
+            Ok(calldata_size as f64 * 1e9)
+        }
+    }
+
+    #[test]
+    fn eip1559_cost_uses_effective_gas_price() {
+        // base fee 10 gwei, tip 2 gwei, cap 30 gwei
+        let estimator =
+            TransactionCostEstimator::new(FixedGasPriceEstimator::eip1559(10e9, 30e9, 2e9));
+        let cost = estimator
+            .cost(100_000.0, Duration::from_secs(30))
+            .wait()
+            .unwrap();
+        assert_approx_eq!(cost.expected_cost(), 100_000.0 * 12e9);
+        assert_approx_eq!(cost.max_cost(), 100_000.0 * 30e9);
+        assert_approx_eq!(cost.expected_cost_in_native_token(), 0.0012);
+    }
+
+    #[test]
+    fn default_estimate_for_tx_ignores_data() {
+        let estimator = FixedGasPriceEstimator::legacy(1e9);
+        let tx = TxSpec {
+            gas_limit: 50_000.0,
+            calldata_len: 100,
+            access_list: vec![([0; 20], vec![[0; 32]; 2])],
+            ..Default::default()
+        };
+        assert_eq!(tx.data_len(), 184);
+        let cost = estimator.estimate_for_tx(&tx).wait().unwrap();
+        assert_eq!(cost.gas_limit, 50_000.0);
+        assert_eq!(cost.l1_fee, 0.0);
+        assert_approx_eq!(cost.expected_cost(), 50_000.0 * 1e9);
+    }
+
+    #[test]
+    fn includes_l1_fee() {
+        let estimator = TransactionCostEstimator::new(FixedGasPriceEstimator::legacy(1e9))
+            .with_l1_fee(FixedL1Fee, 100);
+        assert_approx_eq!(
+            estimator
+                .cost_in_wei(21000.0, Duration::from_secs(30))
+                .wait()
+                .unwrap(),
+            21000.0 * 1e9 + 100.0 * 1e9
+        );
+        assert_approx_eq!(
+            estimator
+                .cost_in_native_token(21000.0, Duration::from_secs(30))
+                .wait()
+                .unwrap(),
+            0.0000211
+        );
+    }
+}
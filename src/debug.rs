@@ -0,0 +1,139 @@
+//! Captures the responses of gas price apis so that an estimate that looks wrong can be traced
+//! back to what the provider returned.
+
+use super::{
+    error::Result,
+    trace,
+    transport::{CacheValidators, Conditional},
+    Transport,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+type Hook = Box<dyn Fn(&str, &str) + Send + Sync>;
+
+// Passes the json of every response of the inner transport to a hook before it is decoded by the
+// estimator. With the `tracing_` feature the responses are also logged at trace level. The body
+// is serialized again from the parsed json so whitespace and key order can differ from what was
+// sent, the values are the same.
+pub struct DebugTransport<T> {
+    inner: T,
+    hook: Option<Hook>,
+}
+
+impl<T: Transport> DebugTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, hook: None }
+    }
+
+    /// Called with the url and the json body of every response.
+    pub fn with_hook(self, hook: impl Fn(&str, &str) + Send + Sync + 'static) -> Self {
+        Self {
+            hook: Some(Box::new(hook)),
+            ..self
+        }
+    }
+
+    fn record<R: DeserializeOwned>(&self, url: &str, response: Value) -> Result<R> {
+        let body = response.to_string();
+        trace::raw_response(url, &body);
+        if let Some(hook) = &self.hook {
+            hook(url, &body);
+        }
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> Transport for DebugTransport<T> {
+    async fn get_json<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+    ) -> Result<R> {
+        let response = self.inner.get_json(url, header).await?;
+        self.record(url, response)
+    }
+
+    async fn post_json<Req, Resp>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        body: &Req,
+    ) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        let response = self.inner.post_json(url, header, body).await?;
+        self.record(url, response)
+    }
+
+    // Not modified responses have no body and are not passed to the hook.
+    async fn get_json_conditional<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        validators: &CacheValidators,
+    ) -> Result<Conditional<R>> {
+        Ok(
+            match self
+                .inner
+                .get_json_conditional(url, header, validators)
+                .await?
+            {
+                Conditional::Modified { value, validators } => Conditional::Modified {
+                    value: self.record(url, value)?,
+                    validators,
+                },
+                Conditional::NotModified => Conditional::NotModified,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::{GasNowGasStation, GasPriceEstimating};
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Fixed;
+
+    #[async_trait::async_trait]
+    impl Transport for Fixed {
+        async fn get_json<R: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<R> {
+            Ok(serde_json::from_str(
+                r#"{"code": 200, "data": {"rapid": 4, "fast": 3, "standard": 2, "slow": 1}}"#,
+            )?)
+        }
+    }
+
+    #[test]
+    fn passes_responses_to_hook() {
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let responses_clone = responses.clone();
+        let transport = DebugTransport::new(Fixed).with_hook(move |url, body| {
+            responses_clone
+                .lock()
+                .unwrap()
+                .push((url.to_string(), body.to_string()))
+        });
+        let gasnow = GasNowGasStation::with_url(transport, "http://localhost");
+        let estimate = gasnow
+            .estimate_with_limits(0.0, std::time::Duration::from_secs(60))
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.legacy, 3.0);
+        let responses = responses.lock().unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].0, "http://localhost");
+        let body: Value = serde_json::from_str(&responses[0].1).unwrap();
+        assert_eq!(body["data"]["rapid"], 4);
+    }
+}
@@ -0,0 +1,133 @@
+//! Error type returned by gas price estimators and transports.
+
+use std::time::Duration;
+
+pub type Result<T, E = GasEstimationError> = std::result::Result<T, E>;
+
+/// Classifies why an estimate failed so that callers can decide on a fallback. For example a
+/// `Transport` error is worth retrying while a `Decode` error will happen again.
+#[derive(Debug, thiserror::Error)]
+pub enum GasEstimationError {
+    /// The request to the gas price source failed (connection errors, http error status codes).
+    /// Error status codes are kept as `HttpStatus` in the error, see `http_status`.
+    #[error("transport error: {0:#}")]
+    Transport(anyhow::Error),
+    /// The response of the gas price source could not be decoded.
+    #[error("decode error: {0:#}")]
+    Decode(anyhow::Error),
+    /// The gas price source refused the request because of too many requests.
+    #[error("rate limited")]
+    RateLimited { retry_after: Option<Duration> },
+    /// The estimator does not support the requested network or operation.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    /// The estimate violates invariants like max_fee_per_gas >= max_priority_fee_per_gas.
+    #[error("invalid estimate: {0}")]
+    InvalidEstimate(String),
+    /// A circuit breaker stopped calling the gas price source after repeated failures. Calls are
+    /// let through again after `retry_after`, `None` while a probe call is in flight.
+    #[error("circuit breaker is open")]
+    CircuitOpen { retry_after: Option<Duration> },
+    /// The estimate did not complete in time.
+    #[error("timeout")]
+    Timeout,
+    /// Any other error.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// The error status code of a response. Transports add it to `GasEstimationError::Transport`
+/// errors so that client errors like an invalid api key can be told apart from server errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("http status {0}")]
+pub struct HttpStatus(pub http::StatusCode);
+
+impl GasEstimationError {
+    /// The status code of a `Transport` error caused by an error response.
+    pub fn http_status(&self) -> Option<http::StatusCode> {
+        match self {
+            Self::Transport(err) => err.downcast_ref::<HttpStatus>().map(|status| status.0),
+            _ => None,
+        }
+    }
+
+    /// Add context to the error message while keeping the error class.
+    pub fn context(self, context: &'static str) -> Self {
+        match self {
+            Self::Transport(err) => Self::Transport(err.context(context)),
+            Self::Decode(err) => Self::Decode(err.context(context)),
+            Self::Other(err) => Self::Other(err.context(context)),
+            err => err,
+        }
+    }
+}
+
+// This allows using `?` on anyhow results. If the anyhow error wraps a `GasEstimationError` the
+// original error class is kept.
+impl From<anyhow::Error> for GasEstimationError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Self>() {
+            Ok(err) => err,
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+impl From<serde_json::Error> for GasEstimationError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Decode(err.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn context_keeps_variant() {
+        let err = GasEstimationError::Transport(anyhow!("connection reset")).context("request");
+        assert!(matches!(err, GasEstimationError::Transport(_)));
+        assert_eq!(
+            err.to_string(),
+            "transport error: request: connection reset"
+        );
+    }
+
+    #[test]
+    fn from_anyhow_keeps_variant() {
+        let err = Err::<(), _>(GasEstimationError::Timeout)
+            .context("request")
+            .unwrap_err();
+        assert!(matches!(
+            GasEstimationError::from(err),
+            GasEstimationError::Timeout
+        ));
+        assert!(matches!(
+            GasEstimationError::from(anyhow!("")),
+            GasEstimationError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn keeps_http_status() {
+        let err = GasEstimationError::Transport(
+            anyhow!("401 Unauthorized").context(HttpStatus(http::StatusCode::UNAUTHORIZED)),
+        )
+        .context("request");
+        assert_eq!(err.http_status(), Some(http::StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            GasEstimationError::Transport(anyhow!("connection reset")).http_status(),
+            None
+        );
+    }
+
+    #[test]
+    fn from_serde_is_decode() {
+        let err = serde_json::from_str::<u32>("").unwrap_err();
+        assert!(matches!(
+            GasEstimationError::from(err),
+            GasEstimationError::Decode(_)
+        ));
+    }
+}
@@ -1,10 +1,111 @@
 //! Ethereum node `GasPriceEstimating` implementation.
+//!
+//! The legacy gas price comes from `eth_gasPrice`. On nodes that support EIP-1559 the base fee of
+//! the pending block comes from `eth_feeHistory` and the priority fee from
+//! `eth_maxPriorityFeePerGas`, or from the median rewards of recent blocks if the node does not
+//! implement that method. `EthNodeGasEstimator` with a `FeeHistoryConfig` instead derives the
+//! priority fee for each time limit from the configured reward percentiles. Its verbose estimates
+//! include the range between the lowest and highest sampled rewards.
+//!
+//! `EthNodeGasLimitEstimator` estimates gas limits with `eth_estimateGas`.
 
-use super::{EstimatedGasPrice, GasPriceEstimating};
-use anyhow::{Context, Result};
+use super::{
+    chain,
+    error::Result,
+    gas_limit::{self, GasLimitEstimating, GasLimitRequest},
+    rpc_percentile::{self, DecodedFeeHistory},
+    trace, EstimateRange, EstimateWithMetadata, EstimatedGasPrice, FeeHistoryConfig,
+    GasEstimationError, GasPrice1559, GasPriceEstimating, GasPriceWei,
+};
+use anyhow::Context;
 use primitive_types::U256;
 use std::time::Duration;
-use web3::{Transport, Web3};
+use web3::{
+    helpers::CallFuture,
+    types::{BlockId, BlockNumber, Bytes, CallRequest, H160},
+    Transport, Web3,
+};
+
+// number of most recent blocks whose median rewards are averaged for the fallback priority fee
+const FEE_HISTORY_BLOCKS: u64 = 10;
+// a coefficient to multiply base_fee_per_gas with, in order to survive base fee increases
+const BASE_FEE_MULTIPLIER: f64 = 2.0;
+
+// Estimates with the node like `Web3` but with configurable fee history sampling.
+pub struct EthNodeGasEstimator<T: Transport> {
+    web3: Web3<T>,
+    fee_history: FeeHistoryConfig,
+    min_priority_fee: f64,
+}
+
+impl<T: Transport> EthNodeGasEstimator<T> {
+    pub fn new(web3: Web3<T>, fee_history: FeeHistoryConfig) -> Self {
+        Self {
+            web3,
+            fee_history,
+            min_priority_fee: chain::ETHEREUM.min_priority_fee,
+        }
+    }
+
+    /// Lowest priority fee that is estimated, 1 gwei by default.
+    pub fn with_min_priority_fee(self, min_priority_fee: f64) -> Self {
+        Self {
+            min_priority_fee,
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> GasPriceEstimating for EthNodeGasEstimator<T>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    fn source(&self) -> &'static str {
+        "eth_node"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        trace::estimate(
+            self.source(),
+            None,
+            estimate(
+                &self.web3,
+                Some((&self.fee_history, time_limit, self.min_priority_fee)),
+            ),
+        )
+        .await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let (price, range) = trace::estimate(
+            self.source(),
+            None,
+            estimate_with_range(
+                &self.web3,
+                Some((&self.fee_history, time_limit, self.min_priority_fee)),
+            ),
+        )
+        .await?;
+        Ok(EstimateWithMetadata {
+            price,
+            source: self.source(),
+            observed_at: crate::time::Instant::now(),
+            confidence: None,
+            divergence: None,
+            range,
+        })
+    }
+}
 
 #[async_trait::async_trait]
 impl<T> GasPriceEstimating for Web3<T>
@@ -12,21 +113,262 @@ where
     T: Transport + Send + Sync,
     <T as Transport>::Out: Send,
 {
+    fn source(&self) -> &'static str {
+        "eth_node"
+    }
+
     async fn estimate_with_limits(
         &self,
         _gas_limit: f64,
         _time_limit: Duration,
     ) -> Result<EstimatedGasPrice> {
-        let legacy = self
-            .eth()
-            .gas_price()
-            .await
-            .context("failed to get web3 gas price")
-            .map(U256::to_f64_lossy)?;
+        trace::estimate(self.source(), None, estimate(self, None)).await
+    }
+}
+
+// Estimates gas limits with the node and adds a safety margin.
+pub struct EthNodeGasLimitEstimator<T: Transport> {
+    web3: Web3<T>,
+    params: gas_limit::Params,
+}
+
+impl<T: Transport> EthNodeGasLimitEstimator<T> {
+    pub fn new(web3: Web3<T>, params: Option<gas_limit::Params>) -> Self {
+        Self {
+            web3,
+            params: params.unwrap_or_default(),
+        }
+    }
+}
 
-        Ok(EstimatedGasPrice {
-            legacy,
+#[async_trait::async_trait]
+impl<T> GasLimitEstimating for EthNodeGasLimitEstimator<T>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    async fn estimate_gas_limit(&self, tx: &GasLimitRequest) -> Result<f64> {
+        let request = CallRequest {
+            from: tx.from.map(H160),
+            to: tx.to.map(H160),
+            value: Some(tx.value.into()),
+            data: Some(Bytes(tx.data.clone())),
             ..Default::default()
+        };
+        let estimate = async {
+            self.web3
+                .eth()
+                .estimate_gas(request, None)
+                .await
+                .context("failed to estimate gas")
+                .map_err(GasEstimationError::Transport)
+                .map(U256::to_f64_lossy)
+        };
+        let block_gas_limit = async {
+            if !self.params.cap_at_block_gas_limit {
+                return Ok(None);
+            }
+            self.web3
+                .eth()
+                .block(BlockId::Number(BlockNumber::Latest))
+                .await
+                .context("failed to get latest block")
+                .map_err(GasEstimationError::Transport)
+                .map(|block| block.map(|block| block.gas_limit.to_f64_lossy()))
+        };
+        let (estimate, block_gas_limit) = futures::try_join!(estimate, block_gas_limit)?;
+        Ok(self.params.gas_limit(estimate, block_gas_limit))
+    }
+}
+
+// Without a config the priority fee comes from the node.
+async fn estimate<T>(
+    web3: &Web3<T>,
+    config: Option<(&FeeHistoryConfig, Duration, f64)>,
+) -> Result<EstimatedGasPrice>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    Ok(estimate_with_range(web3, config).await?.0)
+}
+
+// The range is only known with a config, from the spread of the sampled rewards.
+async fn estimate_with_range<T>(
+    web3: &Web3<T>,
+    config: Option<(&FeeHistoryConfig, Duration, f64)>,
+) -> Result<(EstimatedGasPrice, Option<EstimateRange>)>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    let legacy = web3
+        .eth()
+        .gas_price()
+        .await
+        .context("failed to get web3 gas price")
+        .map_err(GasEstimationError::Transport)
+        .map(U256::to_f64_lossy)?;
+
+    // Nodes without EIP-1559 support fail the fee history request in which case only
+    // the legacy gas price is estimated.
+    let (blocks, percentiles) = match config {
+        Some((config, ..)) => (config.blocks, config.reward_percentiles()),
+        None => (FEE_HISTORY_BLOCKS, vec![50.0]),
+    };
+    let fee_history = web3
+        .eth()
+        .fee_history(blocks.into(), BlockNumber::Latest, Some(percentiles))
+        .await;
+    let (eip1559, range) = match (fee_history, config) {
+        (Ok(fee_history), Some((config, time_limit, min_priority_fee))) => {
+            let fee_history = DecodedFeeHistory::from_web3(&fee_history);
+            let max_priority_fee = |reward: &dyn Fn(usize) -> f64| {
+                rpc_percentile::max_priority_fee(config, time_limit, min_priority_fee, |index| {
+                    Ok(reward(index))
+                })
+            };
+            let reward_range = |index| {
+                config
+                    .reward_range(fee_history.rewards(index))
+                    .unwrap_or_default()
+            };
+            let estimate = eip1559(
+                &fee_history,
+                Some(max_priority_fee(&|index| {
+                    average_reward(&fee_history, index, config)
+                })?),
+            );
+            let low = eip1559(
+                &fee_history,
+                Some(max_priority_fee(&|index| reward_range(index).0)?),
+            );
+            let high = eip1559(
+                &fee_history,
+                Some(max_priority_fee(&|index| reward_range(index).1)?),
+            );
+            let range = match (low, high) {
+                (Some(low), Some(high)) => Some(EstimateRange {
+                    low: EstimatedGasPrice {
+                        legacy,
+                        eip1559: Some(low),
+                    }
+                    .validate()?,
+                    high: EstimatedGasPrice {
+                        legacy,
+                        eip1559: Some(high),
+                    }
+                    .validate()?,
+                }),
+                _ => None,
+            };
+            (estimate, range)
+        }
+        (Ok(fee_history), None) => {
+            let max_priority_fee = CallFuture::<U256, _>::new(
+                web3.transport().execute("eth_maxPriorityFeePerGas", vec![]),
+            )
+            .await
+            .map(U256::to_f64_lossy);
+            if let Err(err) = &max_priority_fee {
+                tracing::debug!(?err, "falling back to fee history rewards");
+            }
+            (
+                eip1559(
+                    &DecodedFeeHistory::from_web3(&fee_history),
+                    max_priority_fee.ok(),
+                ),
+                None,
+            )
+        }
+        (Err(err), _) => {
+            tracing::debug!(?err, "failed to get fee history");
+            (None, None)
+        }
+    };
+
+    Ok((EstimatedGasPrice { legacy, eip1559 }.validate()?, range))
+}
+
+// `None` if the pending block has no base fee.
+fn eip1559(fee_history: &DecodedFeeHistory, max_priority_fee: Option<f64>) -> Option<GasPrice1559> {
+    let base_fee_per_gas = fee_history.base_fee_per_gas?;
+    if base_fee_per_gas == 0.0 {
+        return None;
+    }
+    let max_priority_fee_per_gas =
+        max_priority_fee.unwrap_or_else(|| average_reward(fee_history, 0, &Default::default()));
+    Some(GasPrice1559 {
+        base_fee_per_gas: GasPriceWei(base_fee_per_gas),
+        max_fee_per_gas: GasPriceWei(
+            base_fee_per_gas * BASE_FEE_MULTIPLIER + max_priority_fee_per_gas,
+        ),
+        max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+    })
+}
+
+// Average reward at the given index of the percentiles over the non empty blocks that aren't
+// outliers.
+fn average_reward(fee_history: &DecodedFeeHistory, index: usize, config: &FeeHistoryConfig) -> f64 {
+    config
+        .average_reward(fee_history.rewards(index))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use web3::types::FeeHistory;
+
+    fn fee_history() -> DecodedFeeHistory {
+        DecodedFeeHistory::from_web3(&FeeHistory {
+            oldest_block: BlockNumber::Number(1.into()),
+            base_fee_per_gas: vec![90.into(), 95.into(), 100.into()],
+            gas_used_ratio: vec![0.5, 0.0],
+            reward: Some(vec![vec![4.into()], vec![0.into()]]),
         })
     }
+
+    #[test]
+    fn uses_max_priority_fee() {
+        let eip1559 = eip1559(&fee_history(), Some(2.0)).unwrap();
+        assert_approx_eq!(eip1559.base_fee_per_gas.0, 100.0);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 2.0);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 202.0);
+    }
+
+    #[test]
+    fn falls_back_to_rewards() {
+        let eip1559 = eip1559(&fee_history(), None).unwrap();
+        // the empty block is ignored
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 4.0);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 204.0);
+    }
+
+    #[test]
+    fn no_eip1559_without_base_fee() {
+        let fee_history = DecodedFeeHistory {
+            base_fee_per_gas: Some(0.0),
+            ..fee_history()
+        };
+        assert_eq!(eip1559(&fee_history, Some(2.0)), None);
+        let empty = DecodedFeeHistory {
+            base_fee_per_gas: None,
+            ..fee_history
+        };
+        assert_eq!(eip1559(&empty, Some(2.0)), None);
+    }
+
+    #[test]
+    fn averages_reward_at_percentile_index() {
+        let fee_history = DecodedFeeHistory {
+            gas_used_ratio: vec![0.5, 0.5, 0.0],
+            reward: Some(vec![vec![1.0, 10.0], vec![3.0, 20.0], vec![0.0, 0.0]]),
+            ..fee_history()
+        };
+        assert_approx_eq!(average_reward(&fee_history, 0, &Default::default()), 2.0);
+        assert_approx_eq!(average_reward(&fee_history, 1, &Default::default()), 15.0);
+        assert_approx_eq!(average_reward(&fee_history, 2, &Default::default()), 0.0);
+    }
 }
@@ -0,0 +1,270 @@
+//! Etherscan gas tracker `GasPriceEstimating` implementation.
+//! Api documentation at https://docs.etherscan.io/api-endpoints/gas-tracker .
+
+use super::{
+    auth, error::Result, interpolation, ApiCredentials, EstimatedGasPrice, GasEstimationError,
+    GasPrice1559, GasPriceEstimating, GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_with::rust::display_fromstr;
+use std::{convert::TryInto, time::Duration};
+
+const API_URI: &str = "https://api.etherscan.io/api";
+
+// Etherscan does not publish confirmation times for its gas oracle so we assign each price a time
+// bucket based on the estimates shown at https://etherscan.io/gastracker .
+pub const FAST: Duration = Duration::from_secs(30);
+pub const PROPOSE: Duration = Duration::from_secs(180);
+pub const SAFE: Duration = Duration::from_secs(600);
+
+pub struct EtherscanGasStation<T> {
+    transport: T,
+    credentials: Option<ApiCredentials>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    status: String,
+    message: String,
+    result: serde_json::Value,
+}
+
+// gas prices in gwei
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GasOracle {
+    #[serde(with = "display_fromstr")]
+    pub last_block: u64,
+    #[serde(with = "display_fromstr")]
+    pub safe_gas_price: f64,
+    #[serde(with = "display_fromstr")]
+    pub propose_gas_price: f64,
+    #[serde(with = "display_fromstr")]
+    pub fast_gas_price: f64,
+    // Only returned after the London hard fork.
+    #[serde(rename = "suggestBaseFee", default, with = "optional_display_fromstr")]
+    pub suggest_base_fee: Option<f64>,
+}
+
+mod optional_display_fromstr {
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| value.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+impl<T: Transport> EtherscanGasStation<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            credentials: None,
+        }
+    }
+
+    /// Without an api key Etherscan limits requests to one every five seconds.
+    pub fn with_api_key(transport: T, api_key: String) -> Self {
+        Self::new(transport).with_credentials(ApiCredentials::query_param("apikey", api_key))
+    }
+
+    /// Credentials sent with every request, for example for a paid tier.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    fn uri(&self) -> String {
+        let mut uri = url::Url::parse(API_URI).unwrap();
+        uri.query_pairs_mut()
+            .append_pair("module", "gastracker")
+            .append_pair("action", "gasoracle");
+        uri.into()
+    }
+
+    /// Retrieves the current gas oracle values.
+    pub async fn gas_oracle(&self) -> Result<GasOracle> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.uri(), Default::default())?;
+        let response: Response = self
+            .transport
+            .get_json(&url, header)
+            .await
+            .map_err(|err| err.context("failed to get etherscan gas price"))?;
+        parse_response(response)
+    }
+}
+
+// Etherscan reports errors with status "0" and a string describing the problem in `result`.
+fn parse_response(response: Response) -> Result<GasOracle> {
+    if response.status != "1" {
+        let result = response.result.as_str().unwrap_or_default();
+        if result.to_lowercase().contains("rate limit") {
+            return Err(GasEstimationError::RateLimited { retry_after: None });
+        }
+        return Err(GasEstimationError::Transport(anyhow!(
+            "etherscan error {}: {}",
+            response.message,
+            response.result
+        )));
+    }
+    Ok(serde_json::from_value(response.result)?)
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for EtherscanGasStation<T> {
+    fn source(&self) -> &'static str {
+        "etherscan"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let response = self.gas_oracle().await?;
+        estimate_with_limits(&response, time_limit)
+    }
+}
+
+fn estimate_with_limits(response: &GasOracle, time_limit: Duration) -> Result<EstimatedGasPrice> {
+    let points: &[(f64, f64)] = &[
+        (FAST.as_secs_f64(), response.fast_gas_price),
+        (PROPOSE.as_secs_f64(), response.propose_gas_price),
+        (SAFE.as_secs_f64(), response.safe_gas_price),
+    ];
+    let gas_price_in_gwei =
+        interpolation::interpolate(time_limit.as_secs_f64(), points.try_into()?);
+    let legacy = GasPriceWei::from_gwei(gas_price_in_gwei).0;
+    // The oracle prices are total prices so the tip is what remains after paying the base fee.
+    let eip1559 = response.suggest_base_fee.map(|base_fee| {
+        let base_fee_per_gas = GasPriceWei::from_gwei(base_fee);
+        GasPrice1559 {
+            base_fee_per_gas,
+            max_fee_per_gas: GasPriceWei(legacy),
+            max_priority_fee_per_gas: (GasPriceWei(legacy) - base_fee_per_gas)
+                .max(GasPriceWei(0.0)),
+        }
+    });
+    Ok(EstimatedGasPrice { legacy, eip1559 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::TestTransport;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn deserialize() {
+        let json = r#"
+        {
+            "status": "1",
+            "message": "OK",
+            "result": {
+                "LastBlock": "13053741",
+                "SafeGasPrice": "20",
+                "ProposeGasPrice": "22",
+                "FastGasPrice": "24",
+                "suggestBaseFee": "19.230609716",
+                "gasUsedRatio": "0.370119078777807,0.8954731,0.550911766666667"
+            }
+        }"#;
+        let response = serde_json::from_str::<Response>(json).unwrap();
+        let result = parse_response(response).unwrap();
+        assert_eq!(result.last_block, 13053741);
+        assert_approx_eq!(result.safe_gas_price, 20.0);
+        assert_approx_eq!(result.propose_gas_price, 22.0);
+        assert_approx_eq!(result.fast_gas_price, 24.0);
+        assert_approx_eq!(result.suggest_base_fee.unwrap(), 19.230609716);
+    }
+
+    #[test]
+    fn deserialize_error() {
+        let json = r#"
+        {
+            "status": "0",
+            "message": "NOTOK",
+            "result": "Invalid API Key"
+        }"#;
+        let response = serde_json::from_str::<Response>(json).unwrap();
+        assert!(matches!(
+            parse_response(response),
+            Err(GasEstimationError::Transport(_))
+        ));
+    }
+
+    #[test]
+    fn deserialize_rate_limit() {
+        let json = r#"
+        {
+            "status": "0",
+            "message": "NOTOK",
+            "result": "Max rate limit reached"
+        }"#;
+        let response = serde_json::from_str::<Response>(json).unwrap();
+        assert!(matches!(
+            parse_response(response),
+            Err(GasEstimationError::RateLimited { .. })
+        ));
+    }
+
+    #[test]
+    fn interpolates() {
+        let oracle = GasOracle {
+            safe_gas_price: 1.0,
+            propose_gas_price: 2.0,
+            fast_gas_price: 3.0,
+            suggest_base_fee: Some(1.5),
+            ..Default::default()
+        };
+        let result = estimate_with_limits(&oracle, FAST).unwrap();
+        assert_approx_eq!(result.legacy, 3e9);
+        let eip1559 = result.eip1559.unwrap();
+        assert_approx_eq!(eip1559.base_fee_per_gas.0, 1.5e9);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 3e9);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 1.5e9);
+
+        let result = estimate_with_limits(&oracle, Duration::from_secs(390)).unwrap();
+        assert_approx_eq!(result.legacy, 1.5e9);
+        assert!(result.is_valid());
+    }
+
+    #[test]
+    fn legacy_only_without_base_fee() {
+        let oracle = GasOracle {
+            safe_gas_price: 1.0,
+            propose_gas_price: 2.0,
+            fast_gas_price: 3.0,
+            ..Default::default()
+        };
+        let result = estimate_with_limits(&oracle, SAFE).unwrap();
+        assert_approx_eq!(result.legacy, 1e9);
+        assert!(result.eip1559.is_none());
+    }
+
+    // cargo test etherscan -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let etherscan = EtherscanGasStation::new(TestTransport::default());
+        let response = etherscan.gas_oracle().await.unwrap();
+        println!("{:?}", response);
+        for i in 0..10 {
+            let time_limit = Duration::from_secs(i * 60);
+            let price = estimate_with_limits(&response, time_limit).unwrap();
+            println!(
+                "gas price estimate for {} seconds: {} gwei",
+                time_limit.as_secs(),
+                price.legacy / 1e9,
+            );
+        }
+    }
+}
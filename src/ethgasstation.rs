@@ -1,5 +1,7 @@
-use super::{linear_interpolation, EstimatedGasPrice, GasPriceEstimating, Transport};
-use anyhow::{Context, Result};
+use super::{
+    auth, error::Result, interpolation, ApiCredentials, EstimatedGasPrice, GasPriceEstimating,
+    GasPriceSchedule, Transport,
+};
 use std::{convert::TryInto, time::Duration};
 
 // Gas price estimation with https://ethgasstation.info/ , api https://docs.ethgasstation.info/gas-price .
@@ -8,47 +10,79 @@ const API_URI: &str = "https://ethgasstation.info/api/ethgasAPI.json";
 
 pub struct EthGasStation<T> {
     transport: T,
+    credentials: Option<ApiCredentials>,
 }
 
-// gas prices in gwei*10 (2 gwei is transmitted as `20`)
-// wait times in minutes
-#[derive(Debug, serde::Deserialize)]
+/// The tiers of the ethgasstation api. Gas prices are in gwei*10 (2 gwei is transmitted as `20`),
+/// wait times in minutes.
+#[derive(Clone, Copy, Debug, serde::Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
-struct Response {
-    fastest: f64,
-    fast: f64,
-    average: f64,
-    safe_low: f64,
-    fastest_wait: f64,
-    fast_wait: f64,
-    avg_wait: f64,
-    safe_low_wait: f64,
+pub struct Response {
+    pub fastest: f64,
+    pub fast: f64,
+    pub average: f64,
+    pub safe_low: f64,
+    pub fastest_wait: f64,
+    pub fast_wait: f64,
+    pub avg_wait: f64,
+    pub safe_low_wait: f64,
 }
 
 impl<T: Transport> EthGasStation<T> {
     pub fn new(transport: T) -> Self {
-        Self { transport }
+        Self {
+            transport,
+            credentials: None,
+        }
     }
 
-    async fn gas_price(&self) -> Result<Response> {
+    /// Credentials sent with every request, for example for a paid tier.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    /// Requests the response without interpolating it, for custom mappings of the tiers.
+    pub async fn fetch_raw(&self) -> Result<Response> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), API_URI, Default::default())?;
         self.transport
-            .get_json(API_URI, Default::default())
+            .get_json(&url, header)
             .await
-            .context("failed to get ethgasstation gas price")
+            .map_err(|err| err.context("failed to get ethgasstation gas price"))
     }
 }
 
 #[async_trait::async_trait]
 impl<T: Transport> GasPriceEstimating for EthGasStation<T> {
+    fn source(&self) -> &'static str {
+        "ethgasstation"
+    }
+
     async fn estimate_with_limits(
         &self,
         _gas_limit: f64,
         time_limit: Duration,
     ) -> Result<EstimatedGasPrice> {
-        let response = self.gas_price().await?;
+        let response = self.fetch_raw().await?;
         let result = estimate_with_limits(&response, time_limit)?;
         Ok(result)
     }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let response = self.fetch_raw().await?;
+        GasPriceSchedule::from_estimates(|time_limit| estimate_with_limits(&response, time_limit))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let response = self.fetch_raw().await?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| estimate_with_limits(&response, *time_limit))
+            .collect()
+    }
 }
 
 fn estimate_with_limits(response: &Response, time_limit: Duration) -> Result<EstimatedGasPrice> {
@@ -67,7 +101,7 @@ fn estimate_with_limits(response: &Response, time_limit: Duration) -> Result<Est
         }
     }
     let gas_price_in_x10_gwei =
-        linear_interpolation::interpolate(time_limit_in_minutes, points.as_slice().try_into()?);
+        interpolation::interpolate(time_limit_in_minutes, points.as_slice().try_into()?);
     let gas_price_in_wei = gas_price_in_x10_gwei * 1e8;
     Ok(EstimatedGasPrice {
         legacy: gas_price_in_wei,
@@ -85,7 +119,7 @@ mod tests {
     #[ignore]
     async fn real_request() {
         let ethgasstation = EthGasStation::new(TestTransport::default());
-        let response = ethgasstation.gas_price().await.unwrap();
+        let response = ethgasstation.fetch_raw().await.unwrap();
         println!("{:?}", response);
         for i in 0..10 {
             let time_limit = Duration::from_secs(i * 10);
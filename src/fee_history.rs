@@ -0,0 +1,217 @@
+//! Configuration of the estimators that derive priority fees from the reward percentiles of
+//! `eth_feeHistory`.
+
+use super::{error::Result, GasPriceSchedule};
+use std::time::Duration;
+
+/// Which blocks and reward percentiles are sampled. Chains differ a lot in how full their blocks
+/// are and how tips are distributed so this usually needs tuning per chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeHistoryConfig {
+    // number of most recent blocks whose rewards are sampled
+    pub blocks: u64,
+    // reward percentile used for each time limit, sorted by ascending time limit
+    pub percentiles: Vec<(Duration, f64)>,
+    // discards the rewards of unusual blocks before they are averaged
+    pub outlier_filter: OutlierFilter,
+}
+
+impl Default for FeeHistoryConfig {
+    fn default() -> Self {
+        Self {
+            blocks: 20,
+            percentiles: vec![
+                (Duration::from_secs(15), 90.0),
+                (Duration::from_secs(60), 60.0),
+                (Duration::from_secs(180), 30.0),
+                (Duration::from_secs(600), 10.0),
+            ],
+            outlier_filter: Default::default(),
+        }
+    }
+}
+
+impl FeeHistoryConfig {
+    /// Maps the percentiles to the time limits of the slow, standard and fast urgencies of
+    /// `GasPriceSchedule`, for example 20, 50 and 80.
+    pub fn with_urgencies(blocks: u64, slow: f64, standard: f64, fast: f64) -> Self {
+        Self {
+            blocks,
+            percentiles: vec![
+                (GasPriceSchedule::FAST, fast),
+                (GasPriceSchedule::STANDARD, standard),
+                (GasPriceSchedule::SLOW, slow),
+            ],
+            outlier_filter: Default::default(),
+        }
+    }
+
+    // Mean of the rewards that pass the outlier filter. None if there are no rewards.
+    pub(crate) fn average_reward(&self, rewards: Vec<f64>) -> Option<f64> {
+        let rewards = self.outlier_filter.apply(rewards);
+        match rewards.len() {
+            0 => None,
+            len => Some(rewards.iter().sum::<f64>() / len as f64),
+        }
+    }
+
+    /// Lowest and highest of the rewards that pass the outlier filter, the spread behind the
+    /// range of verbose estimates. None if there are no rewards.
+    pub fn reward_range(&self, rewards: Vec<f64>) -> Option<(f64, f64)> {
+        self.outlier_filter
+            .apply(rewards)
+            .into_iter()
+            .fold(None, |range, reward| match range {
+                None => Some((reward, reward)),
+                Some((low, high)) => Some((reward.min(low), reward.max(high))),
+            })
+    }
+
+    /// The percentiles to request. eth_feeHistory requires them in ascending order which is the
+    /// reverse of the time limit order because faster inclusion needs a higher percentile.
+    pub fn reward_percentiles(&self) -> Vec<f64> {
+        let mut percentiles = self
+            .percentiles
+            .iter()
+            .map(|(_, percentile)| *percentile)
+            .collect::<Vec<_>>();
+        percentiles.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        percentiles.dedup();
+        percentiles
+    }
+
+    // (time limit in seconds, priority fee) points for interpolation. `priority_fee` is called
+    // with the index of the percentile in `reward_percentiles`.
+    pub(crate) fn priority_fee_points(
+        &self,
+        mut priority_fee: impl FnMut(usize) -> Result<f64>,
+    ) -> Result<Vec<(f64, f64)>> {
+        let percentiles = self.reward_percentiles();
+        self.percentiles
+            .iter()
+            .map(|(time_limit, percentile)| {
+                let index = percentiles
+                    .iter()
+                    .position(|p| p == percentile)
+                    .unwrap_or_default();
+                Ok((time_limit.as_secs_f64(), priority_fee(index)?))
+            })
+            .collect()
+    }
+}
+
+/// How the rewards of blocks that differ a lot from the other sampled blocks are discarded, for
+/// example a block with a single transaction with a huge tip. Fewer than four rewards are never
+/// filtered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierFilter {
+    None,
+    /// Discards rewards more than `factor` interquartile ranges below the first or above the third
+    /// quartile (Tukey's fences). 1.5 is the classic factor.
+    Iqr(f64),
+    /// Discards rewards that are further than `threshold` median absolute deviations from the
+    /// median. If more than half the rewards are equal only those are kept.
+    Mad(f64),
+}
+
+impl Default for OutlierFilter {
+    fn default() -> Self {
+        Self::Iqr(1.5)
+    }
+}
+
+impl OutlierFilter {
+    pub fn apply(self, mut values: Vec<f64>) -> Vec<f64> {
+        if values.len() < 4 {
+            return values;
+        }
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let (min, max) = match self {
+            Self::None => return values,
+            Self::Iqr(factor) => {
+                let q1 = quantile(&sorted, 0.25);
+                let q3 = quantile(&sorted, 0.75);
+                let iqr = q3 - q1;
+                (q1 - factor * iqr, q3 + factor * iqr)
+            }
+            Self::Mad(threshold) => {
+                let median = quantile(&sorted, 0.5);
+                let mut deviations = sorted
+                    .iter()
+                    .map(|value| (value - median).abs())
+                    .collect::<Vec<_>>();
+                deviations.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                let mad = quantile(&deviations, 0.5);
+                (median - threshold * mad, median + threshold * mad)
+            }
+        };
+        values.retain(|value| (min..=max).contains(value));
+        values
+    }
+}
+
+// Linear interpolation between the closest ranks. `sorted` must not be empty.
+fn quantile(sorted: &[f64], quantile: f64) -> f64 {
+    let rank = quantile * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_are_ascending_and_unique() {
+        assert_eq!(
+            FeeHistoryConfig::default().reward_percentiles(),
+            vec![10.0, 30.0, 60.0, 90.0]
+        );
+        let config = FeeHistoryConfig::with_urgencies(5, 50.0, 50.0, 80.0);
+        assert_eq!(config.reward_percentiles(), vec![50.0, 80.0]);
+        assert_eq!(
+            config
+                .priority_fee_points(|index| Ok(index as f64))
+                .unwrap(),
+            vec![(60.0, 1.0), (300.0, 0.0), (600.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn iqr_discards_whale_tips() {
+        let rewards = vec![1.0, 2.0, 2.0, 3.0, 100.0];
+        assert_eq!(
+            OutlierFilter::Iqr(1.5).apply(rewards.clone()),
+            vec![1.0, 2.0, 2.0, 3.0]
+        );
+        assert_eq!(OutlierFilter::None.apply(rewards.clone()), rewards);
+        // too few values to tell
+        assert_eq!(
+            OutlierFilter::Iqr(1.5).apply(vec![1.0, 100.0]),
+            vec![1.0, 100.0]
+        );
+        let config = FeeHistoryConfig::default();
+        assert_eq!(config.average_reward(rewards), Some(2.0));
+        assert_eq!(config.average_reward(vec![]), None);
+        assert_eq!(
+            config.reward_range(vec![3.0, 1.0, 2.0, 2.0, 100.0]),
+            Some((1.0, 3.0))
+        );
+        assert_eq!(config.reward_range(vec![]), None);
+    }
+
+    #[test]
+    fn mad_discards_values_far_from_median() {
+        // median 3, absolute deviations 2, 1, 0, 1, 97 so the mad is 1
+        let rewards = vec![1.0, 2.0, 3.0, 4.0, 100.0];
+        assert_eq!(
+            OutlierFilter::Mad(3.0).apply(rewards),
+            vec![1.0, 2.0, 3.0, 4.0]
+        );
+        assert_eq!(
+            OutlierFilter::Mad(3.0).apply(vec![2.0, 2.0, 2.0, 5.0]),
+            vec![2.0, 2.0, 2.0]
+        );
+    }
+}
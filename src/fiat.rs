@@ -0,0 +1,95 @@
+//! Transaction costs in fiat currencies.
+
+use super::{cost::TransactionCostEstimator, error::Result, GasPriceEstimating};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    Usd,
+    Eur,
+}
+
+impl Currency {
+    /// Lowercase ISO 4217 code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Usd => "usd",
+            Currency::Eur => "eur",
+        }
+    }
+}
+
+/// Source of the price of the chain's native token (ETH on mainnet).
+#[async_trait::async_trait]
+pub trait NativeTokenPriceEstimating: Send + Sync {
+    /// Price of one native token in `currency`.
+    async fn native_token_price(&self, currency: Currency) -> Result<f64>;
+}
+
+// Converts the expected transaction cost into a fiat currency.
+pub struct FiatGasCostEstimator<T, P> {
+    cost: TransactionCostEstimator<T>,
+    price: P,
+}
+
+impl<T: GasPriceEstimating, P: NativeTokenPriceEstimating> FiatGasCostEstimator<T, P> {
+    pub fn new(cost: TransactionCostEstimator<T>, price: P) -> Self {
+        Self { cost, price }
+    }
+
+    pub async fn cost_in(
+        &self,
+        currency: Currency,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<f64> {
+        let (cost, price) = futures::try_join!(
+            self.cost.cost_in_native_token(gas_limit, time_limit),
+            self.price.native_token_price(currency),
+        )?;
+        Ok(cost * price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    struct FixedPrice;
+
+    #[async_trait::async_trait]
+    impl NativeTokenPriceEstimating for FixedPrice {
+        async fn native_token_price(&self, currency: Currency) -> Result<f64> {
+            Ok(match currency {
+                Currency::Usd => 2000.0,
+                Currency::Eur => 1800.0,
+            })
+        }
+    }
+
+    #[test]
+    fn converts_cost() {
+        let estimator = FiatGasCostEstimator::new(
+            TransactionCostEstimator::new(FixedGasPriceEstimator::legacy(100e9)),
+            FixedPrice,
+        );
+        // 21000 gas at 100 gwei is 0.0021 ETH
+        assert_approx_eq!(
+            estimator
+                .cost_in(Currency::Usd, 21000.0, Duration::from_secs(30))
+                .wait()
+                .unwrap(),
+            4.2
+        );
+        assert_approx_eq!(
+            estimator
+                .cost_in(Currency::Eur, 21000.0, Duration::from_secs(30))
+                .wait()
+                .unwrap(),
+            3.78
+        );
+    }
+}
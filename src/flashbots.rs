@@ -0,0 +1,350 @@
+//! Priority fee suggestions for transactions that are sent privately to block builders, for
+//! example through Flashbots Protect, instead of the public mempool.
+//!
+//! Builders place bundles and private order flow at the top of their blocks, so the tips paid by
+//! the first transactions of recent blocks show what private transactions need to offer. These
+//! tips are usually well above the public mempool estimate in busy blocks and close to zero in
+//! quiet ones. Payments directly to the block's coinbase are not visible in the transaction fields
+//! and are not taken into account.
+
+use super::{
+    error::Result, interpolation, json_rpc, EstimatedGasPrice, GasEstimationError, GasPrice1559,
+    GasPriceEstimating, GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::json;
+use std::{convert::TryInto, time::Duration};
+
+/// Parameters for the flashbots estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // number of most recent blocks whose top transactions are sampled
+    pub blocks: u64,
+    // number of transactions at the top of every block that are sampled
+    pub top_transactions: usize,
+    // percentile of the sampled tips used for each time limit, sorted by ascending time limit
+    pub buckets: Vec<(Duration, f64)>,
+    // a coefficient to multiply base_fee_per_gas with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+    // priority fee offered when the sampled blocks contain no transactions
+    pub fallback_priority_fee: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            blocks: 5,
+            top_transactions: 5,
+            buckets: vec![
+                (Duration::from_secs(12), 90.0),
+                (Duration::from_secs(60), 50.0),
+                (Duration::from_secs(600), 10.0),
+            ],
+            base_fee_multiplier: 2.0,
+            fallback_priority_fee: 2e9,
+        }
+    }
+}
+
+pub struct FlashbotsPriorityFeeEstimator<T> {
+    transport: T,
+    node_url: String,
+    params: Params,
+}
+
+// Quantities are hex encoded.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Block {
+    number: String,
+    base_fee_per_gas: String,
+    gas_used: String,
+    gas_limit: String,
+    transactions: Vec<Transaction>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transaction {
+    gas_price: Option<String>,
+    max_fee_per_gas: Option<String>,
+    max_priority_fee_per_gas: Option<String>,
+}
+
+impl<T: Transport> FlashbotsPriorityFeeEstimator<T> {
+    pub fn new(transport: T, node_url: String, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    async fn block(&self, number: &str) -> Result<Block> {
+        json_rpc::call(
+            &self.transport,
+            &self.node_url,
+            "eth_getBlockByNumber",
+            json!([number, true]),
+        )
+        .await
+        .map_err(|err| err.context("failed to get block"))
+    }
+
+    // The latest block first.
+    async fn blocks(&self) -> Result<Vec<Block>> {
+        let latest = self.block("latest").await?;
+        let number = json_rpc::quantity_to_f64(&latest.number)? as u64;
+        let older = (1..self.params.blocks.min(number + 1))
+            .map(|offset| format!("0x{:x}", number - offset))
+            .collect::<Vec<_>>();
+        let older =
+            futures::future::try_join_all(older.iter().map(|number| self.block(number))).await?;
+        Ok(std::iter::once(latest).chain(older).collect())
+    }
+
+    /// Suggested max_priority_fee_per_gas in wei for a private transaction to be included within
+    /// the time limit.
+    pub async fn priority_fee(&self, time_limit: Duration) -> Result<f64> {
+        let blocks = self.blocks().await?;
+        priority_fee(&blocks, time_limit, &self.params)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for FlashbotsPriorityFeeEstimator<T> {
+    fn source(&self) -> &'static str {
+        "flashbots"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let blocks = self.blocks().await?;
+        estimate_with_limits(&blocks, time_limit, &self.params)
+    }
+}
+
+// The tip the miner received from the transaction in a block with `base_fee`.
+fn tip(transaction: &Transaction, base_fee: f64) -> Result<f64> {
+    let tip = match (
+        &transaction.max_fee_per_gas,
+        &transaction.max_priority_fee_per_gas,
+    ) {
+        (Some(max_fee), Some(max_priority_fee)) => json_rpc::quantity_to_f64(max_priority_fee)?
+            .min(json_rpc::quantity_to_f64(max_fee)? - base_fee),
+        _ => {
+            let gas_price = transaction.gas_price.as_ref().ok_or_else(|| {
+                GasEstimationError::Decode(anyhow!("transaction without gas price"))
+            })?;
+            json_rpc::quantity_to_f64(gas_price)? - base_fee
+        }
+    };
+    Ok(tip.max(0.0))
+}
+
+// Sorted tips of the top transactions of all blocks.
+fn top_tips(blocks: &[Block], params: &Params) -> Result<Vec<f64>> {
+    let mut tips = Vec::new();
+    for block in blocks {
+        let base_fee = json_rpc::quantity_to_f64(&block.base_fee_per_gas)?;
+        for transaction in block.transactions.iter().take(params.top_transactions) {
+            tips.push(tip(transaction, base_fee)?);
+        }
+    }
+    tips.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(tips)
+}
+
+// Nearest rank percentile of sorted values.
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn priority_fee(blocks: &[Block], time_limit: Duration, params: &Params) -> Result<f64> {
+    let tips = top_tips(blocks, params)?;
+    if tips.is_empty() {
+        return Ok(params.fallback_priority_fee);
+    }
+    let points = params
+        .buckets
+        .iter()
+        .map(|(time_limit, p)| (time_limit.as_secs_f64(), percentile(&tips, *p)))
+        .collect::<Vec<_>>();
+    Ok(interpolation::interpolate(
+        time_limit.as_secs_f64(),
+        points.as_slice().try_into()?,
+    ))
+}
+
+// Base fee of the block after `block` according to EIP-1559.
+fn next_base_fee(block: &Block) -> Result<f64> {
+    let base_fee = json_rpc::quantity_to_f64(&block.base_fee_per_gas)?;
+    let gas_used = json_rpc::quantity_to_f64(&block.gas_used)?;
+    let gas_target = json_rpc::quantity_to_f64(&block.gas_limit)? / 2.0;
+    if gas_target == 0.0 {
+        return Ok(base_fee);
+    }
+    Ok(base_fee * (1.0 + (gas_used - gas_target) / gas_target / 8.0))
+}
+
+fn estimate_with_limits(
+    blocks: &[Block],
+    time_limit: Duration,
+    params: &Params,
+) -> Result<EstimatedGasPrice> {
+    let latest = blocks
+        .first()
+        .ok_or_else(|| GasEstimationError::Decode(anyhow!("no blocks")))?;
+    let base_fee_per_gas = next_base_fee(latest)?;
+    let max_priority_fee_per_gas = priority_fee(blocks, time_limit, params)?;
+    let max_fee_per_gas = base_fee_per_gas * params.base_fee_multiplier + max_priority_fee_per_gas;
+    EstimatedGasPrice {
+        legacy: max_fee_per_gas,
+        eip1559: Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(base_fee_per_gas),
+            max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+            max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+        }),
+    }
+    .validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn eip1559_transaction(max_fee: u64, max_priority_fee: u64) -> Transaction {
+        Transaction {
+            gas_price: Some(format!("0x{:x}", max_fee)),
+            max_fee_per_gas: Some(format!("0x{:x}", max_fee)),
+            max_priority_fee_per_gas: Some(format!("0x{:x}", max_priority_fee)),
+        }
+    }
+
+    fn legacy_transaction(gas_price: u64) -> Transaction {
+        Transaction {
+            gas_price: Some(format!("0x{:x}", gas_price)),
+            ..Default::default()
+        }
+    }
+
+    fn block(transactions: Vec<Transaction>) -> Block {
+        Block {
+            number: "0x10".into(),
+            base_fee_per_gas: "0x64".into(),
+            gas_used: "0x64".into(),
+            gas_limit: "0xc8".into(),
+            transactions,
+        }
+    }
+
+    #[test]
+    fn tips_are_effective_priority_fees() {
+        assert_approx_eq!(tip(&eip1559_transaction(200, 5), 100.0).unwrap(), 5.0);
+        // capped by max fee
+        assert_approx_eq!(tip(&eip1559_transaction(102, 5), 100.0).unwrap(), 2.0);
+        assert_approx_eq!(tip(&legacy_transaction(130), 100.0).unwrap(), 30.0);
+        assert_approx_eq!(tip(&legacy_transaction(50), 100.0).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn samples_top_of_block() {
+        let params = Params {
+            top_transactions: 2,
+            ..Default::default()
+        };
+        let blocks = vec![
+            block(vec![
+                legacy_transaction(200),
+                eip1559_transaction(300, 50),
+                legacy_transaction(101),
+            ]),
+            block(vec![legacy_transaction(110)]),
+        ];
+        assert_eq!(top_tips(&blocks, &params).unwrap(), vec![10.0, 50.0, 100.0]);
+        assert_approx_eq!(
+            priority_fee(&blocks, Duration::from_secs(12), &params).unwrap(),
+            100.0
+        );
+        assert_approx_eq!(
+            priority_fee(&blocks, Duration::from_secs(60), &params).unwrap(),
+            50.0
+        );
+        assert_approx_eq!(
+            priority_fee(&blocks, Duration::from_secs(600), &params).unwrap(),
+            10.0
+        );
+    }
+
+    #[test]
+    fn fallback_without_transactions() {
+        let params = Params::default();
+        let estimate =
+            estimate_with_limits(&[block(vec![])], Duration::from_secs(12), &params).unwrap();
+        assert_approx_eq!(
+            estimate.eip1559.unwrap().max_priority_fee_per_gas.0,
+            params.fallback_priority_fee
+        );
+    }
+
+    #[test]
+    fn next_base_fee_follows_gas_used() {
+        let mut full = block(vec![]);
+        full.gas_used = "0xc8".into();
+        assert_approx_eq!(next_base_fee(&full).unwrap(), 112.5);
+        let mut empty = block(vec![]);
+        empty.gas_used = "0x0".into();
+        assert_approx_eq!(next_base_fee(&empty).unwrap(), 87.5);
+        assert_approx_eq!(next_base_fee(&block(vec![])).unwrap(), 100.0);
+    }
+
+    #[test]
+    fn requests_blocks() {
+        let node = FakeNode::default().with_result(
+            "eth_getBlockByNumber",
+            json!({
+                "number": "0x10",
+                "baseFeePerGas": "0x64",
+                "gasUsed": "0x64",
+                "gasLimit": "0xc8",
+                "transactions": [{
+                    "gasPrice": "0x96",
+                    "maxFeePerGas": "0xc8",
+                    "maxPriorityFeePerGas": "0x32",
+                }],
+            }),
+        );
+        let estimator = FlashbotsPriorityFeeEstimator::new(node, String::new(), None);
+        let estimate = estimator.estimate().wait().unwrap().eip1559.unwrap();
+        assert_approx_eq!(estimate.base_fee_per_gas.0, 100.0);
+        assert_approx_eq!(estimate.max_priority_fee_per_gas.0, 50.0);
+        assert_approx_eq!(estimate.max_fee_per_gas.0, 250.0);
+    }
+
+    // NODE_URL=... cargo test flashbots -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = FlashbotsPriorityFeeEstimator::new(
+            TestTransport::default(),
+            std::env::var("NODE_URL").unwrap(),
+            None,
+        );
+        for time_limit in [12, 60, 600] {
+            let time_limit = Duration::from_secs(time_limit);
+            println!(
+                "{:?}: {:?}",
+                time_limit,
+                estimator.priority_fee(time_limit).await
+            );
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Short horizon forecasts of the base fee, for transactions that are priced now but sent a few
+//! minutes later, like batch auction settlements that happen minutes after the quote.
+//!
+//! The logarithm of the base fee history is smoothed with Holt's linear method (exponential
+//! smoothing of level and trend). The forecast continues the trend and the range widens with the
+//! spread of the one block ahead errors of the smoothing over the history. Both are limited to the
+//! 12.5% per block the base fee can change.
+
+use super::{
+    error::Result,
+    json_rpc::{self, FeeHistory},
+    EstimateRange, EstimatedGasPrice, GasEstimationError, GasPrice1559, GasPriceWei, Transport,
+    DEFAULT_BLOCK_TIME,
+};
+use anyhow::anyhow;
+use std::time::Duration;
+
+// 1 / BASE_FEE_MAX_CHANGE_DENOMINATOR from EIP-1559
+const MAX_CHANGE: f64 = 0.125;
+
+/// Parameters for the forecaster.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // number of most recent blocks the forecast is based on
+    pub fee_history_blocks: u64,
+    pub block_time: Duration,
+    // weight of the newest block in the smoothed level, between 0 and 1
+    pub level_smoothing: f64,
+    // weight of the newest change in the smoothed trend, between 0 and 1
+    pub trend_smoothing: f64,
+    // width of the range in standard deviations of the forecast error
+    pub deviations: f64,
+    // percentile of the priority fees of the recent blocks added to the forecast base fees
+    pub priority_fee_percentile: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            fee_history_blocks: 100,
+            block_time: DEFAULT_BLOCK_TIME,
+            level_smoothing: 0.5,
+            trend_smoothing: 0.1,
+            deviations: 2.0,
+            priority_fee_percentile: 50.0,
+        }
+    }
+}
+
+/// Forecast base fee in wei.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaseFeeForecast {
+    pub expected: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+pub struct BaseFeeForecaster<T> {
+    transport: T,
+    node_url: String,
+    params: Params,
+}
+
+impl<T: Transport> BaseFeeForecaster<T> {
+    pub fn new(transport: T, node_url: String, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    /// The range of gas prices a transaction sent `ahead` from now is likely to need. `low` and
+    /// `high` have the lower and upper bound of the forecast base fee with max_fee_per_gas just
+    /// covering it and the priority fee.
+    pub async fn forecast(&self, ahead: Duration) -> Result<EstimateRange> {
+        let fee_history = json_rpc::fee_history(
+            &self.transport,
+            &self.node_url,
+            self.params.fee_history_blocks,
+            &[self.params.priority_fee_percentile],
+        )
+        .await?;
+        forecast(&fee_history, ahead, &self.params)
+    }
+
+    pub async fn forecast_base_fee(&self, ahead: Duration) -> Result<BaseFeeForecast> {
+        let fee_history = json_rpc::fee_history(
+            &self.transport,
+            &self.node_url,
+            self.params.fee_history_blocks,
+            &[],
+        )
+        .await?;
+        forecast_base_fee(
+            &base_fees(&fee_history)?,
+            blocks(ahead, &self.params),
+            &self.params,
+        )
+    }
+}
+
+fn forecast(fee_history: &FeeHistory, ahead: Duration, params: &Params) -> Result<EstimateRange> {
+    let base_fee = forecast_base_fee(&base_fees(fee_history)?, blocks(ahead, params), params)?;
+    let priority_fee = median_reward(fee_history)?;
+    let price = |base_fee: f64| {
+        let max_fee_per_gas = base_fee + priority_fee;
+        EstimatedGasPrice {
+            legacy: max_fee_per_gas,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee),
+                max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(priority_fee),
+            }),
+        }
+        .validate()
+    };
+    Ok(EstimateRange {
+        low: price(base_fee.low)?,
+        high: price(base_fee.high)?,
+    })
+}
+
+// Blocks after the pending one until `ahead`.
+fn blocks(ahead: Duration, params: &Params) -> u32 {
+    let block_time = params.block_time.as_secs_f64().max(f64::MIN_POSITIVE);
+    (ahead.as_secs_f64() / block_time).ceil() as u32
+}
+
+// The base fees of the history, the last one is the one of the pending block.
+fn base_fees(fee_history: &FeeHistory) -> Result<Vec<f64>> {
+    let base_fees = fee_history
+        .base_fee_per_gas
+        .iter()
+        .map(|base_fee| json_rpc::quantity_to_f64(base_fee))
+        .collect::<Result<Vec<_>>>()?;
+    if base_fees.is_empty() {
+        return Err(GasEstimationError::Decode(anyhow!(
+            "fee history is missing base fee"
+        )));
+    }
+    Ok(base_fees)
+}
+
+// Median of the requested reward percentile over the blocks. Zero without rewards.
+fn median_reward(fee_history: &FeeHistory) -> Result<f64> {
+    let mut rewards = fee_history
+        .reward
+        .iter()
+        .flatten()
+        .filter_map(|rewards| rewards.first())
+        .map(|reward| json_rpc::quantity_to_f64(reward))
+        .collect::<Result<Vec<_>>>()?;
+    rewards.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(match rewards.len() {
+        0 => 0.0,
+        len if len % 2 == 0 => (rewards[len / 2 - 1] + rewards[len / 2]) / 2.0,
+        len => rewards[len / 2],
+    })
+}
+
+/// Forecast of the base fee `blocks` blocks after the last of `base_fees`.
+pub fn forecast_base_fee(
+    base_fees: &[f64],
+    blocks: u32,
+    params: &Params,
+) -> Result<BaseFeeForecast> {
+    let current = *base_fees
+        .last()
+        .ok_or_else(|| GasEstimationError::Other(anyhow!("base fee forecast without base fees")))?;
+    // A base fee of zero has no logarithm but also can't change.
+    if !base_fees.iter().all(|base_fee| *base_fee > 0.0) {
+        return Ok(BaseFeeForecast {
+            expected: current,
+            low: current,
+            high: current,
+        });
+    }
+    let alpha = params.level_smoothing.clamp(0.0, 1.0);
+    let beta = params.trend_smoothing.clamp(0.0, 1.0);
+    let mut logs = base_fees.iter().map(|base_fee| base_fee.ln());
+    let mut level = logs.next().expect("checked not empty");
+    let mut trend = 0.0;
+    let mut squared_errors = 0.0;
+    let mut count = 0;
+    for log in logs {
+        let predicted = level + trend;
+        squared_errors += (log - predicted).powi(2);
+        count += 1;
+        let previous = level;
+        level = alpha * log + (1.0 - alpha) * predicted;
+        trend = beta * (level - previous) + (1.0 - beta) * trend;
+    }
+    let deviation = match count {
+        0 => 0.0,
+        count => (squared_errors / count as f64).sqrt(),
+    };
+    let blocks = blocks as f64;
+    let spread = params.deviations.max(0.0) * deviation * blocks.sqrt();
+    // The forecast starts from the known base fee of the last block.
+    let expected = current.ln() + trend * blocks;
+    let min = current.ln() + (1.0 - MAX_CHANGE).ln() * blocks;
+    let max = current.ln() + (1.0 + MAX_CHANGE).ln() * blocks;
+    Ok(BaseFeeForecast {
+        expected: expected.clamp(min, max).exp(),
+        low: (expected - spread).clamp(min, max).exp(),
+        high: (expected + spread).clamp(min, max).exp(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use serde_json::json;
+
+    #[test]
+    fn constant_history_forecasts_constant() {
+        let forecast = forecast_base_fee(&[100.0; 20], 15, &Default::default()).unwrap();
+        assert_approx_eq!(forecast.expected, 100.0);
+        assert_approx_eq!(forecast.low, 100.0);
+        assert_approx_eq!(forecast.high, 100.0);
+        let forecast = forecast_base_fee(&[0.0, 0.0], 15, &Default::default()).unwrap();
+        assert_eq!(forecast.high, 0.0);
+        assert!(forecast_base_fee(&[], 1, &Default::default()).is_err());
+    }
+
+    #[test]
+    fn continues_trend_within_eip1559_limits() {
+        let rising = (0..50)
+            .map(|block| 100.0 * 1.05f64.powi(block))
+            .collect::<Vec<_>>();
+        let current = *rising.last().unwrap();
+        let forecast = forecast_base_fee(&rising, 10, &Default::default()).unwrap();
+        assert!(forecast.expected > current * 1.3);
+        assert!(forecast.expected < current * 1.05f64.powi(10) * 1.01);
+        assert!(forecast.low <= forecast.expected && forecast.expected <= forecast.high);
+        assert!(forecast.high <= current * 1.125f64.powi(10) * (1.0 + 1e-9));
+
+        // a trend faster than the base fee can change is limited
+        let jumping = (0..10)
+            .map(|block| 100.0 * 2f64.powi(block))
+            .collect::<Vec<_>>();
+        let current = *jumping.last().unwrap();
+        let forecast = forecast_base_fee(&jumping, 4, &Default::default()).unwrap();
+        assert_approx_eq!(forecast.high, current * 1.125f64.powi(4), 1e-6 * current);
+    }
+
+    #[test]
+    fn range_widens_with_volatility_and_horizon() {
+        let volatile = (0..50)
+            .map(|block| if block % 2 == 0 { 100.0 } else { 110.0 })
+            .collect::<Vec<_>>();
+        let near = forecast_base_fee(&volatile, 1, &Default::default()).unwrap();
+        let far = forecast_base_fee(&volatile, 15, &Default::default()).unwrap();
+        assert!(near.low < near.high);
+        assert!(far.high - far.low > near.high - near.low);
+    }
+
+    #[test]
+    fn forecasts_prices_from_fee_history() {
+        let node = FakeNode::default().with_result(
+            "eth_feeHistory",
+            json!({
+                "oldestBlock": "0x1",
+                "baseFeePerGas": ["0x64", "0x64", "0x64"],
+                "gasUsedRatio": [0.5, 0.5],
+                "reward": [["0x1"], ["0x3"]],
+            }),
+        );
+        let forecaster = BaseFeeForecaster::new(node, String::new(), None);
+        let range = forecaster
+            .forecast(Duration::from_secs(180))
+            .wait()
+            .unwrap();
+        for price in [range.low, range.high] {
+            let eip1559 = price.eip1559.unwrap();
+            assert_approx_eq!(eip1559.base_fee_per_gas.0, 100.0);
+            assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 2.0);
+            assert_approx_eq!(eip1559.max_fee_per_gas.0, 102.0);
+        }
+        let base_fee = forecaster
+            .forecast_base_fee(Duration::from_secs(180))
+            .wait()
+            .unwrap();
+        assert_approx_eq!(base_fee.expected, 100.0);
+        assert_eq!(blocks(Duration::from_secs(180), &Default::default()), 15);
+    }
+
+    // NODE_URL=... cargo test forecast -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let forecaster = BaseFeeForecaster::new(
+            TestTransport::default(),
+            std::env::var("NODE_URL").unwrap(),
+            None,
+        );
+        for minutes in [1, 3, 10] {
+            let ahead = Duration::from_secs(60 * minutes);
+            println!(
+                "{}: {:?}",
+                minutes,
+                forecaster.forecast_base_fee(ahead).await
+            );
+        }
+    }
+}
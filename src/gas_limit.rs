@@ -0,0 +1,69 @@
+//! Gas limit estimation so that the whole cost of a transaction can be estimated with this crate.
+//! With the `web3_` feature `eth_node::EthNodeGasLimitEstimator` estimates with `eth_estimateGas`.
+
+use super::error::Result;
+
+/// Transaction to estimate the gas limit for.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GasLimitRequest {
+    pub from: Option<[u8; 20]>,
+    // `None` for contract creations in which case `data` is the init code
+    pub to: Option<[u8; 20]>,
+    // in wei
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+#[async_trait::async_trait]
+pub trait GasLimitEstimating: Send + Sync {
+    /// Gas limit to send the transaction with.
+    async fn estimate_gas_limit(&self, tx: &GasLimitRequest) -> Result<f64>;
+}
+
+/// How the gas used reported by a node is turned into a gas limit.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // a coefficient to multiply the node's estimate with because the gas used can change between
+    // estimation and inclusion, for example when the state the transaction reads changes
+    pub multiplier: f64,
+    // lower the gas limit to the gas limit of the latest block because a transaction with a higher
+    // gas limit can't be included
+    pub cap_at_block_gas_limit: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.2,
+            cap_at_block_gas_limit: true,
+        }
+    }
+}
+
+impl Params {
+    pub fn gas_limit(&self, estimate: f64, block_gas_limit: Option<f64>) -> f64 {
+        let gas_limit = (estimate * self.multiplier).ceil();
+        match block_gas_limit {
+            Some(block_gas_limit) if self.cap_at_block_gas_limit => gas_limit.min(block_gas_limit),
+            _ => gas_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_multiplier_and_cap() {
+        let params = Params::default();
+        assert_eq!(params.gas_limit(100_000.0, Some(30e6)), 120_000.0);
+        assert_eq!(params.gas_limit(100_001.0, None), 120_002.0);
+        assert_eq!(params.gas_limit(29e6, Some(30e6)), 30e6);
+        let params = Params {
+            cap_at_block_gas_limit: false,
+            ..params
+        };
+        assert_eq!(params.gas_limit(29e6, Some(30e6)), 34.8e6);
+    }
+}
@@ -1,12 +1,20 @@
-use anyhow::{anyhow, Result};
+use crate::{
+    bump_strategy::MIN_REPLACEMENT_BUMP, error::Result, time::Instant, GasEstimationError,
+    GasPriceWei, DEFAULT_GAS_LIMIT,
+};
 /// Gas price received from the gas price estimators.
 use serde::Serialize;
+use std::{cmp::Ordering, fmt, time::Duration};
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+// PartialOrd is not derived because comparing the fields lexicographically is meaningless when
+// legacy and EIP-1559 estimates are mixed. Compare with `compare_at` instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
 /// Main gas price structure.
 /// Provide estimated gas prices for both legacy and eip1559 transactions.
 pub struct EstimatedGasPrice {
-    // Estimated gas price for legacy type of transactions.
+    // Estimated gas price in wei for legacy type of transactions. Kept as a plain f64 because it is
+    // the field every user of the crate reads, converting from gwei has to use `GasPriceWei`.
     pub legacy: f64,
     // Estimated gas price for 1559 type of transactions. Optional because not all gas estimators support 1559.
     pub eip1559: Option<GasPrice1559>,
@@ -18,20 +26,72 @@ impl EstimatedGasPrice {
     // (because base_fee_per_gas can change between estimation and mining the tx).
     pub fn effective_gas_price(&self) -> f64 {
         if let Some(gas_price) = &self.eip1559 {
-            std::cmp::min_by(
-                gas_price.max_fee_per_gas,
-                gas_price.max_priority_fee_per_gas + gas_price.base_fee_per_gas,
-                |a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal),
-            )
+            gas_price.effective_gas_price(gas_price.base_fee_per_gas).0
+        } else {
+            self.legacy
+        }
+    }
+
+    // Effective gas price if the transaction is mined in a block with `actual_base_fee`. For legacy
+    // estimates this is the legacy gas price. No rounding is applied.
+    pub fn effective_gas_price_at(&self, actual_base_fee: f64) -> f64 {
+        if let Some(gas_price) = &self.eip1559 {
+            gas_price
+                .effective_gas_price(GasPriceWei(actual_base_fee))
+                .0
         } else {
             self.legacy
         }
     }
 
+    // Orders by the effective gas price the estimates pay in a block with `base_fee`. None if a
+    // price is NaN.
+    pub fn compare_at(&self, other: &Self, base_fee: f64) -> Option<Ordering> {
+        self.effective_gas_price_at(base_fee)
+            .partial_cmp(&other.effective_gas_price_at(base_fee))
+    }
+
+    // Whether a transaction with this gas price pays less than one with `other` in a block with
+    // `base_fee`.
+    pub fn is_cheaper_than(&self, other: &Self, base_fee: f64) -> bool {
+        self.compare_at(other, base_fee) == Some(Ordering::Less)
+    }
+
+    // Legacy only estimate that pays the effective gas price at the estimated base fee. No rounding
+    // is applied so call `ceil` before converting to integer wei.
+    pub fn as_legacy(&self) -> Self {
+        Self {
+            legacy: self.effective_gas_price(),
+            eip1559: None,
+        }
+    }
+
+    // Estimate with 1559 values for the given base fee. Existing 1559 values only get their base fee
+    // replaced. A legacy gas price is converted the same way EIP-1559 treats legacy transactions:
+    // max_fee_per_gas = max_priority_fee_per_gas = gas_price, so the effective gas price stays the
+    // legacy gas price. No rounding is applied.
+    pub fn as_eip1559_with_base_fee(&self, base_fee: f64) -> Self {
+        let eip1559 = match self.eip1559 {
+            Some(gas_price) => GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee),
+                ..gas_price
+            },
+            None => GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee),
+                max_fee_per_gas: GasPriceWei(self.legacy),
+                max_priority_fee_per_gas: GasPriceWei(self.legacy),
+            },
+        };
+        Self {
+            legacy: self.legacy,
+            eip1559: Some(eip1559),
+        }
+    }
+
     // Maximum gas price willing to pay for the transaction.
     pub fn cap(&self) -> f64 {
         self.eip1559
-            .map(|x| x.max_fee_per_gas)
+            .map(|x| x.max_fee_per_gas.0)
             .unwrap_or(self.legacy)
     }
 
@@ -39,23 +99,30 @@ impl EstimatedGasPrice {
     pub fn set_cap(self, max_fee_per_gas: f64) -> Self {
         Self {
             legacy: max_fee_per_gas,
-            eip1559: self.eip1559.map(|x| x.set_cap(max_fee_per_gas)),
+            eip1559: self
+                .eip1559
+                .map(|x| x.set_cap(GasPriceWei(max_fee_per_gas))),
         }
     }
 
     // Maximum tip willing to pay to miners for transaction.
     pub fn tip(&self) -> f64 {
         self.eip1559
-            .map(|x| x.max_priority_fee_per_gas)
+            .map(|x| x.max_priority_fee_per_gas.0)
             .unwrap_or(self.legacy)
     }
 
     pub fn base_fee(&self) -> f64 {
         self.eip1559
-            .map(|x| x.base_fee_per_gas)
+            .map(|x| x.base_fee_per_gas.0)
             .unwrap_or(self.legacy)
     }
 
+    // The legacy gas price in whole wei, rounded like `GasPriceWei::to_u128`.
+    pub fn legacy_wei(&self) -> u128 {
+        GasPriceWei(self.legacy).to_u128()
+    }
+
     // Bump gas price by factor.
     pub fn bump(self, factor: f64) -> Self {
         Self {
@@ -72,7 +139,58 @@ impl EstimatedGasPrice {
         }
     }
 
-    // Ceil gas price (since its defined as float).
+    // Like `bump` but fails with `GasEstimationError::InvalidEstimate` for negative or non finite
+    // factors and if the bumped price is invalid, for example because it overflowed to infinity or
+    // a factor below 1 moved max_fee_per_gas below the base fee.
+    pub fn checked_bump(self, factor: f64) -> Result<Self> {
+        if !(factor.is_finite() && factor >= 0.0) {
+            return Err(GasEstimationError::InvalidEstimate(format!(
+                "invalid bump factor {}",
+                factor
+            )));
+        }
+        self.bump(factor).validate()
+    }
+
+    // Like `bump` but never exceeds `max`, so large factors and infinite results become `max`. A
+    // NaN factor leaves the price unchanged apart from the cap and negative factors are 0. Fails
+    // with `GasEstimationError::InvalidEstimate` if `max` is below the base fee because a
+    // transaction with that cap can't be mined.
+    pub fn saturating_bump(self, factor: f64, max: f64) -> Result<Self> {
+        let factor = if factor.is_nan() {
+            1.0
+        } else {
+            factor.max(0.0)
+        };
+        self.bump(factor).limit_cap(max).validate()
+    }
+
+    // Raises the gas price so that a transaction with it replaces a pending transaction sent with
+    // `previous`. Nodes require every fee to be at least `MIN_REPLACEMENT_BUMP` times the fee of
+    // the pending transaction. For a legacy transaction both the fee cap and the tip are the gas
+    // price. max_fee_per_gas is raised to the priority fee if necessary. The result is rounded up.
+    pub fn bump_for_replacement(&self, previous: &EstimatedGasPrice) -> Self {
+        let min_cap = (previous.cap() * MIN_REPLACEMENT_BUMP).ceil();
+        let min_tip = (previous.tip() * MIN_REPLACEMENT_BUMP).ceil();
+        Self {
+            legacy: self.legacy.max(min_cap),
+            eip1559: self.eip1559.map(|x| {
+                let max_priority_fee_per_gas = x.max_priority_fee_per_gas.max(GasPriceWei(min_tip));
+                GasPrice1559 {
+                    max_fee_per_gas: x
+                        .max_fee_per_gas
+                        .max(GasPriceWei(min_cap))
+                        .max(max_priority_fee_per_gas),
+                    max_priority_fee_per_gas,
+                    ..x
+                }
+            }),
+        }
+        .ceil()
+    }
+
+    // Ceil gas price (since its defined as float). NaN and infinite values are kept as they are so
+    // that `validate` still rejects them afterwards.
     pub fn ceil(self) -> Self {
         Self {
             legacy: self.legacy.ceil(),
@@ -80,43 +198,320 @@ impl EstimatedGasPrice {
         }
     }
 
-    // If current cap if higher then the input, set to input.
+    // If current cap if higher then the input, set to input. NaN prices become the cap and a NaN
+    // cap is ignored, so the result is never NaN unless both are. max_priority_fee_per_gas is
+    // limited to the new max_fee_per_gas. The base fee is left unchanged, so a cap below it
+    // produces an estimate that fails `is_valid`. Use `saturating_bump` or `validate` to get an
+    // error for that instead.
     pub fn limit_cap(self, cap: f64) -> Self {
         Self {
             legacy: self.legacy.min(cap),
-            eip1559: self.eip1559.map(|x| x.limit_cap(cap)),
+            eip1559: self.eip1559.map(|x| x.limit_cap(GasPriceWei(cap))),
         }
     }
 
+    // Whether all prices are finite numbers.
+    pub fn is_finite(&self) -> bool {
+        self.legacy.is_finite() && self.eip1559.map_or(true, |x| x.is_finite())
+    }
+
+    // Whether all prices are 0, like the default estimate.
+    pub fn is_zero(&self) -> bool {
+        self.legacy == 0.0 && self.eip1559.map_or(true, |x| x.is_zero())
+    }
+
     // Validate against rules defined in https://eips.ethereum.org/EIPS/eip-1559
     // max_fee_per_gas >= max_priority_fee_per_gas
     // max_fee_per_gas >= base_fee_per_gas
+    // Additionally all prices have to be finite and non-negative.
     pub fn is_valid(&self) -> bool {
-        self.cap() >= self.tip() && self.cap() >= self.base_fee()
+        self.is_finite()
+            && self.legacy >= 0.0
+            && self.eip1559.map_or(true, |x| x.is_valid())
+            && self.cap() >= self.tip()
+            && self.cap() >= self.base_fee()
     }
 
     // Validate and build Result based on the validation result
     pub fn validate(self) -> Result<EstimatedGasPrice> {
         match self.is_valid() {
             true => Ok(self),
-            false => Err(anyhow!("invalid gas price values: {:?}", self)),
+            false => Err(GasEstimationError::InvalidEstimate(format!(
+                "invalid gas price values: {:?}",
+                self
+            ))),
+        }
+    }
+}
+
+// The estimate that pays the highest effective gas price in a block with `base_fee`, for example to
+// pick the most competitive of several sources. NaN prices are never picked over other prices. The
+// last one wins on ties. None if `estimates` is empty.
+pub fn max_by_effective_price(
+    estimates: impl IntoIterator<Item = EstimatedGasPrice>,
+    base_fee: f64,
+) -> Option<EstimatedGasPrice> {
+    estimates.into_iter().max_by(|a, b| {
+        let (a, b) = (
+            a.effective_gas_price_at(base_fee),
+            b.effective_gas_price_at(base_fee),
+        );
+        match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => a.partial_cmp(&b).unwrap(),
+        }
+    })
+}
+
+/// An estimate together with information about where it came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimateWithMetadata {
+    pub price: EstimatedGasPrice,
+    // Name of the gas price source, see `GasPriceEstimating::source`.
+    pub source: &'static str,
+    // The time at which the estimate was received from the source.
+    pub observed_at: Instant,
+    // Probability of inclusion within the time limit if the source reports one.
+    pub confidence: Option<f64>,
+    // Ratio between the most and least expensive estimate of the sources that a combinator
+    // queried, if it exceeded the factor at which the sources are considered to disagree.
+    pub divergence: Option<f64>,
+    // Bounds around the price if the source reports how uncertain it is.
+    pub range: Option<EstimateRange>,
+}
+
+impl EstimateWithMetadata {
+    /// An estimate of `source` without further information, like the default
+    /// `GasPriceEstimating::estimate_verbose` returns.
+    pub fn new(price: EstimatedGasPrice, source: &'static str, observed_at: Instant) -> Self {
+        Self {
+            price,
+            source,
+            observed_at,
+            confidence: None,
+            divergence: None,
+            range: None,
+        }
+    }
+
+    /// Applies `f` to the price and to the bounds of the range. Decorators use this so that the
+    /// range stays consistent with the price they changed.
+    pub fn map_prices(self, f: impl Fn(EstimatedGasPrice) -> EstimatedGasPrice) -> Self {
+        Self {
+            price: f(self.price),
+            range: self.range.map(|range| EstimateRange {
+                low: f(range.low),
+                high: f(range.high),
+            }),
+            ..self
+        }
+    }
+}
+
+// An estimate that a decorator keeps to serve later calls. Estimates of plain calls are stored
+// without metadata so that only verbose calls that are served from them need the source.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum StoredEstimate {
+    Plain {
+        price: EstimatedGasPrice,
+        observed_at: Instant,
+    },
+    Verbose(EstimateWithMetadata),
+}
+
+impl StoredEstimate {
+    pub(crate) fn price(&self) -> EstimatedGasPrice {
+        match self {
+            Self::Plain { price, .. } => *price,
+            Self::Verbose(estimate) => estimate.price,
+        }
+    }
+
+    // The metadata of the estimate, plain estimates are attributed to `source`.
+    pub(crate) fn with_metadata(
+        self,
+        source: impl FnOnce() -> &'static str,
+    ) -> EstimateWithMetadata {
+        match self {
+            Self::Plain { price, observed_at } => {
+                EstimateWithMetadata::new(price, source(), observed_at)
+            }
+            Self::Verbose(estimate) => estimate,
+        }
+    }
+}
+
+/// Aggressive and conservative alternatives to an estimate with the same time limit, for example
+/// the prices of the neighbouring Blocknative confidence levels. `low` is cheaper but less likely
+/// to be included in time, `high` is more expensive and more likely.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
+pub struct EstimateRange {
+    pub low: EstimatedGasPrice,
+    pub high: EstimatedGasPrice,
+}
+
+/// Estimates for several urgencies at once, for example to let users choose a speed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
+pub struct GasPriceSchedule {
+    pub slow: EstimatedGasPrice,
+    pub standard: EstimatedGasPrice,
+    pub fast: EstimatedGasPrice,
+    pub instant: EstimatedGasPrice,
+}
+
+impl GasPriceSchedule {
+    // Time limits of the urgencies.
+    pub const SLOW: Duration = Duration::from_secs(600);
+    pub const STANDARD: Duration = Duration::from_secs(300);
+    pub const FAST: Duration = Duration::from_secs(60);
+    pub const INSTANT: Duration = Duration::from_secs(15);
+
+    /// Builds the schedule by calling `estimate` with the time limit of every urgency.
+    pub fn from_estimates(
+        mut estimate: impl FnMut(Duration) -> Result<EstimatedGasPrice>,
+    ) -> Result<Self> {
+        Ok(Self {
+            slow: estimate(Self::SLOW)?,
+            standard: estimate(Self::STANDARD)?,
+            fast: estimate(Self::FAST)?,
+            instant: estimate(Self::INSTANT)?,
+        })
+    }
+
+    // Applies `f` to the estimate of every urgency, with its time limit.
+    pub(crate) fn try_map(
+        self,
+        mut f: impl FnMut(Duration, EstimatedGasPrice) -> Result<EstimatedGasPrice>,
+    ) -> Result<Self> {
+        Ok(Self {
+            slow: f(Self::SLOW, self.slow)?,
+            standard: f(Self::STANDARD, self.standard)?,
+            fast: f(Self::FAST, self.fast)?,
+            instant: f(Self::INSTANT, self.instant)?,
+        })
+    }
+
+    pub(crate) fn map(
+        self,
+        mut f: impl FnMut(Duration, EstimatedGasPrice) -> EstimatedGasPrice,
+    ) -> Self {
+        Self {
+            slow: f(Self::SLOW, self.slow),
+            standard: f(Self::STANDARD, self.standard),
+            fast: f(Self::FAST, self.fast),
+            instant: f(Self::INSTANT, self.instant),
         }
     }
+
+    // The (time limit, estimate) pairs of the urgencies.
+    pub(crate) fn entries(&self) -> [(Duration, EstimatedGasPrice); 4] {
+        [
+            (Self::SLOW, self.slow),
+            (Self::STANDARD, self.standard),
+            (Self::FAST, self.fast),
+            (Self::INSTANT, self.instant),
+        ]
+    }
+
+    // The (gas limit, time limit) pairs of the urgencies in the order of `entries`, for caching
+    // the schedule like `estimate_many` with these requests.
+    pub(crate) fn requests() -> [(f64, Duration); 4] {
+        Self::default()
+            .entries()
+            .map(|(time_limit, _)| (DEFAULT_GAS_LIMIT, time_limit))
+    }
+
+    // The schedule of the estimates for `requests`.
+    pub(crate) fn from_requested(estimates: Vec<EstimatedGasPrice>) -> Result<Self> {
+        let count = estimates.len();
+        let [slow, standard, fast, instant]: [EstimatedGasPrice; 4] =
+            estimates.try_into().map_err(|_| {
+                GasEstimationError::Decode(anyhow::anyhow!("{} estimates for 4 urgencies", count))
+            })?;
+        Ok(Self {
+            slow,
+            standard,
+            fast,
+            instant,
+        })
+    }
 }
 
 /// Gas price structure for 1559 transactions.
 /// Contains base_fee_per_gas as an essential part of the gas price estimation.
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
 pub struct GasPrice1559 {
     // Estimated base fee for the pending block (block currently being mined)
-    pub base_fee_per_gas: f64,
+    pub base_fee_per_gas: GasPriceWei,
     // Maximum gas price willing to pay for the transaction.
-    pub max_fee_per_gas: f64,
+    pub max_fee_per_gas: GasPriceWei,
     // Priority fee used to incentivize miners to include the tx in case of network congestion.
-    pub max_priority_fee_per_gas: f64,
+    pub max_priority_fee_per_gas: GasPriceWei,
 }
 
 impl GasPrice1559 {
+    // Gas price paid if the transaction is mined in a block with `base_fee`.
+    pub fn effective_gas_price(&self, base_fee: GasPriceWei) -> GasPriceWei {
+        std::cmp::min_by(
+            self.max_fee_per_gas,
+            self.max_priority_fee_per_gas + base_fee,
+            |a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal),
+        )
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.base_fee_per_gas.is_finite()
+            && self.max_fee_per_gas.is_finite()
+            && self.max_priority_fee_per_gas.is_finite()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.base_fee_per_gas.0 == 0.0
+            && self.max_fee_per_gas.0 == 0.0
+            && self.max_priority_fee_per_gas.0 == 0.0
+    }
+
+    // Finite, non-negative and max_fee_per_gas covers both the base fee and the priority fee.
+    pub fn is_valid(&self) -> bool {
+        self.is_finite()
+            && self.base_fee_per_gas.0 >= 0.0
+            && self.max_priority_fee_per_gas.0 >= 0.0
+            && self.max_fee_per_gas >= self.max_priority_fee_per_gas
+            && self.max_fee_per_gas >= self.base_fee_per_gas
+    }
+
+    // Estimate from integer wei amounts, for example the fees of a pending transaction.
+    pub fn from_wei(
+        base_fee_per_gas: u128,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    ) -> Self {
+        Self {
+            base_fee_per_gas: base_fee_per_gas.into(),
+            max_fee_per_gas: max_fee_per_gas.into(),
+            max_priority_fee_per_gas: max_priority_fee_per_gas.into(),
+        }
+    }
+
+    // The fees in whole wei, rounded like `GasPriceWei::to_u128`. Rounding up keeps
+    // max_fee_per_gas >= max_priority_fee_per_gas.
+    pub fn base_fee_per_gas_wei(&self) -> u128 {
+        self.base_fee_per_gas.to_u128()
+    }
+
+    pub fn max_fee_per_gas_wei(&self) -> u128 {
+        self.max_fee_per_gas.to_u128()
+    }
+
+    pub fn max_priority_fee_per_gas_wei(&self) -> u128 {
+        self.max_priority_fee_per_gas.to_u128()
+    }
+
     // Bump gas price by factor.
     pub fn bump(self, factor: f64) -> Self {
         Self {
@@ -135,7 +530,7 @@ impl GasPrice1559 {
     }
 
     // Set max gas price.
-    pub fn set_cap(self, max_fee_per_gas: f64) -> Self {
+    pub fn set_cap(self, max_fee_per_gas: GasPriceWei) -> Self {
         Self {
             max_fee_per_gas,
             ..self
@@ -152,7 +547,7 @@ impl GasPrice1559 {
     }
 
     // If current cap if higher then the input, set to input.
-    pub fn limit_cap(self, cap: f64) -> Self {
+    pub fn limit_cap(self, cap: GasPriceWei) -> Self {
         Self {
             max_fee_per_gas: self.max_fee_per_gas.min(cap),
             max_priority_fee_per_gas: self
@@ -163,11 +558,140 @@ impl GasPrice1559 {
     }
 }
 
+// `32.1 gwei base + 1.5 gwei tip, cap 60 gwei`
+impl fmt::Display for GasPrice1559 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} base + {} tip, cap {}",
+            self.base_fee_per_gas, self.max_priority_fee_per_gas, self.max_fee_per_gas
+        )
+    }
+}
+
+// The EIP-1559 fees if there are any, otherwise the legacy gas price: `20 gwei`.
+impl fmt::Display for EstimatedGasPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.eip1559 {
+            Some(eip1559) => eip1559.fmt(f),
+            None => GasPriceWei(self.legacy).fmt(f),
+        }
+    }
+}
+
+/// The default serialization uses the snake_case field names of the structs. Use this module with
+/// `#[serde(with = "gas_estimation::gas_price::camel_case")]` on an `EstimatedGasPrice` field to
+/// (de)serialize with camelCase field names instead, for example `maxFeePerGas`.
+pub mod camel_case {
+    use super::EstimatedGasPrice;
+    use crate::GasPriceWei;
+    use serde::{Serialize, Serializer};
+
+    #[derive(Serialize)]
+    #[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
+    #[serde(rename_all = "camelCase")]
+    struct EstimatedGasPriceCamelCase {
+        legacy: f64,
+        eip1559: Option<GasPrice1559CamelCase>,
+    }
+
+    #[derive(Serialize)]
+    #[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
+    #[serde(rename_all = "camelCase")]
+    struct GasPrice1559CamelCase {
+        base_fee_per_gas: GasPriceWei,
+        max_fee_per_gas: GasPriceWei,
+        max_priority_fee_per_gas: GasPriceWei,
+    }
+
+    pub fn serialize<S: Serializer>(
+        estimate: &EstimatedGasPrice,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        EstimatedGasPriceCamelCase {
+            legacy: estimate.legacy,
+            eip1559: estimate.eip1559.map(|x| GasPrice1559CamelCase {
+                base_fee_per_gas: x.base_fee_per_gas,
+                max_fee_per_gas: x.max_fee_per_gas,
+                max_priority_fee_per_gas: x.max_priority_fee_per_gas,
+            }),
+        }
+        .serialize(serializer)
+    }
+
+    #[cfg(feature = "serde_")]
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<EstimatedGasPrice, D::Error> {
+        let estimate =
+            <EstimatedGasPriceCamelCase as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(EstimatedGasPrice {
+            legacy: estimate.legacy,
+            eip1559: estimate.eip1559.map(|x| super::GasPrice1559 {
+                base_fee_per_gas: x.base_fee_per_gas,
+                max_fee_per_gas: x.max_fee_per_gas,
+                max_priority_fee_per_gas: x.max_priority_fee_per_gas,
+            }),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{EstimatedGasPrice, GasPrice1559};
+    use crate::{
+        max_by_effective_price, EstimatedGasPrice, GasEstimationError, GasPrice1559, GasPriceWei,
+    };
     use assert_approx_eq::assert_approx_eq;
 
+    #[test]
+    fn integer_wei_accessors() {
+        let gas_price = GasPrice1559::from_wei(10, 21, 2);
+        assert_eq!(gas_price.max_fee_per_gas, GasPriceWei(21.0));
+        let bumped = gas_price.bump(1.125);
+        assert_eq!(bumped.base_fee_per_gas_wei(), 10);
+        assert_eq!(bumped.max_fee_per_gas_wei(), 24);
+        assert_eq!(bumped.max_priority_fee_per_gas_wei(), 3);
+        let estimate = EstimatedGasPrice {
+            legacy: 20e9 + 0.5,
+            eip1559: None,
+        };
+        assert_eq!(estimate.legacy_wei(), 20_000_000_001);
+    }
+
+    #[test]
+    fn bump_for_replacement_satisfies_replacement_rules() {
+        let previous = EstimatedGasPrice {
+            legacy: 100.0,
+            eip1559: None,
+        };
+        // a 1559 transaction replacing a legacy one needs both fees above the legacy gas price
+        let estimate = EstimatedGasPrice {
+            legacy: 90.0,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(50.0),
+                max_fee_per_gas: GasPriceWei(120.0),
+                max_priority_fee_per_gas: GasPriceWei(2.0),
+            }),
+        };
+        assert_eq!(
+            estimate.bump_for_replacement(&previous),
+            EstimatedGasPrice {
+                legacy: 113.0,
+                eip1559: Some(GasPrice1559 {
+                    base_fee_per_gas: GasPriceWei(50.0),
+                    max_fee_per_gas: GasPriceWei(120.0),
+                    max_priority_fee_per_gas: GasPriceWei(113.0),
+                }),
+            }
+        );
+        // already high enough estimates are kept
+        let estimate = EstimatedGasPrice {
+            legacy: 200.0,
+            eip1559: None,
+        };
+        assert_eq!(estimate.bump_for_replacement(&previous), estimate);
+    }
+
     #[test]
     fn cap_legacy() {
         //assert legacy is returned
@@ -187,7 +711,7 @@ mod tests {
         assert_approx_eq!(
             EstimatedGasPrice {
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 1.0,
+                    max_fee_per_gas: GasPriceWei(1.0),
                     ..Default::default()
                 }),
                 ..Default::default()
@@ -204,7 +728,7 @@ mod tests {
             EstimatedGasPrice {
                 legacy: 1.0,
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 2.0,
+                    max_fee_per_gas: GasPriceWei(2.0),
                     ..Default::default()
                 }),
             }
@@ -218,8 +742,8 @@ mod tests {
         let gas_price = EstimatedGasPrice {
             legacy: 1.0,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 2.0,
-                max_priority_fee_per_gas: 3.0,
+                max_fee_per_gas: GasPriceWei(2.0),
+                max_priority_fee_per_gas: GasPriceWei(3.0),
                 ..Default::default()
             }),
         };
@@ -227,8 +751,8 @@ mod tests {
         let gas_price_bumped = EstimatedGasPrice {
             legacy: 1.125,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 2.25,
-                max_priority_fee_per_gas: 3.375,
+                max_fee_per_gas: GasPriceWei(2.25),
+                max_priority_fee_per_gas: GasPriceWei(3.375),
                 ..Default::default()
             }),
         };
@@ -236,8 +760,8 @@ mod tests {
         let gas_price_bumped_and_ceiled = EstimatedGasPrice {
             legacy: 2.0,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 3.0,
-                max_priority_fee_per_gas: 4.0,
+                max_fee_per_gas: GasPriceWei(3.0),
+                max_priority_fee_per_gas: GasPriceWei(4.0),
                 ..Default::default()
             }),
         };
@@ -251,8 +775,8 @@ mod tests {
         let gas_price = EstimatedGasPrice {
             legacy: 10.0,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 5.0,
-                max_priority_fee_per_gas: 3.0,
+                max_fee_per_gas: GasPriceWei(5.0),
+                max_priority_fee_per_gas: GasPriceWei(3.0),
                 ..Default::default()
             }),
         };
@@ -260,8 +784,8 @@ mod tests {
         let gas_price_capped = EstimatedGasPrice {
             legacy: 4.0,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 4.0,
-                max_priority_fee_per_gas: 3.0,
+                max_fee_per_gas: GasPriceWei(4.0),
+                max_priority_fee_per_gas: GasPriceWei(3.0),
                 ..Default::default()
             }),
         };
@@ -274,8 +798,8 @@ mod tests {
         let gas_price = EstimatedGasPrice {
             legacy: 10.0,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 5.0,
-                max_priority_fee_per_gas: 3.0,
+                max_fee_per_gas: GasPriceWei(5.0),
+                max_priority_fee_per_gas: GasPriceWei(3.0),
                 ..Default::default()
             }),
         };
@@ -283,8 +807,8 @@ mod tests {
         let gas_price_capped = EstimatedGasPrice {
             legacy: 2.0,
             eip1559: Some(GasPrice1559 {
-                max_fee_per_gas: 2.0,
-                max_priority_fee_per_gas: 2.0,
+                max_fee_per_gas: GasPriceWei(2.0),
+                max_priority_fee_per_gas: GasPriceWei(2.0),
                 ..Default::default()
             }),
         };
@@ -311,9 +835,9 @@ mod tests {
         assert_approx_eq!(
             EstimatedGasPrice {
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 10.0,
-                    max_priority_fee_per_gas: 5.0,
-                    base_fee_per_gas: 2.0
+                    max_fee_per_gas: GasPriceWei(10.0),
+                    max_priority_fee_per_gas: GasPriceWei(5.0),
+                    base_fee_per_gas: GasPriceWei(2.0)
                 }),
                 ..Default::default()
             }
@@ -324,9 +848,9 @@ mod tests {
         assert_approx_eq!(
             EstimatedGasPrice {
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 10.0,
-                    max_priority_fee_per_gas: 8.0,
-                    base_fee_per_gas: 2.0
+                    max_fee_per_gas: GasPriceWei(10.0),
+                    max_priority_fee_per_gas: GasPriceWei(8.0),
+                    base_fee_per_gas: GasPriceWei(2.0)
                 }),
                 ..Default::default()
             }
@@ -337,9 +861,9 @@ mod tests {
         assert_approx_eq!(
             EstimatedGasPrice {
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 10.0,
-                    max_priority_fee_per_gas: 10.0,
-                    base_fee_per_gas: 2.0
+                    max_fee_per_gas: GasPriceWei(10.0),
+                    max_priority_fee_per_gas: GasPriceWei(10.0),
+                    base_fee_per_gas: GasPriceWei(2.0)
                 }),
                 ..Default::default()
             }
@@ -348,14 +872,84 @@ mod tests {
         );
     }
 
+    #[test]
+    fn effective_gas_price_at_actual_base_fee() {
+        let gas_price = EstimatedGasPrice {
+            eip1559: Some(GasPrice1559 {
+                max_fee_per_gas: GasPriceWei(10.0),
+                max_priority_fee_per_gas: GasPriceWei(5.0),
+                base_fee_per_gas: GasPriceWei(2.0),
+            }),
+            legacy: 8.0,
+        };
+        assert_approx_eq!(gas_price.effective_gas_price_at(1.0), 6.0);
+        assert_approx_eq!(gas_price.effective_gas_price_at(6.0), 10.0);
+        assert_approx_eq!(
+            EstimatedGasPrice {
+                legacy: 8.0,
+                ..Default::default()
+            }
+            .effective_gas_price_at(1.0),
+            8.0
+        );
+    }
+
+    #[test]
+    fn as_legacy() {
+        let gas_price = EstimatedGasPrice {
+            eip1559: Some(GasPrice1559 {
+                max_fee_per_gas: GasPriceWei(10.0),
+                max_priority_fee_per_gas: GasPriceWei(5.0),
+                base_fee_per_gas: GasPriceWei(2.0),
+            }),
+            legacy: 8.0,
+        };
+        assert_eq!(
+            gas_price.as_legacy(),
+            EstimatedGasPrice {
+                legacy: 7.0,
+                eip1559: None
+            }
+        );
+    }
+
+    #[test]
+    fn as_eip1559_with_base_fee() {
+        let legacy = EstimatedGasPrice {
+            legacy: 8.0,
+            ..Default::default()
+        };
+        let converted = legacy.as_eip1559_with_base_fee(2.0);
+        assert_eq!(
+            converted.eip1559,
+            Some(GasPrice1559 {
+                max_fee_per_gas: GasPriceWei(8.0),
+                max_priority_fee_per_gas: GasPriceWei(8.0),
+                base_fee_per_gas: GasPriceWei(2.0),
+            })
+        );
+        assert_approx_eq!(
+            converted.effective_gas_price(),
+            legacy.effective_gas_price()
+        );
+        assert_eq!(
+            converted
+                .as_eip1559_with_base_fee(3.0)
+                .eip1559
+                .unwrap()
+                .base_fee_per_gas,
+            GasPriceWei(3.0)
+        );
+    }
+
     #[test]
     fn estimate_legacy_and_eip1559() {
         assert_approx_eq!(
             EstimatedGasPrice {
                 eip1559: Some(GasPrice1559 {
-                    max_fee_per_gas: 10.0,
-                    max_priority_fee_per_gas: 5.0,
-                    base_fee_per_gas: 2.0
+                    max_fee_per_gas: GasPriceWei(10.0),
+                    max_priority_fee_per_gas: GasPriceWei(5.0),
+                    base_fee_per_gas: GasPriceWei(2.0)
                 }),
                 legacy: 8.0
             }
@@ -363,4 +957,216 @@ mod tests {
             7.0
         );
     }
+
+    fn estimate() -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy: 3.0,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(1.0),
+                max_fee_per_gas: GasPriceWei(4.0),
+                max_priority_fee_per_gas: GasPriceWei(2.0),
+            }),
+        }
+    }
+
+    #[test]
+    fn serializes_snake_case() {
+        assert_eq!(
+            serde_json::to_value(estimate()).unwrap(),
+            serde_json::json!({
+                "legacy": 3.0,
+                "eip1559": {
+                    "base_fee_per_gas": 1.0,
+                    "max_fee_per_gas": 4.0,
+                    "max_priority_fee_per_gas": 2.0,
+                },
+            })
+        );
+    }
+
+    #[derive(serde::Serialize)]
+    #[cfg_attr(feature = "serde_", derive(serde::Deserialize, Debug, PartialEq))]
+    struct CamelCase {
+        #[serde(with = "super::camel_case")]
+        estimate: EstimatedGasPrice,
+    }
+
+    #[test]
+    fn serializes_camel_case() {
+        let json = serde_json::json!({
+            "estimate": {
+                "legacy": 3.0,
+                "eip1559": {
+                    "baseFeePerGas": 1.0,
+                    "maxFeePerGas": 4.0,
+                    "maxPriorityFeePerGas": 2.0,
+                },
+            },
+        });
+        let value = CamelCase {
+            estimate: estimate(),
+        };
+        assert_eq!(serde_json::to_value(&value).unwrap(), json);
+        #[cfg(feature = "serde_")]
+        assert_eq!(serde_json::from_value::<CamelCase>(json).unwrap(), value);
+    }
+
+    #[cfg(feature = "serde_")]
+    #[test]
+    fn deserializes() {
+        let json = serde_json::to_string(&estimate()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<EstimatedGasPrice>(&json).unwrap(),
+            estimate()
+        );
+        let legacy: EstimatedGasPrice =
+            serde_json::from_str(r#"{"legacy": 1.0, "eip1559": null}"#).unwrap();
+        assert!(legacy.eip1559.is_none());
+    }
+
+    #[test]
+    fn compares_by_effective_price_at_base_fee() {
+        let legacy = EstimatedGasPrice {
+            legacy: 20.0,
+            eip1559: None,
+        };
+        // lexicographically larger because of the higher legacy price
+        let eip1559 = EstimatedGasPrice {
+            legacy: 30.0,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(10.0),
+                max_fee_per_gas: GasPriceWei(30.0),
+                max_priority_fee_per_gas: GasPriceWei(2.0),
+            }),
+        };
+        // pays base fee plus tip
+        assert!(eip1559.is_cheaper_than(&legacy, 10.0));
+        assert!(!legacy.is_cheaper_than(&eip1559, 10.0));
+        // pays the cap
+        assert!(legacy.is_cheaper_than(&eip1559, 40.0));
+        assert_eq!(
+            eip1559.compare_at(&eip1559, 10.0),
+            Some(std::cmp::Ordering::Equal)
+        );
+
+        assert_eq!(
+            max_by_effective_price([legacy, eip1559], 10.0),
+            Some(legacy)
+        );
+        assert_eq!(
+            max_by_effective_price([legacy, eip1559], 25.0),
+            Some(eip1559)
+        );
+        let nan = EstimatedGasPrice {
+            legacy: f64::NAN,
+            eip1559: None,
+        };
+        assert_eq!(max_by_effective_price([legacy, nan], 10.0), Some(legacy));
+        assert_eq!(max_by_effective_price([], 10.0), None);
+    }
+
+    #[test]
+    fn predicates_and_display() {
+        let eip1559 = GasPrice1559 {
+            base_fee_per_gas: GasPriceWei::from_gwei(32.1),
+            max_fee_per_gas: GasPriceWei::from_gwei(60.0),
+            max_priority_fee_per_gas: GasPriceWei::from_gwei(1.5),
+        };
+        let estimate = EstimatedGasPrice {
+            legacy: 60e9,
+            eip1559: Some(eip1559),
+        };
+        assert_eq!(
+            estimate.to_string(),
+            "32.1 gwei base + 1.5 gwei tip, cap 60 gwei"
+        );
+        assert_eq!(estimate.as_legacy().to_string(), "33.6 gwei");
+        assert!(estimate.is_valid() && !estimate.is_zero());
+        assert!(EstimatedGasPrice::default().is_zero());
+        assert!(GasPrice1559::default().is_zero());
+
+        let negative_tip = GasPrice1559 {
+            max_priority_fee_per_gas: GasPriceWei(-1.0),
+            ..eip1559
+        };
+        assert!(!negative_tip.is_valid());
+        assert!(!EstimatedGasPrice {
+            legacy: 60e9,
+            eip1559: Some(negative_tip)
+        }
+        .is_valid());
+        assert!(!EstimatedGasPrice {
+            legacy: -1.0,
+            eip1559: None
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn checked_and_saturating_bumps() {
+        let estimate = EstimatedGasPrice {
+            legacy: 20.0,
+            eip1559: Some(GasPrice1559::from_wei(10, 20, 2)),
+        };
+        assert_eq!(estimate.checked_bump(2.0).unwrap(), estimate.bump(2.0));
+        assert!(estimate.checked_bump(f64::NAN).is_err());
+        assert!(estimate.checked_bump(-1.0).is_err());
+        assert!(estimate.checked_bump(f64::MAX).is_err());
+        // max_fee_per_gas below the base fee
+        assert!(estimate.checked_bump(0.1).is_err());
+
+        assert_eq!(
+            estimate.saturating_bump(2.0, 100.0).unwrap(),
+            estimate.bump(2.0)
+        );
+        let saturated = estimate.saturating_bump(f64::MAX, 100.0).unwrap();
+        assert_eq!(saturated.legacy, 100.0);
+        assert_eq!(saturated.cap(), 100.0);
+        assert_eq!(saturated.tip(), 100.0);
+        assert!(saturated.is_valid());
+        assert_eq!(estimate.saturating_bump(f64::NAN, 100.0).unwrap(), estimate);
+    }
+
+    #[test]
+    fn cap_below_base_fee() {
+        let estimate = EstimatedGasPrice {
+            legacy: 20.0,
+            eip1559: Some(GasPrice1559::from_wei(10, 20, 2)),
+        };
+        assert!(matches!(
+            estimate.saturating_bump(2.0, 5.0),
+            Err(GasEstimationError::InvalidEstimate(_))
+        ));
+        let capped = estimate.limit_cap(5.0);
+        assert_eq!(capped.cap(), 5.0);
+        assert_eq!(capped.base_fee(), 10.0);
+        assert!(!capped.is_valid());
+    }
+
+    #[test]
+    fn nan_handling_of_ceil_and_limit_cap() {
+        let nan = EstimatedGasPrice {
+            legacy: f64::NAN,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(1.0),
+                max_fee_per_gas: GasPriceWei(f64::NAN),
+                max_priority_fee_per_gas: GasPriceWei(1.0),
+            }),
+        };
+        assert!(!nan.ceil().is_valid());
+        let capped = nan.limit_cap(5.0);
+        assert_eq!(capped.legacy, 5.0);
+        assert_eq!(capped.cap(), 5.0);
+        assert_eq!(capped.tip(), 1.0);
+        let estimate = EstimatedGasPrice {
+            legacy: 3.0,
+            eip1559: None,
+        };
+        assert_eq!(estimate.limit_cap(f64::NAN), estimate);
+        assert!(!EstimatedGasPrice {
+            legacy: f64::INFINITY,
+            eip1559: None
+        }
+        .is_valid());
+    }
 }
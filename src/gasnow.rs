@@ -1,20 +1,35 @@
-use super::{linear_interpolation, EstimatedGasPrice, GasPriceEstimating, Transport};
-use anyhow::{anyhow, Context, Result};
-use futures::lock::Mutex;
-use std::{
-    convert::TryInto,
-    future::Future,
-    time::{Duration, Instant},
+use super::{
+    auth,
+    error::Result,
+    interpolation::InterpolationStrategy,
+    time::{Clock, Instant, SystemClock},
+    trace, ApiCredentials, EstimatedGasPrice, GasEstimationError, GasPriceEstimating,
+    GasPriceSchedule, Transport,
 };
+use anyhow::anyhow;
+use futures::lock::Mutex;
+use http::header::HeaderMap;
+use serde_json::Value;
+use std::{convert::TryInto, future::Future, time::Duration};
 
 // Gas price estimation with https://www.gasnow.org/ , api at https://taichi.network/#gasnow .
 
 const API_URI: &str = "https://etherchain.org/api/gasnow";
-const RATE_LIMIT: Duration = Duration::from_secs(15);
+/// How often GasNow is requested at most unless configured with `with_refresh_rate`.
+pub const DEFAULT_REFRESH_RATE: Duration = Duration::from_secs(15);
 
 pub struct GasNowGasStation<T> {
     transport: T,
+    url: String,
+    // Sent with every request, for example to authenticate with self hosted services.
+    header: HeaderMap,
+    credentials: Option<ApiCredentials>,
+    format: ResponseFormat,
+    interpolation: InterpolationStrategy,
     last_response: Mutex<Option<CachedResponse>>,
+    // Responses, including errors, are reused for this long.
+    refresh_rate: Duration,
+    clock: Box<dyn Clock>,
 }
 
 struct CachedResponse {
@@ -24,13 +39,15 @@ struct CachedResponse {
     data: Option<Response>,
 }
 
+/// A response in the GasNow format, which every `ResponseFormat` is converted to.
 #[derive(Clone, Copy, Debug, Default, serde::Deserialize, PartialEq)]
 pub struct Response {
+    /// 200 unless the service reported an error.
     pub code: u32,
     pub data: ResponseData,
 }
 
-// gas prices in wei
+/// Gas prices in wei for inclusion within `RAPID`, `FAST`, `STANDARD` and `SLOW`.
 #[derive(Clone, Copy, Debug, Default, serde::Deserialize, PartialEq)]
 pub struct ResponseData {
     pub rapid: f64,
@@ -39,6 +56,91 @@ pub struct ResponseData {
     pub slow: f64,
 }
 
+/// Response shapes of the services with a GasNow like api. All of them are converted to the GasNow
+/// `Response`. Unknown fields are ignored and numbers can also be sent as strings. Only the fast
+/// and standard prices are required, a missing rapid price is the fast price and a missing slow
+/// price is the standard price.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// `{"code": 200, "data": {"rapid": 4, "fast": 3, "standard": 2, "slow": 1}}` with prices in
+    /// wei, used by GasNow and the gasnow endpoint of etherchain.
+    #[default]
+    GasNow,
+    /// `{"fastest": 4, "fast": 3, "standard": 2, "safeLow": 1}` with prices in gwei, used by the
+    /// gas price oracle of etherchain.
+    Etherchain,
+    /// `{"FastGasPrice": "3", "ProposeGasPrice": "2", "SafeGasPrice": "1"}` with prices in gwei,
+    /// used by MetaSwap.
+    MetaSwap,
+}
+
+impl ResponseFormat {
+    /// Parses a response body. Never panics, invalid input results in a decode error, which makes
+    /// this suitable as a fuzzing target.
+    pub fn parse_slice(self, body: &[u8]) -> Result<Response> {
+        self.parse(&serde_json::from_slice(body)?)
+    }
+
+    pub fn parse(self, response: &Value) -> Result<Response> {
+        // (rapid, fast, standard, slow) field names and the factor to convert to wei
+        let (data, [rapid, fast, standard, slow], factor) = match self {
+            Self::GasNow => (
+                response.get("data").unwrap_or(response),
+                ["rapid", "fast", "standard", "slow"],
+                1.0,
+            ),
+            Self::Etherchain => (response, ["fastest", "fast", "standard", "safeLow"], 1e9),
+            Self::MetaSwap => (
+                response,
+                ["", "FastGasPrice", "ProposeGasPrice", "SafeGasPrice"],
+                1e9,
+            ),
+        };
+        let price = |name: &str| -> Result<Option<f64>> {
+            match data.get(name) {
+                None | Some(Value::Null) => Ok(None),
+                Some(value) => match number(value) {
+                    Some(price) if price.is_finite() && price >= 0.0 => Ok(Some(price * factor)),
+                    _ => Err(GasEstimationError::Decode(anyhow!(
+                        "invalid {} price {}",
+                        name,
+                        value
+                    ))),
+                },
+            }
+        };
+        let required = |name: &str| -> Result<f64> {
+            price(name)?.ok_or_else(|| {
+                GasEstimationError::Decode(anyhow!("missing {} price in {:?} response", name, self))
+            })
+        };
+        let fast = required(fast)?;
+        let standard = required(standard)?;
+        let code = response
+            .get("code")
+            .and_then(Value::as_u64)
+            .and_then(|code| code.try_into().ok())
+            .unwrap_or(200);
+        Ok(Response {
+            code,
+            data: ResponseData {
+                rapid: price(rapid)?.unwrap_or(fast),
+                fast,
+                standard,
+                slow: price(slow)?.unwrap_or(standard),
+            },
+        })
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    }
+}
+
 pub const RAPID: Duration = Duration::from_secs(15);
 pub const FAST: Duration = Duration::from_secs(60);
 pub const STANDARD: Duration = Duration::from_secs(300);
@@ -48,6 +150,14 @@ pub fn estimate_with_limits(
     _gas_limit: f64,
     time_limit: Duration,
     response: &ResponseData,
+) -> Result<EstimatedGasPrice> {
+    estimate_with_strategy(time_limit, response, InterpolationStrategy::Linear)
+}
+
+pub fn estimate_with_strategy(
+    time_limit: Duration,
+    response: &ResponseData,
+    strategy: InterpolationStrategy,
 ) -> Result<EstimatedGasPrice> {
     let points: &[(f64, f64)] = &[
         (RAPID.as_secs_f64(), response.rapid),
@@ -56,24 +166,96 @@ pub fn estimate_with_limits(
         (SLOW.as_secs_f64(), response.slow),
     ];
     Ok(EstimatedGasPrice {
-        legacy: linear_interpolation::interpolate(time_limit.as_secs_f64(), points.try_into()?),
+        legacy: strategy.interpolate(time_limit.as_secs_f64(), points.try_into()?),
         ..Default::default()
     })
 }
 
 impl<T: Transport> GasNowGasStation<T> {
     pub fn new(transport: T) -> Self {
+        Self::with_url(transport, API_URI)
+    }
+
+    /// Use a different service with the same api as GasNow, for example a self hosted instance.
+    pub fn with_url(transport: T, url: impl Into<String>) -> Self {
         Self {
             transport,
+            url: url.into(),
+            header: Default::default(),
+            credentials: None,
+            format: Default::default(),
+            interpolation: Default::default(),
             last_response: Default::default(),
+            refresh_rate: DEFAULT_REFRESH_RATE,
+            clock: Box::new(SystemClock),
         }
     }
 
+    /// Headers sent with every request, for example an api key.
+    pub fn with_header(self, header: HeaderMap) -> Self {
+        Self { header, ..self }
+    }
+
+    /// Credentials sent with every request, for example for a paid tier.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    /// The response format of the service at the url, GasNow by default.
+    pub fn with_format(self, format: ResponseFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    /// How to interpolate between the GasNow buckets, linear by default.
+    pub fn with_interpolation(self, interpolation: InterpolationStrategy) -> Self {
+        Self {
+            interpolation,
+            ..self
+        }
+    }
+
+    /// Request the api at most once per `refresh_rate`, for example faster for a mirror without
+    /// the rate limit of GasNow.
+    pub fn with_refresh_rate(self, refresh_rate: Duration) -> Self {
+        Self {
+            refresh_rate,
+            ..self
+        }
+    }
+
+    /// The effective time between requests, estimates in between use the cached response.
+    pub fn refresh_rate(&self) -> Duration {
+        self.refresh_rate
+    }
+
+    /// The time used to enforce the rate limit, the system clock by default.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            ..self
+        }
+    }
+
+    /// The response without interpolating it, for custom mappings of the tiers. Shares the cache
+    /// of the estimates so it doesn't request more often than the refresh rate.
+    pub async fn fetch_raw(&self) -> Result<Response> {
+        self.gas_price_with_cache(self.clock.now(), || self.gas_price_without_cache())
+            .await
+    }
+
     async fn gas_price_without_cache(&self) -> Result<Response> {
-        self.transport
-            .get_json(API_URI, Default::default())
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.url, self.header.clone())?;
+        let response: Value = self
+            .transport
+            .get_json(&url, header)
             .await
-            .context("failed to get gasnow gas price")
+            .map_err(|err| err.context("failed to get gasnow gas price"))?;
+        trace::response("gasnow", &response);
+        self.format.parse(&response)
     }
 
     // Ensures that no requests are made faster than the rate limit by caching the previous
@@ -91,12 +273,13 @@ impl<T: Transport> GasNowGasStation<T> {
         // checked_duration_since to catch this.
         let mut lock = self.last_response.lock().await;
         match lock.as_ref() {
-            Some(cached) if now.saturating_duration_since(cached.time) < RATE_LIMIT => {
+            Some(cached) if now.saturating_duration_since(cached.time) < self.refresh_rate => {
                 match cached.data {
                     Some(response) => Ok(response),
                     None => Err(anyhow!(
                         "previous gasnow response was error and cache has not yet expired"
-                    )),
+                    )
+                    .into()),
                 }
             }
             _ => {
@@ -113,16 +296,43 @@ impl<T: Transport> GasNowGasStation<T> {
 
 #[async_trait::async_trait]
 impl<T: Transport> GasPriceEstimating for GasNowGasStation<T> {
+    fn source(&self) -> &'static str {
+        "gasnow"
+    }
+
     async fn estimate_with_limits(
         &self,
-        gas_limit: f64,
+        _gas_limit: f64,
         time_limit: Duration,
     ) -> Result<EstimatedGasPrice> {
-        let response = self
-            .gas_price_with_cache(Instant::now(), || self.gas_price_without_cache())
-            .await?
-            .data;
-        estimate_with_limits(gas_limit, time_limit, &response)
+        trace::estimate(self.source(), Some(&self.url), async {
+            let response = self.fetch_raw().await?.data;
+            estimate_with_strategy(time_limit, &response, self.interpolation)
+        })
+        .await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        trace::estimate(self.source(), Some(&self.url), async {
+            let response = self.fetch_raw().await?.data;
+            GasPriceSchedule::from_estimates(|time_limit| {
+                estimate_with_strategy(time_limit, &response, self.interpolation)
+            })
+        })
+        .await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        trace::estimate(self.source(), Some(&self.url), async {
+            let response = self.fetch_raw().await?.data;
+            requests
+                .iter()
+                .map(|(_, time_limit)| {
+                    estimate_with_strategy(*time_limit, &response, self.interpolation)
+                })
+                .collect()
+        })
+        .await
     }
 }
 
@@ -131,6 +341,7 @@ mod tests {
     use super::super::tests::{FutureWaitExt as _, TestTransport};
     use super::*;
     use futures::FutureExt;
+    use serde::de::DeserializeOwned;
     use std::future::{ready, Pending};
 
     fn panic_future() -> Pending<Result<Response>> {
@@ -149,6 +360,109 @@ mod tests {
         assert!(result.legacy > 3.0 && result.legacy < 4.0);
     }
 
+    #[derive(Default)]
+    struct RecordingTransport {
+        requests: std::sync::Mutex<Vec<(String, HeaderMap)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for RecordingTransport {
+        async fn get_json<T: DeserializeOwned>(&self, url: &str, header: HeaderMap) -> Result<T> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((url.to_string(), header));
+            Ok(serde_json::from_str(
+                r#"{"code":200,"data":{"rapid":4,"fast":3,"standard":2,"slow":1}}"#,
+            )?)
+        }
+    }
+
+    #[test]
+    fn uses_custom_url_and_header() {
+        let mut header = HeaderMap::new();
+        header.insert("x-api-key", "secret".parse().unwrap());
+        let gasnow = GasNowGasStation::with_url(RecordingTransport::default(), "http://localhost")
+            .with_header(header.clone());
+        let estimate = gasnow.estimate_with_limits(0., RAPID).wait().unwrap();
+        assert_eq!(estimate.legacy, 4.0);
+        let requests = gasnow.transport.requests.lock().unwrap();
+        assert_eq!(*requests, vec![("http://localhost".to_string(), header)]);
+    }
+
+    #[test]
+    fn applies_credentials() {
+        let gasnow = GasNowGasStation::with_url(RecordingTransport::default(), "http://localhost")
+            .with_credentials(ApiCredentials::query_param("key", "secret"));
+        gasnow.estimate_with_limits(0., RAPID).wait().unwrap();
+        let requests = gasnow.transport.requests.lock().unwrap();
+        assert_eq!(requests[0].0, "http://localhost/?key=secret");
+    }
+
+    #[test]
+    fn rate_limit_uses_clock() {
+        let clock = crate::time::tests::ManualClock::default();
+        let gasnow = GasNowGasStation::new(RecordingTransport::default()).with_clock(clock.clone());
+        gasnow.estimate().wait().unwrap();
+        gasnow.estimate().wait().unwrap();
+        let response = gasnow.fetch_raw().wait().unwrap();
+        assert_eq!(response.data.standard, 2.0);
+        assert_eq!(gasnow.transport.requests.lock().unwrap().len(), 1);
+        clock.advance(DEFAULT_REFRESH_RATE);
+        gasnow.estimate().wait().unwrap();
+        assert_eq!(gasnow.transport.requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn refresh_rate_is_configurable() {
+        let clock = crate::time::tests::ManualClock::default();
+        let gasnow = GasNowGasStation::new(RecordingTransport::default())
+            .with_refresh_rate(Duration::from_secs(2))
+            .with_clock(clock.clone());
+        assert_eq!(gasnow.refresh_rate(), Duration::from_secs(2));
+        gasnow.estimate().wait().unwrap();
+        clock.advance(Duration::from_secs(1));
+        gasnow.estimate().wait().unwrap();
+        assert_eq!(gasnow.transport.requests.lock().unwrap().len(), 1);
+        clock.advance(Duration::from_secs(1));
+        gasnow.estimate().wait().unwrap();
+        assert_eq!(gasnow.transport.requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn uses_interpolation_strategy() {
+        let gasnow = GasNowGasStation::new(RecordingTransport::default())
+            .with_interpolation(InterpolationStrategy::Step);
+        let estimate = gasnow
+            .estimate_with_limits(0., Duration::from_secs(59))
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.legacy, 4.0);
+    }
+
+    #[test]
+    fn schedule_uses_one_request() {
+        let gasnow = GasNowGasStation::new(RecordingTransport::default());
+        let schedule = gasnow.estimate_schedule().wait().unwrap();
+        assert_eq!(schedule.slow.legacy, 1.0);
+        assert_eq!(schedule.standard.legacy, 2.0);
+        assert_eq!(schedule.fast.legacy, 3.0);
+        assert_eq!(schedule.instant.legacy, 4.0);
+        assert_eq!(gasnow.transport.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn many_uses_one_request() {
+        let gasnow = GasNowGasStation::new(RecordingTransport::default());
+        let estimates = gasnow
+            .estimate_many(&[(21000.0, RAPID), (100_000.0, SLOW)])
+            .wait()
+            .unwrap();
+        assert_eq!(estimates[0].legacy, 4.0);
+        assert_eq!(estimates[1].legacy, 1.0);
+        assert_eq!(gasnow.transport.requests.lock().unwrap().len(), 1);
+    }
+
     #[test]
     fn cache_works_ok() {
         let gasnow = GasNowGasStation::new(TestTransport::default());
@@ -173,7 +487,7 @@ mod tests {
         );
 
         // cache gets updated after expiry
-        let now = now + RATE_LIMIT;
+        let now = now + DEFAULT_REFRESH_RATE;
         let response = Response {
             code: 1,
             ..Default::default()
@@ -200,7 +514,7 @@ mod tests {
         let now = Instant::now();
 
         assert!(gasnow
-            .gas_price_with_cache(now, || ready(Err(anyhow!(""))))
+            .gas_price_with_cache(now, || ready(Err(anyhow!("").into())))
             .wait()
             .is_err());
         // panic_future isn't called
@@ -237,4 +551,81 @@ mod tests {
             std::thread::sleep(Duration::from_secs(1));
         }
     }
+
+    #[test]
+    fn parses_response_formats() {
+        let expected = |rapid, slow| Response {
+            code: 200,
+            data: ResponseData {
+                rapid,
+                fast: 3e9,
+                standard: 2e9,
+                slow,
+            },
+        };
+        let gasnow = r#"{"code":200,"data":{"rapid":4e9,"fast":3e9,"standard":2e9,"slow":1e9,"timestamp":1,"priceUSD":2000}}"#;
+        assert_eq!(
+            ResponseFormat::GasNow
+                .parse_slice(gasnow.as_bytes())
+                .unwrap(),
+            expected(4e9, 1e9)
+        );
+        let etherchain =
+            r#"{"safeLow":"1.0","standard":2,"fast":3,"fastest":4,"currentBaseFee":1.5}"#;
+        assert_eq!(
+            ResponseFormat::Etherchain
+                .parse_slice(etherchain.as_bytes())
+                .unwrap(),
+            expected(4e9, 1e9)
+        );
+        let metaswap = r#"{"SafeGasPrice":"1","ProposeGasPrice":"2","FastGasPrice":"3"}"#;
+        assert_eq!(
+            ResponseFormat::MetaSwap
+                .parse_slice(metaswap.as_bytes())
+                .unwrap(),
+            expected(3e9, 1e9)
+        );
+        // missing optional tiers
+        let gasnow = r#"{"data":{"fast":3e9,"standard":2e9}}"#;
+        assert_eq!(
+            ResponseFormat::GasNow
+                .parse_slice(gasnow.as_bytes())
+                .unwrap(),
+            expected(3e9, 2e9)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_responses_without_panicking() {
+        let valid = r#"{"code":200,"data":{"rapid":4,"fast":3,"standard":2,"slow":1}}"#;
+        for format in [
+            ResponseFormat::GasNow,
+            ResponseFormat::Etherchain,
+            ResponseFormat::MetaSwap,
+        ] {
+            for end in 0..valid.len() {
+                let _ = format.parse_slice(&valid.as_bytes()[..end]);
+            }
+        }
+        for invalid in [
+            r#"{"data":{"fast":3}}"#,
+            r#"{"data":{"fast":-3,"standard":2}}"#,
+            r#"{"data":{"fast":"x","standard":2}}"#,
+            r#"{"data":{"fast":[],"standard":2}}"#,
+            r#"[]"#,
+            r#"null"#,
+        ] {
+            assert!(ResponseFormat::GasNow
+                .parse_slice(invalid.as_bytes())
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn uses_response_format() {
+        let gasnow = GasNowGasStation::new(RecordingTransport::default())
+            .with_format(ResponseFormat::Etherchain);
+        // the recorded response is in the GasNow format
+        assert!(gasnow.estimate().wait().is_err());
+    }
 }
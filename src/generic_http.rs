@@ -0,0 +1,305 @@
+//! Estimates from any gas api that publishes a few speed tiers as json, configured with the paths
+//! of the prices in the response instead of a dedicated estimator for every service.
+
+use super::{
+    auth, error::Result, interpolation, trace, ApiCredentials, EstimatedGasPrice,
+    GasEstimationError, GasPrice1559, GasPriceEstimating, GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use serde_json::Value;
+use std::{convert::TryInto, time::Duration};
+
+/// Where to find the prices of one tier. Paths are dot separated field names where numeric
+/// segments index into arrays, for example `result.FastGasPrice` or `blockPrices.0.baseFee`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tier {
+    /// The time within which a transaction with these prices is expected to be included.
+    pub time_limit: Duration,
+    /// Path of the legacy gas price.
+    pub price: String,
+    /// Paths of the EIP-1559 fees, required for every tier if `Config::base_fee_per_gas` is set.
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+}
+
+impl Tier {
+    pub fn new(time_limit: Duration, price: impl Into<String>) -> Self {
+        Self {
+            time_limit,
+            price: price.into(),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+        }
+    }
+
+    pub fn with_eip1559(
+        self,
+        max_fee_per_gas: impl Into<String>,
+        max_priority_fee_per_gas: impl Into<String>,
+    ) -> Self {
+        Self {
+            max_fee_per_gas: Some(max_fee_per_gas.into()),
+            max_priority_fee_per_gas: Some(max_priority_fee_per_gas.into()),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub url: String,
+    pub fast: Tier,
+    pub standard: Tier,
+    pub slow: Tier,
+    /// Path of the base fee. Estimates are EIP-1559 estimates if set.
+    pub base_fee_per_gas: Option<String>,
+    /// Factor converting the numbers of the response to wei, 1e9 for apis that report gwei.
+    pub scale: f64,
+}
+
+impl Config {
+    /// A legacy api reporting prices in wei.
+    pub fn new(url: impl Into<String>, fast: Tier, standard: Tier, slow: Tier) -> Self {
+        Self {
+            url: url.into(),
+            fast,
+            standard,
+            slow,
+            base_fee_per_gas: None,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_base_fee(self, base_fee_per_gas: impl Into<String>) -> Self {
+        Self {
+            base_fee_per_gas: Some(base_fee_per_gas.into()),
+            ..self
+        }
+    }
+
+    pub fn with_scale(self, scale: f64) -> Self {
+        Self { scale, ..self }
+    }
+}
+
+pub struct ConfigurableEstimator<T> {
+    transport: T,
+    config: Config,
+    credentials: Option<ApiCredentials>,
+}
+
+impl<T: Transport> ConfigurableEstimator<T> {
+    /// Fails with `GasEstimationError::Unsupported` if the base fee is configured but a tier is
+    /// missing its EIP-1559 fees.
+    pub fn new(transport: T, config: Config) -> Result<Self> {
+        if config.base_fee_per_gas.is_some() {
+            let tiers = [&config.fast, &config.standard, &config.slow];
+            if tiers.iter().any(|tier| {
+                tier.max_fee_per_gas.is_none() || tier.max_priority_fee_per_gas.is_none()
+            }) {
+                return Err(GasEstimationError::Unsupported(
+                    "EIP-1559 estimates require the fee paths of every tier".into(),
+                ));
+            }
+        }
+        Ok(Self {
+            transport,
+            config,
+            credentials: None,
+        })
+    }
+
+    /// Credentials sent with every request, for example for a private api.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for ConfigurableEstimator<T> {
+    fn source(&self) -> &'static str {
+        "generic_http"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        trace::estimate(self.source(), Some(&self.config.url), async {
+            let (url, header) = auth::authorize(
+                self.credentials.as_ref(),
+                &self.config.url,
+                Default::default(),
+            )?;
+            let response: Value = self
+                .transport
+                .get_json(&url, header)
+                .await
+                .map_err(|err| err.context("failed to get gas price"))?;
+            trace::response(self.source(), &response);
+            estimate_with_limits(&self.config, &response, time_limit)
+        })
+        .await
+    }
+}
+
+fn estimate_with_limits(
+    config: &Config,
+    response: &Value,
+    time_limit: Duration,
+) -> Result<EstimatedGasPrice> {
+    let tiers = [&config.fast, &config.standard, &config.slow];
+    let interpolate = |path: fn(&Tier) -> Option<&String>| -> Result<f64> {
+        let points = tiers
+            .iter()
+            .map(|tier| {
+                let path = path(tier).expect("fee paths checked in constructor");
+                Ok((
+                    tier.time_limit.as_secs_f64(),
+                    price(response, path, config.scale)?,
+                ))
+            })
+            .collect::<Result<Vec<(f64, f64)>>>()?;
+        Ok(interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            points.as_slice().try_into()?,
+        ))
+    };
+    let legacy = interpolate(|tier| Some(&tier.price))?;
+    let eip1559 = match &config.base_fee_per_gas {
+        Some(base_fee_per_gas) => Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(price(response, base_fee_per_gas, config.scale)?),
+            max_fee_per_gas: GasPriceWei(interpolate(|tier| tier.max_fee_per_gas.as_ref())?),
+            max_priority_fee_per_gas: GasPriceWei(interpolate(|tier| {
+                tier.max_priority_fee_per_gas.as_ref()
+            })?),
+        }),
+        None => None,
+    };
+    EstimatedGasPrice { legacy, eip1559 }.validate()
+}
+
+// The number at `path` in wei. Numbers can also be sent as strings.
+fn price(response: &Value, path: &str, scale: f64) -> Result<f64> {
+    let value = path
+        .split('.')
+        .try_fold(response, |value, segment| match value {
+            Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+            value => value.get(segment),
+        })
+        .ok_or_else(|| GasEstimationError::Decode(anyhow!("missing {} in response", path)))?;
+    let number = match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    };
+    match number {
+        Some(number) if number.is_finite() && number >= 0.0 => Ok(number * scale),
+        _ => Err(GasEstimationError::Decode(anyhow!(
+            "invalid {} price {}",
+            path,
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    struct JsonTransport(Value);
+
+    #[async_trait::async_trait]
+    impl Transport for JsonTransport {
+        async fn get_json<T: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<T> {
+            Ok(serde_json::from_value(self.0.clone())?)
+        }
+    }
+
+    fn tier(time_limit: u64, name: &str) -> Tier {
+        Tier::new(
+            Duration::from_secs(time_limit),
+            format!("result.{}.price", name),
+        )
+        .with_eip1559(
+            format!("result.{}.maxFee", name),
+            format!("result.{}.tip", name),
+        )
+    }
+
+    #[test]
+    fn reads_configured_paths() {
+        let response = json!({
+            "result": {
+                "fast": {"price": "30", "maxFee": 40, "tip": 3},
+                "standard": {"price": "20", "maxFee": 30, "tip": 2},
+                "slow": {"price": "10", "maxFee": 20, "tip": 1}
+            },
+            "blocks": [{"baseFee": 9.5}]
+        });
+        let config = Config::new(
+            "",
+            tier(15, "fast"),
+            tier(60, "standard"),
+            tier(300, "slow"),
+        )
+        .with_base_fee("blocks.0.baseFee")
+        .with_scale(1e9);
+        let estimator = ConfigurableEstimator::new(JsonTransport(response), config).unwrap();
+
+        let price = estimator
+            .estimate_with_limits(21000., Duration::from_secs(60))
+            .wait()
+            .unwrap();
+        assert_approx_eq!(price.legacy, 20e9);
+        let eip1559 = price.eip1559.unwrap();
+        assert_approx_eq!(eip1559.base_fee_per_gas.0, 9.5e9);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 30e9);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 2e9);
+
+        let price = estimator
+            .estimate_with_limits(21000., Duration::from_secs(180))
+            .wait()
+            .unwrap();
+        assert_approx_eq!(price.legacy, 15e9);
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+        let legacy_tier = |time_limit| Tier::new(Duration::from_secs(time_limit), "price");
+        let config = Config::new("", legacy_tier(15), legacy_tier(60), legacy_tier(300));
+        assert!(matches!(
+            ConfigurableEstimator::new(
+                JsonTransport(json!({})),
+                config.clone().with_base_fee("base")
+            ),
+            Err(GasEstimationError::Unsupported(_))
+        ));
+
+        let estimator = ConfigurableEstimator::new(JsonTransport(json!({})), config).unwrap();
+        assert!(matches!(
+            estimator.estimate().wait(),
+            Err(GasEstimationError::Decode(_))
+        ));
+        assert!(matches!(
+            price(&json!({"price": "fast"}), "price", 1.0),
+            Err(GasEstimationError::Decode(_))
+        ));
+        assert!(matches!(
+            price(&json!({"prices": [1]}), "prices.1", 1.0),
+            Err(GasEstimationError::Decode(_))
+        ));
+    }
+}
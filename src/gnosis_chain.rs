@@ -0,0 +1,242 @@
+//! Gnosis Chain (formerly xDai) gas estimation with the gas price oracle of the Blockscout
+//! explorer. Api documentation at https://docs.blockscout.com/devs/apis/rest .
+
+use super::{
+    auth, chain, error::Result, interpolation, ApiCredentials, EstimatedGasPrice, GasPrice1559,
+    GasPriceEstimating, GasPriceWei, Transport,
+};
+use serde::Deserialize;
+use std::{convert::TryInto, time::Duration};
+
+const API_URI: &str = "https://gnosis.blockscout.com/api/v2/stats";
+
+// Used when the oracle does not report confirmation times. Gnosis Chain produces a block every five
+// seconds.
+pub const FAST: Duration = Duration::from_secs(5);
+pub const AVERAGE: Duration = Duration::from_secs(15);
+pub const SLOW: Duration = Duration::from_secs(30);
+
+// The base fee can increase by 12.5% per block so this survives several full blocks.
+const BASE_FEE_MULTIPLIER: f64 = 2.0;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Response {
+    pub gas_prices: GasPrices,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GasPrices {
+    pub slow: Tier,
+    pub average: Tier,
+    pub fast: Tier,
+}
+
+// Prices in gwei.
+#[derive(Debug, Default, Deserialize)]
+pub struct Tier {
+    pub price: f64,
+    // expected confirmation time in milliseconds
+    pub time: Option<f64>,
+    pub base_fee: Option<f64>,
+    pub priority_fee: Option<f64>,
+}
+
+pub struct GnosisChainGasStation<T> {
+    transport: T,
+    url: String,
+    credentials: Option<ApiCredentials>,
+}
+
+impl<T: Transport> GnosisChainGasStation<T> {
+    pub fn new(transport: T) -> Self {
+        Self::with_url(transport, API_URI)
+    }
+
+    /// Use another Blockscout instance, for example a self hosted one.
+    pub fn with_url(transport: T, url: impl Into<String>) -> Self {
+        Self {
+            transport,
+            url: url.into(),
+            credentials: None,
+        }
+    }
+
+    /// Credentials sent with every request, for example for a paid tier.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    pub async fn gas_prices(&self) -> Result<GasPrices> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.url, Default::default())?;
+        self.transport
+            .get_json::<Response>(&url, header)
+            .await
+            .map(|response| response.gas_prices)
+            .map_err(|err| err.context("failed to get gnosis chain gas price"))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for GnosisChainGasStation<T> {
+    fn source(&self) -> &'static str {
+        "gnosis_chain"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::GNOSIS_CHAIN.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let gas_prices = self.gas_prices().await?;
+        estimate_with_limits(&gas_prices, time_limit)
+    }
+}
+
+fn estimate_with_limits(gas_prices: &GasPrices, time_limit: Duration) -> Result<EstimatedGasPrice> {
+    let tiers = [&gas_prices.fast, &gas_prices.average, &gas_prices.slow];
+    let reported_times = tiers
+        .iter()
+        .map(|tier| tier.time.map(|time| time / 1000.0))
+        .collect::<Option<Vec<f64>>>()
+        .filter(|times| times.windows(2).all(|window| window[0] < window[1]));
+    let times = reported_times.unwrap_or_else(|| {
+        [FAST, AVERAGE, SLOW]
+            .iter()
+            .map(Duration::as_secs_f64)
+            .collect()
+    });
+    let interpolate = |value: fn(&Tier) -> Option<f64>| -> Result<Option<f64>> {
+        let points = match times
+            .iter()
+            .zip(tiers)
+            .map(|(time, tier)| Some((*time, GasPriceWei::from_gwei(value(tier)?).0)))
+            .collect::<Option<Vec<(f64, f64)>>>()
+        {
+            Some(points) => points,
+            None => return Ok(None),
+        };
+        Ok(Some(interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            points.as_slice().try_into()?,
+        )))
+    };
+
+    let legacy = interpolate(|tier| Some(tier.price))?.unwrap_or_default();
+    let eip1559 = match (
+        gas_prices.average.base_fee,
+        interpolate(|tier| tier.priority_fee)?,
+    ) {
+        (Some(base_fee), Some(priority_fee)) => {
+            let base_fee = GasPriceWei::from_gwei(base_fee);
+            Some(GasPrice1559 {
+                base_fee_per_gas: base_fee,
+                max_fee_per_gas: base_fee * BASE_FEE_MULTIPLIER + GasPriceWei(priority_fee),
+                max_priority_fee_per_gas: GasPriceWei(priority_fee),
+            })
+        }
+        _ => None,
+    };
+    EstimatedGasPrice { legacy, eip1559 }.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::TestTransport;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn deserialize() {
+        let json = r#"
+        {
+            "average_block_time": 5000.0,
+            "gas_prices": {
+                "slow": { "price": 1.1, "time": 30000.0, "base_fee": 1.0, "priority_fee": 0.1 },
+                "average": { "price": 1.5, "time": 15000.0, "base_fee": 1.0, "priority_fee": 0.5 },
+                "fast": { "price": 2.0, "time": 5000.0, "base_fee": 1.0, "priority_fee": 1.0 }
+            },
+            "total_blocks": "30000000"
+        }"#;
+        let response = serde_json::from_str::<Response>(json).unwrap();
+        assert_approx_eq!(response.gas_prices.slow.price, 1.1);
+        assert_eq!(response.gas_prices.average.time, Some(15000.0));
+        assert_eq!(response.gas_prices.fast.priority_fee, Some(1.0));
+    }
+
+    #[test]
+    fn interpolates_reported_times() {
+        let gas_prices = GasPrices {
+            fast: Tier {
+                price: 3.0,
+                time: Some(10_000.0),
+                base_fee: Some(1.0),
+                priority_fee: Some(2.0),
+            },
+            average: Tier {
+                price: 2.0,
+                time: Some(20_000.0),
+                base_fee: Some(1.0),
+                priority_fee: Some(1.0),
+            },
+            slow: Tier {
+                price: 1.0,
+                time: Some(40_000.0),
+                base_fee: Some(1.0),
+                priority_fee: Some(0.0),
+            },
+        };
+        let price = estimate_with_limits(&gas_prices, Duration::from_secs(30)).unwrap();
+        assert_approx_eq!(price.legacy, 1.5e9);
+        let eip1559 = price.eip1559.unwrap();
+        assert_approx_eq!(eip1559.base_fee_per_gas.0, 1e9);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 0.5e9);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 2.5e9);
+    }
+
+    #[test]
+    fn legacy_only_oracle_uses_default_times() {
+        let gas_prices = GasPrices {
+            fast: Tier {
+                price: 3.0,
+                ..Default::default()
+            },
+            average: Tier {
+                price: 2.0,
+                ..Default::default()
+            },
+            slow: Tier {
+                price: 1.0,
+                ..Default::default()
+            },
+        };
+        let price = estimate_with_limits(&gas_prices, FAST).unwrap();
+        assert_approx_eq!(price.legacy, 3e9);
+        assert!(price.eip1559.is_none());
+        let price = estimate_with_limits(&gas_prices, Duration::from_secs(600)).unwrap();
+        assert_approx_eq!(price.legacy, 1e9);
+    }
+
+    // cargo test gnosis_chain -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let gas_station = GnosisChainGasStation::new(TestTransport::default());
+        let gas_prices = gas_station.gas_prices().await.unwrap();
+        println!("{:?}", gas_prices);
+        for time_limit in [FAST, AVERAGE, SLOW] {
+            println!(
+                "{:?}: {:?}",
+                time_limit,
+                estimate_with_limits(&gas_prices, time_limit)
+            );
+        }
+    }
+}
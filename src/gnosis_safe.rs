@@ -1,8 +1,20 @@
 //! Gnosis Safe gas station `GasPriceEstimating` implementation.
-//! Api documentation at https://safe-relay.gnosis.io/ .
+//!
+//! The relay gas station (https://safe-relay.gnosis.io/) has been deprecated. The Safe
+//! transaction service has no gas price endpoint of its own, instead the Safe apps use the gas
+//! price settings of the chain config served by the Safe client gateway
+//! (https://safe-client.safe.global/v1/chains/{chain_id}). A chain lists one or more of a fixed
+//! legacy price, fixed EIP-1559 fees or a third party oracle. The relay is still available with
+//! `GnosisSafeGasStation::legacy`.
 
-use super::{linear_interpolation, EstimatedGasPrice, GasPriceEstimating, Transport};
-use anyhow::{anyhow, Context, Result};
+use super::{
+    auth,
+    chain::{self, ChainConfig},
+    error::Result,
+    interpolation, trace, ApiCredentials, EstimatedGasPrice, GasEstimationError, GasPrice1559,
+    GasPriceEstimating, GasPriceWei, Transport,
+};
+use anyhow::anyhow;
 use serde::Deserialize;
 use serde_with::rust::display_fromstr;
 use std::{convert::TryInto, time::Duration};
@@ -11,6 +23,13 @@ use std::{convert::TryInto, time::Duration};
 const DEFAULT_MAINNET_URI: &str = "https://safe-relay.gnosis.io/api/v1/gas-station/";
 const DEFAULT_RINKEBY_URI: &str = "https://safe-relay.rinkeby.gnosis.io/api/v1/gas-station/";
 
+const CLIENT_GATEWAY_URI: &str = "https://safe-client.safe.global";
+
+/// The url of the chain config that contains the gas price settings of the chain.
+pub fn chain_info_url(chain_id: u64) -> String {
+    format!("{}/v1/chains/{}", CLIENT_GATEWAY_URI, chain_id)
+}
+
 pub fn api_url_from_network_id(network_id: &str) -> Option<&'static str> {
     match network_id {
         "1" => Some(DEFAULT_MAINNET_URI),
@@ -37,6 +56,44 @@ pub struct GasPrices {
     pub fastest: f64,
 }
 
+/// The part of the chain config of the client gateway that is needed for gas prices.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainInfo {
+    pub gas_price: Vec<GasPriceConfig>,
+}
+
+/// Gas price setting of a chain. Settings are tried in order until one of them succeeds. Values
+/// are in wei.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GasPriceConfig {
+    // The gas price is the `gas_parameter` field of the json returned by `uri`, in units of
+    // `gwei_factor` wei.
+    #[serde(rename_all = "camelCase")]
+    Oracle {
+        uri: String,
+        gas_parameter: String,
+        #[serde(with = "display_fromstr")]
+        gwei_factor: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Fixed {
+        #[serde(with = "display_fromstr")]
+        wei_value: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Fixed1559 {
+        #[serde(with = "display_fromstr")]
+        max_fee_per_gas: f64,
+        #[serde(with = "display_fromstr")]
+        max_priority_fee_per_gas: f64,
+    },
+    // Types added to the api after this was written.
+    #[serde(other)]
+    Unknown,
+}
+
 // The gnosis safe gas station looks at the gas price of all transactions in the last 200 blocks.
 // The fast gas price is the price at the 75th percentile of gas prices and so on.
 const FAST_PERCENTILE: f64 = 0.75;
@@ -47,54 +104,174 @@ const SAFE_LOW_PERCENTILE: f64 = 0.3;
 // percentiles into time. The standard percentile is 50% which means transactions at this gas price
 // were included in 50% of the blocks. Thus for every block a transaction at this price has a 0.5
 // chance to be included. We treat this as a geometric distribution in which the expected time for
-// the event to happen (transaction is included in block) is 1/p. Blocks happen every 15 seconds so
-// this is how we get a time estimate.
+// the event to happen (transaction is included in block) is 1/p. Multiplied with the block time of
+// the chain this is how we get a time estimate.
 // In reality this estimation can be problematic when gas prices are changing quickly. When prices
 // are rising, the prices calculated based on the last 200 blocks lag behind the real price. For
 // this reason we insert two extra points for the linear interpolation below in estimate_limits.
 // We do not make use of the lowest and fastest gas price because they are too strong outliers. The
 // lowest gas price is skewed by miners including their own transactions at 1 gwei. The highest gas
 // price can be 1000 times the fast gas price which is not reasonable.
-const SECONDS_PER_BLOCK: f64 = 15.0;
-// Treat percentiles as probabilities for geometric distribution.
-const FAST_TIME: f64 = SECONDS_PER_BLOCK / FAST_PERCENTILE;
-const STANDARD_TIME: f64 = SECONDS_PER_BLOCK / STANDARD_PERCENTILE;
-const SAFE_LOW_TIME: f64 = SECONDS_PER_BLOCK / SAFE_LOW_PERCENTILE;
 
-/// Retrieve gas prices from the Gnosis Safe gas station service.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Api {
+    ClientGateway,
+    Relay,
+}
+
+/// Retrieve gas prices from the Gnosis Safe services.
 #[derive(Debug)]
 pub struct GnosisSafeGasStation<T> {
     transport: T,
     uri: String,
+    api: Api,
+    chain: ChainConfig,
+    credentials: Option<ApiCredentials>,
 }
 
 impl<T: Transport> GnosisSafeGasStation<T> {
+    /// Uses the gas price settings of the chain config of the Safe client gateway. The settings
+    /// contain a single price so the time limit of estimates is ignored.
+    pub fn new(chain_id: u64, transport: T) -> Self {
+        Self {
+            transport,
+            uri: chain_info_url(chain_id),
+            api: Api::ClientGateway,
+            chain: ChainConfig::from_chain_id(chain_id).unwrap_or(chain::ETHEREUM),
+            credentials: None,
+        }
+    }
+
+    #[deprecated(note = "the relay gas station is deprecated, use `new` or `legacy`")]
     pub fn with_network_id(network_id: &str, transport: T) -> Result<Self> {
+        Self::legacy(network_id, transport)
+    }
+
+    /// Uses the deprecated relay gas station, which is only available for mainnet and rinkeby.
+    pub fn legacy(network_id: &str, transport: T) -> Result<Self> {
         let uri = api_url_from_network_id(network_id)
-            .ok_or_else(|| anyhow!("unsupported network id {}", network_id))?
+            .ok_or_else(|| {
+                GasEstimationError::Unsupported(format!("unsupported network id {}", network_id))
+            })?
             .into();
-        Ok(Self { transport, uri })
+        let chain = network_id
+            .parse()
+            .ok()
+            .and_then(ChainConfig::from_chain_id)
+            .unwrap_or(chain::ETHEREUM);
+        Ok(Self {
+            transport,
+            uri,
+            api: Api::Relay,
+            chain,
+            credentials: None,
+        })
+    }
+
+    /// Credentials sent with every request, to the gas station or the client gateway.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    /// Overrides the chain config derived from the network id, for example to change the block
+    /// time.
+    pub fn with_chain_config(self, chain: ChainConfig) -> Self {
+        Self { chain, ..self }
     }
 
-    /// Retrieves the current gas prices from the gas station.
+    /// Retrieves the current gas prices from the relay gas station.
     pub async fn gas_prices(&self) -> Result<GasPrices> {
-        self.transport
-            .get_json(&self.uri, Default::default())
+        if self.api != Api::Relay {
+            return Err(GasEstimationError::Unsupported(
+                "the relay gas station is only used by legacy estimators".to_string(),
+            ));
+        }
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.uri, Default::default())?;
+        let response = self
+            .transport
+            .get_json(&url, header)
             .await
-            .context("failed to get gnosissafe gas price")
+            .map_err(|err| err.context("failed to get gnosissafe gas price"))?;
+        trace::response("gnosis_safe", &response);
+        Ok(response)
+    }
+
+    /// Retrieves the gas price settings of the chain from the client gateway.
+    pub async fn chain_info(&self) -> Result<ChainInfo> {
+        if self.api != Api::ClientGateway {
+            return Err(GasEstimationError::Unsupported(
+                "legacy estimators don't use the client gateway".to_string(),
+            ));
+        }
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.uri, Default::default())?;
+        let response: ChainInfo = self
+            .transport
+            .get_json(&url, header)
+            .await
+            .map_err(|err| err.context("failed to get safe chain info"))?;
+        trace::response("gnosis_safe", &response);
+        Ok(response)
+    }
+
+    async fn estimate_from_chain_info(&self) -> Result<EstimatedGasPrice> {
+        let chain_info = self.chain_info().await?;
+        let mut last_error = None;
+        for config in &chain_info.gas_price {
+            let result = match config {
+                GasPriceConfig::Oracle {
+                    uri,
+                    gas_parameter,
+                    gwei_factor,
+                } => self
+                    .transport
+                    .get_json::<serde_json::Value>(uri, Default::default())
+                    .await
+                    .and_then(|response| oracle_gas_price(&response, gas_parameter, *gwei_factor)),
+                config => estimate_from_config(config),
+            };
+            match result {
+                Ok(estimate) => return Ok(estimate),
+                Err(err) => {
+                    tracing::debug!(?err, ?config, "safe gas price setting failed");
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| anyhow!("chain has no gas price settings").into())
+            .context("no safe gas price setting succeeded"))
     }
 }
 
 #[async_trait::async_trait]
 impl<T: Transport> GasPriceEstimating for GnosisSafeGasStation<T> {
+    fn source(&self) -> &'static str {
+        "gnosis_safe"
+    }
+
+    fn block_time(&self) -> Duration {
+        self.chain.block_time
+    }
+
     // The default implementation calls estimate_with_limits with 30 seconds which would result in
     // the standard time instead of fast. So to keep that behavior we implement it manually.
     async fn estimate(&self) -> Result<EstimatedGasPrice> {
-        let response = self.gas_prices().await?;
-        Ok(EstimatedGasPrice {
-            legacy: response.fast,
-            ..Default::default()
+        trace::estimate(self.source(), Some(&self.uri), async {
+            if self.api == Api::ClientGateway {
+                return self.estimate_from_chain_info().await;
+            }
+            let response = self.gas_prices().await?;
+            Ok(EstimatedGasPrice {
+                legacy: response.fast,
+                ..Default::default()
+            })
         })
+        .await
     }
 
     async fn estimate_with_limits(
@@ -102,9 +279,71 @@ impl<T: Transport> GasPriceEstimating for GnosisSafeGasStation<T> {
         gas_limit: f64,
         time_limit: Duration,
     ) -> Result<EstimatedGasPrice> {
-        let response = self.gas_prices().await?;
-        let result = estimate_with_limits(&response, gas_limit, time_limit)?;
-        Ok(result)
+        trace::estimate(self.source(), Some(&self.uri), async {
+            if self.api == Api::ClientGateway {
+                return self.estimate_from_chain_info().await;
+            }
+            let response = self.gas_prices().await?;
+            estimate_with_limits(&response, gas_limit, time_limit, self.chain.block_time)
+        })
+        .await
+    }
+}
+
+// Estimate of the settings that don't need another request. The base fee of fixed EIP-1559 fees
+// is unknown, it is assumed to be the highest base fee at which the full priority fee is paid.
+fn estimate_from_config(config: &GasPriceConfig) -> Result<EstimatedGasPrice> {
+    match config {
+        GasPriceConfig::Fixed { wei_value } => Ok(EstimatedGasPrice {
+            legacy: *wei_value,
+            eip1559: None,
+        }),
+        GasPriceConfig::Fixed1559 {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        } => EstimatedGasPrice {
+            legacy: *max_fee_per_gas,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(max_fee_per_gas - max_priority_fee_per_gas),
+                max_fee_per_gas: GasPriceWei(*max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(*max_priority_fee_per_gas),
+            }),
+        }
+        .validate(),
+        GasPriceConfig::Oracle { .. } => {
+            Err(anyhow!("oracle gas price setting needs a request").into())
+        }
+        GasPriceConfig::Unknown => Err(GasEstimationError::Unsupported(
+            "unknown gas price setting".to_string(),
+        )),
+    }
+}
+
+// Oracles either return the gas parameter at the top level or wrapped in a `data` or `result`
+// object, for example etherscan's gas oracle. The value can be a number or a string.
+fn oracle_gas_price(
+    response: &serde_json::Value,
+    gas_parameter: &str,
+    gwei_factor: f64,
+) -> Result<EstimatedGasPrice> {
+    let data = response
+        .get("data")
+        .or_else(|| response.get("result"))
+        .unwrap_or(response);
+    let value = data.get(gas_parameter).and_then(|value| match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(string) => string.parse().ok(),
+        _ => None,
+    });
+    match value {
+        Some(value) => Ok(EstimatedGasPrice {
+            legacy: value * gwei_factor,
+            eip1559: None,
+        }),
+        None => Err(GasEstimationError::Decode(anyhow!(
+            "oracle response has no gas price {}",
+            gas_parameter
+        ))),
     }
 }
 
@@ -112,16 +351,19 @@ fn estimate_with_limits(
     response: &GasPrices,
     _gas_limit: f64,
     time_limit: Duration,
+    block_time: Duration,
 ) -> Result<EstimatedGasPrice> {
+    // Treat percentiles as probabilities for geometric distribution.
+    let block_time = block_time.as_secs_f64();
     let points: &[(f64, f64)] = &[
         (0.0, response.fast * 2.0),
-        (FAST_TIME, response.fast),
-        (STANDARD_TIME, response.standard),
-        (SAFE_LOW_TIME, response.safe_low),
+        (block_time / FAST_PERCENTILE, response.fast),
+        (block_time / STANDARD_PERCENTILE, response.standard),
+        (block_time / SAFE_LOW_PERCENTILE, response.safe_low),
         (600.0, response.safe_low / 2.0),
     ];
     Ok(EstimatedGasPrice {
-        legacy: linear_interpolation::interpolate(time_limit.as_secs_f64(), points.try_into()?),
+        legacy: interpolation::interpolate(time_limit.as_secs_f64(), points.try_into()?),
         ..Default::default()
     })
 }
@@ -163,21 +405,37 @@ pub mod tests {
             fast: 400.0,
             fastest: 500.0,
         };
-        let estimate = estimate_with_limits(&price, 0.0, Duration::from_secs(30)).unwrap();
+        let estimate = estimate_with_limits(
+            &price,
+            0.0,
+            Duration::from_secs(30),
+            Duration::from_secs(15),
+        )
+        .unwrap();
         assert_approx_eq!(estimate.legacy, 300.0);
+        // the standard time is shorter with faster blocks
+        let estimate =
+            estimate_with_limits(&price, 0.0, Duration::from_secs(30), Duration::from_secs(5))
+                .unwrap();
+        assert!(estimate.legacy < 300.0);
     }
 
     // cargo test -p services-core gnosis_safe -- --ignored --nocapture
     #[tokio::test]
     #[ignore]
     async fn real_request() {
-        let gas_station =
-            GnosisSafeGasStation::with_network_id("1", TestTransport::default()).unwrap();
+        let gas_station = GnosisSafeGasStation::legacy("1", TestTransport::default()).unwrap();
         let response = gas_station.gas_prices().await.unwrap();
         println!("{:?}", response);
         for i in 0..10 {
             let time_limit = Duration::from_secs(i * 10);
-            let price = estimate_with_limits(&response, DEFAULT_GAS_LIMIT, time_limit).unwrap();
+            let price = estimate_with_limits(
+                &response,
+                DEFAULT_GAS_LIMIT,
+                time_limit,
+                gas_station.block_time(),
+            )
+            .unwrap();
             println!(
                 "gas price estimate for {} seconds: {} gwei",
                 time_limit.as_secs(),
@@ -185,4 +443,79 @@ pub mod tests {
             );
         }
     }
+
+    #[test]
+    fn deserialize_chain_info() {
+        let json = r#"
+        {
+            "chainId": "1",
+            "gasPrice": [
+                {
+                    "type": "ORACLE",
+                    "uri": "https://api.etherscan.io/api?module=gastracker&action=gasoracle",
+                    "gasParameter": "FastGasPrice",
+                    "gweiFactor": "1000000000.000000000"
+                },
+                { "type": "FIXED", "weiValue": "24000000000" },
+                { "type": "FIXED1559", "maxFeePerGas": "3000000000", "maxPriorityFeePerGas": "1000000000" },
+                { "type": "SOMETHING_NEW" }
+            ]
+        }"#;
+        let result = serde_json::from_str::<ChainInfo>(json).unwrap();
+        assert_eq!(
+            result.gas_price,
+            vec![
+                GasPriceConfig::Oracle {
+                    uri: "https://api.etherscan.io/api?module=gastracker&action=gasoracle"
+                        .to_string(),
+                    gas_parameter: "FastGasPrice".to_string(),
+                    gwei_factor: 1e9,
+                },
+                GasPriceConfig::Fixed { wei_value: 24e9 },
+                GasPriceConfig::Fixed1559 {
+                    max_fee_per_gas: 3e9,
+                    max_priority_fee_per_gas: 1e9,
+                },
+                GasPriceConfig::Unknown,
+            ]
+        );
+        let estimate = estimate_from_config(&result.gas_price[2]).unwrap();
+        assert_eq!(estimate.cap(), 3e9);
+        assert_eq!(estimate.tip(), 1e9);
+        assert_eq!(estimate.base_fee(), 2e9);
+        assert_eq!(
+            estimate_from_config(&result.gas_price[1]).unwrap().legacy,
+            24e9
+        );
+        assert!(estimate_from_config(&result.gas_price[3]).is_err());
+    }
+
+    #[test]
+    fn reads_oracle_gas_price() {
+        let etherscan = serde_json::json!({
+            "status": "1",
+            "result": { "SafeGasPrice": "20", "FastGasPrice": "25.5" }
+        });
+        assert_approx_eq!(
+            oracle_gas_price(&etherscan, "FastGasPrice", 1e9)
+                .unwrap()
+                .legacy,
+            25.5e9
+        );
+        let top_level = serde_json::json!({ "fast": 30 });
+        assert_approx_eq!(
+            oracle_gas_price(&top_level, "fast", 1e9).unwrap().legacy,
+            30e9
+        );
+        assert!(oracle_gas_price(&top_level, "slow", 1e9).is_err());
+    }
+
+    // cargo test -p services-core gnosis_safe -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request_client_gateway() {
+        let gas_station = GnosisSafeGasStation::new(100, TestTransport::default());
+        println!("{:?}", gas_station.chain_info().await.unwrap());
+        println!("{:?}", gas_station.estimate().await.unwrap());
+    }
 }
@@ -0,0 +1,429 @@
+//! gRPC service over an estimator, so that services in other languages can query the same
+//! estimator stack. The messages and the service are defined in `proto/gas_estimation.proto`.
+//!
+//! The prost messages and the tonic server are written out here instead of being generated by
+//! tonic-build so that building the crate doesn't need protoc. They have to be kept in sync with
+//! the proto file, which the tests check for the field names and tags of the messages and for the
+//! method path.
+
+use super::{
+    GasEstimationError, GasPriceEstimating, GasPriceWei, DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT,
+};
+use std::{sync::Arc, time::Duration};
+use tonic::{
+    codegen::{empty_body, http, Body, BoxFuture, Context, Poll, Service, StdError},
+    Status,
+};
+
+/// The messages of `proto/gas_estimation.proto`.
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EstimateRequest {
+        #[prost(double, optional, tag = "1")]
+        pub gas_limit: Option<f64>,
+        #[prost(double, optional, tag = "2")]
+        pub time_limit_seconds: Option<f64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GasPrice1559 {
+        #[prost(double, tag = "1")]
+        pub base_fee_per_gas: f64,
+        #[prost(double, tag = "2")]
+        pub max_fee_per_gas: f64,
+        #[prost(double, tag = "3")]
+        pub max_priority_fee_per_gas: f64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EstimatedGasPrice {
+        #[prost(double, tag = "1")]
+        pub legacy: f64,
+        #[prost(message, optional, tag = "2")]
+        pub eip1559: Option<GasPrice1559>,
+    }
+}
+
+impl From<super::EstimatedGasPrice> for proto::EstimatedGasPrice {
+    fn from(price: super::EstimatedGasPrice) -> Self {
+        Self {
+            legacy: price.legacy,
+            eip1559: price.eip1559.map(|eip1559| proto::GasPrice1559 {
+                base_fee_per_gas: eip1559.base_fee_per_gas.0,
+                max_fee_per_gas: eip1559.max_fee_per_gas.0,
+                max_priority_fee_per_gas: eip1559.max_priority_fee_per_gas.0,
+            }),
+        }
+    }
+}
+
+impl From<proto::EstimatedGasPrice> for super::EstimatedGasPrice {
+    fn from(price: proto::EstimatedGasPrice) -> Self {
+        Self {
+            legacy: price.legacy,
+            eip1559: price.eip1559.map(|eip1559| super::GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(eip1559.base_fee_per_gas),
+                max_fee_per_gas: GasPriceWei(eip1559.max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(eip1559.max_priority_fee_per_gas),
+            }),
+        }
+    }
+}
+
+const SERVICE_NAME: &str = "gas_estimation.v1.GasEstimation";
+const ESTIMATE_PATH: &str = "/gas_estimation.v1.GasEstimation/Estimate";
+
+/// Serves the `GasEstimation` service of the proto file with the estimates of `estimator`, to be
+/// added to a `tonic::transport::Server`.
+pub struct GasEstimationServer<E: ?Sized> {
+    estimator: Arc<E>,
+}
+
+impl<E: ?Sized> GasEstimationServer<E> {
+    pub fn new(estimator: Arc<E>) -> Self {
+        Self { estimator }
+    }
+}
+
+impl<E: ?Sized> Clone for GasEstimationServer<E> {
+    fn clone(&self) -> Self {
+        Self {
+            estimator: self.estimator.clone(),
+        }
+    }
+}
+
+impl<E: ?Sized> tonic::server::NamedService for GasEstimationServer<E> {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<E, B> Service<http::Request<B>> for GasEstimationServer<E>
+where
+    E: GasPriceEstimating + ?Sized + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let estimator = self.estimator.clone();
+        match request.uri().path() {
+            ESTIMATE_PATH => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(Estimate(estimator), request).await)
+            }),
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header(
+                        "grpc-status",
+                        (tonic::Code::Unimplemented as i32).to_string(),
+                    )
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .expect("valid response"))
+            }),
+        }
+    }
+}
+
+// The unary `Estimate` method.
+struct Estimate<E: ?Sized>(Arc<E>);
+
+impl<E: GasPriceEstimating + ?Sized + 'static> tonic::server::UnaryService<proto::EstimateRequest>
+    for Estimate<E>
+{
+    type Response = proto::EstimatedGasPrice;
+    type Future = BoxFuture<tonic::Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: tonic::Request<proto::EstimateRequest>) -> Self::Future {
+        let estimator = self.0.clone();
+        Box::pin(async move {
+            let request = request.into_inner();
+            let gas_limit = request.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT);
+            let time_limit = match request.time_limit_seconds {
+                Some(seconds) => Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| Status::invalid_argument("invalid time_limit_seconds"))?,
+                None => DEFAULT_TIME_LIMIT,
+            };
+            if !(gas_limit.is_finite() && gas_limit >= 0.0) {
+                return Err(Status::invalid_argument("invalid gas_limit"));
+            }
+            let estimate = estimator
+                .estimate_with_limits(gas_limit, time_limit)
+                .await
+                .map_err(status)?;
+            Ok(tonic::Response::new(estimate.into()))
+        })
+    }
+}
+
+fn status(err: GasEstimationError) -> Status {
+    let message = err.to_string();
+    match err {
+        GasEstimationError::RateLimited { retry_after } => {
+            with_retry_pushback(Status::resource_exhausted(message), retry_after)
+        }
+        GasEstimationError::Timeout => Status::deadline_exceeded(message),
+        GasEstimationError::Unsupported(_) => Status::unimplemented(message),
+        GasEstimationError::CircuitOpen { retry_after } => {
+            with_retry_pushback(Status::unavailable(message), retry_after)
+        }
+        _ => Status::unavailable(message),
+    }
+}
+
+// Tells clients when to retry with the `grpc-retry-pushback-ms` trailer of gRPC retries.
+fn with_retry_pushback(mut status: Status, retry_after: Option<Duration>) -> Status {
+    if let Some(retry_after) = retry_after {
+        let millis = retry_after.as_millis().to_string();
+        if let Ok(value) = millis.parse() {
+            status
+                .metadata_mut()
+                .insert("grpc-retry-pushback-ms", value);
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{testing::FixedGasPriceEstimator, EstimatedGasPrice};
+    use super::*;
+    use futures::channel::oneshot;
+    use prost::encoding::{decode_key, WireType};
+    use std::collections::HashMap;
+
+    #[test]
+    fn converts_estimates() {
+        let price = EstimatedGasPrice {
+            legacy: 30e9,
+            eip1559: Some(super::super::GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(20e9),
+                max_fee_per_gas: GasPriceWei(42e9),
+                max_priority_fee_per_gas: GasPriceWei(1.5e9),
+            }),
+        };
+        let message = proto::EstimatedGasPrice::from(price);
+        assert_eq!(message.eip1559.as_ref().unwrap().max_fee_per_gas, 42e9);
+        assert_eq!(EstimatedGasPrice::from(message), price);
+        let legacy = EstimatedGasPrice {
+            legacy: 1.0,
+            eip1559: None,
+        };
+        assert_eq!(
+            EstimatedGasPrice::from(proto::EstimatedGasPrice::from(legacy)),
+            legacy
+        );
+    }
+
+    async fn estimate(
+        channel: tonic::transport::Channel,
+        request: proto::EstimateRequest,
+    ) -> Result<proto::EstimatedGasPrice, Status> {
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        let response = client
+            .unary(
+                tonic::Request::new(request),
+                http::uri::PathAndQuery::from_static(ESTIMATE_PATH),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    // (message, field) -> (type, tag) of the messages in the proto file
+    fn proto_fields() -> HashMap<(String, String), (String, u32)> {
+        let mut fields = HashMap::new();
+        let mut message = None;
+        for line in include_str!("../proto/gas_estimation.proto").lines() {
+            let line = line.split("//").next().unwrap().trim();
+            if let Some(name) = line
+                .strip_prefix("message ")
+                .and_then(|line| line.strip_suffix('{'))
+            {
+                message = Some(name.trim().to_string());
+            } else if line == "}" {
+                message = None;
+            } else if let (Some(message), Some(field)) = (&message, line.strip_suffix(';')) {
+                let tokens = field.split_whitespace().collect::<Vec<_>>();
+                let tokens = tokens.strip_prefix(&["optional"]).unwrap_or(&tokens);
+                match tokens {
+                    [type_, name, "=", tag] => fields.insert(
+                        (message.clone(), name.to_string()),
+                        (type_.to_string(), tag.parse().unwrap()),
+                    ),
+                    _ => panic!("unexpected field {:?}", field),
+                };
+            }
+        }
+        fields
+    }
+
+    // The tag and wire type of the first field of the encoded message, the messages below have
+    // exactly one field set.
+    fn encoded_key(message: impl prost::Message) -> (u32, WireType) {
+        let encoded = message.encode_to_vec();
+        decode_key(&mut encoded.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn messages_match_proto_file() {
+        use proto::*;
+        let messages = [
+            (
+                "EstimateRequest",
+                "gas_limit",
+                encoded_key(EstimateRequest {
+                    gas_limit: Some(1.0),
+                    ..Default::default()
+                }),
+            ),
+            (
+                "EstimateRequest",
+                "time_limit_seconds",
+                encoded_key(EstimateRequest {
+                    time_limit_seconds: Some(1.0),
+                    ..Default::default()
+                }),
+            ),
+            (
+                "GasPrice1559",
+                "base_fee_per_gas",
+                encoded_key(GasPrice1559 {
+                    base_fee_per_gas: 1.0,
+                    ..Default::default()
+                }),
+            ),
+            (
+                "GasPrice1559",
+                "max_fee_per_gas",
+                encoded_key(GasPrice1559 {
+                    max_fee_per_gas: 1.0,
+                    ..Default::default()
+                }),
+            ),
+            (
+                "GasPrice1559",
+                "max_priority_fee_per_gas",
+                encoded_key(GasPrice1559 {
+                    max_priority_fee_per_gas: 1.0,
+                    ..Default::default()
+                }),
+            ),
+            (
+                "EstimatedGasPrice",
+                "legacy",
+                encoded_key(proto::EstimatedGasPrice {
+                    legacy: 1.0,
+                    ..Default::default()
+                }),
+            ),
+            (
+                "EstimatedGasPrice",
+                "eip1559",
+                encoded_key(proto::EstimatedGasPrice {
+                    eip1559: Some(Default::default()),
+                    ..Default::default()
+                }),
+            ),
+        ];
+        let mut proto_fields = proto_fields();
+        for (message, field, (tag, wire_type)) in messages {
+            let (type_, proto_tag) = proto_fields
+                .remove(&(message.to_string(), field.to_string()))
+                .unwrap_or_else(|| panic!("{}.{} is not in the proto file", message, field));
+            assert_eq!(tag, proto_tag, "tag of {}.{}", message, field);
+            let proto_wire_type = match type_.as_str() {
+                "double" => WireType::SixtyFourBit,
+                _ => WireType::LengthDelimited,
+            };
+            assert_eq!(wire_type, proto_wire_type, "type of {}.{}", message, field);
+        }
+        assert!(
+            proto_fields.is_empty(),
+            "fields missing from the messages: {:?}",
+            proto_fields.keys()
+        );
+    }
+
+    #[test]
+    fn method_matches_proto_file() {
+        let proto = include_str!("../proto/gas_estimation.proto");
+        assert!(proto.contains("package gas_estimation.v1;"));
+        assert!(proto.contains("service GasEstimation {"));
+        assert!(proto.contains("rpc Estimate(EstimateRequest) returns (EstimatedGasPrice);"));
+        assert_eq!(SERVICE_NAME, "gas_estimation.v1.GasEstimation");
+        assert_eq!(ESTIMATE_PATH, format!("/{}/Estimate", SERVICE_NAME));
+    }
+
+    #[tokio::test]
+    async fn serves_estimates() {
+        let estimator = Arc::new(FixedGasPriceEstimator::legacy(100.0));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(GasEstimationServer::new(estimator.clone()))
+                .serve_with_incoming_shutdown(
+                    tonic::transport::server::TcpIncoming::from_listener(listener, true, None)
+                        .unwrap(),
+                    async {
+                        let _ = stopped.await;
+                    },
+                ),
+        );
+        let channel = tonic::transport::Endpoint::from_shared(url)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        let price = estimate(channel.clone(), Default::default()).await.unwrap();
+        assert_eq!(price.legacy, 100.0);
+        assert_eq!(price.eip1559, None);
+
+        let request = proto::EstimateRequest {
+            gas_limit: None,
+            time_limit_seconds: Some(-1.0),
+        };
+        let err = estimate(channel.clone(), request).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let request = proto::EstimateRequest {
+            gas_limit: Some(f64::INFINITY),
+            time_limit_seconds: None,
+        };
+        let err = estimate(channel.clone(), request).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        estimator.push_result(Err(GasEstimationError::RateLimited { retry_after: None }));
+        let err = estimate(channel.clone(), Default::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(err.metadata().get("grpc-retry-pushback-ms").is_none());
+
+        estimator.push_result(Err(GasEstimationError::CircuitOpen {
+            retry_after: Some(Duration::from_secs(2)),
+        }));
+        let err = estimate(channel.clone(), Default::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        assert_eq!(
+            err.metadata().get("grpc-retry-pushback-ms").unwrap(),
+            "2000"
+        );
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! What callers know about the transaction they estimate for, passed with
+//! `GasPriceEstimating::estimate_with_hints` so that estimators can adjust their output to how the
+//! transaction is going to be sent.
+
+use super::EstimatedGasPrice;
+
+/// Hints about the transaction an estimate is for. Estimators ignore hints they can't use, the
+/// default hints are a new transaction sent to the public mempool.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EstimationHints {
+    /// The transaction is sent privately to block builders instead of the public mempool, so the
+    /// tips of pending mempool transactions don't have to be outbid.
+    pub private_submission: bool,
+    /// Gas price of the pending transaction this one replaces. Nodes only accept a replacement if
+    /// its fees are higher by `bump_strategy::MIN_REPLACEMENT_BUMP`.
+    pub replacing: Option<EstimatedGasPrice>,
+    /// The transaction settles a batch auction. Settlements are submitted privately by the solver
+    /// that won the auction, so this implies `private_submission`.
+    pub batch_auction_settlement: bool,
+}
+
+impl EstimationHints {
+    pub fn private() -> Self {
+        Self {
+            private_submission: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn replacing(previous: EstimatedGasPrice) -> Self {
+        Self {
+            replacing: Some(previous),
+            ..Default::default()
+        }
+    }
+
+    pub fn batch_auction_settlement() -> Self {
+        Self {
+            batch_auction_settlement: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private_submission || self.batch_auction_settlement
+    }
+
+    /// The adjustments every estimator makes by default: raising the price of replacements to the
+    /// node replacement rules.
+    pub fn adjust(&self, price: EstimatedGasPrice) -> EstimatedGasPrice {
+        match &self.replacing {
+            Some(previous) => price.bump_for_replacement(previous),
+            None => price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        testing::FixedGasPriceEstimator, tests::FutureWaitExt as _, GasPriceEstimating,
+        DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT,
+    };
+    use super::*;
+
+    #[test]
+    fn bumps_replacements_by_default() {
+        let estimator = FixedGasPriceEstimator::legacy(100.0);
+        let estimate = |hints: &EstimationHints| {
+            estimator
+                .estimate_with_hints(DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT, hints)
+                .wait()
+                .unwrap()
+                .legacy
+        };
+        assert_eq!(estimate(&Default::default()), 100.0);
+        assert_eq!(estimate(&EstimationHints::private()), 100.0);
+        let previous = EstimatedGasPrice {
+            legacy: 100.0,
+            eip1559: None,
+        };
+        assert_eq!(estimate(&EstimationHints::replacing(previous)), 113.0);
+    }
+
+    #[test]
+    fn settlements_are_private() {
+        assert!(EstimationHints::batch_auction_settlement().is_private());
+        assert!(EstimationHints::private().is_private());
+        assert!(!EstimationHints::default().is_private());
+    }
+}
@@ -0,0 +1,399 @@
+use super::{
+    error::Result, time::Instant, EstimateWithMetadata, EstimatedGasPrice, EstimationHints,
+    GasPrice1559, GasPriceEstimating, GasPriceSchedule, GasPriceWei, TransactionCost, TxSpec,
+    DEFAULT_TIME_LIMIT,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Bounded in memory history of gas price estimates. Once `capacity` estimates are recorded the
+/// oldest estimate is dropped for every new one. Estimates for different time limits differ, so a
+/// history should only contain estimates for one of them.
+pub struct GasPriceHistory {
+    capacity: usize,
+    entries: Mutex<VecDeque<(Instant, EstimatedGasPrice)>>,
+}
+
+impl GasPriceHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, estimate: EstimatedGasPrice) {
+        self.record_at(Instant::now(), estimate)
+    }
+
+    fn record_at(&self, time: Instant, estimate: EstimatedGasPrice) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((time, estimate));
+    }
+
+    /// Time weighted average of the estimates over the last `duration`. Every estimate is weighted
+    /// by how long it was the most recent one. The 1559 values are only averaged if all estimates
+    /// have them.
+    pub fn average_over(&self, duration: Duration) -> Option<EstimatedGasPrice> {
+        self.average_over_at(Instant::now(), duration)
+    }
+
+    /// The estimate at percentile `p` (0.0 to 1.0) of the estimates recorded in the last
+    /// `duration`, ordered by effective gas price.
+    pub fn percentile_over(&self, duration: Duration, p: f64) -> Option<EstimatedGasPrice> {
+        self.percentile_over_at(Instant::now(), duration, p)
+    }
+
+    /// The estimate with the highest effective gas price recorded in the last `duration`.
+    pub fn max_over(&self, duration: Duration) -> Option<EstimatedGasPrice> {
+        self.percentile_over(duration, 1.0)
+    }
+
+    fn average_over_at(&self, now: Instant, duration: Duration) -> Option<EstimatedGasPrice> {
+        let start = now.checked_sub(duration);
+        let entries = self.entries.lock().unwrap();
+        // The estimate that was current at the start of the window is included with the part of
+        // its lifetime that overlaps the window.
+        let weighted = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (time, estimate))| {
+                let end = entries.get(i + 1).map(|(next, _)| *next).unwrap_or(now);
+                let begin = start.map_or(*time, |start| (*time).max(start));
+                (end > begin).then(|| ((end - begin).as_secs_f64(), *estimate))
+            })
+            .collect::<Vec<_>>();
+        if !weighted.is_empty() {
+            return weighted_average(&weighted);
+        }
+        // All estimates were recorded at `now` so there is no duration to weight by.
+        let recent = entries
+            .iter()
+            .filter(|(time, _)| start.map_or(true, |start| *time >= start))
+            .map(|(_, estimate)| (1.0, *estimate))
+            .collect::<Vec<_>>();
+        weighted_average(&recent)
+    }
+
+    fn percentile_over_at(
+        &self,
+        now: Instant,
+        duration: Duration,
+        p: f64,
+    ) -> Option<EstimatedGasPrice> {
+        let start = now.checked_sub(duration);
+        let mut recent = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(time, _)| start.map_or(true, |start| *time >= start))
+            .map(|(_, estimate)| *estimate)
+            .collect::<Vec<_>>();
+        recent.sort_by(|a, b| {
+            a.effective_gas_price()
+                .partial_cmp(&b.effective_gas_price())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let index = (recent.len().checked_sub(1)? as f64 * p.clamp(0.0, 1.0)).round() as usize;
+        recent.get(index).copied()
+    }
+}
+
+fn weighted_average(estimates: &[(f64, EstimatedGasPrice)]) -> Option<EstimatedGasPrice> {
+    let total: f64 = estimates.iter().map(|(weight, _)| weight).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let average = |value: fn(&EstimatedGasPrice) -> f64| {
+        estimates
+            .iter()
+            .map(|(weight, estimate)| weight * value(estimate))
+            .sum::<f64>()
+            / total
+    };
+    let all_eip1559 = estimates
+        .iter()
+        .all(|(_, estimate)| estimate.eip1559.is_some());
+    Some(EstimatedGasPrice {
+        legacy: average(|estimate| estimate.legacy),
+        eip1559: all_eip1559.then(|| GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(average(|estimate| estimate.base_fee())),
+            max_fee_per_gas: GasPriceWei(average(|estimate| estimate.cap())),
+            max_priority_fee_per_gas: GasPriceWei(average(|estimate| estimate.tip())),
+        }),
+    })
+}
+
+// Records the successful estimates of the inner estimator for one time limit into a shared
+// history. Estimates for other time limits are passed through without being recorded.
+pub struct RecordingGasPriceEstimating<T> {
+    inner: T,
+    history: Arc<GasPriceHistory>,
+    time_limit: Duration,
+}
+
+impl<T: GasPriceEstimating> RecordingGasPriceEstimating<T> {
+    // Records the estimates for `DEFAULT_TIME_LIMIT`, which includes `estimate`.
+    pub fn new(inner: T, history: Arc<GasPriceHistory>) -> Self {
+        Self {
+            inner,
+            history,
+            time_limit: DEFAULT_TIME_LIMIT,
+        }
+    }
+
+    // The time limit whose estimates are recorded.
+    pub fn with_time_limit(self, time_limit: Duration) -> Self {
+        Self { time_limit, ..self }
+    }
+
+    pub fn history(&self) -> Arc<GasPriceHistory> {
+        self.history.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for RecordingGasPriceEstimating<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_limits(gas_limit, time_limit)
+            .await?;
+        if time_limit == self.time_limit {
+            self.history.record(estimate);
+        }
+        Ok(estimate)
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        let estimate = self.inner.estimate().await?;
+        if self.time_limit == DEFAULT_TIME_LIMIT {
+            self.history.record(estimate);
+        }
+        Ok(estimate)
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let estimate = self.inner.estimate_verbose(gas_limit, time_limit).await?;
+        if time_limit == self.time_limit {
+            self.history.record(estimate.price);
+        }
+        Ok(estimate)
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        // Hinted estimates are adjusted for one transaction and are not recorded.
+        self.inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let cost = self.inner.estimate_for_tx(tx).await?;
+        if tx.time_limit == self.time_limit {
+            self.history.record(cost.gas_price);
+        }
+        Ok(cost)
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let schedule = self.inner.estimate_schedule().await?;
+        for (time_limit, estimate) in schedule.entries() {
+            if time_limit == self.time_limit {
+                self.history.record(estimate);
+            }
+        }
+        Ok(schedule)
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let estimates = self.inner.estimate_many(requests).await?;
+        for ((_, time_limit), estimate) in requests.iter().zip(&estimates) {
+            if *time_limit == self.time_limit {
+                self.history.record(*estimate);
+            }
+        }
+        Ok(estimates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn capacity_is_bounded() {
+        let history = GasPriceHistory::new(2);
+        let now = Instant::now();
+        history.record_at(now, price(1.0));
+        history.record_at(now, price(2.0));
+        history.record_at(now, price(3.0));
+        let entries = history.entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1, price(2.0));
+    }
+
+    #[test]
+    fn average_is_time_weighted() {
+        let history = GasPriceHistory::new(10);
+        let start = Instant::now();
+        history.record_at(start, price(100.0));
+        history.record_at(start + Duration::from_secs(10), price(1.0));
+        history.record_at(start + Duration::from_secs(13), price(2.0));
+        let now = start + Duration::from_secs(14);
+
+        // 1.0 for 3 seconds and 2.0 for 1 second
+        let average = history
+            .average_over_at(now, Duration::from_secs(4))
+            .unwrap();
+        assert_approx_eq!(average.legacy, 1.25);
+
+        // the first estimate was current for the first 2 seconds of the window
+        let average = history
+            .average_over_at(now, Duration::from_secs(6))
+            .unwrap();
+        assert_approx_eq!(average.legacy, (200.0 + 3.0 + 2.0) / 6.0);
+    }
+
+    #[test]
+    fn average_without_elapsed_time() {
+        let history = GasPriceHistory::new(10);
+        let now = Instant::now();
+        assert!(history
+            .average_over_at(now, Duration::from_secs(1))
+            .is_none());
+        history.record_at(now, price(1.0));
+        history.record_at(now, price(3.0));
+        let average = history
+            .average_over_at(now, Duration::from_secs(1))
+            .unwrap();
+        assert_approx_eq!(average.legacy, 2.0);
+    }
+
+    #[test]
+    fn average_eip1559() {
+        let history = GasPriceHistory::new(10);
+        let now = Instant::now();
+        let estimate = |x: f64| EstimatedGasPrice {
+            legacy: x,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(x),
+                max_fee_per_gas: GasPriceWei(2.0 * x),
+                max_priority_fee_per_gas: GasPriceWei(x / 2.0),
+            }),
+        };
+        history.record_at(now, estimate(1.0));
+        history.record_at(now, estimate(3.0));
+        let average = history
+            .average_over_at(now, Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(average, estimate(2.0));
+
+        history.record_at(now, price(2.0));
+        let average = history
+            .average_over_at(now, Duration::from_secs(1))
+            .unwrap();
+        assert!(average.eip1559.is_none());
+    }
+
+    #[test]
+    fn percentiles_and_max() {
+        let history = GasPriceHistory::new(10);
+        let now = Instant::now();
+        history.record_at(now - Duration::from_secs(100), price(100.0));
+        for legacy in [3.0, 1.0, 2.0] {
+            history.record_at(now, price(legacy));
+        }
+        let window = Duration::from_secs(10);
+        assert_eq!(
+            history.percentile_over_at(now, window, 0.0),
+            Some(price(1.0))
+        );
+        assert_eq!(
+            history.percentile_over_at(now, window, 0.5),
+            Some(price(2.0))
+        );
+        assert_eq!(
+            history.percentile_over_at(now, window, 1.0),
+            Some(price(3.0))
+        );
+        assert_eq!(
+            history.percentile_over_at(now, Duration::from_secs(1000), 1.0),
+            Some(price(100.0))
+        );
+    }
+
+    #[test]
+    fn records_estimates() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(1)
+            .returning(|| Ok(price(1.0)));
+        let recording = RecordingGasPriceEstimating::new(inner, Arc::new(GasPriceHistory::new(10)));
+        recording.estimate().wait().unwrap();
+        assert_eq!(
+            recording.history().max_over(Duration::from_secs(60)),
+            Some(price(1.0))
+        );
+    }
+
+    #[test]
+    fn records_only_the_configured_time_limit() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate_with_limits()
+            .returning(|_, time_limit| Ok(price(time_limit.as_secs_f64())));
+        inner.expect_estimate().returning(|| Ok(price(0.0)));
+        let recording = RecordingGasPriceEstimating::new(inner, Arc::new(GasPriceHistory::new(10)))
+            .with_time_limit(Duration::from_secs(60));
+        for secs in [15, 60, 600] {
+            recording
+                .estimate_with_limits(21000.0, Duration::from_secs(secs))
+                .wait()
+                .unwrap();
+        }
+        recording.estimate().wait().unwrap();
+        let entries = recording.history.entries.lock().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].1, price(60.0));
+    }
+}
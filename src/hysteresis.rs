@@ -0,0 +1,276 @@
+//! Suppression of small estimate changes. Callers that replace pending transactions whenever the
+//! estimate changes would otherwise replace them for changes of a single wei.
+
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasPrice1559,
+    GasPriceEstimating, GasPriceSchedule, GasPriceWei, TransactionCost, TxSpec,
+};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+pub struct Params {
+    // Relative change of the priority fee to the last returned one below which the last returned
+    // priority fee is kept, for example 0.1 for 10%.
+    pub priority_fee_threshold: f64,
+    // Same for max_fee_per_gas and legacy gas prices.
+    pub max_fee_threshold: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            priority_fee_threshold: 0.1,
+            max_fee_threshold: 0.1,
+        }
+    }
+}
+
+// Returns the last returned values of the inner estimator's estimates until they change by more
+// than the thresholds. The priority fee and max_fee_per_gas are handled separately while the
+// base fee is always the newest. The last returned estimates are kept per time limit.
+pub struct HysteresisEstimator<T> {
+    inner: T,
+    params: Params,
+    // `None` is used for `estimate` like in `CachedGasPriceEstimating`.
+    last: Mutex<HashMap<Option<Duration>, EstimatedGasPrice>>,
+}
+
+impl<T: GasPriceEstimating> HysteresisEstimator<T> {
+    pub fn new(inner: T, params: Option<Params>) -> Self {
+        Self {
+            inner,
+            params: params.unwrap_or_default(),
+            last: Default::default(),
+        }
+    }
+
+    fn filter(&self, key: Option<Duration>, estimate: EstimatedGasPrice) -> EstimatedGasPrice {
+        let mut last = self.last.lock().unwrap();
+        let result = match last.get(&key) {
+            Some(previous) if previous.eip1559.is_some() == estimate.eip1559.is_some() => {
+                self.keep_small_changes(previous, &estimate)
+            }
+            _ => estimate,
+        };
+        last.insert(key, result);
+        result
+    }
+
+    fn keep_small_changes(
+        &self,
+        previous: &EstimatedGasPrice,
+        estimate: &EstimatedGasPrice,
+    ) -> EstimatedGasPrice {
+        let max_fee =
+            |previous: f64, value: f64| select(previous, value, self.params.max_fee_threshold);
+        EstimatedGasPrice {
+            legacy: max_fee(previous.legacy, estimate.legacy),
+            eip1559: previous
+                .eip1559
+                .zip(estimate.eip1559)
+                .map(|(previous, estimate)| {
+                    let max_fee_per_gas = GasPriceWei(max_fee(
+                        previous.max_fee_per_gas.0,
+                        estimate.max_fee_per_gas.0,
+                    ));
+                    let max_priority_fee_per_gas = GasPriceWei(select(
+                        previous.max_priority_fee_per_gas.0,
+                        estimate.max_priority_fee_per_gas.0,
+                        self.params.priority_fee_threshold,
+                    ));
+                    GasPrice1559 {
+                        base_fee_per_gas: estimate.base_fee_per_gas,
+                        max_fee_per_gas,
+                        // A kept max_fee_per_gas can be below a new priority fee.
+                        max_priority_fee_per_gas: max_priority_fee_per_gas.min(max_fee_per_gas),
+                    }
+                }),
+        }
+    }
+}
+
+// `value` if it differs from `previous` by more than `threshold` relative to `previous`.
+fn select(previous: f64, value: f64, threshold: f64) -> f64 {
+    if (value - previous).abs() > threshold * previous.abs() {
+        value
+    } else {
+        previous
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for HysteresisEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_limits(gas_limit, time_limit)
+            .await?;
+        Ok(self.filter(Some(time_limit), estimate))
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        let estimate = self.inner.estimate().await?;
+        Ok(self.filter(None, estimate))
+    }
+
+    // The range is the one of the newest inner estimate, only the price is filtered.
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let estimate = self.inner.estimate_verbose(gas_limit, time_limit).await?;
+        Ok(EstimateWithMetadata {
+            price: self.filter(Some(time_limit), estimate.price),
+            ..estimate
+        })
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await?;
+        // Holding back a replacement could undo its bump so replacements are passed through.
+        if hints.replacing.is_some() {
+            return Ok(estimate);
+        }
+        Ok(self.filter(Some(time_limit), estimate))
+    }
+
+    // Only the gas price is filtered, the l1 fee depends on the transaction.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let cost = self.inner.estimate_for_tx(tx).await?;
+        Ok(TransactionCost {
+            gas_price: self.filter(Some(tx.time_limit), cost.gas_price),
+            ..cost
+        })
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let schedule = self.inner.estimate_schedule().await?;
+        Ok(schedule.map(|time_limit, estimate| self.filter(Some(time_limit), estimate)))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let estimates = self.inner.estimate_many(requests).await?;
+        Ok(requests
+            .iter()
+            .zip(estimates)
+            .map(|((_, time_limit), estimate)| self.filter(Some(*time_limit), estimate))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+
+    fn eip1559(base_fee: f64, max_fee: f64, priority_fee: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy: max_fee,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee),
+                max_fee_per_gas: GasPriceWei(max_fee),
+                max_priority_fee_per_gas: GasPriceWei(priority_fee),
+            }),
+        }
+    }
+
+    #[test]
+    fn keeps_fees_until_threshold_is_exceeded() {
+        let estimator =
+            HysteresisEstimator::new(FixedGasPriceEstimator::eip1559(10.0, 100.0, 10.0), None);
+        assert_eq!(
+            estimator.estimate().wait().unwrap(),
+            eip1559(10.0, 100.0, 10.0)
+        );
+
+        estimator.inner.set(eip1559(11.0, 105.0, 10.5));
+        assert_eq!(
+            estimator.estimate().wait().unwrap(),
+            eip1559(11.0, 100.0, 10.0)
+        );
+
+        // Only the priority fee changed enough.
+        estimator.inner.set(eip1559(11.0, 105.0, 12.0));
+        assert_eq!(
+            estimator.estimate().wait().unwrap(),
+            EstimatedGasPrice {
+                legacy: 100.0,
+                ..eip1559(11.0, 100.0, 12.0)
+            }
+        );
+
+        estimator.inner.set(eip1559(11.0, 120.0, 12.5));
+        assert_eq!(
+            estimator.estimate().wait().unwrap(),
+            eip1559(11.0, 120.0, 12.0)
+        );
+    }
+
+    #[test]
+    fn filters_hinted_estimates_except_replacements() {
+        let estimator =
+            HysteresisEstimator::new(FixedGasPriceEstimator::eip1559(10.0, 100.0, 10.0), None);
+        let time_limit = Duration::from_secs(60);
+        let estimate = |hints| {
+            estimator
+                .estimate_with_hints(21000.0, time_limit, &hints)
+                .wait()
+                .unwrap()
+        };
+        estimate(EstimationHints::default());
+        estimator.inner.set(eip1559(11.0, 105.0, 10.5));
+        assert_eq!(
+            estimate(EstimationHints::private()),
+            eip1559(11.0, 100.0, 10.0)
+        );
+        // the replaced transaction is cheap so the max fee is the one of the inner estimate
+        let replacement = estimate(EstimationHints::replacing(eip1559(10.0, 50.0, 5.0)));
+        assert_eq!(replacement.cap(), 105.0);
+    }
+
+    #[test]
+    fn thresholds_are_relative_to_last_returned_estimate() {
+        let estimator = HysteresisEstimator::new(
+            FixedGasPriceEstimator::legacy(100.0),
+            Some(Params {
+                priority_fee_threshold: 0.1,
+                max_fee_threshold: 0.1,
+            }),
+        );
+        estimator.estimate().wait().unwrap();
+        // Creeping up in small steps doesn't move the reference.
+        for gas_price in [105.0, 108.0, 110.0] {
+            estimator.inner.set(EstimatedGasPrice {
+                legacy: gas_price,
+                eip1559: None,
+            });
+            assert_eq!(estimator.estimate().wait().unwrap().legacy, 100.0);
+        }
+        estimator.inner.set(EstimatedGasPrice {
+            legacy: 111.0,
+            eip1559: None,
+        });
+        assert_eq!(estimator.estimate().wait().unwrap().legacy, 111.0);
+    }
+}
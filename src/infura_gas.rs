@@ -0,0 +1,311 @@
+//! Infura Gas API `GasPriceEstimating` implementation.
+//! Api documentation at https://docs.metamask.io/services/reference/gas-api/ .
+
+use super::{
+    auth, chain, error::Result, interpolation, ApiCredentials, ChainConfig, EstimatedGasPrice,
+    GasPrice1559, GasPriceEstimating, GasPriceSchedule, GasPriceWei, Transport,
+};
+use serde::Deserialize;
+use serde_with::rust::display_fromstr;
+use std::{convert::TryInto, time::Duration};
+
+pub const DEFAULT_URL: &str = "https://gas.api.infura.io";
+
+// Time limits of the tiers if the wait time estimates of the response are not usable. The api
+// returns wait times that overlap between tiers on some networks.
+pub const HIGH: Duration = Duration::from_secs(15);
+pub const MEDIUM: Duration = Duration::from_secs(45);
+pub const LOW: Duration = Duration::from_secs(120);
+
+/// Suggested fees in gwei.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestedGasFees {
+    pub low: Tier,
+    pub medium: Tier,
+    pub high: Tier,
+    #[serde(with = "display_fromstr")]
+    pub estimated_base_fee: f64,
+    // between 0 and 1
+    #[serde(default)]
+    pub network_congestion: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tier {
+    #[serde(with = "display_fromstr")]
+    pub suggested_max_priority_fee_per_gas: f64,
+    #[serde(with = "display_fromstr")]
+    pub suggested_max_fee_per_gas: f64,
+    // milliseconds
+    #[serde(default)]
+    pub max_wait_time_estimate: u64,
+}
+
+impl SuggestedGasFees {
+    // (time limit, tier) from fast to slow. The wait times of the response are used if they are
+    // ascending, otherwise the default time limits.
+    fn tiers(&self) -> [(Duration, &Tier); 3] {
+        let tiers = [&self.high, &self.medium, &self.low];
+        let waits = tiers.map(|tier| tier.max_wait_time_estimate);
+        let times = if waits[0] > 0 && waits[0] < waits[1] && waits[1] < waits[2] {
+            waits.map(Duration::from_millis)
+        } else {
+            [HIGH, MEDIUM, LOW]
+        };
+        [
+            (times[0], tiers[0]),
+            (times[1], tiers[1]),
+            (times[2], tiers[2]),
+        ]
+    }
+}
+
+pub struct InfuraGasStation<T> {
+    transport: T,
+    url: String,
+    block_time: Duration,
+    credentials: Option<ApiCredentials>,
+}
+
+impl<T: Transport> InfuraGasStation<T> {
+    /// With an api key secret the key and secret are sent with basic authentication. Without one
+    /// the key is part of the url, which only works if the key does not require the secret.
+    pub fn new(
+        chain_id: u64,
+        transport: T,
+        api_key: impl Into<String>,
+        api_key_secret: Option<String>,
+    ) -> Self {
+        let api_key = api_key.into();
+        let (url, credentials) = match api_key_secret {
+            Some(password) => (
+                format!("{}/networks/{}/suggestedGasFees", DEFAULT_URL, chain_id),
+                Some(ApiCredentials::Basic {
+                    username: api_key,
+                    password,
+                }),
+            ),
+            None => (
+                format!(
+                    "{}/v3/{}/networks/{}/suggestedGasFees",
+                    DEFAULT_URL, api_key, chain_id
+                ),
+                None,
+            ),
+        };
+        Self {
+            transport,
+            url,
+            block_time: ChainConfig::from_chain_id(chain_id)
+                .unwrap_or(chain::ETHEREUM)
+                .block_time,
+            credentials,
+        }
+    }
+
+    /// For a proxy of the api, the chain id has to be part of the url.
+    pub fn with_url(self, url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..self
+        }
+    }
+
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    pub async fn suggested_gas_fees(&self) -> Result<SuggestedGasFees> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.url, Default::default())?;
+        self.transport
+            .get_json(&url, header)
+            .await
+            .map_err(|err| err.context("failed to get infura suggested gas fees"))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for InfuraGasStation<T> {
+    fn source(&self) -> &'static str {
+        "infura"
+    }
+
+    fn block_time(&self) -> Duration {
+        self.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let response = self.suggested_gas_fees().await?;
+        estimate_with_limits(&response, time_limit)
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let response = self.suggested_gas_fees().await?;
+        GasPriceSchedule::from_estimates(|time_limit| estimate_with_limits(&response, time_limit))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let response = self.suggested_gas_fees().await?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| estimate_with_limits(&response, *time_limit))
+            .collect()
+    }
+}
+
+fn estimate_with_limits(
+    response: &SuggestedGasFees,
+    time_limit: Duration,
+) -> Result<EstimatedGasPrice> {
+    let tiers = response.tiers();
+    let interpolate = |value: fn(&Tier) -> f64| -> Result<f64> {
+        let points = tiers
+            .iter()
+            .map(|(time, tier)| (time.as_secs_f64(), GasPriceWei::from_gwei(value(tier)).0))
+            .collect::<Vec<_>>();
+        Ok(interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            points.as_slice().try_into()?,
+        ))
+    };
+    let eip1559 = GasPrice1559 {
+        base_fee_per_gas: GasPriceWei::from_gwei(response.estimated_base_fee),
+        max_fee_per_gas: GasPriceWei(interpolate(|tier| tier.suggested_max_fee_per_gas)?),
+        max_priority_fee_per_gas: GasPriceWei(interpolate(|tier| {
+            tier.suggested_max_priority_fee_per_gas
+        })?),
+    };
+    EstimatedGasPrice {
+        legacy: eip1559.max_fee_per_gas.0,
+        eip1559: Some(eip1559),
+    }
+    .validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use http::header::{HeaderMap, AUTHORIZATION};
+    use serde::de::DeserializeOwned;
+
+    const RESPONSE: &str = r#"{
+        "low": {
+            "suggestedMaxPriorityFeePerGas": "0.05",
+            "suggestedMaxFeePerGas": "16.334026964",
+            "minWaitTimeEstimate": 15000,
+            "maxWaitTimeEstimate": 30000
+        },
+        "medium": {
+            "suggestedMaxPriorityFeePerGas": "0.1",
+            "suggestedMaxFeePerGas": "22.083436402",
+            "minWaitTimeEstimate": 15000,
+            "maxWaitTimeEstimate": 45000
+        },
+        "high": {
+            "suggestedMaxPriorityFeePerGas": "0.3",
+            "suggestedMaxFeePerGas": "27.982845839",
+            "minWaitTimeEstimate": 15000,
+            "maxWaitTimeEstimate": 60000
+        },
+        "estimatedBaseFee": "16.284026964",
+        "networkCongestion": 0.5125,
+        "latestPriorityFeeRange": ["0.131926278", "5"],
+        "historicalPriorityFeeRange": ["0.02", "33.290763188"],
+        "historicalBaseFeeRange": ["13.12293239", "18.15169906"],
+        "priorityFeeTrend": "down",
+        "baseFeeTrend": "up"
+    }"#;
+
+    #[test]
+    fn deserialize() {
+        let response: SuggestedGasFees = serde_json::from_str(RESPONSE).unwrap();
+        assert_approx_eq!(response.low.suggested_max_priority_fee_per_gas, 0.05);
+        assert_approx_eq!(response.high.suggested_max_fee_per_gas, 27.982845839);
+        assert_approx_eq!(response.estimated_base_fee, 16.284026964);
+        assert_approx_eq!(response.network_congestion, 0.5125);
+        assert_eq!(response.medium.max_wait_time_estimate, 45000);
+    }
+
+    #[test]
+    fn maps_tiers_to_time_limits() {
+        // the example wait times are descending from high to low so they are not used
+        let response: SuggestedGasFees = serde_json::from_str(RESPONSE).unwrap();
+        let high = estimate_with_limits(&response, HIGH).unwrap();
+        assert_approx_eq!(high.tip(), 0.3e9);
+        assert_approx_eq!(high.cap(), 27.982845839e9, 1.0);
+        assert_approx_eq!(high.base_fee(), 16.284026964e9, 1.0);
+        let low = estimate_with_limits(&response, LOW).unwrap();
+        assert_approx_eq!(low.tip(), 0.05e9);
+
+        let response = SuggestedGasFees {
+            high: Tier {
+                max_wait_time_estimate: 10_000,
+                ..response.high
+            },
+            medium: Tier {
+                max_wait_time_estimate: 20_000,
+                ..response.medium
+            },
+            low: Tier {
+                max_wait_time_estimate: 40_000,
+                ..response.low
+            },
+            ..response
+        };
+        let medium = estimate_with_limits(&response, Duration::from_secs(20)).unwrap();
+        assert_approx_eq!(medium.tip(), 0.1e9);
+    }
+
+    struct Fixed;
+
+    #[async_trait::async_trait]
+    impl Transport for Fixed {
+        async fn get_json<R: DeserializeOwned>(&self, url: &str, header: HeaderMap) -> Result<R> {
+            match header.get(AUTHORIZATION) {
+                Some(authorization) => {
+                    assert_eq!(url, "https://gas.api.infura.io/networks/1/suggestedGasFees");
+                    assert_eq!(authorization, "Basic a2V5OnNlY3JldA==");
+                }
+                None => assert_eq!(
+                    url,
+                    "https://gas.api.infura.io/v3/key/networks/1/suggestedGasFees"
+                ),
+            }
+            Ok(serde_json::from_str(RESPONSE)?)
+        }
+    }
+
+    #[test]
+    fn sends_credentials() {
+        let estimator = InfuraGasStation::new(1, Fixed, "key", Some("secret".to_string()));
+        assert!(estimator.estimate().wait().is_ok());
+        let estimator = InfuraGasStation::new(1, Fixed, "key", None);
+        assert!(estimator.estimate().wait().is_ok());
+    }
+
+    // INFURA_API_KEY=... cargo test infura_gas -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = InfuraGasStation::new(
+            1,
+            TestTransport::default(),
+            std::env::var("INFURA_API_KEY").unwrap(),
+            std::env::var("INFURA_API_KEY_SECRET").ok(),
+        );
+        println!("{:?}", estimator.suggested_gas_fees().await);
+        println!("{:?}", estimator.estimate_schedule().await);
+    }
+}
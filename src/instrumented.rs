@@ -0,0 +1,204 @@
+use super::{
+    error::Result, time::Instant, EstimateWithMetadata, EstimatedGasPrice, EstimationHints,
+    GasPriceEstimating, GasPriceSchedule, TransactionCost, TxSpec,
+};
+use std::{future::Future, sync::Arc, time::Duration};
+
+/// Receives the outcome of every estimate of an instrumented estimator. Implement this to export
+/// request counts, error counts, latencies and the returned gas prices to a metrics system like
+/// Prometheus.
+#[cfg_attr(test, mockall::automock)]
+pub trait Metrics: Send + Sync {
+    fn estimate_succeeded(&self, estimator: &str, latency: Duration, estimate: &EstimatedGasPrice);
+    fn estimate_failed(&self, estimator: &str, latency: Duration);
+}
+
+// Reports every estimate of the inner estimator to `Metrics` under the given name.
+pub struct InstrumentedGasPriceEstimating<T> {
+    inner: T,
+    name: String,
+    metrics: Arc<dyn Metrics>,
+}
+
+impl<T: GasPriceEstimating> InstrumentedGasPriceEstimating<T> {
+    pub fn new(inner: T, name: impl Into<String>, metrics: Arc<dyn Metrics>) -> Self {
+        Self {
+            inner,
+            name: name.into(),
+            metrics,
+        }
+    }
+
+    // `price` picks the reported gas price out of the result.
+    async fn measure<R>(
+        &self,
+        estimate: impl Future<Output = Result<R>>,
+        price: impl Fn(&R) -> &EstimatedGasPrice,
+    ) -> Result<R> {
+        let start = Instant::now();
+        let result = estimate.await;
+        let latency = start.elapsed();
+        match &result {
+            Ok(estimate) => self
+                .metrics
+                .estimate_succeeded(&self.name, latency, price(estimate)),
+            Err(_) => self.metrics.estimate_failed(&self.name, latency),
+        }
+        result
+    }
+
+    // Like `measure` for calls that return `count` estimates, each of them is reported with the
+    // latency of the whole call.
+    async fn measure_each<R>(
+        &self,
+        estimate: impl Future<Output = Result<R>>,
+        count: usize,
+        prices: impl Fn(&R) -> Vec<EstimatedGasPrice>,
+    ) -> Result<R> {
+        let start = Instant::now();
+        let result = estimate.await;
+        let latency = start.elapsed();
+        match &result {
+            Ok(estimate) => {
+                for price in prices(estimate) {
+                    self.metrics.estimate_succeeded(&self.name, latency, &price);
+                }
+            }
+            Err(_) => {
+                for _ in 0..count {
+                    self.metrics.estimate_failed(&self.name, latency);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for InstrumentedGasPriceEstimating<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.measure(
+            self.inner.estimate_with_limits(gas_limit, time_limit),
+            |price| price,
+        )
+        .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.measure(self.inner.estimate(), |price| price).await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.measure(
+            self.inner.estimate_verbose(gas_limit, time_limit),
+            |estimate| &estimate.price,
+        )
+        .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.measure(
+            self.inner.estimate_with_hints(gas_limit, time_limit, hints),
+            |price| price,
+        )
+        .await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.measure(self.inner.estimate_for_tx(tx), |cost| &cost.gas_price)
+            .await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.measure_each(self.inner.estimate_schedule(), 4, |schedule| {
+            schedule
+                .entries()
+                .iter()
+                .map(|(_, estimate)| *estimate)
+                .collect()
+        })
+        .await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.measure_each(
+            self.inner.estimate_many(requests),
+            requests.len(),
+            |estimates| estimates.clone(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+    use anyhow::anyhow;
+    use mockall::predicate::{always, eq};
+
+    #[test]
+    fn reports_success() {
+        let estimate = EstimatedGasPrice {
+            legacy: 1.0,
+            ..Default::default()
+        };
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(1)
+            .returning(move || Ok(estimate));
+        let mut metrics = MockMetrics::new();
+        metrics
+            .expect_estimate_succeeded()
+            .with(eq("test"), always(), eq(estimate))
+            .times(1)
+            .return_const(());
+
+        let instrumented = InstrumentedGasPriceEstimating::new(inner, "test", Arc::new(metrics));
+        assert_eq!(instrumented.estimate().wait().unwrap(), estimate);
+    }
+
+    #[test]
+    fn reports_failure() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate_with_limits()
+            .times(1)
+            .returning(|_, _| Err(anyhow!("").into()));
+        let mut metrics = MockMetrics::new();
+        metrics
+            .expect_estimate_failed()
+            .with(eq("test"), always())
+            .times(1)
+            .return_const(());
+
+        let instrumented = InstrumentedGasPriceEstimating::new(inner, "test", Arc::new(metrics));
+        assert!(instrumented
+            .estimate_with_limits(21000.0, Duration::from_secs(30))
+            .wait()
+            .is_err());
+    }
+}
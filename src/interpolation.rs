@@ -0,0 +1,307 @@
+//! Interpolation between the (time, gas price) points reported by gas stations.
+//!
+//! The points are validated once by converting them into `Points`:
+//! `interpolate(x, points.as_slice().try_into()?)`.
+
+use anyhow::{anyhow, Error};
+use std::convert::TryFrom;
+
+/// (x, y)
+pub type Point = (f64, f64);
+
+/// Not empty, finite and contains unique x values sorted in ascending order.
+#[derive(Copy, Clone, Debug)]
+pub struct Points<'a>(&'a [Point]);
+
+impl<'a> Points<'a> {
+    pub fn as_slice(&self) -> &'a [Point] {
+        self.0
+    }
+}
+
+impl<'a> TryFrom<&'a [Point]> for Points<'a> {
+    type Error = Error;
+
+    fn try_from(points: &'a [Point]) -> Result<Self, Self::Error> {
+        let is_finite = points
+            .iter()
+            .all(|point| point.0.is_finite() && point.1.is_finite());
+        let is_sorted_and_unique = points.windows(2).all(|window| window[0].0 < window[1].0);
+        if points.is_empty() {
+            Err(anyhow!("points is empty"))
+        } else if !is_finite {
+            Err(anyhow!("points contains non finite value"))
+        } else if !is_sorted_and_unique {
+            Err(anyhow!("points is not sorted an unique"))
+        } else {
+            Ok(Self(points))
+        }
+    }
+}
+
+/// What happens to values outside of the points.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Bounds {
+    /// The y of the first or last point.
+    #[default]
+    Clamp,
+    /// Continue the line through the first two or last two points. With a single point this is
+    /// the same as clamping.
+    Extrapolate,
+}
+
+/// Linearly interpolate `value` between `points`.
+///
+/// If `value` is smaller than the first point or larger than the last it is clamped.
+pub fn interpolate(value: f64, points: Points) -> f64 {
+    interpolate_with_bounds(value, points, Bounds::Clamp)
+}
+
+/// Linearly interpolate `value` between `points` with the given behavior outside of the points.
+pub fn interpolate_with_bounds(value: f64, points: Points, bounds: Bounds) -> f64 {
+    let points = points.0;
+    let line = |window: &[Point]| {
+        // https://en.wikipedia.org/wiki/Linear_interpolation#Linear_interpolation_between_two_known_points
+        let (x, x0, y0, x1, y1) = (value, window[0].0, window[0].1, window[1].0, window[1].1);
+        y0 + (x - x0) * ((y1 - y0) / (x1 - x0))
+    };
+    let extrapolate = bounds == Bounds::Extrapolate && points.len() > 1;
+    if value < points[0].0 {
+        match extrapolate {
+            true => line(&points[..2]),
+            false => points[0].1,
+        }
+    } else if let Some(window) = points
+        .windows(2)
+        .find(|window| value >= window[0].0 && value < window[1].0)
+    {
+        line(window)
+    } else {
+        match extrapolate {
+            true => line(&points[points.len() - 2..]),
+            false => points.last().unwrap().1,
+        }
+    }
+}
+
+/// How to interpolate between points. Values outside of the points are clamped for all
+/// strategies.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum InterpolationStrategy {
+    /// y of the closest point at or below `value`. For gas prices over time limits this is the
+    /// price of the next faster bucket.
+    Step,
+    /// Straight line between neighbouring points.
+    #[default]
+    Linear,
+    /// Straight line between the logarithms of y, which is a geometric instead of an arithmetic
+    /// mean. Falls back to linear interpolation if a y value is not positive.
+    LogLinear,
+    /// Piecewise cubic Hermite interpolation that preserves monotonicity (Fritsch-Carlson). Unlike
+    /// linear interpolation it follows the curvature of the points without overshooting them.
+    Pchip,
+}
+
+impl InterpolationStrategy {
+    pub fn interpolate(&self, value: f64, points: Points) -> f64 {
+        match self {
+            Self::Step => step(value, points),
+            Self::Linear => interpolate(value, points),
+            Self::LogLinear => log_linear(value, points),
+            Self::Pchip => pchip(value, points),
+        }
+    }
+}
+
+fn step(value: f64, points: Points) -> f64 {
+    let points = points.0;
+    match points.iter().rposition(|point| point.0 <= value) {
+        Some(index) => points[index].1,
+        None => points[0].1,
+    }
+}
+
+fn log_linear(value: f64, points: Points) -> f64 {
+    if points.0.iter().any(|point| point.1 <= 0.0) {
+        return interpolate(value, points);
+    }
+    let logarithms = points
+        .0
+        .iter()
+        .map(|point| (point.0, point.1.ln()))
+        .collect::<Vec<_>>();
+    interpolate(value, Points(&logarithms)).exp()
+}
+
+fn pchip(value: f64, points: Points) -> f64 {
+    let points = points.0;
+    let last = points.len() - 1;
+    if value <= points[0].0 {
+        return points[0].1;
+    }
+    if value >= points[last].0 {
+        return points[last].1;
+    }
+    let widths = points
+        .windows(2)
+        .map(|window| window[1].0 - window[0].0)
+        .collect::<Vec<_>>();
+    let slopes = points
+        .windows(2)
+        .zip(&widths)
+        .map(|(window, width)| (window[1].1 - window[0].1) / width)
+        .collect::<Vec<_>>();
+    // Derivative at point i. Zero at local extrema, otherwise the weighted harmonic mean of the
+    // neighbouring slopes. The end points use the slope of their segment.
+    let derivative = |i: usize| -> f64 {
+        if i == 0 {
+            return slopes[0];
+        }
+        if i == last {
+            return slopes[last - 1];
+        }
+        let (s0, s1) = (slopes[i - 1], slopes[i]);
+        if s0 * s1 <= 0.0 {
+            return 0.0;
+        }
+        let (h0, h1) = (widths[i - 1], widths[i]);
+        let (w0, w1) = (2.0 * h1 + h0, h1 + 2.0 * h0);
+        (w0 + w1) / (w0 / s0 + w1 / s1)
+    };
+    let i = points
+        .windows(2)
+        .position(|window| value < window[1].0)
+        .unwrap();
+    let ((x0, y0), (_, y1), h) = (points[i], points[i + 1], widths[i]);
+    let t = (value - x0) / h;
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+        + (t3 - 2.0 * t2 + t) * h * derivative(i)
+        + (-2.0 * t3 + 3.0 * t2) * y1
+        + (t3 - t2) * h * derivative(i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn interpolate_() {
+        let points = Points::try_from([(1.0, 1.0), (2.0, 2.0), (3.0, 3.0)].as_ref()).unwrap();
+        assert_approx_eq!(interpolate(0.5, points), 1.0);
+        assert_approx_eq!(interpolate(1.0, points), 1.0);
+        assert_approx_eq!(interpolate(1.5, points), 1.5);
+        assert_approx_eq!(interpolate(2.0, points), 2.0);
+        assert_approx_eq!(interpolate(2.5, points), 2.5);
+        assert_approx_eq!(interpolate(3.0, points), 3.0);
+        assert_approx_eq!(interpolate(3.5, points), 3.0);
+    }
+
+    #[test]
+    fn interpolate_works_with_single_point() {
+        let points = Points::try_from([(1.0, 1.0)].as_ref()).unwrap();
+        assert_approx_eq!(interpolate(0.5, points), 1.0);
+        assert_approx_eq!(interpolate(1.0, points), 1.0);
+        assert_approx_eq!(interpolate(1.5, points), 1.0);
+    }
+
+    #[test]
+    fn interpolate_works_with_varying_x_and_y_deltas() {
+        let points = Points::try_from([(0.0, 1.0), (1.0, 0.0), (3.0, 1.0)].as_ref()).unwrap();
+        assert_approx_eq!(interpolate(0.0, points), 1.0);
+        assert_approx_eq!(interpolate(0.4, points), 0.6);
+        assert_approx_eq!(interpolate(0.5, points), 0.5);
+        assert_approx_eq!(interpolate(0.6, points), 0.4);
+        assert_approx_eq!(interpolate(1.0, points), 0.0);
+        assert_approx_eq!(interpolate(1.5, points), 0.25);
+        assert_approx_eq!(interpolate(2.0, points), 0.5);
+        assert_approx_eq!(interpolate(2.5, points), 0.75);
+        assert_approx_eq!(interpolate(3.0, points), 1.0);
+    }
+
+    #[test]
+    fn extrapolates() {
+        let points = Points::try_from([(1.0, 1.0), (2.0, 3.0), (4.0, 4.0)].as_ref()).unwrap();
+        let bounds = Bounds::Extrapolate;
+        assert_approx_eq!(interpolate_with_bounds(0.0, points, bounds), -1.0);
+        assert_approx_eq!(interpolate_with_bounds(1.5, points, bounds), 2.0);
+        assert_approx_eq!(interpolate_with_bounds(4.0, points, bounds), 4.0);
+        assert_approx_eq!(interpolate_with_bounds(6.0, points, bounds), 5.0);
+        assert_approx_eq!(interpolate_with_bounds(6.0, points, Bounds::Clamp), 4.0);
+
+        let points = Points::try_from([(1.0, 1.0)].as_ref()).unwrap();
+        assert_approx_eq!(interpolate_with_bounds(0.0, points, bounds), 1.0);
+        assert_approx_eq!(interpolate_with_bounds(2.0, points, bounds), 1.0);
+    }
+
+    #[test]
+    fn step_uses_previous_point() {
+        let points = Points::try_from([(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)].as_ref()).unwrap();
+        let step = InterpolationStrategy::Step;
+        assert_approx_eq!(step.interpolate(0.5, points), 3.0);
+        assert_approx_eq!(step.interpolate(1.5, points), 3.0);
+        assert_approx_eq!(step.interpolate(2.0, points), 2.0);
+        assert_approx_eq!(step.interpolate(2.9, points), 2.0);
+        assert_approx_eq!(step.interpolate(4.0, points), 1.0);
+    }
+
+    #[test]
+    fn log_linear_uses_geometric_mean() {
+        let points = Points::try_from([(0.0, 1.0), (1.0, 100.0)].as_ref()).unwrap();
+        let log_linear = InterpolationStrategy::LogLinear;
+        assert_approx_eq!(log_linear.interpolate(0.5, points), 10.0);
+        assert_approx_eq!(log_linear.interpolate(2.0, points), 100.0);
+        // falls back to linear
+        let points = Points::try_from([(0.0, 0.0), (1.0, 100.0)].as_ref()).unwrap();
+        assert_approx_eq!(log_linear.interpolate(0.5, points), 50.0);
+    }
+
+    #[test]
+    fn pchip_is_monotone_and_hits_points() {
+        let points =
+            Points::try_from([(15.0, 100.0), (60.0, 40.0), (300.0, 30.0), (600.0, 25.0)].as_ref())
+                .unwrap();
+        let pchip = InterpolationStrategy::Pchip;
+        for point in points.0 {
+            assert_approx_eq!(pchip.interpolate(point.0, points), point.1);
+        }
+        let mut previous = f64::INFINITY;
+        for x in 0..700 {
+            let y = pchip.interpolate(x as f64, points);
+            assert!(y <= previous);
+            previous = y;
+        }
+        // cheaper than linear interpolation between the first two buckets
+        assert!(pchip.interpolate(30.0, points) < interpolate(30.0, points));
+    }
+
+    #[test]
+    fn pchip_flattens_at_extrema() {
+        let points = Points::try_from([(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)].as_ref()).unwrap();
+        let pchip = InterpolationStrategy::Pchip;
+        assert!(pchip.interpolate(0.9, points) <= 1.0);
+        assert!(pchip.interpolate(1.1, points) <= 1.0);
+    }
+
+    #[test]
+    fn points_must_not_be_empty() {
+        assert!(Points::try_from([].as_ref()).is_err());
+    }
+
+    #[test]
+    fn points_must_not_be_nan() {
+        assert!(Points::try_from([(f64::NAN, 0.0)].as_ref()).is_err());
+        assert!(Points::try_from([(0.0, f64::NAN)].as_ref()).is_err());
+    }
+
+    #[test]
+    fn points_must_be_sorted() {
+        assert!(Points::try_from([(1.0, 0.0), (0.0, 0.0)].as_ref()).is_err());
+    }
+
+    #[test]
+    fn points_must_be_unique() {
+        assert!(Points::try_from([(0.0, 0.0), (0.0, 1.0)].as_ref()).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+// Minimal Ethereum JSON-RPC client on top of `Transport::post_json` for the estimators that need a
+// few node calls without depending on web3.
+
+use super::{error::Result, GasEstimationError, Transport};
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Deserialize)]
+struct Response<R> {
+    result: Option<R>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+pub async fn call<R: DeserializeOwned>(
+    transport: &impl Transport,
+    url: &str,
+    method: &str,
+    params: Value,
+) -> Result<R> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+    let response: Response<R> = transport
+        .post_json(url, Default::default(), &request)
+        .await?;
+    parse_response(response)
+}
+
+fn parse_response<R>(response: Response<R>) -> Result<R> {
+    if let Some(error) = response.error {
+        return Err(GasEstimationError::Transport(anyhow!(
+            "json rpc error {}: {}",
+            error.code,
+            error.message
+        )));
+    }
+    response
+        .result
+        .ok_or_else(|| GasEstimationError::Decode(anyhow!("json rpc response without result")))
+}
+
+// Quantities are hex encoded.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    // One more entry than blocks, the last one is the base fee of the pending block.
+    #[serde(default)]
+    pub base_fee_per_gas: Vec<String>,
+    pub gas_used_ratio: Vec<f64>,
+    pub reward: Option<Vec<Vec<String>>>,
+}
+
+// `eth_feeHistory` for the latest `block_count` blocks. `reward_percentiles` must be ascending.
+pub async fn fee_history(
+    transport: &impl Transport,
+    url: &str,
+    block_count: u64,
+    reward_percentiles: &[f64],
+) -> Result<FeeHistory> {
+    call(
+        transport,
+        url,
+        "eth_feeHistory",
+        json!([format!("0x{:x}", block_count), "latest", reward_percentiles]),
+    )
+    .await
+    .map_err(|err| err.context("failed to get fee history"))
+}
+
+// `eth_call` against the latest block returning the raw return data.
+pub async fn eth_call(
+    transport: &impl Transport,
+    url: &str,
+    to: &str,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    let result: String = call(
+        transport,
+        url,
+        "eth_call",
+        json!([{ "to": to, "data": to_hex(data) }, "latest"]),
+    )
+    .await?;
+    from_hex(&result)
+}
+
+// Decodes a hex encoded quantity like the result of `eth_gasPrice`.
+pub fn quantity_to_f64(quantity: &str) -> Result<f64> {
+    let digits = quantity.strip_prefix("0x").unwrap_or(quantity);
+    if digits.is_empty() {
+        return Err(GasEstimationError::Decode(anyhow!(
+            "empty quantity {:?}",
+            quantity
+        )));
+    }
+    digits.chars().try_fold(0.0, |acc, c| {
+        c.to_digit(16)
+            .map(|d| acc * 16.0 + d as f64)
+            .ok_or_else(|| {
+                GasEstimationError::Decode(anyhow!("invalid hex quantity {:?}", quantity))
+            })
+    })
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if digits.len() % 2 != 0 {
+        return Err(GasEstimationError::Decode(anyhow!(
+            "odd length hex {:?}",
+            hex
+        )));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|err| GasEstimationError::Decode(anyhow!(err).context("invalid hex")))
+        })
+        .collect()
+}
+
+// The abi word at `index` of contract return data as a (lossy) float.
+pub fn word_to_f64(data: &[u8], index: usize) -> Result<f64> {
+    let word = data.get(index * 32..(index + 1) * 32).ok_or_else(|| {
+        GasEstimationError::Decode(anyhow!("return data too short for word {}", index))
+    })?;
+    Ok(word
+        .iter()
+        .fold(0.0, |acc, byte| acc * 256.0 + *byte as f64))
+}
+
+// Abi encoding of a dynamic `bytes` value without the head offset: length followed by the data
+// padded to a multiple of 32 bytes.
+pub fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    let mut encoded = uint_word(data.len() as u64).to_vec();
+    encoded.extend_from_slice(data);
+    encoded.resize(32 + data.len().div_ceil(32) * 32, 0);
+    encoded
+}
+
+pub fn uint_word(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::HashMap;
+
+    // JSON-RPC node that answers with fixed results. `eth_call` results are keyed by
+    // "eth_call:<selector>" so that different contract calls can be answered.
+    #[derive(Default)]
+    pub struct FakeNode {
+        pub results: HashMap<String, Value>,
+    }
+
+    impl FakeNode {
+        pub fn with_result(mut self, key: &str, result: Value) -> Self {
+            self.results.insert(key.to_string(), result);
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for FakeNode {
+        async fn get_json<T: DeserializeOwned>(
+            &self,
+            url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<T> {
+            Err(GasEstimationError::Unsupported(url.to_string()))
+        }
+
+        async fn post_json<Req, Resp>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+            body: &Req,
+        ) -> Result<Resp>
+        where
+            Req: Serialize + Send + Sync,
+            Resp: DeserializeOwned,
+        {
+            let request = serde_json::to_value(body)?;
+            let mut key = request["method"].as_str().unwrap_or_default().to_string();
+            if key == "eth_call" {
+                let data = request["params"][0]["data"].as_str().unwrap_or_default();
+                key = format!("{}:{}", key, &data[..10.min(data.len())]);
+            }
+            let response = match self.results.get(&key) {
+                Some(result) => json!({ "jsonrpc": "2.0", "id": 1, "result": result }),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": { "code": -32601, "message": format!("no result for {}", key) },
+                }),
+            };
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    #[test]
+    fn parses_quantities() {
+        assert_eq!(quantity_to_f64("0x0").unwrap(), 0.0);
+        assert_eq!(quantity_to_f64("0x3b9aca00").unwrap(), 1e9);
+        assert!(quantity_to_f64("0x").is_err());
+        assert!(quantity_to_f64("0xg").is_err());
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let bytes = vec![0x00, 0x01, 0xab, 0xff];
+        assert_eq!(to_hex(&bytes), "0x0001abff");
+        assert_eq!(from_hex("0x0001abff").unwrap(), bytes);
+        assert!(from_hex("0x123").is_err());
+    }
+
+    #[test]
+    fn decodes_words() {
+        let mut data = uint_word(1).to_vec();
+        data.extend_from_slice(&uint_word(1_000_000_000));
+        assert_eq!(word_to_f64(&data, 0).unwrap(), 1.0);
+        assert_eq!(word_to_f64(&data, 1).unwrap(), 1e9);
+        assert!(word_to_f64(&data, 2).is_err());
+    }
+
+    #[test]
+    fn encodes_bytes() {
+        assert_eq!(encode_bytes(&[]), uint_word(0).to_vec());
+        let encoded = encode_bytes(&[1, 2, 3]);
+        assert_eq!(encoded.len(), 64);
+        assert_eq!(&encoded[..32], &uint_word(3));
+        assert_eq!(&encoded[32..35], &[1, 2, 3]);
+        assert!(encoded[35..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn rpc_error_is_transport_error() {
+        let response: Response<String> = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"execution reverted"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            parse_response(response),
+            Err(GasEstimationError::Transport(_))
+        ));
+        let response: Response<String> =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"result":"0x1"}"#).unwrap();
+        assert_eq!(parse_response(response).unwrap(), "0x1");
+    }
+}
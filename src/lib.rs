@@ -1,33 +1,204 @@
 //! # Features
-//! `web3_`: Implements `GasPriceEstimating` for `Web3`.
+//! `web3_`: Implements `GasPriceEstimating` for `Web3` and converts estimates into the fees of
+//! `TransactionParameters`. `NativeGasEstimator` also needs `tokio_` or `async-std_` for its
+//! background task.
+//! `ethers_`: Converts estimates into the fees of ethers' `Eip1559TransactionRequest`.
+//! `primitive-types`: `U256` conversions of `GasPriceWei`, enabled by `web3_`.
+//! `tokio_`: Estimators and decorators that spawn tasks or sleep on the tokio runtime.
+//! `blocking`: `BlockingGasPriceEstimator` for synchronous estimates on an internal runtime.
+//! `async-std_`: Runs the background tasks of `BlockNative` and `NativeGasEstimator` on async-std
+//! when `tokio_` is disabled.
+//! `reqwest_`: `Transport` implementation based on reqwest.
+//! `serde_`: Implements `Deserialize` for the gas price types. `Serialize` is always implemented.
+//! `grpc`: tonic service and prost messages of `proto/gas_estimation.proto` over an estimator.
+//! `server`: Serves the estimates of an estimator as a local http api with hyper.
+//! `tracing_`: Spans for every estimate with the source, url, latency and decoded responses.
+//! `coingecko`: Native token prices from CoinGecko for fiat denominated gas costs.
+//! `cli`: The `gas-estimate` binary printing the estimates of selected providers.
+//! `config`: Estimator stacks loaded from TOML or YAML files.
+//! `test_util`: Canned api responses and a `Transport` serving them for integration tests.
+//!
+//! Without `tokio_`, `reqwest_` and `web3_` the crate builds for `wasm32-unknown-unknown`, see
+//! `time` for how the estimators tell the time there.
 
-#[cfg(feature = "tokio_")]
+pub mod alchemy;
+pub mod anomaly;
+pub mod arbitrum;
+pub mod auth;
+pub mod base_fee;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod blocknative;
+pub mod builder;
+pub mod bump_strategy;
+pub mod cached;
+pub mod chain;
+pub mod circuit_breaker;
+#[cfg(feature = "coingecko")]
+pub mod coingecko;
+pub mod combined;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod cost;
+pub mod debug;
+pub mod error;
 #[cfg(feature = "web3_")]
 pub mod eth_node;
+pub mod etherscan;
 pub mod ethgasstation;
+pub mod fee_history;
+pub mod fiat;
+pub mod flashbots;
+pub mod forecast;
+pub mod gas_limit;
 pub mod gas_price;
 pub mod gasnow;
+pub mod generic_http;
+pub mod gnosis_chain;
 pub mod gnosis_safe;
-mod linear_interpolation;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hints;
+pub mod history;
+pub mod hysteresis;
+pub mod infura_gas;
+pub mod instrumented;
+pub mod interpolation;
+mod json_rpc;
+pub mod limits;
+pub mod linea;
+pub mod mempool;
 #[cfg(feature = "web3_")]
+pub mod native;
+#[cfg(all(feature = "web3_", any(feature = "tokio_", feature = "async-std_")))]
 pub mod nativegasestimator;
+pub mod oneinch;
+pub mod op_stack;
+#[cfg(feature = "tokio_")]
+pub mod polling;
+pub mod polygon;
 pub mod priority;
-
 #[cfg(feature = "tokio_")]
+pub mod racing;
+pub mod rate_limit;
+pub mod readiness;
+pub mod registry;
+pub mod replay;
+#[cfg(feature = "tokio_")]
+pub mod retry;
+pub mod rpc_percentile;
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+pub mod runtime;
+pub mod scroll;
+pub mod seasonal;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod smoothing;
+pub mod snapshot;
+pub mod strategy;
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
+mod task;
+#[cfg(feature = "test_util")]
+pub mod test_util;
+pub mod testing;
+pub mod time;
+#[cfg(feature = "tokio_")]
+pub mod timeout;
+mod trace;
+#[cfg(any(feature = "web3_", feature = "ethers_"))]
+mod transaction;
+pub mod transport;
+#[cfg(feature = "web3_")]
+pub mod txpool;
+pub mod units;
+pub mod validate;
+#[cfg(feature = "tokio_")]
+pub mod ws_util;
+pub mod zksync;
+
+pub use alchemy::AlchemyGasEstimator;
+pub use anomaly::DivergenceDetectingEstimator;
+pub use arbitrum::ArbitrumGasEstimator;
+pub use auth::ApiCredentials;
+pub use base_fee::BaseFeePredictor;
+#[cfg(feature = "blocking")]
+pub use blocking::BlockingGasPriceEstimator;
+#[cfg(any(feature = "tokio_", feature = "async-std_"))]
 pub use blocknative::BlockNative;
+pub use blocknative::BlocknativeGasStation;
+#[cfg(feature = "tokio_")]
+pub use blocknative::BlocknativeWebSocketGasStation;
+pub use builder::EstimatorBuilder;
+pub use bump_strategy::BumpStrategy;
+pub use cached::CachedGasPriceEstimating;
+pub use chain::ChainConfig;
+pub use circuit_breaker::CircuitBreakerEstimator;
+#[cfg(feature = "coingecko")]
+pub use coingecko::CoinGeckoPriceEstimator;
+pub use combined::MedianGasPriceEstimating;
+pub use cost::{TransactionCost, TransactionCostEstimator, TxSpec};
+pub use debug::DebugTransport;
+pub use error::GasEstimationError;
+pub use etherscan::EtherscanGasStation;
 pub use ethgasstation::EthGasStation;
-pub use gas_price::{EstimatedGasPrice, GasPrice1559};
+pub use fee_history::FeeHistoryConfig;
+pub use fiat::FiatGasCostEstimator;
+pub use flashbots::FlashbotsPriorityFeeEstimator;
+pub use forecast::BaseFeeForecaster;
+pub use gas_limit::{GasLimitEstimating, GasLimitRequest};
+pub use gas_price::{
+    max_by_effective_price, EstimateRange, EstimateWithMetadata, EstimatedGasPrice, GasPrice1559,
+    GasPriceSchedule,
+};
 pub use gasnow::GasNowGasStation;
+pub use generic_http::ConfigurableEstimator;
+pub use gnosis_chain::GnosisChainGasStation;
 pub use gnosis_safe::GnosisSafeGasStation;
+pub use hints::EstimationHints;
+pub use history::{GasPriceHistory, RecordingGasPriceEstimating};
+pub use hysteresis::HysteresisEstimator;
+pub use infura_gas::InfuraGasStation;
+pub use instrumented::InstrumentedGasPriceEstimating;
+pub use limits::ClampedEstimator;
+pub use linea::LineaGasEstimator;
+pub use mempool::MempoolTipEstimator;
+pub use oneinch::OneInchGasStation;
+pub use op_stack::OpStackGasEstimator;
+#[cfg(feature = "tokio_")]
+pub use polling::BackgroundPollingEstimator;
+pub use polygon::PolygonGasStation;
 pub use priority::PriorityGasPriceEstimating;
+#[cfg(feature = "tokio_")]
+pub use racing::RacingGasPriceEstimating;
+pub use rate_limit::{RateLimitedEstimator, RateLimiter};
+pub use readiness::Readiness;
+pub use registry::ChainEstimatorRegistry;
+pub use replay::ReplayEstimator;
+#[cfg(feature = "tokio_")]
+pub use retry::RetryGasPriceEstimating;
+pub use rpc_percentile::RpcPercentileEstimator;
+pub use scroll::ScrollGasEstimator;
+pub use seasonal::SeasonalStats;
+pub use smoothing::EwmaEstimator;
+pub use strategy::UrgencyPolicy;
+#[cfg(feature = "tokio_")]
+pub use timeout::{TimeLimitedEstimator, TimeLimitedTransport};
+#[cfg(feature = "reqwest_")]
+pub use transport::ReqwestTransport;
+pub use transport::{PlaybackTransport, RecordingTransport};
+pub use units::{GasPriceGwei, GasPriceWei};
+pub use validate::SanitizingEstimator;
+pub use zksync::ZkSyncGasEstimator;
 
-use anyhow::Result;
-use serde::de::DeserializeOwned;
+use error::Result;
+use serde::{de::DeserializeOwned, Serialize};
 use std::time::Duration;
+use time::Instant;
 
 pub const DEFAULT_GAS_LIMIT: f64 = 21000.0;
 pub const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(30);
+// Block time of Ethereum mainnet since the merge.
+pub const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(12);
 
 #[cfg_attr(test, mockall::automock)]
 #[async_trait::async_trait]
@@ -43,6 +214,211 @@ pub trait GasPriceEstimating: Send + Sync {
         gas_limit: f64,
         time_limit: Duration,
     ) -> Result<EstimatedGasPrice>;
+    /// Like `estimate_with_limits` for a transaction described by `hints`. By default replacements
+    /// are raised to the node replacement rules and the other hints are ignored. Combinators pass
+    /// the hints to the estimators they use.
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        let price = self.estimate_with_limits(gas_limit, time_limit).await?;
+        Ok(hints.adjust(price))
+    }
+    /// Estimate the gas price for a transaction that uses <gas> to be mined within <blocks> blocks.
+    /// By default the blocks are converted into a time limit with `block_time`.
+    async fn estimate_for_blocks(&self, gas_limit: f64, blocks: u32) -> Result<EstimatedGasPrice> {
+        self.estimate_with_limits(gas_limit, self.block_time() * blocks)
+            .await
+    }
+    /// Estimate the gas price, gas limit and additional fees of a specific transaction. By default
+    /// only the limits of `tx` are used and there is no additional fee. Estimators for rollups that
+    /// charge for posting transaction data to L1 override this, decorators and combinators pass it
+    /// to the estimators they wrap so that the L1 fee is kept.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let gas_price = self
+            .estimate_with_limits(tx.gas_limit, tx.time_limit)
+            .await?;
+        Ok(TransactionCost {
+            gas_price,
+            gas_limit: tx.gas_limit,
+            l1_fee: 0.0,
+        })
+    }
+    /// Average time between blocks of the chain the estimator is used for. Decorators report the
+    /// block time of the estimator they wrap.
+    fn block_time(&self) -> Duration {
+        DEFAULT_BLOCK_TIME
+    }
+    /// Name of the gas price source reported in `EstimateWithMetadata`. Decorators report the
+    /// source of the estimator they wrap.
+    fn source(&self) -> &'static str {
+        "unknown"
+    }
+    /// Like `estimate_with_limits` but also reports where the estimate came from. Combinators
+    /// report the metadata of the estimate they picked and decorators the metadata of the
+    /// estimator they wrap.
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let price = self.estimate_with_limits(gas_limit, time_limit).await?;
+        Ok(EstimateWithMetadata::new(
+            price,
+            self.source(),
+            Instant::now(),
+        ))
+    }
+    /// Estimates for all urgencies of `GasPriceSchedule` with the default gas limit. Sources that
+    /// report all urgencies in one response override this so that only one request is made.
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let estimate = |time_limit| self.estimate_with_limits(DEFAULT_GAS_LIMIT, time_limit);
+        let (slow, standard, fast, instant) = futures::try_join!(
+            estimate(GasPriceSchedule::SLOW),
+            estimate(GasPriceSchedule::STANDARD),
+            estimate(GasPriceSchedule::FAST),
+            estimate(GasPriceSchedule::INSTANT),
+        )?;
+        Ok(GasPriceSchedule {
+            slow,
+            standard,
+            fast,
+            instant,
+        })
+    }
+    /// Estimates for several (gas limit, time limit) pairs in the order of `requests`. Sources
+    /// whose response contains the whole fee curve override this so that only one request is
+    /// made.
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        futures::future::try_join_all(
+            requests
+                .iter()
+                .map(|(gas_limit, time_limit)| self.estimate_with_limits(*gas_limit, *time_limit)),
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating + ?Sized> GasPriceEstimating for Box<T> {
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.as_ref().estimate().await
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.as_ref()
+            .estimate_with_limits(gas_limit, time_limit)
+            .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.as_ref()
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await
+    }
+
+    async fn estimate_for_blocks(&self, gas_limit: f64, blocks: u32) -> Result<EstimatedGasPrice> {
+        self.as_ref().estimate_for_blocks(gas_limit, blocks).await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.as_ref().estimate_for_tx(tx).await
+    }
+
+    fn block_time(&self) -> Duration {
+        self.as_ref().block_time()
+    }
+
+    fn source(&self) -> &'static str {
+        self.as_ref().source()
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.as_ref().estimate_verbose(gas_limit, time_limit).await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.as_ref().estimate_schedule().await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.as_ref().estimate_many(requests).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating + ?Sized> GasPriceEstimating for std::sync::Arc<T> {
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.as_ref().estimate().await
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.as_ref()
+            .estimate_with_limits(gas_limit, time_limit)
+            .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.as_ref()
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await
+    }
+
+    async fn estimate_for_blocks(&self, gas_limit: f64, blocks: u32) -> Result<EstimatedGasPrice> {
+        self.as_ref().estimate_for_blocks(gas_limit, blocks).await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.as_ref().estimate_for_tx(tx).await
+    }
+
+    fn block_time(&self) -> Duration {
+        self.as_ref().block_time()
+    }
+
+    fn source(&self) -> &'static str {
+        self.as_ref().source()
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.as_ref().estimate_verbose(gas_limit, time_limit).await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.as_ref().estimate_schedule().await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.as_ref().estimate_many(requests).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -52,6 +428,62 @@ pub trait Transport: Send + Sync {
         url: &str,
         header: http::header::HeaderMap,
     ) -> Result<T>;
+
+    /// Sends `body` as json in a POST request and decodes the json response. Needed by estimators
+    /// that talk to JSON-RPC nodes or aggregator APIs. Transports that only support GET requests
+    /// can keep the default implementation which returns `GasEstimationError::Unsupported`.
+    async fn post_json<Req, Resp>(
+        &self,
+        url: &str,
+        _header: http::header::HeaderMap,
+        _body: &Req,
+    ) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        Err(GasEstimationError::Unsupported(format!(
+            "transport does not support POST requests to {}",
+            url
+        )))
+    }
+
+    /// GET request that the server can answer with `Conditional::NotModified` if the response
+    /// has not changed since the one `validators` are from. Transports without support for
+    /// conditional requests can keep the default implementation which always makes a full
+    /// request without validators.
+    async fn get_json_conditional<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        _validators: &transport::CacheValidators,
+    ) -> Result<transport::Conditional<T>> {
+        Ok(transport::Conditional::Modified {
+            value: self.get_json(url, header).await?,
+            validators: Default::default(),
+        })
+    }
+}
+
+/// Connects to websocket endpoints for estimators that stream gas prices.
+#[async_trait::async_trait]
+pub trait WebSocketTransport: Send + Sync {
+    type Connection: WebSocketConnection;
+
+    async fn connect(&self, url: &str, header: http::header::HeaderMap)
+        -> Result<Self::Connection>;
+}
+
+/// An open websocket connection that exchanges text messages.
+#[async_trait::async_trait]
+pub trait WebSocketConnection: Send {
+    async fn send(&mut self, message: String) -> Result<()>;
+    /// Sends a ping frame. The implementation handles the pong. Does nothing by default.
+    async fn ping(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Returns `None` when the connection has been closed.
+    async fn receive(&mut self) -> Option<Result<String>>;
 }
 
 #[cfg(test)]
@@ -73,12 +505,140 @@ mod tests {
                 .get(url)
                 .headers(header)
                 .send()
-                .await?
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| GasEstimationError::Transport(err.into()))?
                 .text()
-                .await?;
+                .await
+                .map_err(|err| GasEstimationError::Transport(err.into()))?;
 
             Ok(serde_json::from_str(&json)?)
         }
+
+        async fn post_json<Req, Resp>(
+            &self,
+            url: &str,
+            header: http::header::HeaderMap,
+            body: &Req,
+        ) -> Result<Resp>
+        where
+            Req: Serialize + Send + Sync,
+            Resp: DeserializeOwned,
+        {
+            let json = reqwest::Client::new()
+                .post(url)
+                .headers(header)
+                .json(body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| GasEstimationError::Transport(err.into()))?
+                .text()
+                .await
+                .map_err(|err| GasEstimationError::Transport(err.into()))?;
+
+            Ok(serde_json::from_str(&json)?)
+        }
+    }
+
+    struct GetOnlyTransport;
+
+    #[async_trait::async_trait]
+    impl Transport for GetOnlyTransport {
+        async fn get_json<T: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<T> {
+            Ok(serde_json::from_str("null")?)
+        }
+    }
+
+    struct Fixed;
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for Fixed {
+        fn source(&self) -> &'static str {
+            "fixed"
+        }
+
+        async fn estimate_with_limits(
+            &self,
+            gas_limit: f64,
+            _time_limit: Duration,
+        ) -> Result<EstimatedGasPrice> {
+            Ok(EstimatedGasPrice {
+                legacy: gas_limit,
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn default_estimate_verbose() {
+        let before = Instant::now();
+        let result = Fixed.estimate_verbose(1.0, Duration::ZERO).wait().unwrap();
+        assert_eq!(result.price.legacy, 1.0);
+        assert_eq!(result.source, "fixed");
+        assert!(result.observed_at >= before);
+        assert_eq!(result.confidence, None);
+    }
+
+    // Gas price is the inverse of the time limit in seconds.
+    struct Inverse;
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for Inverse {
+        async fn estimate_with_limits(
+            &self,
+            _gas_limit: f64,
+            time_limit: Duration,
+        ) -> Result<EstimatedGasPrice> {
+            Ok(EstimatedGasPrice {
+                legacy: 1.0 / time_limit.as_secs_f64(),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn default_estimate_schedule() {
+        let schedule = Inverse.estimate_schedule().wait().unwrap();
+        assert_eq!(schedule.slow.legacy, 1.0 / 600.0);
+        assert_eq!(schedule.standard.legacy, 1.0 / 300.0);
+        assert_eq!(schedule.fast.legacy, 1.0 / 60.0);
+        assert_eq!(schedule.instant.legacy, 1.0 / 15.0);
+    }
+
+    #[test]
+    fn default_estimate_many() {
+        let requests = [
+            (21000.0, Duration::from_secs(10)),
+            (100_000.0, Duration::from_secs(20)),
+        ];
+        let estimates = Inverse.estimate_many(&requests).wait().unwrap();
+        let legacy = estimates.iter().map(|estimate| estimate.legacy);
+        assert_eq!(legacy.collect::<Vec<_>>(), vec![0.1, 0.05]);
+        assert!(Inverse.estimate_many(&[]).wait().unwrap().is_empty());
+    }
+
+    #[test]
+    fn default_estimate_for_blocks() {
+        let estimate = Inverse.estimate_for_blocks(0.0, 5).wait().unwrap();
+        assert_eq!(estimate.legacy, 1.0 / 60.0);
+        let estimate = Box::new(Inverse)
+            .estimate_for_blocks(0.0, 1)
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.legacy, 1.0 / 12.0);
+    }
+
+    #[test]
+    fn post_is_unsupported_by_default() {
+        let result = GetOnlyTransport
+            .post_json::<_, ()>("", Default::default(), &())
+            .wait();
+        assert!(matches!(result, Err(GasEstimationError::Unsupported(_))));
     }
 
     pub trait FutureWaitExt: Future + Sized {
@@ -0,0 +1,247 @@
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasPrice1559,
+    GasPriceEstimating, GasPriceSchedule, GasPriceWei, TransactionCost, TxSpec,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Limits applied by `ClampedEstimator`. Clones share the values so that they can be changed at
+/// runtime, for example from a config reload, while the estimator is in use.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    // f64 bits
+    max_fee_per_gas: Arc<AtomicU64>,
+    min_priority_fee: Arc<AtomicU64>,
+}
+
+impl Default for Limits {
+    // No limits.
+    fn default() -> Self {
+        Self::new(f64::INFINITY, 0.0)
+    }
+}
+
+impl Limits {
+    pub fn new(max_fee_per_gas: f64, min_priority_fee: f64) -> Self {
+        Self {
+            max_fee_per_gas: Arc::new(AtomicU64::new(max_fee_per_gas.to_bits())),
+            min_priority_fee: Arc::new(AtomicU64::new(min_priority_fee.to_bits())),
+        }
+    }
+
+    pub fn max_fee_per_gas(&self) -> f64 {
+        f64::from_bits(self.max_fee_per_gas.load(Ordering::Relaxed))
+    }
+
+    pub fn set_max_fee_per_gas(&self, max_fee_per_gas: f64) {
+        self.max_fee_per_gas
+            .store(max_fee_per_gas.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn min_priority_fee(&self) -> f64 {
+        f64::from_bits(self.min_priority_fee.load(Ordering::Relaxed))
+    }
+
+    pub fn set_min_priority_fee(&self, min_priority_fee: f64) {
+        self.min_priority_fee
+            .store(min_priority_fee.to_bits(), Ordering::Relaxed);
+    }
+
+    // The priority fee is raised to the floor first, max_fee_per_gas is raised by the same amount
+    // so that the headroom for base fee increases is kept. Then the cap is applied, which wins
+    // over the floor. Legacy gas prices are only capped.
+    pub fn apply(&self, estimate: EstimatedGasPrice) -> EstimatedGasPrice {
+        let min_priority_fee = GasPriceWei(self.min_priority_fee());
+        let floored = EstimatedGasPrice {
+            eip1559: estimate.eip1559.map(|x| {
+                let max_priority_fee_per_gas = x.max_priority_fee_per_gas.max(min_priority_fee);
+                GasPrice1559 {
+                    max_fee_per_gas: x.max_fee_per_gas + max_priority_fee_per_gas
+                        - x.max_priority_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    ..x
+                }
+            }),
+            ..estimate
+        };
+        floored.limit_cap(self.max_fee_per_gas())
+    }
+}
+
+// Applies an absolute maximum fee per gas and a minimum priority fee to the estimates of the inner
+// estimator.
+pub struct ClampedEstimator<T> {
+    inner: T,
+    limits: Limits,
+}
+
+impl<T: GasPriceEstimating> ClampedEstimator<T> {
+    pub fn new(inner: T, limits: Limits) -> Self {
+        Self { inner, limits }
+    }
+
+    // Handle to change the limits.
+    pub fn limits(&self) -> Limits {
+        self.limits.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for ClampedEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_limits(gas_limit, time_limit)
+            .await?;
+        Ok(self.limits.apply(estimate))
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        let estimate = self.inner.estimate().await?;
+        Ok(self.limits.apply(estimate))
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let estimate = self.inner.estimate_verbose(gas_limit, time_limit).await?;
+        Ok(estimate.map_prices(|price| self.limits.apply(price)))
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await?;
+        Ok(self.limits.apply(estimate))
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let cost = self.inner.estimate_for_tx(tx).await?;
+        Ok(TransactionCost {
+            gas_price: self.limits.apply(cost.gas_price),
+            ..cost
+        })
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let schedule = self.inner.estimate_schedule().await?;
+        Ok(schedule.map(|_, estimate| self.limits.apply(estimate)))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let estimates = self.inner.estimate_many(requests).await?;
+        Ok(estimates
+            .into_iter()
+            .map(|estimate| self.limits.apply(estimate))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+
+    fn eip1559(base_fee: f64, max_fee: f64, priority_fee: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy: max_fee,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee),
+                max_fee_per_gas: GasPriceWei(max_fee),
+                max_priority_fee_per_gas: GasPriceWei(priority_fee),
+            }),
+        }
+    }
+
+    #[test]
+    fn default_does_not_change_estimates() {
+        let estimate = eip1559(10.0, 30.0, 2.0);
+        assert_eq!(Limits::default().apply(estimate), estimate);
+    }
+
+    #[test]
+    fn raises_priority_fee_to_floor() {
+        let limits = Limits::new(f64::INFINITY, 5.0);
+        assert_eq!(
+            limits.apply(eip1559(10.0, 30.0, 2.0)),
+            EstimatedGasPrice {
+                legacy: 30.0,
+                ..eip1559(10.0, 33.0, 5.0)
+            }
+        );
+        // legacy prices are not raised
+        let legacy = EstimatedGasPrice {
+            legacy: 1.0,
+            eip1559: None,
+        };
+        assert_eq!(limits.apply(legacy), legacy);
+    }
+
+    #[test]
+    fn cap_wins_over_floor() {
+        let limits = Limits::new(20.0, 25.0);
+        assert_eq!(
+            limits.apply(eip1559(10.0, 30.0, 2.0)),
+            eip1559(10.0, 20.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn limits_can_be_changed_at_runtime() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(2)
+            .returning(|| Ok(eip1559(10.0, 30.0, 2.0)));
+        let estimator = ClampedEstimator::new(inner, Limits::default());
+        assert_eq!(estimator.estimate().wait().unwrap().cap(), 30.0);
+        estimator.limits().set_max_fee_per_gas(25.0);
+        assert_eq!(estimator.estimate().wait().unwrap().cap(), 25.0);
+    }
+
+    #[test]
+    fn passes_hints() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate_with_hints()
+            .withf(|_, _, hints| hints.is_private())
+            .times(1)
+            .returning(|_, _, _| Ok(eip1559(10.0, 30.0, 2.0)));
+        let estimator = ClampedEstimator::new(inner, Limits::new(25.0, 0.0));
+        let estimate = estimator
+            .estimate_with_hints(
+                21000.0,
+                Duration::from_secs(30),
+                &EstimationHints::private(),
+            )
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.cap(), 25.0);
+    }
+}
@@ -0,0 +1,188 @@
+//! Linea gas estimation.
+//!
+//! Linea has no separate L1 data fee. Instead the priority fee a transaction needs depends on its
+//! calldata because the sequencer orders transactions by their profitability, including the cost
+//! of posting their data to L1. `linea_estimateGas` returns the priority fee for a specific
+//! transaction. Documentation at
+//! https://docs.linea.build/get-started/how-to/gas-fees .
+
+use super::{
+    chain,
+    cost::{TransactionCost, TxSpec},
+    error::Result,
+    json_rpc::{self, to_hex},
+    op_stack::{self, incompressible_calldata, Params},
+    EstimatedGasPrice, GasPriceEstimating, Transport,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Estimate for a specific Linea transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LineaGasEstimate {
+    // Includes the priority fee the transaction's calldata requires.
+    pub gas_price: EstimatedGasPrice,
+    pub gas_limit: f64,
+}
+
+impl LineaGasEstimate {
+    pub fn total_fee(&self) -> f64 {
+        self.gas_limit * self.gas_price.effective_gas_price()
+    }
+}
+
+// Quantities are hex encoded.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    gas_limit: String,
+    base_fee_per_gas: String,
+    priority_fee_per_gas: String,
+}
+
+/// Estimates gas prices through a Linea node's JSON-RPC api. Requires a transport that supports
+/// `Transport::post_json`.
+pub struct LineaGasEstimator<T> {
+    transport: T,
+    node_url: String,
+    params: Params,
+}
+
+impl<T: Transport> LineaGasEstimator<T> {
+    pub fn new(transport: T, node_url: String, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    /// Estimate the gas limit and gas price for sending `data` from `from` to `to`.
+    pub async fn estimate_gas(
+        &self,
+        from: [u8; 20],
+        to: [u8; 20],
+        data: &[u8],
+    ) -> Result<LineaGasEstimate> {
+        let response: Response = json_rpc::call(
+            &self.transport,
+            &self.node_url,
+            "linea_estimateGas",
+            json!([{ "from": to_hex(&from), "to": to_hex(&to), "data": to_hex(data) }]),
+        )
+        .await
+        .map_err(|err| err.context("failed to call linea_estimateGas"))?;
+        Ok(LineaGasEstimate {
+            gas_price: op_stack::estimate(
+                json_rpc::quantity_to_f64(&response.base_fee_per_gas)?,
+                json_rpc::quantity_to_f64(&response.priority_fee_per_gas)?,
+                &self.params,
+            )?,
+            gas_limit: json_rpc::quantity_to_f64(&response.gas_limit)?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for LineaGasEstimator<T> {
+    fn source(&self) -> &'static str {
+        "linea"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::LINEA.block_time
+    }
+
+    // The price of a transaction without calldata. Transactions with calldata need a higher
+    // priority fee, use `estimate_for_tx` or `estimate_gas` for them.
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        Ok(self.estimate_gas([0; 20], [0; 20], &[]).await?.gas_price)
+    }
+
+    // The price is estimated for a call of the zero address with calldata of the same size
+    // because only the size of the transaction data is known.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let estimate = self
+            .estimate_gas([0; 20], [0; 20], &incompressible_calldata(tx.data_len()))
+            .await?;
+        Ok(TransactionCost {
+            gas_price: estimate.gas_price,
+            gas_limit: tx.gas_limit,
+            l1_fee: 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+
+    #[test]
+    fn estimates_through_node() {
+        let node = FakeNode::default().with_result(
+            "linea_estimateGas",
+            json!({
+                "gasLimit": "0x5208",
+                "baseFeePerGas": "0x7",
+                "priorityFeePerGas": "0x3b9aca00",
+            }),
+        );
+        let estimator = LineaGasEstimator::new(node, String::new(), None);
+        let estimate = estimator
+            .estimate_gas([0; 20], [0; 20], &[])
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.gas_limit, 21000.0);
+        let eip1559 = estimate.gas_price.eip1559.unwrap();
+        assert_eq!(eip1559.base_fee_per_gas.0, 7.0);
+        assert_eq!(eip1559.max_fee_per_gas.0, 14.0 + 1e9);
+        assert_eq!(eip1559.max_priority_fee_per_gas.0, 1e9);
+        assert_eq!(estimate.total_fee(), 21000.0 * (7.0 + 1e9));
+
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                gas_limit: 100_000.0,
+                calldata_len: 100,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.gas_limit, 100_000.0);
+        assert_eq!(cost.expected_cost(), 100_000.0 * (7.0 + 1e9));
+    }
+
+    #[test]
+    fn fails_on_invalid_quantity() {
+        let node = FakeNode::default().with_result(
+            "linea_estimateGas",
+            json!({ "gasLimit": "0x5208", "baseFeePerGas": "0x", "priorityFeePerGas": "0x1" }),
+        );
+        let estimator = LineaGasEstimator::new(node, String::new(), None);
+        assert!(estimator.estimate().wait().is_err());
+    }
+
+    // LINEA_NODE_URL=... cargo test linea -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = LineaGasEstimator::new(
+            TestTransport::default(),
+            std::env::var("LINEA_NODE_URL").unwrap(),
+            None,
+        );
+        println!("{:?}", estimator.estimate().await);
+        println!(
+            "{:?}",
+            estimator
+                .estimate_gas([0x11; 20], [0x22; 20], &[0xff; 1000])
+                .await
+        );
+    }
+}
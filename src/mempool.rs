@@ -0,0 +1,273 @@
+//! Priority fees from the tips of pending transactions, as published by mempool based apis like
+//! the one of ultrasound.money.
+//!
+//! Block based oracles only see tips of transactions that were already included, so during a
+//! sudden congestion spike they lag behind by several blocks. The tips currently offered in the
+//! mempool react immediately, which makes them better for short time limits.
+//!
+//! The api is expected to return the base fee of the next block and percentiles of the tips of
+//! pending transactions in wei:
+//! `{"baseFeePerGas": 1e10, "tipPercentiles": {"10": 1e8, "50": 1e9, "90": 5e9}}`.
+//! Percentile keys can be prefixed with `p`, for example `"p50"`, and numbers can be strings.
+
+use super::{
+    auth, error::Result, interpolation, ApiCredentials, EstimatedGasPrice, EstimationHints,
+    GasEstimationError, GasPrice1559, GasPriceEstimating, GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+use std::{collections::BTreeMap, convert::TryInto, time::Duration};
+
+pub const DEFAULT_URL: &str = "https://ultrasound.money/api/v2/fees/mempool-tips";
+
+/// Parameters for the mempool estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // percentile of the pending tips used for each time limit, sorted by ascending time limit
+    pub buckets: Vec<(Duration, f64)>,
+    // a coefficient to multiply base_fee_per_gas with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            buckets: vec![
+                (Duration::from_secs(12), 90.0),
+                (Duration::from_secs(36), 50.0),
+                (Duration::from_secs(120), 25.0),
+                (Duration::from_secs(600), 10.0),
+            ],
+            base_fee_multiplier: 2.0,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    #[serde(alias = "base_fee_per_gas", alias = "baseFee")]
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub base_fee_per_gas: f64,
+    #[serde(alias = "tip_percentiles")]
+    #[serde_as(as = "BTreeMap<_, PickFirst<(_, DisplayFromStr)>>")]
+    pub tip_percentiles: BTreeMap<String, f64>,
+}
+
+impl Response {
+    // (percentile, tip) sorted by percentile.
+    fn tip_points(&self) -> Result<Vec<(f64, f64)>> {
+        let mut points = self
+            .tip_percentiles
+            .iter()
+            .map(|(percentile, tip)| {
+                let percentile = percentile
+                    .trim_start_matches('p')
+                    .parse::<f64>()
+                    .map_err(|err| anyhow!(err).context("invalid tip percentile"))?;
+                Ok((percentile, *tip))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(points)
+    }
+}
+
+pub struct MempoolTipEstimator<T> {
+    transport: T,
+    url: String,
+    credentials: Option<ApiCredentials>,
+    params: Params,
+}
+
+impl<T: Transport> MempoolTipEstimator<T> {
+    pub fn new(transport: T, params: Option<Params>) -> Self {
+        Self::with_url(transport, DEFAULT_URL, params)
+    }
+
+    /// For other apis that report pending tips in the same format.
+    pub fn with_url(transport: T, url: impl Into<String>, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            url: url.into(),
+            credentials: None,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    /// Credentials sent with every request, for example for a paid tier.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    pub async fn pending_tips(&self) -> Result<Response> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.url, Default::default())?;
+        self.transport
+            .get_json(&url, header)
+            .await
+            .map_err(|err| err.context("failed to get mempool tips"))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for MempoolTipEstimator<T> {
+    fn source(&self) -> &'static str {
+        "mempool"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let response = self.pending_tips().await?;
+        estimate(&response, time_limit, &self.params)
+    }
+
+    // Private transactions don't compete with the pending tips so this estimator does not apply to
+    // them. In a `PriorityGasPriceEstimating` the next estimator is used instead.
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        if hints.is_private() {
+            return Err(GasEstimationError::Unsupported(
+                "mempool tips are not relevant for private transactions".into(),
+            ));
+        }
+        let price = self.estimate_with_limits(gas_limit, time_limit).await?;
+        Ok(hints.adjust(price))
+    }
+}
+
+fn estimate(
+    response: &Response,
+    time_limit: Duration,
+    params: &Params,
+) -> Result<EstimatedGasPrice> {
+    let tips = response.tip_points()?;
+    let tips = tips
+        .as_slice()
+        .try_into()
+        .map_err(|err: anyhow::Error| GasEstimationError::Decode(err.context("no pending tips")))?;
+    let points = params
+        .buckets
+        .iter()
+        .map(|(time, percentile)| {
+            (
+                time.as_secs_f64(),
+                interpolation::interpolate(*percentile, tips),
+            )
+        })
+        .collect::<Vec<_>>();
+    let max_priority_fee_per_gas =
+        interpolation::interpolate(time_limit.as_secs_f64(), points.as_slice().try_into()?);
+    let max_fee_per_gas =
+        response.base_fee_per_gas * params.base_fee_multiplier + max_priority_fee_per_gas;
+    EstimatedGasPrice {
+        legacy: max_fee_per_gas,
+        eip1559: Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(response.base_fee_per_gas),
+            max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+            max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+        }),
+    }
+    .validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn response() -> Response {
+        serde_json::from_str(
+            r#"{
+                "baseFeePerGas": "10000000000",
+                "tipPercentiles": {"p10": 1e8, "p50": "1000000000", "p90": 5e9, "p25": 5e8}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn deserialize() {
+        let response = response();
+        assert_eq!(response.base_fee_per_gas, 1e10);
+        assert_eq!(
+            response.tip_points().unwrap(),
+            vec![(10.0, 1e8), (25.0, 5e8), (50.0, 1e9), (90.0, 5e9)]
+        );
+        let snake_case: Response =
+            serde_json::from_str(r#"{"base_fee_per_gas": 1, "tip_percentiles": {"50": 2}}"#)
+                .unwrap();
+        assert_eq!(snake_case.tip_points().unwrap(), vec![(50.0, 2.0)]);
+    }
+
+    #[test]
+    fn interpolates_percentiles() {
+        let params = Params::default();
+        let fast = estimate(&response(), Duration::from_secs(12), &params).unwrap();
+        assert_approx_eq!(fast.tip(), 5e9);
+        assert_approx_eq!(fast.cap(), 2e10 + 5e9);
+        assert_approx_eq!(fast.legacy, 2.5e10);
+        let standard = estimate(&response(), Duration::from_secs(36), &params).unwrap();
+        assert_approx_eq!(standard.tip(), 1e9);
+        // between the 50th and 25th percentile
+        let slower = estimate(&response(), Duration::from_secs(78), &params).unwrap();
+        assert_approx_eq!(slower.tip(), 7.5e8);
+    }
+
+    #[test]
+    fn does_not_estimate_private_transactions() {
+        struct NoTransport;
+        #[async_trait::async_trait]
+        impl Transport for NoTransport {
+            async fn get_json<T: serde::de::DeserializeOwned>(
+                &self,
+                url: &str,
+                _header: http::header::HeaderMap,
+            ) -> Result<T> {
+                Err(GasEstimationError::Transport(anyhow!(
+                    "no request to {}",
+                    url
+                )))
+            }
+        }
+        let estimator = MempoolTipEstimator::new(NoTransport, None);
+        let estimate = |hints| {
+            estimator
+                .estimate_with_hints(21000.0, Duration::from_secs(12), &hints)
+                .wait()
+        };
+        assert!(matches!(
+            estimate(EstimationHints::batch_auction_settlement()),
+            Err(GasEstimationError::Unsupported(_))
+        ));
+        assert!(matches!(
+            estimate(EstimationHints::default()),
+            Err(GasEstimationError::Transport(_))
+        ));
+    }
+
+    #[test]
+    fn fails_without_tips() {
+        let response = Response {
+            base_fee_per_gas: 1e10,
+            ..Default::default()
+        };
+        assert!(matches!(
+            estimate(&response, Duration::from_secs(12), &Default::default()),
+            Err(GasEstimationError::Decode(_))
+        ));
+    }
+}
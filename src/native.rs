@@ -0,0 +1,204 @@
+//! Pull based EIP-1559 gas price estimator using the reward percentiles returned by
+//! `eth_feeHistory`.
+//!
+//! Unlike `nativegasestimator::NativeGasEstimator` this does not run a background task. Every
+//! estimate is a single `eth_feeHistory` call to the node and is estimated from the reward
+//! percentiles like `rpc_percentile::RpcPercentileEstimator` does.
+
+use super::{
+    chain::{self, ChainConfig},
+    error::Result,
+    rpc_percentile::{self, DecodedFeeHistory},
+    EstimatedGasPrice, FeeHistoryConfig, GasEstimationError, GasPriceEstimating,
+};
+use anyhow::Context;
+use std::time::Duration;
+use web3::{
+    types::{BlockNumber, FeeHistory},
+    Transport, Web3,
+};
+
+/// Parameters for the fee history estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // sampled blocks and the reward percentile used for each time limit
+    pub fee_history: FeeHistoryConfig,
+    // a coefficient to multiply base_fee_per_gas with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+    // priority fee offered when there are no recent transactions
+    pub fallback_priority_fee: f64,
+    // lowest priority fee that is estimated
+    pub min_priority_fee: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            fee_history: Default::default(),
+            base_fee_multiplier: 2.0,
+            fallback_priority_fee: 2e9,
+            min_priority_fee: chain::ETHEREUM.min_priority_fee,
+        }
+    }
+}
+
+pub struct FeeHistoryGasEstimator<T: Transport> {
+    web3: Web3<T>,
+    params: Params,
+}
+
+impl<T: Transport> FeeHistoryGasEstimator<T> {
+    pub fn new(transport: T, params: Option<Params>) -> Self {
+        Self {
+            web3: Web3::new(transport),
+            params: params.unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> GasPriceEstimating for FeeHistoryGasEstimator<T>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    fn source(&self) -> &'static str {
+        "fee_history"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let fee_history = self
+            .web3
+            .eth()
+            .fee_history(
+                self.params.fee_history.blocks.into(),
+                BlockNumber::Latest,
+                Some(self.params.fee_history.reward_percentiles()),
+            )
+            .await
+            .context("failed to get fee history")
+            .map_err(GasEstimationError::Transport)?;
+        estimate_with_limits(&fee_history, time_limit, &self.params)
+    }
+}
+
+// The estimation of `RpcPercentileEstimator` on Ethereum with the minimum priority fee of the
+// params.
+fn estimate_with_limits(
+    fee_history: &FeeHistory,
+    time_limit: Duration,
+    params: &Params,
+) -> Result<EstimatedGasPrice> {
+    let chain = ChainConfig {
+        min_priority_fee: params.min_priority_fee,
+        ..chain::ETHEREUM
+    };
+    let params = rpc_percentile::Params {
+        fee_history: params.fee_history.clone(),
+        base_fee_multiplier: params.base_fee_multiplier,
+        fallback_priority_fee: params.fallback_priority_fee,
+    };
+    rpc_percentile::estimate_with_limits(
+        &DecodedFeeHistory::from_web3(fee_history),
+        time_limit,
+        &chain,
+        &params,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn fee_history() -> FeeHistory {
+        FeeHistory {
+            oldest_block: BlockNumber::Number(100.into()),
+            base_fee_per_gas: vec![90.into(), 95.into(), 100.into()],
+            gas_used_ratio: vec![0.5, 0.0],
+            reward: Some(vec![
+                vec![1.into(), 2.into(), 3.into(), 4.into()],
+                vec![0.into(), 0.into(), 0.into(), 0.into()],
+            ]),
+        }
+    }
+
+    #[test]
+    fn fallback_without_transactions() {
+        let params = Params::default();
+        let fee_history = FeeHistory {
+            gas_used_ratio: vec![0.0, 0.0],
+            ..fee_history()
+        };
+        let price = estimate_with_limits(&fee_history, Duration::from_secs(15), &params).unwrap();
+        assert_approx_eq!(
+            price.eip1559.unwrap().max_priority_fee_per_gas.0,
+            params.fallback_priority_fee
+        );
+    }
+
+    #[test]
+    fn estimate_maps_time_limit_to_percentile() {
+        let params = Params {
+            min_priority_fee: 0.0,
+            ..Default::default()
+        };
+        let fast = estimate_with_limits(&fee_history(), Duration::from_secs(15), &params)
+            .unwrap()
+            .eip1559
+            .unwrap();
+        assert_approx_eq!(fast.base_fee_per_gas.0, 100.0);
+        assert_approx_eq!(fast.max_priority_fee_per_gas.0, 4.0);
+        assert_approx_eq!(fast.max_fee_per_gas.0, 204.0);
+
+        let slow = estimate_with_limits(&fee_history(), Duration::from_secs(600), &params)
+            .unwrap()
+            .eip1559
+            .unwrap();
+        assert_approx_eq!(slow.max_priority_fee_per_gas.0, 1.0);
+        assert_approx_eq!(slow.max_fee_per_gas.0, 201.0);
+
+        // raised to the default minimum
+        let slow = estimate_with_limits(
+            &fee_history(),
+            Duration::from_secs(600),
+            &Default::default(),
+        )
+        .unwrap()
+        .eip1559
+        .unwrap();
+        assert_approx_eq!(slow.max_priority_fee_per_gas.0, 1e9);
+    }
+
+    #[test]
+    fn estimate_fails_without_rewards() {
+        let fee_history = FeeHistory {
+            reward: None,
+            ..fee_history()
+        };
+        assert!(
+            estimate_with_limits(&fee_history, Duration::from_secs(15), &Default::default())
+                .is_err()
+        );
+    }
+
+    // NODE_URL=... cargo test native:: --features web3_ -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let transport = web3::transports::Http::new(&std::env::var("NODE_URL").unwrap()).unwrap();
+        let estimator = FeeHistoryGasEstimator::new(transport, None);
+        for i in 0..10 {
+            let time_limit = Duration::from_secs(i * 60);
+            let price = estimator
+                .estimate_with_limits(0.0, time_limit)
+                .await
+                .unwrap();
+            println!("gas price estimate for {} seconds: {:?}", i * 60, price);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 //! Native gas price estimator based on the https://github.com/zsfelfoldi/feehistory/blob/main/docs/feeOracle.md
 
-use super::{linear_interpolation, EstimatedGasPrice, GasPrice1559, GasPriceEstimating};
-use anyhow::{anyhow, ensure, Result};
+use super::{
+    chain, error::Result, interpolation, runtime, task::BackgroundTask, EstimatedGasPrice,
+    GasPrice1559, GasPriceEstimating, GasPriceWei, Readiness,
+};
+use anyhow::{anyhow, ensure};
 use std::{
     convert::TryInto,
     f64::consts::{E, PI},
@@ -9,7 +12,6 @@ use std::{
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use tokio::task::{self, JoinHandle};
 use web3::{
     types::{BlockNumber, U256},
     Transport,
@@ -41,6 +43,8 @@ pub struct Params {
     pub extra_priority_fee_boost: f64,
     // priority fee offered when there are no recent transactions
     pub fallback_priority_fee: f64,
+    // lowest priority fee that is suggested
+    pub min_priority_fee: f64,
     // a coefficient to multiply base_fee_per_gas with, in order to increase chances of transaction inclusion
     pub bump_cap_coefficient: f64,
     // number of blocks to consider for fee history calculation
@@ -59,6 +63,7 @@ impl Default for Params {
             extra_priority_fee_ratio: 0.25,
             extra_priority_fee_boost: 1559.0,
             fallback_priority_fee: 2e9,
+            min_priority_fee: chain::ETHEREUM.min_priority_fee,
             bump_cap_coefficient: 2.0,
             fee_history_blocks: 300,
         }
@@ -85,12 +90,11 @@ impl Default for CachedResponse {
 
 pub struct NativeGasEstimator {
     cached_response: Arc<Mutex<CachedResponse>>,
-    handle: JoinHandle<()>,
+    task: BackgroundTask,
 }
 
 impl Drop for NativeGasEstimator {
     fn drop(&mut self) {
-        self.handle.abort();
         self.cached_response = Default::default();
     }
 }
@@ -131,14 +135,16 @@ impl NativeGasEstimator {
             }
             Err(err) => {
                 tracing::warn!(?err, "failed to calculate initial fees");
-                return Err(anyhow!("failed to calculate initial fees"));
+                return Err(anyhow!("failed to calculate initial fees").into());
             }
         }
 
         //spawn task for updating the cached response every RATE_LIMIT seconds
-        let handle = task::spawn(async move {
+        let runtime = runtime::default_runtime();
+        let task_runtime = runtime.clone();
+        let task = BackgroundTask::spawn_on(runtime.as_ref(), async move {
             loop {
-                tokio::time::sleep(RATE_LIMIT).await;
+                task_runtime.sleep(RATE_LIMIT).await;
                 let start = Instant::now();
                 let fee = suggest_fee(transport.clone(), &params).await;
                 tracing::debug!("suggested fees in {} s", start.elapsed().as_secs_f32());
@@ -170,9 +176,15 @@ impl NativeGasEstimator {
 
         Ok(Self {
             cached_response,
-            handle,
+            task,
         })
     }
+
+    /// Stops the background task and waits until it has finished. Estimates fail once the last
+    /// calculation is stale.
+    pub async fn shutdown(&mut self) {
+        self.task.shutdown().await;
+    }
 }
 
 // suggest_fee returns fee suggestion at the latest block
@@ -182,7 +194,7 @@ impl NativeGasEstimator {
 async fn suggest_fee<T: Transport + Send + Sync>(
     transport: T,
     params: &Params,
-) -> Result<Vec<(f64, EstimatedGasPrice)>> {
+) -> anyhow::Result<Vec<(f64, EstimatedGasPrice)>> {
     let web3 = web3::Web3::new(transport.clone());
     let fee_history = web3
         .eth()
@@ -242,14 +254,16 @@ async fn suggest_fee<T: Transport + Send + Sync>(
             time_factor,
             EstimatedGasPrice {
                 eip1559: Some(GasPrice1559 {
-                    base_fee_per_gas: fee_history
-                        .base_fee_per_gas
-                        .last()
-                        .copied()
-                        .unwrap_or_default()
-                        .low_u64() as f64,
-                    max_fee_per_gas: min_base_fee + priority_fee,
-                    max_priority_fee_per_gas: priority_fee + extra_fee,
+                    base_fee_per_gas: GasPriceWei(
+                        fee_history
+                            .base_fee_per_gas
+                            .last()
+                            .copied()
+                            .unwrap_or_default()
+                            .low_u64() as f64,
+                    ),
+                    max_fee_per_gas: GasPriceWei(min_base_fee + priority_fee),
+                    max_priority_fee_per_gas: GasPriceWei(priority_fee + extra_fee),
                 }),
                 ..Default::default()
             },
@@ -266,7 +280,7 @@ async fn collect_rewards<T: Transport + Send + Sync>(
     first_block: u64,
     gas_used_ratio: Vec<f64>,
     params: &Params,
-) -> Result<Vec<u64>> {
+) -> anyhow::Result<Vec<u64>> {
     let mut percentiles = vec![];
     for i in 0..=params.max_reward_percentile {
         percentiles.push(i as f64);
@@ -322,7 +336,11 @@ async fn collect_rewards<T: Transport + Send + Sync>(
 
 // maxBlockCount returns the number of consecutive blocks suitable for priority fee suggestion (gasUsedRatio non-zero
 // and not higher than 0.9).
-fn max_block_count(gas_used_ratio: &[f64], last_index: usize, need_blocks: usize) -> Result<usize> {
+fn max_block_count(
+    gas_used_ratio: &[f64],
+    last_index: usize,
+    need_blocks: usize,
+) -> anyhow::Result<usize> {
     ensure!(
         gas_used_ratio.len() > last_index,
         "max_block_count invalid input"
@@ -338,15 +356,16 @@ fn max_block_count(gas_used_ratio: &[f64], last_index: usize, need_blocks: usize
 // suggestPriorityFee suggests a priority fee (maxPriorityFeePerGas) value that's usually sufficient for blocks that
 // are not full.
 fn suggest_priority_fee(rewards: &[u64], time_factor: f64, params: &Params) -> f64 {
-    if rewards.is_empty() {
-        return params.fallback_priority_fee;
-    }
-
-    let factor = (params.min_block_percentile
-        + (params.max_block_percentile - params.min_block_percentile) / time_factor)
-        / 100.0;
-    let index = ((rewards.len() - 1) as f64 * factor).floor() as usize;
-    rewards[index] as f64 + params.extra_priority_fee_boost
+    let priority_fee = if rewards.is_empty() {
+        params.fallback_priority_fee
+    } else {
+        let factor = (params.min_block_percentile
+            + (params.max_block_percentile - params.min_block_percentile) / time_factor)
+            / 100.0;
+        let index = ((rewards.len() - 1) as f64 * factor).floor() as usize;
+        rewards[index] as f64 + params.extra_priority_fee_boost
+    };
+    priority_fee.max(params.min_priority_fee)
 }
 
 // predictMinBaseFee calculates an average of base fees in the sampleMinPercentile to sampleMaxPercentile percentile
@@ -393,6 +412,10 @@ fn sampling_curve(percentile: f64, params: &Params) -> f64 {
 
 #[async_trait::async_trait]
 impl GasPriceEstimating for NativeGasEstimator {
+    fn source(&self) -> &'static str {
+        "native"
+    }
+
     async fn estimate_with_limits(
         &self,
         _gas_limit: f64,
@@ -404,17 +427,29 @@ impl GasPriceEstimating for NativeGasEstimator {
     }
 }
 
-fn estimate_with_limits(
-    time_limit: Duration,
-    cached_response: CachedResponse,
-) -> Result<EstimatedGasPrice> {
+#[async_trait::async_trait]
+impl Readiness for NativeGasEstimator {
+    async fn ready(&self) -> bool {
+        check_cached_response(&self.cached_response.lock().unwrap()).is_ok()
+    }
+}
+
+fn check_cached_response(cached_response: &CachedResponse) -> Result<()> {
     if Instant::now().saturating_duration_since(cached_response.time) > CACHED_RESPONSE_VALIDITY {
-        return Err(anyhow!("cached response is stale"));
+        return Err(anyhow!("cached response is stale").into());
     }
 
     if cached_response.data.is_empty() {
-        return Err(anyhow!("no cached data exist"));
+        return Err(anyhow!("no cached data exist").into());
     }
+    Ok(())
+}
+
+fn estimate_with_limits(
+    time_limit: Duration,
+    cached_response: CachedResponse,
+) -> Result<EstimatedGasPrice> {
+    check_cached_response(&cached_response)?;
 
     let max_fee_per_gas_points = cached_response
         .data
@@ -422,7 +457,7 @@ fn estimate_with_limits(
         .map(|(time_limit, gas_price)| {
             (
                 *time_limit,
-                gas_price.eip1559.unwrap_or_default().max_fee_per_gas,
+                gas_price.eip1559.unwrap_or_default().max_fee_per_gas.0,
             )
         })
         .collect::<Vec<(f64, f64)>>();
@@ -435,7 +470,8 @@ fn estimate_with_limits(
                 gas_price
                     .eip1559
                     .unwrap_or_default()
-                    .max_priority_fee_per_gas,
+                    .max_priority_fee_per_gas
+                    .0,
             )
         })
         .collect::<Vec<(f64, f64)>>();
@@ -445,19 +481,19 @@ fn estimate_with_limits(
     {
         eip1559.base_fee_per_gas
     } else {
-        return Err(anyhow!("no eip1559 estimate exist"));
+        return Err(anyhow!("no eip1559 estimate exist").into());
     };
 
     EstimatedGasPrice {
         eip1559: Some(GasPrice1559 {
-            max_fee_per_gas: linear_interpolation::interpolate(
+            max_fee_per_gas: GasPriceWei(interpolation::interpolate(
                 time_limit.as_secs_f64(),
                 max_fee_per_gas_points.as_slice().try_into()?,
-            ),
-            max_priority_fee_per_gas: linear_interpolation::interpolate(
+            )),
+            max_priority_fee_per_gas: GasPriceWei(interpolation::interpolate(
                 time_limit.as_secs_f64(),
                 max_priority_fee_per_gas_points.as_slice().try_into()?,
-            ),
+            )),
             base_fee_per_gas,
         }),
         ..Default::default()
@@ -0,0 +1,271 @@
+//! 1inch gas price api `GasPriceEstimating` implementation. One api key covers all chains the api
+//! supports.
+//! Api documentation at https://portal.1inch.dev/documentation/gas-price/introduction .
+
+use super::{
+    auth, chain, error::Result, interpolation, ApiCredentials, ChainConfig, EstimatedGasPrice,
+    GasPrice1559, GasPriceEstimating, GasPriceSchedule, GasPriceWei, Transport,
+};
+use serde::Deserialize;
+use serde_with::rust::display_fromstr;
+use std::{convert::TryInto, time::Duration};
+
+pub const DEFAULT_URL: &str = "https://api.1inch.dev/gas-price/v1.4";
+
+// The api does not publish confirmation times for its tiers so they are mapped to the urgencies
+// of `GasPriceSchedule`.
+pub const INSTANT: Duration = GasPriceSchedule::INSTANT;
+pub const HIGH: Duration = GasPriceSchedule::FAST;
+pub const MEDIUM: Duration = GasPriceSchedule::STANDARD;
+pub const LOW: Duration = GasPriceSchedule::SLOW;
+
+/// Gas prices in wei. Chains with EIP-1559 get fee tiers, others legacy gas prices.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Response {
+    #[serde(rename_all = "camelCase")]
+    Eip1559 {
+        #[serde(with = "display_fromstr")]
+        base_fee: f64,
+        low: Tier,
+        medium: Tier,
+        high: Tier,
+        instant: Tier,
+    },
+    Legacy {
+        #[serde(with = "display_fromstr")]
+        standard: f64,
+        #[serde(with = "display_fromstr")]
+        fast: f64,
+        #[serde(with = "display_fromstr")]
+        instant: f64,
+    },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Tier {
+    #[serde(with = "display_fromstr")]
+    pub max_priority_fee_per_gas: f64,
+    #[serde(with = "display_fromstr")]
+    pub max_fee_per_gas: f64,
+}
+
+pub struct OneInchGasStation<T> {
+    transport: T,
+    url: String,
+    block_time: Duration,
+    credentials: Option<ApiCredentials>,
+}
+
+impl<T: Transport> OneInchGasStation<T> {
+    /// The api key is sent as bearer token.
+    pub fn new(chain_id: u64, transport: T, api_key: impl Into<String>) -> Self {
+        Self {
+            transport,
+            url: format!("{}/{}", DEFAULT_URL, chain_id),
+            block_time: ChainConfig::from_chain_id(chain_id)
+                .unwrap_or(chain::ETHEREUM)
+                .block_time,
+            credentials: Some(ApiCredentials::Bearer(api_key.into())),
+        }
+    }
+
+    /// For a proxy of the api, the chain id has to be part of the url.
+    pub fn with_url(self, url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..self
+        }
+    }
+
+    /// Replaces the bearer token, for example with credentials for a proxy.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    pub async fn gas_prices(&self) -> Result<Response> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.url, Default::default())?;
+        self.transport
+            .get_json(&url, header)
+            .await
+            .map_err(|err| err.context("failed to get 1inch gas price"))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for OneInchGasStation<T> {
+    fn source(&self) -> &'static str {
+        "oneinch"
+    }
+
+    fn block_time(&self) -> Duration {
+        self.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let response = self.gas_prices().await?;
+        estimate_with_limits(&response, time_limit)
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let response = self.gas_prices().await?;
+        GasPriceSchedule::from_estimates(|time_limit| estimate_with_limits(&response, time_limit))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let response = self.gas_prices().await?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| estimate_with_limits(&response, *time_limit))
+            .collect()
+    }
+}
+
+fn estimate_with_limits(response: &Response, time_limit: Duration) -> Result<EstimatedGasPrice> {
+    let interpolate = |points: &[(Duration, f64)]| -> Result<f64> {
+        let points = points
+            .iter()
+            .map(|(time, value)| (time.as_secs_f64(), *value))
+            .collect::<Vec<_>>();
+        Ok(interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            points.as_slice().try_into()?,
+        ))
+    };
+    match response {
+        Response::Eip1559 {
+            base_fee,
+            low,
+            medium,
+            high,
+            instant,
+        } => {
+            let tiers = [
+                (INSTANT, instant),
+                (HIGH, high),
+                (MEDIUM, medium),
+                (LOW, low),
+            ];
+            let max_fee_per_gas =
+                interpolate(&tiers.map(|(time, tier)| (time, tier.max_fee_per_gas)))?;
+            let max_priority_fee_per_gas =
+                interpolate(&tiers.map(|(time, tier)| (time, tier.max_priority_fee_per_gas)))?;
+            EstimatedGasPrice {
+                legacy: max_fee_per_gas,
+                eip1559: Some(GasPrice1559 {
+                    base_fee_per_gas: GasPriceWei(*base_fee),
+                    max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                    max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+                }),
+            }
+            .validate()
+        }
+        Response::Legacy {
+            standard,
+            fast,
+            instant,
+        } => EstimatedGasPrice {
+            legacy: interpolate(&[(INSTANT, *instant), (HIGH, *fast), (MEDIUM, *standard)])?,
+            eip1559: None,
+        }
+        .validate(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use http::header::{HeaderMap, AUTHORIZATION};
+    use serde::de::DeserializeOwned;
+
+    const EIP1559: &str = r#"{
+        "baseFee": "10000000000",
+        "low": {"maxPriorityFeePerGas": "100000000", "maxFeePerGas": "12000000000"},
+        "medium": {"maxPriorityFeePerGas": "500000000", "maxFeePerGas": "13000000000"},
+        "high": {"maxPriorityFeePerGas": "1000000000", "maxFeePerGas": "15000000000"},
+        "instant": {"maxPriorityFeePerGas": "2000000000", "maxFeePerGas": "20000000000"}
+    }"#;
+
+    #[test]
+    fn deserialize() {
+        let response: Response = serde_json::from_str(EIP1559).unwrap();
+        assert!(matches!(response, Response::Eip1559 { base_fee, .. } if base_fee == 1e10));
+        let response: Response = serde_json::from_str(
+            r#"{"standard": "3000000000", "fast": "4000000000", "instant": "5000000000"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response,
+            Response::Legacy {
+                standard: 3e9,
+                fast: 4e9,
+                instant: 5e9,
+            }
+        );
+    }
+
+    #[test]
+    fn interpolates_tiers() {
+        let response: Response = serde_json::from_str(EIP1559).unwrap();
+        let instant = estimate_with_limits(&response, INSTANT).unwrap();
+        assert_approx_eq!(instant.cap(), 2e10);
+        assert_approx_eq!(instant.tip(), 2e9);
+        assert_approx_eq!(instant.base_fee(), 1e10);
+        let between = estimate_with_limits(&response, Duration::from_secs(450)).unwrap();
+        assert_approx_eq!(between.cap(), 1.25e10);
+        assert_approx_eq!(between.tip(), 3e8);
+
+        let legacy = Response::Legacy {
+            standard: 3e9,
+            fast: 4e9,
+            instant: 5e9,
+        };
+        let slow = estimate_with_limits(&legacy, LOW).unwrap();
+        assert_eq!(slow.eip1559, None);
+        assert_approx_eq!(slow.legacy, 3e9);
+    }
+
+    struct Fixed;
+
+    #[async_trait::async_trait]
+    impl Transport for Fixed {
+        async fn get_json<R: DeserializeOwned>(&self, url: &str, header: HeaderMap) -> Result<R> {
+            assert_eq!(url, "https://api.1inch.dev/gas-price/v1.4/137");
+            assert_eq!(header[AUTHORIZATION], "Bearer key");
+            Ok(serde_json::from_str(EIP1559)?)
+        }
+    }
+
+    #[test]
+    fn requests_chain_with_api_key() {
+        let estimator = OneInchGasStation::new(137, Fixed, "key");
+        assert_eq!(estimator.block_time(), chain::POLYGON.block_time);
+        let schedule = estimator.estimate_schedule().wait().unwrap();
+        assert_approx_eq!(schedule.slow.tip(), 1e8);
+        assert_approx_eq!(schedule.instant.tip(), 2e9);
+    }
+
+    // ONEINCH_API_KEY=... cargo test oneinch -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = OneInchGasStation::new(
+            1,
+            TestTransport::default(),
+            std::env::var("ONEINCH_API_KEY").unwrap(),
+        );
+        println!("{:?}", estimator.gas_prices().await);
+        println!("{:?}", estimator.estimate_schedule().await);
+    }
+}
@@ -0,0 +1,331 @@
+//! OP stack (Optimism, Base) gas estimation.
+//!
+//! Besides the L2 EIP-1559 gas price OP stack transactions pay an L1 data fee for posting their
+//! data to L1. The fee does not depend on the gas limit and usually dominates the total cost. It is
+//! computed by the GasPriceOracle predeploy. Documentation at
+//! https://docs.optimism.io/stack/transactions/fees .
+
+use super::{
+    chain,
+    cost::{TransactionCost, TxSpec},
+    error::Result,
+    json_rpc::{self, encode_bytes, uint_word, word_to_f64},
+    EstimatedGasPrice, GasEstimationError, GasPrice1559, GasPriceEstimating, GasPriceWei,
+    Transport, DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT,
+};
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Address of the GasPriceOracle predeploy.
+pub const GAS_PRICE_ORACLE: &str = "0x420000000000000000000000000000000000000f";
+// getL1Fee(bytes)
+const GET_L1_FEE: [u8; 4] = [0x49, 0x94, 0x8e, 0x0e];
+
+/// Parameters for the OP stack estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // a coefficient to multiply the L2 base fee with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            base_fee_multiplier: 2.0,
+        }
+    }
+}
+
+/// Transaction to estimate the total fee for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxParams {
+    pub gas_limit: f64,
+    pub time_limit: Duration,
+    // size of the transaction calldata in bytes
+    pub calldata_size: usize,
+}
+
+impl Default for TxParams {
+    fn default() -> Self {
+        Self {
+            gas_limit: DEFAULT_GAS_LIMIT,
+            time_limit: DEFAULT_TIME_LIMIT,
+            calldata_size: 0,
+        }
+    }
+}
+
+/// Estimate for a specific OP stack transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OpStackGasEstimate {
+    // L2 gas price.
+    pub gas_price: EstimatedGasPrice,
+    pub gas_limit: f64,
+    // L1 data fee in wei, paid in addition to the L2 execution fee.
+    pub l1_fee: f64,
+}
+
+impl OpStackGasEstimate {
+    // L2 execution fee at the estimated base fee.
+    pub fn l2_fee(&self) -> f64 {
+        self.gas_limit * self.gas_price.effective_gas_price()
+    }
+
+    pub fn total_fee(&self) -> f64 {
+        self.l2_fee() + self.l1_fee
+    }
+}
+
+/// Estimates gas prices through an OP stack node's JSON-RPC api. Requires a transport that
+/// supports `Transport::post_json`.
+pub struct OpStackGasEstimator<T> {
+    transport: T,
+    node_url: String,
+    params: Params,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Block {
+    base_fee_per_gas: Option<String>,
+}
+
+impl<T: Transport> OpStackGasEstimator<T> {
+    pub fn new(transport: T, node_url: String, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    /// Estimate the L2 gas price and the L1 data fee of a transaction.
+    pub async fn estimate_with_tx(&self, tx: &TxParams) -> Result<OpStackGasEstimate> {
+        let (gas_price, l1_fee) = futures::try_join!(
+            self.estimate_with_limits(tx.gas_limit, tx.time_limit),
+            self.l1_fee(tx.calldata_size),
+        )?;
+        Ok(OpStackGasEstimate {
+            gas_price,
+            gas_limit: tx.gas_limit,
+            l1_fee,
+        })
+    }
+
+    /// L1 data fee in wei for a transaction with `calldata_size` bytes of calldata.
+    pub async fn l1_fee(&self, calldata_size: usize) -> Result<f64> {
+        let result = json_rpc::eth_call(
+            &self.transport,
+            &self.node_url,
+            GAS_PRICE_ORACLE,
+            &get_l1_fee_call(&incompressible_calldata(calldata_size)),
+        )
+        .await
+        .map_err(|err| err.context("failed to call getL1Fee"))?;
+        word_to_f64(&result, 0)
+    }
+}
+
+// Base fee of the latest block.
+pub(crate) async fn base_fee(transport: &impl Transport, node_url: &str) -> Result<f64> {
+    let block: Block = json_rpc::call(
+        transport,
+        node_url,
+        "eth_getBlockByNumber",
+        json!(["latest", false]),
+    )
+    .await
+    .map_err(|err| err.context("failed to get latest block"))?;
+    let base_fee = block
+        .base_fee_per_gas
+        .ok_or_else(|| GasEstimationError::Decode(anyhow!("latest block has no base fee")))?;
+    json_rpc::quantity_to_f64(&base_fee)
+}
+
+pub(crate) async fn priority_fee(transport: &impl Transport, node_url: &str) -> Result<f64> {
+    let priority_fee: String =
+        json_rpc::call(transport, node_url, "eth_maxPriorityFeePerGas", json!([]))
+            .await
+            .map_err(|err| err.context("failed to get max priority fee"))?;
+    json_rpc::quantity_to_f64(&priority_fee)
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for OpStackGasEstimator<T> {
+    fn source(&self) -> &'static str {
+        "op_stack"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::OPTIMISM.block_time
+    }
+
+    // Only the L2 gas price, use `estimate_with_tx` to include the L1 data fee.
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let (base_fee, priority_fee) = futures::try_join!(
+            base_fee(&self.transport, &self.node_url),
+            priority_fee(&self.transport, &self.node_url),
+        )?;
+        estimate(base_fee, priority_fee, &self.params)
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let estimate = self
+            .estimate_with_tx(&TxParams {
+                gas_limit: tx.gas_limit,
+                time_limit: tx.time_limit,
+                calldata_size: tx.data_len(),
+            })
+            .await?;
+        Ok(TransactionCost {
+            gas_price: estimate.gas_price,
+            gas_limit: estimate.gas_limit,
+            l1_fee: estimate.l1_fee,
+        })
+    }
+}
+
+pub(crate) fn estimate(
+    base_fee: f64,
+    priority_fee: f64,
+    params: &Params,
+) -> Result<EstimatedGasPrice> {
+    let max_fee_per_gas = base_fee * params.base_fee_multiplier + priority_fee;
+    EstimatedGasPrice {
+        legacy: max_fee_per_gas,
+        eip1559: Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(base_fee),
+            max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+            max_priority_fee_per_gas: GasPriceWei(priority_fee),
+        }),
+    }
+    .validate()
+}
+
+// Also the signature of Scroll's L1GasPriceOracle.
+pub(crate) fn get_l1_fee_call(data: &[u8]) -> Vec<u8> {
+    let mut call = GET_L1_FEE.to_vec();
+    call.extend_from_slice(&uint_word(32));
+    call.extend_from_slice(&encode_bytes(data));
+    call
+}
+
+// The oracle charges zero bytes less than other bytes and (since the Fjord upgrade) charges the
+// compressed size, so a constant filler would underestimate real calldata. Bytes from a linear
+// congruential generator are non zero and do not compress.
+pub(crate) fn incompressible_calldata(size: usize) -> Vec<u8> {
+    let mut state: u32 = 1;
+    (0..size)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ((state >> 16) as u8) | 1
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::{tests::FakeNode, to_hex};
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+
+    #[test]
+    fn encodes_call() {
+        let call = get_l1_fee_call(&[0xaa]);
+        assert_eq!(&call[..4], &GET_L1_FEE);
+        assert_eq!(&call[4..36], &uint_word(32));
+        assert_eq!(&call[36..68], &uint_word(1));
+        assert_eq!(call[68], 0xaa);
+        assert_eq!(call.len(), 4 + 3 * 32);
+    }
+
+    #[test]
+    fn calldata_is_non_zero_and_varied() {
+        let calldata = incompressible_calldata(1000);
+        assert_eq!(calldata.len(), 1000);
+        assert!(calldata.iter().all(|byte| *byte != 0));
+        assert!(calldata.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn estimates_l2_price() {
+        let price = estimate(100.0, 10.0, &Default::default()).unwrap();
+        let eip1559 = price.eip1559.unwrap();
+        assert_eq!(eip1559.base_fee_per_gas, GasPriceWei(100.0));
+        assert_eq!(eip1559.max_fee_per_gas, GasPriceWei(210.0));
+        assert_eq!(eip1559.max_priority_fee_per_gas, GasPriceWei(10.0));
+        assert_eq!(price.legacy, 210.0);
+    }
+
+    #[test]
+    fn estimates_total_fee() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({ "baseFeePerGas": "0x64" }))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"))
+            .with_result("eth_call:0x49948e0e", json!(to_hex(&uint_word(1_000_000))));
+        let estimator = OpStackGasEstimator::new(node, String::new(), None);
+        let estimate = estimator
+            .estimate_with_tx(&TxParams {
+                gas_limit: 100_000.0,
+                calldata_size: 200,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(estimate.l1_fee, 1e6);
+        // effective gas price is base fee + priority fee
+        assert_eq!(estimate.l2_fee(), 100_000.0 * 110.0);
+        assert_eq!(estimate.total_fee(), 1e6 + 1.1e7);
+
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                gas_limit: 100_000.0,
+                calldata_len: 200,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.l1_fee, 1e6);
+        assert_eq!(cost.expected_cost(), 1e6 + 1.1e7);
+    }
+
+    #[test]
+    fn fails_without_base_fee() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({}))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"));
+        let estimator = OpStackGasEstimator::new(node, String::new(), None);
+        assert!(matches!(
+            estimator.estimate().wait(),
+            Err(GasEstimationError::Decode(_))
+        ));
+    }
+
+    // OP_NODE_URL=... cargo test op_stack -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = OpStackGasEstimator::new(
+            TestTransport::default(),
+            std::env::var("OP_NODE_URL").unwrap(),
+            None,
+        );
+        for calldata_size in [0, 100, 1000] {
+            let tx = TxParams {
+                calldata_size,
+                ..Default::default()
+            };
+            println!(
+                "{}: {:?}",
+                calldata_size,
+                estimator.estimate_with_tx(&tx).await
+            );
+        }
+    }
+}
@@ -0,0 +1,515 @@
+//! Keeps the estimates of a pull based source fresh in the background so that estimates are served
+//! from memory, the same model as the websocket estimators.
+
+use super::{
+    error::Result,
+    interpolation, snapshot,
+    task::BackgroundTask,
+    time::{Clock, Instant, SystemClock},
+    EstimatedGasPrice, GasEstimationError, GasPrice1559, GasPriceEstimating, GasPriceSchedule,
+    GasPriceWei, Readiness, DEFAULT_TIME_LIMIT,
+};
+use anyhow::anyhow;
+use std::{convert::TryInto, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::watch;
+
+/// Parameters for the background polling estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // time between refreshes of the inner estimator
+    pub interval: Duration,
+    // estimates fail when the last successful refresh is older than this
+    pub max_staleness: Duration,
+    // file the schedule is saved to after every refresh and restored from if the first estimate
+    // fails on startup
+    pub snapshot: Option<PathBuf>,
+    // snapshots older than this are not restored and restored estimates fail once they are older
+    pub max_snapshot_age: Duration,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            max_staleness: Duration::from_secs(60),
+            snapshot: None,
+            max_snapshot_age: Duration::from_secs(600),
+        }
+    }
+}
+
+impl Params {
+    fn save_snapshot(&self, schedule: &GasPriceSchedule) {
+        if let Some(path) = &self.snapshot {
+            // The file is small so the blocking write doesn't hold up the runtime noticeably.
+            if let Err(err) = snapshot::save(path, schedule) {
+                tracing::warn!(?err, "failed to save snapshot");
+            }
+        }
+    }
+
+    // The snapshot and the time at which it was saved if it is recent enough.
+    fn restore_snapshot(&self, now: Instant) -> Option<(GasPriceSchedule, Instant)> {
+        let (schedule, age) = match snapshot::load(self.snapshot.as_ref()?) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                tracing::warn!(?err, "failed to restore snapshot");
+                return None;
+            }
+        };
+        if age > self.max_snapshot_age {
+            tracing::warn!(?age, "snapshot is too old to restore");
+            return None;
+        }
+        Some((schedule, now.checked_sub(age).unwrap_or(now)))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Update {
+    time: Instant,
+    schedule: GasPriceSchedule,
+    // whether the schedule comes from the snapshot because no refresh has succeeded yet
+    restored: bool,
+}
+
+// Refreshes the schedule of the inner estimator in a background task. Estimates for other time
+// limits are interpolated between the urgencies of the schedule.
+pub struct BackgroundPollingEstimator {
+    update: watch::Receiver<Update>,
+    // Estimate for the default time limit, updated when a refresh changes it.
+    prices: watch::Receiver<EstimatedGasPrice>,
+    max_staleness: Duration,
+    max_snapshot_age: Duration,
+    source: &'static str,
+    block_time: Duration,
+    clock: Arc<dyn Clock>,
+    task: BackgroundTask,
+}
+
+impl BackgroundPollingEstimator {
+    /// Fails if the first estimate of the inner estimator fails and there is no snapshot to
+    /// restore.
+    pub async fn new(
+        inner: impl GasPriceEstimating + 'static,
+        params: Option<Params>,
+    ) -> Result<Self> {
+        Self::with_clock(inner, params, Arc::new(SystemClock)).await
+    }
+
+    /// Like `new` but the refresh interval and staleness are measured with `clock`.
+    pub async fn with_clock(
+        inner: impl GasPriceEstimating + 'static,
+        params: Option<Params>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self> {
+        let params = params.unwrap_or_default();
+        let initial = match inner.estimate_schedule().await {
+            Ok(schedule) => {
+                params.save_snapshot(&schedule);
+                Update {
+                    time: clock.now(),
+                    schedule,
+                    restored: false,
+                }
+            }
+            Err(err) => {
+                tracing::warn!(?err, "failed to get initial estimate");
+                match params.restore_snapshot(clock.now()) {
+                    Some((schedule, time)) => Update {
+                        time,
+                        schedule,
+                        restored: true,
+                    },
+                    None => return Err(err.context("failed to get initial estimate")),
+                }
+            }
+        };
+        let (prices_sender, prices) = watch::channel(estimate_from_schedule(
+            &initial.schedule,
+            DEFAULT_TIME_LIMIT,
+        )?);
+        let (sender, update) = watch::channel(initial);
+        let max_staleness = params.max_staleness;
+        let max_snapshot_age = params.max_snapshot_age;
+        let source = inner.source();
+        let block_time = inner.block_time();
+        let interval = params.interval;
+        let task_clock = clock.clone();
+        let task = BackgroundTask::spawn(async move {
+            loop {
+                task_clock.sleep(interval).await;
+                match inner.estimate_schedule().await {
+                    Ok(schedule) => {
+                        // Only fails when all receivers are dropped, which means the estimator
+                        // was dropped and this task is being aborted.
+                        let _ = sender.send(Update {
+                            time: task_clock.now(),
+                            schedule,
+                            restored: false,
+                        });
+                        params.save_snapshot(&schedule);
+                        match estimate_from_schedule(&schedule, DEFAULT_TIME_LIMIT) {
+                            Ok(price) => {
+                                prices_sender.send_if_modified(|current| {
+                                    let modified = *current != price;
+                                    *current = price;
+                                    modified
+                                });
+                            }
+                            Err(err) => tracing::warn!(?err, source, "failed to estimate"),
+                        }
+                    }
+                    Err(err) => tracing::warn!(?err, source, "failed to refresh estimate"),
+                }
+            }
+        });
+        Ok(Self {
+            update,
+            prices,
+            max_staleness,
+            max_snapshot_age,
+            source,
+            block_time,
+            clock,
+            task,
+        })
+    }
+
+    /// Time of the last successful refresh.
+    pub fn last_update(&self) -> Instant {
+        self.update.borrow().time
+    }
+
+    /// Whether estimates come from the snapshot because no refresh has succeeded since startup.
+    /// The estimator is not ready until then.
+    pub fn is_restored(&self) -> bool {
+        self.update.borrow().restored
+    }
+
+    /// Receives the estimate for the default time limit whenever a refresh changes it.
+    pub fn subscribe(&self) -> watch::Receiver<EstimatedGasPrice> {
+        self.prices.clone()
+    }
+
+    fn schedule(&self, now: Instant) -> Result<GasPriceSchedule> {
+        let update = *self.update.borrow();
+        let age = now.saturating_duration_since(update.time);
+        let max_age = if update.restored {
+            self.max_snapshot_age
+        } else {
+            self.max_staleness
+        };
+        if age > max_age {
+            return Err(anyhow!("estimate is stale, last refresh {:?} ago", age).into());
+        }
+        Ok(update.schedule)
+    }
+
+    /// Stops the background task and waits until it has finished. Estimates fail once the last
+    /// refresh is stale.
+    pub async fn shutdown(&mut self) {
+        self.task.shutdown().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl GasPriceEstimating for BackgroundPollingEstimator {
+    fn source(&self) -> &'static str {
+        self.source
+    }
+
+    fn block_time(&self) -> Duration {
+        self.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        estimate_from_schedule(&self.schedule(self.clock.now())?, time_limit)
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.schedule(self.clock.now())
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let schedule = self.schedule(self.clock.now())?;
+        requests
+            .iter()
+            .map(|(_, time_limit)| estimate_from_schedule(&schedule, *time_limit))
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl Readiness for BackgroundPollingEstimator {
+    async fn ready(&self) -> bool {
+        !self.is_restored() && self.schedule(self.clock.now()).is_ok()
+    }
+}
+
+// Interpolates every field between the urgencies. EIP-1559 values are only estimated if all
+// urgencies have them.
+fn estimate_from_schedule(
+    schedule: &GasPriceSchedule,
+    time_limit: Duration,
+) -> Result<EstimatedGasPrice> {
+    let urgencies = [
+        (GasPriceSchedule::INSTANT, schedule.instant),
+        (GasPriceSchedule::FAST, schedule.fast),
+        (GasPriceSchedule::STANDARD, schedule.standard),
+        (GasPriceSchedule::SLOW, schedule.slow),
+    ];
+    let interpolate = |field: &dyn Fn(&EstimatedGasPrice) -> Option<f64>| -> Result<Option<f64>> {
+        let points = urgencies
+            .iter()
+            .map(|(time, estimate)| Some((time.as_secs_f64(), field(estimate)?)))
+            .collect::<Option<Vec<_>>>();
+        match points {
+            Some(points) => Ok(Some(interpolation::interpolate(
+                time_limit.as_secs_f64(),
+                points.as_slice().try_into()?,
+            ))),
+            None => Ok(None),
+        }
+    };
+    let legacy = interpolate(&|estimate| Some(estimate.legacy))?
+        .ok_or_else(|| GasEstimationError::Other(anyhow!("empty schedule")))?;
+    let base_fee = interpolate(&|estimate| Some(estimate.eip1559?.base_fee_per_gas.0))?;
+    let max_fee = interpolate(&|estimate| Some(estimate.eip1559?.max_fee_per_gas.0))?;
+    let tip = interpolate(&|estimate| Some(estimate.eip1559?.max_priority_fee_per_gas.0))?;
+    let eip1559 = match (base_fee, max_fee, tip) {
+        (Some(base_fee), Some(max_fee), Some(tip)) => Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(base_fee),
+            max_fee_per_gas: GasPriceWei(max_fee),
+            max_priority_fee_per_gas: GasPriceWei(tip),
+        }),
+        _ => None,
+    };
+    Ok(EstimatedGasPrice { legacy, eip1559 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use std::sync::Arc;
+
+    fn legacy(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            eip1559: None,
+        }
+    }
+
+    #[test]
+    fn interpolates_schedule() {
+        let schedule = GasPriceSchedule {
+            slow: legacy(1.0),
+            standard: legacy(2.0),
+            fast: legacy(3.0),
+            instant: legacy(4.0),
+        };
+        let estimate = estimate_from_schedule(&schedule, Duration::from_secs(15)).unwrap();
+        assert_eq!(estimate, legacy(4.0));
+        let estimate = estimate_from_schedule(&schedule, Duration::from_secs(180)).unwrap();
+        assert_approx_eq!(estimate.legacy, 2.5);
+
+        let eip1559 = |max_fee| EstimatedGasPrice {
+            legacy: max_fee,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(1.0),
+                max_fee_per_gas: GasPriceWei(max_fee),
+                max_priority_fee_per_gas: GasPriceWei(max_fee - 1.0),
+            }),
+        };
+        let schedule = GasPriceSchedule {
+            slow: eip1559(2.0),
+            standard: eip1559(2.0),
+            fast: eip1559(4.0),
+            instant: eip1559(6.0),
+        };
+        let estimate = estimate_from_schedule(&schedule, Duration::from_secs(30)).unwrap();
+        let expected = 6.0 - 2.0 * 15.0 / 45.0;
+        assert_approx_eq!(estimate.eip1559.unwrap().max_fee_per_gas.0, expected);
+        assert_approx_eq!(
+            estimate.eip1559.unwrap().max_priority_fee_per_gas.0,
+            expected - 1.0
+        );
+        // mixed schedules only have legacy prices
+        let schedule = GasPriceSchedule {
+            slow: legacy(2.0),
+            ..schedule
+        };
+        let estimate = estimate_from_schedule(&schedule, Duration::from_secs(30)).unwrap();
+        assert!(estimate.eip1559.is_none());
+    }
+
+    #[tokio::test]
+    async fn refreshes_in_background() {
+        let inner = Arc::new(FixedGasPriceEstimator::legacy(1.0));
+        let estimator = BackgroundPollingEstimator::new(
+            inner.clone(),
+            Some(Params {
+                interval: Duration::from_millis(10),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(estimator.source(), "fixed");
+        assert_eq!(estimator.estimate().await.unwrap(), legacy(1.0));
+        inner.set(legacy(2.0));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(estimator.estimate().await.unwrap(), legacy(2.0));
+    }
+
+    #[tokio::test]
+    async fn subscribers_see_changes() {
+        let inner = Arc::new(FixedGasPriceEstimator::legacy(1.0));
+        let estimator = BackgroundPollingEstimator::new(
+            inner.clone(),
+            Some(Params {
+                interval: Duration::from_millis(10),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        let mut prices = estimator.subscribe();
+        assert_eq!(*prices.borrow(), legacy(1.0));
+        // unchanged refreshes don't notify
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!prices.has_changed().unwrap());
+        inner.set(legacy(2.0));
+        tokio::time::timeout(Duration::from_secs(1), prices.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*prices.borrow(), legacy(2.0));
+    }
+
+    #[tokio::test]
+    async fn stale_after_failing_refreshes() {
+        let inner = Arc::new(FixedGasPriceEstimator::legacy(1.0));
+        let estimator = BackgroundPollingEstimator::new(
+            inner.clone(),
+            Some(Params {
+                interval: Duration::from_millis(10),
+                max_staleness: Duration::from_millis(30),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        for _ in 0..100 {
+            inner.push_result(Err(GasEstimationError::Timeout));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(estimator.estimate().await.is_err());
+        assert!(estimator.last_update().elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn staleness_uses_clock() {
+        let inner = Arc::new(FixedGasPriceEstimator::legacy(1.0));
+        let clock = crate::time::tests::ManualClock::default();
+        let estimator = BackgroundPollingEstimator::with_clock(
+            inner.clone(),
+            Some(Params {
+                interval: Duration::from_secs(60),
+                max_staleness: Duration::from_secs(30),
+                ..Default::default()
+            }),
+            Arc::new(clock.clone()),
+        )
+        .await
+        .unwrap();
+        let start = estimator.last_update();
+        assert!(estimator.estimate().await.is_ok());
+        assert!(estimator.ready().await);
+        for _ in 0..100 {
+            inner.push_result(Err(GasEstimationError::Timeout));
+        }
+        // The fake clock advances by the interval whenever the background task sleeps.
+        for _ in 0..10 {
+            if estimator.estimate().await.is_err() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert!(estimator.estimate().await.is_err());
+        assert!(!estimator.ready().await);
+        assert_eq!(estimator.last_update(), start);
+        assert!(clock.now().duration_since(start) >= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_refreshing() {
+        let inner = Arc::new(FixedGasPriceEstimator::legacy(1.0));
+        let mut estimator = BackgroundPollingEstimator::new(
+            inner.clone(),
+            Some(Params {
+                interval: Duration::from_millis(10),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        estimator.shutdown().await;
+        let calls = inner.calls();
+        inner.set(legacy(2.0));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(inner.calls(), calls);
+        assert_eq!(estimator.estimate().await.unwrap(), legacy(1.0));
+    }
+
+    #[tokio::test]
+    async fn fails_without_initial_estimate() {
+        let inner = FixedGasPriceEstimator::legacy(1.0);
+        for _ in 0..4 {
+            inner.push_result(Err(GasEstimationError::Timeout));
+        }
+        assert!(BackgroundPollingEstimator::new(inner, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn restores_snapshot_if_initial_estimate_fails() {
+        let path = crate::snapshot::tests::path("restores_snapshot");
+        let params = Params {
+            interval: Duration::from_secs(60),
+            snapshot: Some(path.clone()),
+            ..Default::default()
+        };
+        let inner = Arc::new(FixedGasPriceEstimator::legacy(1.0));
+        BackgroundPollingEstimator::new(inner.clone(), Some(params.clone()))
+            .await
+            .unwrap();
+
+        // restarted during an outage of the source
+        inner.set(legacy(2.0));
+        inner.push_result(Err(GasEstimationError::Timeout));
+        let clock = crate::time::tests::ManualClock::default();
+        let estimator =
+            BackgroundPollingEstimator::with_clock(inner, Some(params), Arc::new(clock.clone()))
+                .await
+                .unwrap();
+        assert!(estimator.is_restored());
+        assert!(!estimator.ready().await);
+        assert_eq!(estimator.estimate().await.unwrap(), legacy(1.0));
+        // The fake clock advances by the interval whenever the background task sleeps.
+        for _ in 0..10 {
+            if !estimator.is_restored() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert!(estimator.ready().await);
+        assert_eq!(estimator.estimate().await.unwrap(), legacy(2.0));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Polygon gas station `GasPriceEstimating` implementation.
+//! Api documentation at https://docs.polygon.technology/docs/develop/tools/polygon-gas-station/ .
+
+use super::{
+    auth, chain,
+    error::Result,
+    interpolation,
+    transport::{CacheValidators, Conditional},
+    ApiCredentials, EstimatedGasPrice, GasEstimationError, GasPrice1559, GasPriceEstimating,
+    GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::{convert::TryInto, sync::Mutex, time::Duration};
+
+/// The default uris at which the gas station api is available under.
+const DEFAULT_MAINNET_URI: &str = "https://gasstation-mainnet.matic.network/v2";
+const DEFAULT_MUMBAI_URI: &str = "https://gasstation-mumbai.matic.today/v2";
+
+pub fn api_url_from_network_id(network_id: &str) -> Option<&'static str> {
+    match network_id {
+        "137" => Some(DEFAULT_MAINNET_URI),
+        "80001" => Some(DEFAULT_MUMBAI_URI),
+        _ => None,
+    }
+}
+
+// The gas station does not publish confirmation times for its tiers. Blocks are produced every two
+// seconds so we assume the tiers get included within a few blocks of each other.
+pub const FAST: Duration = Duration::from_secs(6);
+pub const STANDARD: Duration = Duration::from_secs(15);
+pub const SAFE_LOW: Duration = Duration::from_secs(30);
+
+/// Gas prices in gwei retrieved from the gas station.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GasPrices {
+    pub safe_low: Tier,
+    pub standard: Tier,
+    pub fast: Tier,
+    pub estimated_base_fee: f64,
+    pub block_time: u64,
+    pub block_number: u64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tier {
+    pub max_priority_fee: f64,
+    pub max_fee: f64,
+}
+
+/// Retrieve gas prices from the Polygon gas station service. Requests are conditional on the last
+/// response so that a transport with support for them only downloads prices that changed.
+#[derive(Debug)]
+pub struct PolygonGasStation<T> {
+    transport: T,
+    uri: String,
+    credentials: Option<ApiCredentials>,
+    // the last response and its validators if the gas station sent any
+    last_response: Mutex<Option<(CacheValidators, GasPrices)>>,
+}
+
+impl<T: Transport> PolygonGasStation<T> {
+    pub fn with_network_id(network_id: &str, transport: T) -> Result<Self> {
+        let uri = api_url_from_network_id(network_id)
+            .ok_or_else(|| {
+                GasEstimationError::Unsupported(format!("unsupported network id {}", network_id))
+            })?
+            .into();
+        Ok(Self {
+            transport,
+            uri,
+            credentials: None,
+            last_response: Default::default(),
+        })
+    }
+
+    /// Credentials sent with every request, for example for a paid tier.
+    pub fn with_credentials(self, credentials: ApiCredentials) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    /// Retrieves the current gas prices from the gas station.
+    pub async fn gas_prices(&self) -> Result<GasPrices> {
+        let (url, header) =
+            auth::authorize(self.credentials.as_ref(), &self.uri, Default::default())?;
+        let last_response = self.last_response.lock().unwrap().clone();
+        let validators = match &last_response {
+            Some((validators, _)) => validators.clone(),
+            None => Default::default(),
+        };
+        let response: Conditional<GasPrices> = self
+            .transport
+            .get_json_conditional(&url, header, &validators)
+            .await
+            .map_err(|err| err.context("failed to get polygon gas price"))?;
+        match response {
+            Conditional::Modified { value, validators } => {
+                *self.last_response.lock().unwrap() =
+                    (!validators.is_empty()).then(|| (validators, value.clone()));
+                Ok(value)
+            }
+            Conditional::NotModified => match last_response {
+                Some((_, prices)) => Ok(prices),
+                None => Err(GasEstimationError::Decode(anyhow!(
+                    "not modified response without previous polygon gas price"
+                ))),
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for PolygonGasStation<T> {
+    fn source(&self) -> &'static str {
+        "polygon"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::POLYGON.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let response = self.gas_prices().await?;
+        estimate_with_limits(&response, time_limit)
+    }
+}
+
+fn estimate_with_limits(response: &GasPrices, time_limit: Duration) -> Result<EstimatedGasPrice> {
+    let tiers = [
+        (FAST, &response.fast),
+        (STANDARD, &response.standard),
+        (SAFE_LOW, &response.safe_low),
+    ];
+    let max_fee_per_gas_points = tiers
+        .iter()
+        .map(|(time, tier)| (time.as_secs_f64(), GasPriceWei::from_gwei(tier.max_fee).0))
+        .collect::<Vec<(f64, f64)>>();
+    let max_priority_fee_per_gas_points = tiers
+        .iter()
+        .map(|(time, tier)| {
+            (
+                time.as_secs_f64(),
+                GasPriceWei::from_gwei(tier.max_priority_fee).0,
+            )
+        })
+        .collect::<Vec<(f64, f64)>>();
+
+    let eip1559 = GasPrice1559 {
+        base_fee_per_gas: GasPriceWei::from_gwei(response.estimated_base_fee),
+        max_fee_per_gas: GasPriceWei(interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            max_fee_per_gas_points.as_slice().try_into()?,
+        )),
+        max_priority_fee_per_gas: GasPriceWei(interpolation::interpolate(
+            time_limit.as_secs_f64(),
+            max_priority_fee_per_gas_points.as_slice().try_into()?,
+        )),
+    };
+    EstimatedGasPrice {
+        legacy: eip1559.max_fee_per_gas.0,
+        eip1559: Some(eip1559),
+    }
+    .validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn deserialize() {
+        let json = r#"
+        {
+            "safeLow": {
+                "maxPriorityFee": 30.7611840636,
+                "maxFee": 30.7611840796
+            },
+            "standard": {
+                "maxPriorityFee": 32.146027800733336,
+                "maxFee": 32.14602781673334
+            },
+            "fast": {
+                "maxPriorityFee": 33.284344224133335,
+                "maxFee": 33.284344240133336
+            },
+            "estimatedBaseFee": 1.6e-8,
+            "blockTime": 6,
+            "blockNumber": 24962816
+        }"#;
+        let result = serde_json::from_str::<GasPrices>(json).unwrap();
+        assert_approx_eq!(result.safe_low.max_priority_fee, 30.7611840636);
+        assert_approx_eq!(result.standard.max_fee, 32.14602781673334);
+        assert_approx_eq!(result.fast.max_priority_fee, 33.284344224133335);
+        assert_approx_eq!(result.estimated_base_fee, 1.6e-8);
+        assert_eq!(result.block_time, 6);
+        assert_eq!(result.block_number, 24962816);
+    }
+
+    #[test]
+    fn interpolates() {
+        let prices = GasPrices {
+            safe_low: Tier {
+                max_priority_fee: 1.0,
+                max_fee: 2.0,
+            },
+            standard: Tier {
+                max_priority_fee: 2.0,
+                max_fee: 3.0,
+            },
+            fast: Tier {
+                max_priority_fee: 3.0,
+                max_fee: 4.0,
+            },
+            estimated_base_fee: 1.0,
+            ..Default::default()
+        };
+        let price = estimate_with_limits(&prices, FAST).unwrap();
+        let eip1559 = price.eip1559.unwrap();
+        assert_approx_eq!(eip1559.base_fee_per_gas.0, 1e9);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 4e9);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 3e9);
+        assert_approx_eq!(price.legacy, 4e9);
+
+        let price = estimate_with_limits(&prices, Duration::from_secs(600)).unwrap();
+        let eip1559 = price.eip1559.unwrap();
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 2e9);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 1e9);
+    }
+
+    // Answers with not modified once the request has the etag of the first response.
+    #[derive(Default)]
+    struct EtagTransport {
+        requests: Mutex<Vec<CacheValidators>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for EtagTransport {
+        async fn get_json<R: serde::de::DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<R> {
+            unreachable!()
+        }
+
+        async fn get_json_conditional<R: serde::de::DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+            validators: &CacheValidators,
+        ) -> Result<Conditional<R>> {
+            self.requests.lock().unwrap().push(validators.clone());
+            if validators.etag.is_some() {
+                return Ok(Conditional::NotModified);
+            }
+            let prices = serde_json::json!({
+                "safeLow": { "maxPriorityFee": 1.0, "maxFee": 2.0 },
+                "standard": { "maxPriorityFee": 2.0, "maxFee": 3.0 },
+                "fast": { "maxPriorityFee": 3.0, "maxFee": 4.0 },
+                "estimatedBaseFee": 1.0,
+                "blockTime": 2,
+                "blockNumber": 1
+            });
+            Ok(Conditional::Modified {
+                value: serde_json::from_value(prices)?,
+                validators: CacheValidators {
+                    etag: Some("\"v1\"".parse().unwrap()),
+                    last_modified: None,
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn reuses_not_modified_response() {
+        let gas_station =
+            PolygonGasStation::with_network_id("137", EtagTransport::default()).unwrap();
+        for _ in 0..2 {
+            let price = gas_station
+                .estimate_with_limits(21000.0, FAST)
+                .wait()
+                .unwrap();
+            assert_approx_eq!(price.eip1559.unwrap().max_fee_per_gas.0, 4e9);
+        }
+        let requests = gas_station.transport.requests.lock().unwrap();
+        assert!(requests[0].is_empty());
+        assert_eq!(requests[1].etag.as_ref().unwrap(), "\"v1\"");
+    }
+
+    #[test]
+    fn unsupported_network() {
+        assert!(PolygonGasStation::with_network_id("1", TestTransport::default()).is_err());
+    }
+
+    // cargo test polygon -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let gas_station =
+            PolygonGasStation::with_network_id("137", TestTransport::default()).unwrap();
+        let response = gas_station.gas_prices().await.unwrap();
+        println!("{:?}", response);
+        for i in 0..10 {
+            let time_limit = Duration::from_secs(i * 5);
+            let price = estimate_with_limits(&response, time_limit).unwrap();
+            println!(
+                "gas price estimate for {} seconds: {:?}",
+                time_limit.as_secs(),
+                price,
+            );
+        }
+    }
+}
@@ -1,8 +1,16 @@
-use super::{EstimatedGasPrice, GasPriceEstimating};
-use anyhow::{anyhow, Result};
+use super::{
+    error::Result,
+    time::{Clock, Instant, SystemClock},
+    trace, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasEstimationError,
+    GasPriceEstimating, TransactionCost, TxSpec,
+};
+use anyhow::anyhow;
 use std::{
     future::Future,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
     time::Duration,
 };
 
@@ -12,14 +20,100 @@ use std::{
 // still getting them when the estimator really goes down.
 const LOG_ERROR_AFTER_N_ERRORS: usize = 10;
 
-// Uses the first successful estimator.
+/// Parameters for ordering estimators by their recent health.
+#[derive(Debug, Clone)]
+pub struct HealthParams {
+    // weight of the newest result in the moving averages of success rate and latency
+    pub smoothing: f64,
+    // estimators with a lower success rate are tried after the healthy ones
+    pub min_success_rate: f64,
+    // estimators with a higher average latency are tried after the healthy ones
+    pub max_latency: Option<Duration>,
+    // unhealthy estimators get their configured position back when they have not been tried for
+    // this long so that they can recover
+    pub recheck_interval: Duration,
+}
+
+impl Default for HealthParams {
+    fn default() -> Self {
+        Self {
+            smoothing: 0.2,
+            min_success_rate: 0.5,
+            max_latency: None,
+            recheck_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Recent health of an estimator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimatorHealth {
+    // Position of the estimator in the list passed to `new`.
+    pub index: usize,
+    // Exponential moving average of successes (1) and failures (0).
+    pub success_rate: f64,
+    // Exponential moving average of the latency. `None` until the estimator has been used.
+    pub average_latency: Option<Duration>,
+    pub healthy: bool,
+}
+
+// Uses the first successful estimator. By default estimators are tried in the order passed to
+// `new`. With `with_health_ordering` unhealthy estimators are moved to the back. With
+// `with_query_all` all estimators are queried concurrently so that failures of lower priority
+// estimators are noticed even while a higher priority one works.
 pub struct PriorityGasPriceEstimating {
     estimators: Vec<Estimator>,
+    health_params: Option<HealthParams>,
+    query_all: bool,
+    // indices of the estimators that failed in the last call
+    last_failures: Mutex<Vec<usize>>,
+    clock: Box<dyn Clock>,
 }
 
 struct Estimator {
     estimator: Box<dyn GasPriceEstimating>,
     errors_in_a_row: AtomicUsize,
+    health: Mutex<Health>,
+}
+
+struct Health {
+    success_rate: f64,
+    average_latency: Option<Duration>,
+    last_attempt: Option<Instant>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            success_rate: 1.0,
+            average_latency: None,
+            last_attempt: None,
+        }
+    }
+}
+
+impl Health {
+    fn record(&mut self, params: &HealthParams, now: Instant, latency: Duration, success: bool) {
+        let smoothing = params.smoothing.clamp(0.0, 1.0);
+        let success = if success { 1.0 } else { 0.0 };
+        self.success_rate = self.success_rate * (1.0 - smoothing) + success * smoothing;
+        self.average_latency = Some(match self.average_latency {
+            Some(average) => average.mul_f64(1.0 - smoothing) + latency.mul_f64(smoothing),
+            None => latency,
+        });
+        self.last_attempt = Some(now);
+    }
+
+    fn is_healthy(&self, params: &HealthParams, now: Instant) -> bool {
+        let recheck = self.last_attempt.map_or(true, |last_attempt| {
+            now.saturating_duration_since(last_attempt) >= params.recheck_interval
+        });
+        let fast_enough = match (params.max_latency, self.average_latency) {
+            (Some(max_latency), Some(latency)) => latency <= max_latency,
+            _ => true,
+        };
+        recheck || (self.success_rate >= params.min_success_rate && fast_enough)
+    }
 }
 
 impl PriorityGasPriceEstimating {
@@ -29,49 +123,232 @@ impl PriorityGasPriceEstimating {
             .map(|estimator| Estimator {
                 estimator,
                 errors_in_a_row: AtomicUsize::new(0),
+                health: Default::default(),
             })
             .collect();
-        Self { estimators }
+        Self {
+            estimators,
+            health_params: None,
+            query_all: false,
+            last_failures: Default::default(),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    // Query all estimators on every call and use the result of the highest priority one that
+    // succeeded. Failures are logged and reported by `failed_in_last_call`.
+    pub fn with_query_all(self) -> Self {
+        Self {
+            query_all: true,
+            ..self
+        }
     }
 
-    async fn prioritize<'a, T, F>(&'a self, operation: T) -> Result<EstimatedGasPrice>
+    /// Positions in the list passed to `new` of the estimators that failed in the last call. When
+    /// not querying all estimators this only contains the ones that were tried before the
+    /// successful one.
+    pub fn failed_in_last_call(&self) -> Vec<usize> {
+        self.last_failures.lock().unwrap().clone()
+    }
+
+    // Track success rate and latency of the estimators and try unhealthy ones last.
+    pub fn with_health_ordering(self, health_params: HealthParams) -> Self {
+        Self {
+            health_params: Some(health_params),
+            ..self
+        }
+    }
+
+    /// The time used to measure latencies and to recheck unhealthy estimators, the system clock
+    /// by default.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            ..self
+        }
+    }
+
+    /// The health of all estimators in the order they are currently tried.
+    pub fn ranking(&self) -> Vec<EstimatorHealth> {
+        self.ranking_at(self.clock.now())
+    }
+
+    fn ranking_at(&self, now: Instant) -> Vec<EstimatorHealth> {
+        let default_params = HealthParams::default();
+        let params = self.health_params.as_ref().unwrap_or(&default_params);
+        let mut ranking = self
+            .estimators
+            .iter()
+            .enumerate()
+            .map(|(index, estimator)| {
+                let health = estimator.health.lock().unwrap();
+                EstimatorHealth {
+                    index,
+                    success_rate: health.success_rate,
+                    average_latency: health.average_latency,
+                    healthy: health.is_healthy(params, now),
+                }
+            })
+            .collect::<Vec<_>>();
+        if self.health_params.is_some() {
+            // Healthy estimators keep their configured order, unhealthy ones follow by success
+            // rate. The sort is stable so equal success rates keep their configured order.
+            ranking.sort_by(|a, b| match (a.healthy, b.healthy) {
+                (true, true) => std::cmp::Ordering::Equal,
+                (true, false) => std::cmp::Ordering::Less,
+                (false, true) => std::cmp::Ordering::Greater,
+                (false, false) => b
+                    .success_rate
+                    .partial_cmp(&a.success_rate)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            });
+        }
+        ranking
+    }
+
+    async fn prioritize<'a, T, F, R>(&'a self, operation: T) -> Result<R>
     where
         T: Fn(&'a dyn GasPriceEstimating) -> F,
-        F: Future<Output = Result<EstimatedGasPrice>>,
+        F: Future<Output = Result<R>>,
     {
-        for (i, estimator) in self.estimators.iter().enumerate() {
-            match operation(estimator.estimator.as_ref()).await {
-                Ok(result) => {
-                    estimator.errors_in_a_row.store(0, Ordering::SeqCst);
-                    return Ok(result);
+        let ranking = self
+            .ranking_at(self.clock.now())
+            .iter()
+            .map(|health| health.index)
+            .collect::<Vec<_>>();
+        let mut failures = Vec::new();
+        let mut success = None;
+        if self.query_all {
+            let results = futures::future::join_all(
+                ranking
+                    .iter()
+                    .map(|&i| self.measure(i, operation(self.estimators[i].estimator.as_ref()))),
+            )
+            .await;
+            for (i, result) in ranking.into_iter().zip(results) {
+                match result {
+                    Ok(result) if success.is_none() => success = Some(result),
+                    Ok(_) | Err(GasEstimationError::Unsupported(_)) => (),
+                    Err(_) => failures.push(i),
                 }
-                Err(err) => {
-                    let num_errors = estimator.errors_in_a_row.fetch_add(1, Ordering::SeqCst) + 1;
-                    if num_errors < LOG_ERROR_AFTER_N_ERRORS {
-                        tracing::warn!("gas estimator {} failed: {:?}", i, err);
-                    } else {
-                        tracing::error!("gas estimator {} failed: {:?}", i, err);
+            }
+        } else {
+            for i in ranking {
+                match self
+                    .measure(i, operation(self.estimators[i].estimator.as_ref()))
+                    .await
+                {
+                    Ok(result) => {
+                        success = Some(result);
+                        break;
                     }
+                    Err(GasEstimationError::Unsupported(_)) => (),
+                    Err(_) => failures.push(i),
+                }
+            }
+        }
+        *self.last_failures.lock().unwrap() = failures;
+        success.ok_or_else(|| anyhow!("all gas estimators failed").into())
+    }
+
+    // Records the health of estimator `i` and logs failures. Estimators that don't support a
+    // request, like `MempoolTipEstimator` for private transactions, are skipped without counting
+    // it as a failure.
+    async fn measure<R>(&self, i: usize, operation: impl Future<Output = Result<R>>) -> Result<R> {
+        let default_params = HealthParams::default();
+        let params = self.health_params.as_ref().unwrap_or(&default_params);
+        let estimator = &self.estimators[i];
+        let start = self.clock.now();
+        let result = operation.await;
+        if let Err(GasEstimationError::Unsupported(reason)) = &result {
+            tracing::debug!(
+                "gas estimator {} does not support the request: {}",
+                i,
+                reason
+            );
+            return result;
+        }
+        let end = self.clock.now();
+        estimator.health.lock().unwrap().record(
+            params,
+            end,
+            end.saturating_duration_since(start),
+            result.is_ok(),
+        );
+        match &result {
+            Ok(_) => estimator.errors_in_a_row.store(0, Ordering::SeqCst),
+            Err(err) => {
+                let num_errors = estimator.errors_in_a_row.fetch_add(1, Ordering::SeqCst) + 1;
+                if num_errors < LOG_ERROR_AFTER_N_ERRORS {
+                    tracing::warn!("gas estimator {} failed: {:?}", i, err);
+                } else {
+                    tracing::error!("gas estimator {} failed: {:?}", i, err);
                 }
             }
         }
-        Err(anyhow!("all gas estimators failed"))
+        result
     }
 }
 
 #[async_trait::async_trait]
 impl GasPriceEstimating for PriorityGasPriceEstimating {
+    fn source(&self) -> &'static str {
+        "priority"
+    }
+
     async fn estimate_with_limits(
         &self,
         gas_limit: f64,
         time_limit: Duration,
     ) -> Result<EstimatedGasPrice> {
-        self.prioritize(|estimator| estimator.estimate_with_limits(gas_limit, time_limit))
-            .await
+        trace::estimate(
+            self.source(),
+            None,
+            self.prioritize(|estimator| estimator.estimate_with_limits(gas_limit, time_limit)),
+        )
+        .await
     }
 
     async fn estimate(&self) -> Result<EstimatedGasPrice> {
-        self.prioritize(|estimator| estimator.estimate()).await
+        trace::estimate(
+            self.source(),
+            None,
+            self.prioritize(|estimator| estimator.estimate()),
+        )
+        .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        trace::estimate(
+            self.source(),
+            None,
+            self.prioritize(|estimator| {
+                estimator.estimate_with_hints(gas_limit, time_limit, hints)
+            }),
+        )
+        .await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.prioritize(|estimator| estimator.estimate_verbose(gas_limit, time_limit))
+            .await
+    }
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        trace::estimate(
+            self.source(),
+            None,
+            self.prioritize(|estimator| estimator.estimate_for_tx(tx)),
+        )
+        .await
     }
 }
 
@@ -108,7 +385,7 @@ mod tests {
         estimator_0
             .expect_estimate()
             .times(1)
-            .returning(|| Err(anyhow!("")));
+            .returning(|| Err(anyhow!("").into()));
         estimator_1.expect_estimate().times(1).returning(|| {
             Ok(EstimatedGasPrice {
                 legacy: 2.0,
@@ -122,6 +399,231 @@ mod tests {
         assert_approx_eq!(result.legacy, 2.0);
     }
 
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn passes_hints() {
+        // like the mempool estimator which does not estimate private transactions
+        let mut estimator_0 = MockGasPriceEstimating::new();
+        let mut estimator_1 = MockGasPriceEstimating::new();
+        estimator_0
+            .expect_estimate_with_hints()
+            .times(2)
+            .returning(|_, _, hints| match hints.is_private() {
+                true => Err(anyhow!("").into()),
+                false => Ok(price(1.0)),
+            });
+        estimator_1
+            .expect_estimate_with_hints()
+            .times(1)
+            .returning(|_, _, _| Ok(price(2.0)));
+
+        let priority =
+            PriorityGasPriceEstimating::new(vec![Box::new(estimator_0), Box::new(estimator_1)]);
+        let estimate = |hints| {
+            priority
+                .estimate_with_hints(21000.0, Duration::from_secs(30), &hints)
+                .now_or_never()
+                .unwrap()
+                .unwrap()
+                .legacy
+        };
+        assert_approx_eq!(estimate(EstimationHints::default()), 1.0);
+        assert_approx_eq!(estimate(EstimationHints::private()), 2.0);
+    }
+
+    #[test]
+    fn unsupported_is_not_a_failure() {
+        let mut estimator_0 = MockGasPriceEstimating::new();
+        let mut estimator_1 = MockGasPriceEstimating::new();
+        estimator_0
+            .expect_estimate_with_hints()
+            .times(3)
+            .returning(|_, _, _| Err(GasEstimationError::Unsupported("private".into())));
+        estimator_1
+            .expect_estimate_with_hints()
+            .times(3)
+            .returning(|_, _, _| Ok(price(2.0)));
+
+        let priority =
+            PriorityGasPriceEstimating::new(vec![Box::new(estimator_0), Box::new(estimator_1)])
+                .with_health_ordering(HealthParams {
+                    smoothing: 0.3,
+                    ..Default::default()
+                });
+        for _ in 0..3 {
+            let result = priority
+                .estimate_with_hints(
+                    21000.0,
+                    Duration::from_secs(30),
+                    &EstimationHints::private(),
+                )
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+            assert_approx_eq!(result.legacy, 2.0);
+        }
+        assert!(priority.failed_in_last_call().is_empty());
+        let ranking = priority.ranking();
+        assert_eq!(ranking[0].index, 0);
+        assert!(ranking[0].healthy);
+        assert_approx_eq!(ranking[0].success_rate, 1.0);
+    }
+
+    #[test]
+    fn health_ordering_moves_failing_estimator_back() {
+        let mut estimator_0 = MockGasPriceEstimating::new();
+        let mut estimator_1 = MockGasPriceEstimating::new();
+
+        // Fails twice which brings the success rate below 0.5 with smoothing 0.3 after which it is
+        // no longer tried first.
+        estimator_0
+            .expect_estimate()
+            .times(2)
+            .returning(|| Err(anyhow!("").into()));
+        estimator_1
+            .expect_estimate()
+            .times(3)
+            .returning(|| Ok(price(2.0)));
+
+        let priority =
+            PriorityGasPriceEstimating::new(vec![Box::new(estimator_0), Box::new(estimator_1)])
+                .with_health_ordering(HealthParams {
+                    smoothing: 0.3,
+                    ..Default::default()
+                });
+        for _ in 0..3 {
+            let result = priority.estimate().now_or_never().unwrap().unwrap();
+            assert_approx_eq!(result.legacy, 2.0);
+        }
+        let ranking = priority.ranking();
+        assert_eq!(ranking[0].index, 1);
+        assert!(ranking[0].healthy);
+        assert_eq!(ranking[1].index, 0);
+        assert!(!ranking[1].healthy);
+        assert_approx_eq!(ranking[1].success_rate, 0.49);
+    }
+
+    #[test]
+    fn unhealthy_estimator_is_rechecked() {
+        let priority = PriorityGasPriceEstimating::new(vec![
+            Box::new(MockGasPriceEstimating::new()),
+            Box::new(MockGasPriceEstimating::new()),
+        ])
+        .with_health_ordering(Default::default());
+        let now = Instant::now();
+        *priority.estimators[0].health.lock().unwrap() = Health {
+            success_rate: 0.0,
+            average_latency: None,
+            last_attempt: Some(now),
+        };
+        let order = |now| {
+            priority
+                .ranking_at(now)
+                .iter()
+                .map(|health| health.index)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(now), vec![1, 0]);
+        assert_eq!(order(now + Duration::from_secs(60)), vec![0, 1]);
+    }
+
+    #[test]
+    fn recheck_uses_clock() {
+        let mut estimator_0 = MockGasPriceEstimating::new();
+        let mut estimator_1 = MockGasPriceEstimating::new();
+        estimator_0
+            .expect_estimate()
+            .times(2)
+            .returning(|| Err(anyhow!("").into()));
+        estimator_1
+            .expect_estimate()
+            .times(2)
+            .returning(|| Ok(price(2.0)));
+        let clock = crate::time::tests::ManualClock::default();
+        let priority =
+            PriorityGasPriceEstimating::new(vec![Box::new(estimator_0), Box::new(estimator_1)])
+                .with_health_ordering(HealthParams {
+                    min_success_rate: 0.9,
+                    ..Default::default()
+                })
+                .with_clock(clock.clone());
+        priority.estimate().now_or_never().unwrap().unwrap();
+        assert_eq!(priority.ranking()[0].index, 1);
+        clock.advance(Duration::from_secs(60));
+        // the failing estimator is tried first again after the recheck interval
+        assert_eq!(priority.ranking()[0].index, 0);
+        priority.estimate().now_or_never().unwrap().unwrap();
+    }
+
+    #[test]
+    fn slow_estimator_is_unhealthy() {
+        let params = HealthParams {
+            max_latency: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let mut health = Health::default();
+        health.record(&params, now, Duration::from_millis(100), true);
+        assert!(health.is_healthy(&params, now));
+        health.record(&params, now, Duration::from_secs(10), true);
+        assert!(!health.is_healthy(&params, now));
+    }
+
+    #[test]
+    fn ranking_without_health_ordering_is_fixed() {
+        let priority = PriorityGasPriceEstimating::new(vec![
+            Box::new(MockGasPriceEstimating::new()),
+            Box::new(MockGasPriceEstimating::new()),
+        ]);
+        priority.estimators[0].health.lock().unwrap().success_rate = 0.0;
+        let order = priority
+            .ranking()
+            .iter()
+            .map(|health| health.index)
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec![0, 1]);
+    }
+
+    #[test]
+    fn verbose_reports_source_of_used_estimator() {
+        let mut estimator_0 = MockGasPriceEstimating::new();
+        let mut estimator_1 = MockGasPriceEstimating::new();
+
+        estimator_0
+            .expect_estimate_verbose()
+            .times(1)
+            .returning(|_, _| Err(anyhow!("").into()));
+        estimator_1
+            .expect_estimate_verbose()
+            .times(1)
+            .returning(|_, _| {
+                Ok(EstimateWithMetadata {
+                    price: price(2.0),
+                    source: "second",
+                    observed_at: Instant::now(),
+                    confidence: None,
+                    divergence: None,
+                    range: None,
+                })
+            });
+
+        let priority =
+            PriorityGasPriceEstimating::new(vec![Box::new(estimator_0), Box::new(estimator_1)]);
+        let result = priority
+            .estimate_verbose(0.0, Duration::ZERO)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.source, "second");
+        assert_approx_eq!(result.price.legacy, 2.0);
+    }
+
     #[test]
     fn prioritize_fails_if_all_fail() {
         let mut estimator_0 = MockGasPriceEstimating::new();
@@ -130,15 +632,45 @@ mod tests {
         estimator_0
             .expect_estimate()
             .times(1)
-            .returning(|| Err(anyhow!("")));
+            .returning(|| Err(anyhow!("").into()));
         estimator_1
             .expect_estimate()
             .times(1)
-            .returning(|| Err(anyhow!("")));
+            .returning(|| Err(anyhow!("").into()));
 
         let priority =
             PriorityGasPriceEstimating::new(vec![Box::new(estimator_0), Box::new(estimator_1)]);
         let result = priority.estimate().now_or_never().unwrap();
         assert!(result.is_err());
     }
+
+    #[test]
+    fn query_all_reports_failures() {
+        let mut estimator_0 = MockGasPriceEstimating::new();
+        let mut estimator_1 = MockGasPriceEstimating::new();
+        let mut estimator_2 = MockGasPriceEstimating::new();
+
+        estimator_0
+            .expect_estimate()
+            .times(1)
+            .returning(|| Err(anyhow!("").into()));
+        estimator_1
+            .expect_estimate()
+            .times(1)
+            .returning(|| Ok(price(1.0)));
+        estimator_2
+            .expect_estimate()
+            .times(1)
+            .returning(|| Err(anyhow!("").into()));
+
+        let priority = PriorityGasPriceEstimating::new(vec![
+            Box::new(estimator_0),
+            Box::new(estimator_1),
+            Box::new(estimator_2),
+        ])
+        .with_query_all();
+        let result = priority.estimate().now_or_never().unwrap().unwrap();
+        assert_approx_eq!(result.legacy, 1.0);
+        assert_eq!(priority.failed_in_last_call(), vec![0, 2]);
+    }
 }
@@ -0,0 +1,258 @@
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasEstimationError,
+    GasPriceEstimating, TransactionCost, TxSpec,
+};
+use anyhow::anyhow;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::{future::Future, time::Duration};
+use tokio::time::{timeout_at, Instant};
+
+// Queries all estimators concurrently and returns the first successful estimate so that a slow
+// estimator does not delay the result.
+// With a grace period the racer waits that long after the first success for estimators that come
+// earlier in the list, which are preferred.
+pub struct RacingGasPriceEstimating {
+    estimators: Vec<Box<dyn GasPriceEstimating>>,
+    deadline: Duration,
+    grace_period: Duration,
+}
+
+impl RacingGasPriceEstimating {
+    // Fails with `GasEstimationError::Timeout` if no estimator succeeds within `deadline`.
+    pub fn new(estimators: Vec<Box<dyn GasPriceEstimating>>, deadline: Duration) -> Self {
+        Self {
+            estimators,
+            deadline,
+            grace_period: Duration::ZERO,
+        }
+    }
+
+    pub fn with_grace_period(self, grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            ..self
+        }
+    }
+
+    async fn race<'a, T, F, R>(&'a self, operation: T) -> Result<R>
+    where
+        T: Fn(&'a dyn GasPriceEstimating) -> F,
+        F: Future<Output = Result<R>>,
+    {
+        let deadline = Instant::now() + self.deadline;
+        let mut pending = self
+            .estimators
+            .iter()
+            .enumerate()
+            .map(|(i, estimator)| {
+                let future = operation(estimator.as_ref());
+                async move { (i, future.await) }
+            })
+            .collect::<FuturesUnordered<_>>();
+        let mut finished = vec![false; self.estimators.len()];
+        // The successful estimate of the estimator with the lowest index so far.
+        let mut best: Option<(usize, R)> = None;
+        let mut wait_until = deadline;
+
+        loop {
+            let (i, result) = match timeout_at(wait_until, pending.next()).await {
+                Ok(Some(next)) => next,
+                // all estimators finished or the grace period is over
+                Ok(None) | Err(_) if best.is_some() => break,
+                Ok(None) => return Err(anyhow!("all gas estimators failed").into()),
+                Err(_) => return Err(GasEstimationError::Timeout),
+            };
+            finished[i] = true;
+            match result {
+                Ok(estimate) => {
+                    if best.is_none() {
+                        wait_until = deadline.min(Instant::now() + self.grace_period);
+                    }
+                    if best
+                        .as_ref()
+                        .map_or(true, |(best_index, _)| i < *best_index)
+                    {
+                        best = Some((i, estimate));
+                    }
+                }
+                Err(err) => tracing::warn!("gas estimator {} failed: {:?}", i, err),
+            }
+            if let Some((best_index, _)) = &best {
+                // No estimator that is preferred over the best one is still running.
+                if finished[..*best_index].iter().all(|finished| *finished) {
+                    break;
+                }
+            }
+        }
+        let (_, estimate) = best.expect("loop only breaks with a result");
+        Ok(estimate)
+    }
+}
+
+#[async_trait::async_trait]
+impl GasPriceEstimating for RacingGasPriceEstimating {
+    fn source(&self) -> &'static str {
+        "racing"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.race(|estimator| estimator.estimate_with_limits(gas_limit, time_limit))
+            .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.race(|estimator| estimator.estimate()).await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.race(|estimator| estimator.estimate_with_hints(gas_limit, time_limit, hints))
+            .await
+    }
+
+    // Reports the metadata of the estimator whose estimate was picked.
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.race(|estimator| estimator.estimate_verbose(gas_limit, time_limit))
+            .await
+    }
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.race(|estimator| estimator.estimate_for_tx(tx)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Delayed {
+        delay: Duration,
+        legacy: Option<f64>,
+        source: &'static str,
+    }
+
+    fn delayed(millis: u64, legacy: Option<f64>) -> Box<dyn GasPriceEstimating> {
+        Box::new(Delayed {
+            delay: Duration::from_millis(millis),
+            legacy,
+            source: "delayed",
+        })
+    }
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for Delayed {
+        fn source(&self) -> &'static str {
+            self.source
+        }
+
+        async fn estimate_with_limits(
+            &self,
+            _gas_limit: f64,
+            _time_limit: Duration,
+        ) -> Result<EstimatedGasPrice> {
+            tokio::time::sleep(self.delay).await;
+            match self.legacy {
+                Some(legacy) => Ok(EstimatedGasPrice {
+                    legacy,
+                    ..Default::default()
+                }),
+                None => Err(anyhow!("").into()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_first_success() {
+        let racing = RacingGasPriceEstimating::new(
+            vec![
+                delayed(500, Some(1.0)),
+                delayed(0, None),
+                delayed(10, Some(3.0)),
+            ],
+            Duration::from_secs(5),
+        );
+        let start = Instant::now();
+        assert_eq!(racing.estimate().await.unwrap().legacy, 3.0);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn verbose_reports_winning_source() {
+        let racing = RacingGasPriceEstimating::new(
+            vec![
+                delayed(500, Some(1.0)),
+                Box::new(Delayed {
+                    delay: Duration::ZERO,
+                    legacy: Some(2.0),
+                    source: "fast",
+                }),
+            ],
+            Duration::from_secs(5),
+        );
+        let estimate = racing
+            .estimate_verbose(21000.0, Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(estimate.source, "fast");
+        assert_eq!(estimate.price.legacy, 2.0);
+    }
+
+    #[tokio::test]
+    async fn grace_period_prefers_earlier_estimators() {
+        let racing = RacingGasPriceEstimating::new(
+            vec![delayed(50, Some(1.0)), delayed(0, Some(2.0))],
+            Duration::from_secs(5),
+        )
+        .with_grace_period(Duration::from_secs(1));
+        let start = Instant::now();
+        assert_eq!(racing.estimate().await.unwrap().legacy, 1.0);
+        // returns as soon as the preferred estimator finished instead of waiting for the grace
+        // period
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn grace_period_expires() {
+        let racing = RacingGasPriceEstimating::new(
+            vec![delayed(5000, Some(1.0)), delayed(0, Some(2.0))],
+            Duration::from_secs(10),
+        )
+        .with_grace_period(Duration::from_millis(10));
+        assert_eq!(racing.estimate().await.unwrap().legacy, 2.0);
+    }
+
+    #[tokio::test]
+    async fn fails_if_all_fail() {
+        let racing = RacingGasPriceEstimating::new(
+            vec![delayed(0, None), delayed(0, None)],
+            Duration::from_secs(5),
+        );
+        assert!(matches!(
+            racing.estimate().await,
+            Err(GasEstimationError::Other(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let racing = RacingGasPriceEstimating::new(
+            vec![delayed(5000, Some(1.0))],
+            Duration::from_millis(10),
+        );
+        assert!(matches!(
+            racing.estimate().await,
+            Err(GasEstimationError::Timeout)
+        ));
+    }
+}
@@ -0,0 +1,470 @@
+use super::{
+    error::Result, gas_price::StoredEstimate, time::Instant, EstimateWithMetadata,
+    EstimatedGasPrice, EstimationHints, GasEstimationError, GasPriceEstimating, GasPriceSchedule,
+    TransactionCost, TxSpec,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Token bucket allowing `max_requests` requests per `window`. Requests are spread evenly: a token
+/// is refilled every `window / max_requests`. Share one limiter (through an `Arc`) between all
+/// estimators that use the same rate limited api.
+pub struct RateLimiter {
+    capacity: f64,
+    window: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    // The time the tokens were last refilled. `None` until the first request.
+    refilled: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        let capacity = max_requests as f64;
+        Self {
+            capacity,
+            window,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                refilled: None,
+            }),
+        }
+    }
+
+    /// Takes a token if one is available. Otherwise returns how long it takes until the next token
+    /// is available.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        if self.capacity <= 0.0 {
+            return Err(self.window);
+        }
+        let refill_rate = self.capacity / self.window.as_secs_f64();
+        if let Some(refilled) = bucket.refilled {
+            let elapsed = now.saturating_duration_since(refilled).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * refill_rate).min(self.capacity);
+        }
+        // Keep the refill time monotonic when callers race with slightly older instants.
+        bucket.refilled = Some(bucket.refilled.map_or(now, |refilled| refilled.max(now)));
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill_rate))
+        }
+    }
+}
+
+/// How old the last estimate returned while throttled can be by default.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+// Limits the calls to the inner estimator with a (possibly shared) rate limiter. When throttled
+// the last estimate for the same limits is returned if it is younger than the max age. Otherwise
+// the call fails with `GasEstimationError::RateLimited`.
+pub struct RateLimitedEstimator<T> {
+    inner: T,
+    limiter: Arc<RateLimiter>,
+    max_age: Duration,
+    // The estimates and the time they were fetched.
+    last_estimates: Mutex<HashMap<Key, (Instant, StoredEstimate)>>,
+}
+
+// Callers usually use a few fixed limits so this is only reached if the limits vary between
+// requests, for example with a time limit computed by the caller.
+const MAX_LAST_ESTIMATES: usize = 64;
+
+// `None` is used for `estimate` because estimators can implement it differently from
+// `estimate_with_limits`.
+type Key = Option<(u64, Duration)>;
+
+impl<T: GasPriceEstimating> RateLimitedEstimator<T> {
+    pub fn new(inner: T, limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            inner,
+            limiter,
+            max_age: DEFAULT_MAX_AGE,
+            last_estimates: Default::default(),
+        }
+    }
+
+    // Throttled calls fail instead of returning an estimate older than `max_age`.
+    pub fn with_max_age(self, max_age: Duration) -> Self {
+        Self { max_age, ..self }
+    }
+
+    async fn estimate_rate_limited<Fut>(
+        &self,
+        now: Instant,
+        key: Key,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<EstimatedGasPrice>
+    where
+        Fut: Future<Output = Result<EstimatedGasPrice>>,
+    {
+        let fetch = || async {
+            let price = fetch().await?;
+            Ok(StoredEstimate::Plain {
+                price,
+                observed_at: now,
+            })
+        };
+        self.stored_rate_limited(now, key, fetch)
+            .await
+            .map(|estimate| estimate.price())
+    }
+
+    // Serves `estimate_verbose`, plain and verbose calls for the same limits share the last
+    // estimate.
+    async fn verbose_rate_limited<Fut>(
+        &self,
+        now: Instant,
+        key: Key,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<EstimateWithMetadata>
+    where
+        Fut: Future<Output = Result<EstimateWithMetadata>>,
+    {
+        let fetch = || async { Ok(StoredEstimate::Verbose(fetch().await?)) };
+        self.stored_rate_limited(now, key, fetch)
+            .await
+            .map(|estimate| estimate.with_metadata(|| self.inner.source()))
+    }
+
+    async fn stored_rate_limited<Fut>(
+        &self,
+        now: Instant,
+        key: Key,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<StoredEstimate>
+    where
+        Fut: Future<Output = Result<StoredEstimate>>,
+    {
+        if let Err(retry_after) = self.limiter.try_acquire_at(now) {
+            return match self.last_estimates.lock().unwrap().get(&key) {
+                Some((time, estimate)) if now.saturating_duration_since(*time) <= self.max_age => {
+                    Ok(*estimate)
+                }
+                _ => Err(GasEstimationError::RateLimited {
+                    retry_after: Some(retry_after),
+                }),
+            };
+        }
+        let result = fetch().await;
+        if let Ok(estimate) = &result {
+            let mut last_estimates = self.last_estimates.lock().unwrap();
+            self.remember(&mut last_estimates, now, key, *estimate);
+        }
+        result
+    }
+
+    // Serves several limits with one call of the inner estimator and one token. When throttled the
+    // last estimates are returned if there is one for all of the limits.
+    async fn many_rate_limited<Fut>(
+        &self,
+        now: Instant,
+        requests: &[(f64, Duration)],
+        fetch: impl FnOnce() -> Fut,
+    ) -> Result<Vec<EstimatedGasPrice>>
+    where
+        Fut: Future<Output = Result<Vec<EstimatedGasPrice>>>,
+    {
+        let key =
+            |(gas_limit, time_limit): &(f64, Duration)| Some((gas_limit.to_bits(), *time_limit));
+        if let Err(retry_after) = self.limiter.try_acquire_at(now) {
+            let last_estimates = self.last_estimates.lock().unwrap();
+            return requests
+                .iter()
+                .map(|limits| match last_estimates.get(&key(limits)) {
+                    Some((time, estimate))
+                        if now.saturating_duration_since(*time) <= self.max_age =>
+                    {
+                        Ok(estimate.price())
+                    }
+                    _ => Err(GasEstimationError::RateLimited {
+                        retry_after: Some(retry_after),
+                    }),
+                })
+                .collect();
+        }
+        let estimates = fetch().await?;
+        let mut last_estimates = self.last_estimates.lock().unwrap();
+        for (limits, price) in requests.iter().zip(&estimates) {
+            let estimate = StoredEstimate::Plain {
+                price: *price,
+                observed_at: now,
+            };
+            self.remember(&mut last_estimates, now, key(limits), estimate);
+        }
+        Ok(estimates)
+    }
+
+    // Estimates older than the max age are never returned so they are dropped first. If there are
+    // still `MAX_LAST_ESTIMATES` left the oldest one is replaced.
+    fn remember(
+        &self,
+        last_estimates: &mut HashMap<Key, (Instant, StoredEstimate)>,
+        now: Instant,
+        key: Key,
+        estimate: StoredEstimate,
+    ) {
+        if last_estimates.len() >= MAX_LAST_ESTIMATES && !last_estimates.contains_key(&key) {
+            last_estimates
+                .retain(|_, (time, _)| now.saturating_duration_since(*time) <= self.max_age);
+            if last_estimates.len() >= MAX_LAST_ESTIMATES {
+                let oldest = last_estimates
+                    .iter()
+                    .min_by_key(|(_, (time, _))| *time)
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    last_estimates.remove(&oldest);
+                }
+            }
+        }
+        last_estimates.insert(key, (now, estimate));
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for RateLimitedEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let key = Some((gas_limit.to_bits(), time_limit));
+        self.estimate_rate_limited(Instant::now(), key, || {
+            self.inner.estimate_with_limits(gas_limit, time_limit)
+        })
+        .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.estimate_rate_limited(Instant::now(), None, || self.inner.estimate())
+            .await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let key = Some((gas_limit.to_bits(), time_limit));
+        self.verbose_rate_limited(Instant::now(), key, || {
+            self.inner.estimate_verbose(gas_limit, time_limit)
+        })
+        .await
+    }
+
+    // Hinted estimates are for one transaction so throttled calls fail instead of returning the
+    // last estimate.
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.limiter
+            .try_acquire_at(Instant::now())
+            .map_err(|retry_after| GasEstimationError::RateLimited {
+                retry_after: Some(retry_after),
+            })?;
+        self.inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await
+    }
+
+    // The cost depends on the transaction data so throttled calls fail instead of returning the
+    // last cost.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.limiter
+            .try_acquire_at(Instant::now())
+            .map_err(|retry_after| GasEstimationError::RateLimited {
+                retry_after: Some(retry_after),
+            })?;
+        self.inner.estimate_for_tx(tx).await
+    }
+
+    // Shares the last estimates of `estimate_with_limits` for the urgencies with the default gas
+    // limit.
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let requests = GasPriceSchedule::requests();
+        let estimates = self
+            .many_rate_limited(Instant::now(), &requests, || async {
+                let schedule = self.inner.estimate_schedule().await?;
+                Ok(schedule
+                    .entries()
+                    .iter()
+                    .map(|(_, estimate)| *estimate)
+                    .collect())
+            })
+            .await?;
+        GasPriceSchedule::from_requested(estimates)
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.many_rate_limited(Instant::now(), requests, || {
+            self.inner.estimate_many(requests)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+    use std::future::{ready, Pending};
+
+    fn panic_future() -> Pending<Result<EstimatedGasPrice>> {
+        panic!()
+    }
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bounds_last_estimates() {
+        let limiter = Arc::new(RateLimiter::new(1000, Duration::from_secs(1)));
+        let estimator = RateLimitedEstimator::new(MockGasPriceEstimating::new(), limiter)
+            .with_max_age(Duration::from_secs(10));
+        let now = Instant::now();
+        let estimate = |time: Instant, i: usize| {
+            let key = Some((21000f64.to_bits(), Duration::from_millis(i as u64)));
+            estimator
+                .estimate_rate_limited(time, key, || ready(Ok(price(i as f64))))
+                .wait()
+                .unwrap();
+        };
+        for i in 0..=MAX_LAST_ESTIMATES {
+            estimate(now, i);
+        }
+        assert_eq!(
+            estimator.last_estimates.lock().unwrap().len(),
+            MAX_LAST_ESTIMATES
+        );
+        // all estimates are older than the max age
+        estimate(now + Duration::from_secs(11), MAX_LAST_ESTIMATES + 1);
+        assert_eq!(estimator.last_estimates.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn bucket_refills_over_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(now).is_ok());
+        assert!(limiter.try_acquire_at(now).is_ok());
+        assert_eq!(limiter.try_acquire_at(now), Err(Duration::from_secs(5)));
+        let retry_after = limiter
+            .try_acquire_at(now + Duration::from_secs(3))
+            .unwrap_err();
+        assert!((retry_after.as_secs_f64() - 2.0).abs() < 1e-6);
+        assert!(limiter.try_acquire_at(now + Duration::from_secs(5)).is_ok());
+        // does not accumulate more than the capacity
+        let later = now + Duration::from_secs(100);
+        assert!(limiter.try_acquire_at(later).is_ok());
+        assert!(limiter.try_acquire_at(later).is_ok());
+        assert!(limiter.try_acquire_at(later).is_err());
+    }
+
+    #[test]
+    fn zero_capacity_never_acquires() {
+        let limiter = RateLimiter::new(0, Duration::from_secs(1));
+        assert!(limiter.try_acquire_at(Instant::now()).is_err());
+    }
+
+    #[test]
+    fn serves_last_estimate_when_throttled() {
+        let limiter = Arc::new(RateLimiter::new(1, Duration::from_secs(10)));
+        let estimator = RateLimitedEstimator::new(MockGasPriceEstimating::new(), limiter);
+        let now = Instant::now();
+        let key = Some((1, Duration::from_secs(1)));
+        assert_eq!(
+            estimator
+                .estimate_rate_limited(now, key, || ready(Ok(price(1.0))))
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+        // panic_future isn't called
+        assert_eq!(
+            estimator
+                .estimate_rate_limited(now, key, panic_future)
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+        // no previous estimate for other limits
+        assert!(matches!(
+            estimator
+                .estimate_rate_limited(now, None, panic_future)
+                .wait(),
+            Err(GasEstimationError::RateLimited {
+                retry_after: Some(_)
+            })
+        ));
+    }
+
+    #[test]
+    fn old_estimates_are_not_served() {
+        let limiter = Arc::new(RateLimiter::new(1, Duration::from_secs(1000)));
+        let estimator = RateLimitedEstimator::new(MockGasPriceEstimating::new(), limiter)
+            .with_max_age(Duration::from_secs(10));
+        let now = Instant::now();
+        estimator
+            .estimate_rate_limited(now, None, || ready(Ok(price(1.0))))
+            .wait()
+            .unwrap();
+        assert_eq!(
+            estimator
+                .estimate_rate_limited(now + Duration::from_secs(10), None, panic_future)
+                .wait()
+                .unwrap(),
+            price(1.0)
+        );
+        assert!(matches!(
+            estimator
+                .estimate_rate_limited(now + Duration::from_secs(11), None, panic_future)
+                .wait(),
+            Err(GasEstimationError::RateLimited {
+                retry_after: Some(_)
+            })
+        ));
+    }
+
+    #[test]
+    fn limiter_is_shared() {
+        let limiter = Arc::new(RateLimiter::new(1, Duration::from_secs(10)));
+        let mut first = MockGasPriceEstimating::new();
+        first
+            .expect_estimate()
+            .times(1)
+            .returning(|| Ok(price(1.0)));
+        let first = RateLimitedEstimator::new(first, limiter.clone());
+        let second = RateLimitedEstimator::new(MockGasPriceEstimating::new(), limiter);
+        assert_eq!(first.estimate().wait().unwrap(), price(1.0));
+        assert!(second.estimate().wait().is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Readiness of the estimators that update their estimates in the background, so that services can
+//! gate their health endpoints on having a fresh estimate instead of serving errors for the first
+//! seconds after startup.
+
+/// Implemented by estimators that serve estimates from memory, like the websocket and polling
+/// estimators.
+#[async_trait::async_trait]
+pub trait Readiness: Send + Sync {
+    /// Whether estimates currently succeed because a fresh update has been received.
+    async fn ready(&self) -> bool;
+}
+
+#[async_trait::async_trait]
+impl<T: Readiness + ?Sized> Readiness for Box<T> {
+    async fn ready(&self) -> bool {
+        self.as_ref().ready().await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Readiness + ?Sized> Readiness for std::sync::Arc<T> {
+    async fn ready(&self) -> bool {
+        self.as_ref().ready().await
+    }
+}
+
+/// Whether all estimators are ready, for example the ones behind a priority estimator.
+pub async fn all_ready(estimators: &[&dyn Readiness]) -> bool {
+    futures::future::join_all(estimators.iter().map(|estimator| estimator.ready()))
+        .await
+        .into_iter()
+        .all(|ready| ready)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+
+    struct Fixed(bool);
+
+    #[async_trait::async_trait]
+    impl Readiness for Fixed {
+        async fn ready(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn all_must_be_ready() {
+        let boxed: Box<dyn Readiness> = Box::new(Fixed(true));
+        assert!(all_ready(&[&Fixed(true), &boxed]).wait());
+        assert!(!all_ready(&[&Fixed(true), &Fixed(false)]).wait());
+        assert!(all_ready(&[]).wait());
+    }
+}
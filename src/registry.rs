@@ -0,0 +1,390 @@
+//! Maps chain ids to the estimator used for that chain so that services estimating on several
+//! chains don't have to maintain the mapping themselves.
+
+use super::{
+    chain::{self, ChainConfig},
+    error::Result,
+    ArbitrumGasEstimator, EstimatedGasPrice, EthGasStation, EtherscanGasStation,
+    GasEstimationError, GasNowGasStation, GasPriceEstimating, GnosisChainGasStation,
+    GnosisSafeGasStation, LineaGasEstimator, OpStackGasEstimator, PolygonGasStation,
+    PriorityGasPriceEstimating, RpcPercentileEstimator, ScrollGasEstimator, Transport,
+    ZkSyncGasEstimator,
+};
+use std::{collections::HashMap, time::Duration};
+
+#[derive(Default)]
+pub struct ChainEstimatorRegistry {
+    estimators: HashMap<u64, Box<dyn GasPriceEstimating>>,
+}
+
+impl ChainEstimatorRegistry {
+    /// Registers `default_estimator` for every chain it supports. `node_urls` maps chain ids to
+    /// JSON-RPC node urls, chains that only have node based estimators are skipped without one.
+    pub fn with_defaults<T: Transport + Clone + 'static>(
+        transport: T,
+        node_urls: &HashMap<u64, String>,
+    ) -> Self {
+        let estimators = SUPPORTED_CHAINS
+            .iter()
+            .filter_map(|chain_id| {
+                let node_url = node_urls.get(chain_id).map(String::as_str);
+                let estimator = default_estimator(*chain_id, transport.clone(), node_url)?;
+                Some((*chain_id, estimator))
+            })
+            .collect();
+        Self { estimators }
+    }
+
+    /// Replaces the estimator for the chain, for example with one that needs an api key.
+    pub fn with_estimator(mut self, chain_id: u64, estimator: Box<dyn GasPriceEstimating>) -> Self {
+        self.estimators.insert(chain_id, estimator);
+        self
+    }
+
+    pub fn get(&self, chain_id: u64) -> Option<&dyn GasPriceEstimating> {
+        self.estimators.get(&chain_id).map(AsRef::as_ref)
+    }
+
+    pub fn chain_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.estimators.keys().copied()
+    }
+
+    /// Fails with `GasEstimationError::Unsupported` if no estimator is registered for the chain.
+    pub async fn estimate_for_chain(
+        &self,
+        chain_id: u64,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.get(chain_id)
+            .ok_or_else(|| {
+                GasEstimationError::Unsupported(format!("no estimator for chain {}", chain_id))
+            })?
+            .estimate_with_limits(gas_limit, time_limit)
+            .await
+    }
+}
+
+const SUPPORTED_CHAINS: &[u64] = &[
+    1, 10, 56, 100, 137, 250, 324, 8453, 42161, 43114, 59144, 80001, 534352,
+];
+
+/// The estimator we use by default for the chain. Estimators that need an api key or a background
+/// task (like Blocknative on mainnet) are not included and can be added with
+/// `ChainEstimatorRegistry::with_estimator`.
+pub fn default_estimator<T: Transport + Clone + 'static>(
+    chain_id: u64,
+    transport: T,
+    node_url: Option<&str>,
+) -> Option<Box<dyn GasPriceEstimating>> {
+    let node_url = node_url.map(String::from);
+    match chain_id {
+        1 => {
+            let mut estimators: Vec<Box<dyn GasPriceEstimating>> = vec![
+                Box::new(GasNowGasStation::new(transport.clone())),
+                Box::new(EtherscanGasStation::new(transport.clone())),
+            ];
+            if let Some(node_url) = node_url {
+                estimators.push(Box::new(RpcPercentileEstimator::new(
+                    transport,
+                    node_url,
+                    chain::ETHEREUM,
+                    None,
+                )));
+            }
+            Some(Box::new(PriorityGasPriceEstimating::new(estimators)))
+        }
+        137 | 80001 => PolygonGasStation::with_network_id(&chain_id.to_string(), transport)
+            .ok()
+            .map(|estimator| Box::new(estimator) as Box<dyn GasPriceEstimating>),
+        100 => Some(Box::new(GnosisChainGasStation::new(transport))),
+        10 | 8453 | 42161 | 324 | 59144 | 534352 | 56 | 43114 | 250 => {
+            node_estimator(chain_id, transport, node_url?)
+        }
+        _ => None,
+    }
+}
+
+// The estimator of the node of the chain.
+fn node_estimator<T: Transport + 'static>(
+    chain_id: u64,
+    transport: T,
+    node_url: String,
+) -> Option<Box<dyn GasPriceEstimating>> {
+    match chain_id {
+        10 | 8453 => Some(Box::new(OpStackGasEstimator::new(
+            transport, node_url, None,
+        ))),
+        42161 => Some(Box::new(ArbitrumGasEstimator::new(transport, node_url))),
+        324 => Some(Box::new(ZkSyncGasEstimator::new(transport, node_url))),
+        59144 => Some(Box::new(LineaGasEstimator::new(transport, node_url, None))),
+        534352 => Some(Box::new(ScrollGasEstimator::new(transport, node_url, None))),
+        _ => Some(Box::new(RpcPercentileEstimator::new(
+            transport,
+            node_url,
+            ChainConfig::from_chain_id(chain_id)?,
+            None,
+        ))),
+    }
+}
+
+// The environment variables read by `default_estimator_from_env`.
+#[derive(Debug, Default)]
+struct Env {
+    node_url: Option<String>,
+    blocknative_api_key: Option<String>,
+    gas_estimators: Option<String>,
+}
+
+/// Assembles the estimator for the chain from environment variables like our services do:
+/// - `NODE_URL`: the JSON-RPC node used by the node based estimators.
+/// - `BLOCKNATIVE_API_KEY`: with the `tokio_` feature Blocknative is tried before the mainnet
+///   defaults.
+/// - `GAS_ESTIMATORS`: comma separated sources in order of priority that replace the defaults.
+///   Known are `GasNow`, `EthGasStation`, `Etherscan`, `GnosisSafe`, `Polygon`, `GnosisChain`,
+///   `Node` and `BlockNative`, case insensitively and ignoring surrounding whitespace.
+///
+/// Without `GAS_ESTIMATORS` this is `default_estimator`. Fails with
+/// `GasEstimationError::Unsupported` for unknown sources, empty entries like in `GasNow,,` and
+/// sources that are missing their variables.
+pub async fn default_estimator_from_env<T: Transport + Clone + 'static>(
+    chain_id: u64,
+    transport: T,
+) -> Result<Box<dyn GasPriceEstimating>> {
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let env = Env {
+        node_url: var("NODE_URL"),
+        blocknative_api_key: var("BLOCKNATIVE_API_KEY"),
+        gas_estimators: var("GAS_ESTIMATORS"),
+    };
+    estimator_from_env(chain_id, transport, env).await
+}
+
+async fn estimator_from_env<T: Transport + Clone + 'static>(
+    chain_id: u64,
+    transport: T,
+    env: Env,
+) -> Result<Box<dyn GasPriceEstimating>> {
+    let unsupported = |message: String| GasEstimationError::Unsupported(message);
+    let gas_estimators = match &env.gas_estimators {
+        Some(gas_estimators) => gas_estimators,
+        None => {
+            let default = default_estimator(chain_id, transport.clone(), env.node_url.as_deref())
+                .ok_or_else(|| {
+                unsupported(format!("no default estimator for chain {}", chain_id))
+            })?;
+            return match env.blocknative_api_key {
+                #[cfg(feature = "tokio_")]
+                Some(api_key) if chain_id == 1 => {
+                    let blocknative = blocknative(transport, api_key).await?;
+                    Ok(Box::new(PriorityGasPriceEstimating::new(vec![
+                        blocknative,
+                        default,
+                    ])))
+                }
+                _ => Ok(default),
+            };
+        }
+    };
+
+    let mut estimators: Vec<Box<dyn GasPriceEstimating>> = Vec::new();
+    for name in gas_estimators.split(',').map(str::trim) {
+        if name.is_empty() {
+            return Err(unsupported(format!(
+                "GAS_ESTIMATORS {:?} contains an empty entry",
+                gas_estimators
+            )));
+        }
+        estimators.push(
+            estimator_by_name(
+                name,
+                chain_id,
+                transport.clone(),
+                env.node_url.as_deref(),
+                env.blocknative_api_key.as_deref(),
+            )
+            .await?,
+        );
+    }
+    // Splitting never yields zero entries.
+    match estimators.len() {
+        1 => Ok(estimators.pop().unwrap()),
+        _ => Ok(Box::new(PriorityGasPriceEstimating::new(estimators))),
+    }
+}
+
+/// The estimator for one of the `GAS_ESTIMATORS` names of `default_estimator_from_env`, case
+/// insensitively. `node_url` and `blocknative_api_key` are only needed by the sources that use
+/// them.
+pub async fn estimator_by_name<T: Transport + Clone + 'static>(
+    name: &str,
+    chain_id: u64,
+    transport: T,
+    node_url: Option<&str>,
+    blocknative_api_key: Option<&str>,
+) -> Result<Box<dyn GasPriceEstimating>> {
+    let unsupported = |message: String| GasEstimationError::Unsupported(message);
+    Ok(match name.to_lowercase().as_str() {
+        "gasnow" => Box::new(GasNowGasStation::new(transport)),
+        "ethgasstation" => Box::new(EthGasStation::new(transport)),
+        "etherscan" => Box::new(EtherscanGasStation::new(transport)),
+        "gnosissafe" => Box::new(GnosisSafeGasStation::new(chain_id, transport)),
+        "polygon" => Box::new(PolygonGasStation::with_network_id(
+            &chain_id.to_string(),
+            transport,
+        )?),
+        "gnosischain" => Box::new(GnosisChainGasStation::new(transport)),
+        "node" => {
+            let node_url = node_url
+                .map(String::from)
+                .ok_or_else(|| unsupported("Node estimator requires NODE_URL".into()))?;
+            node_estimator(chain_id, transport, node_url)
+                .ok_or_else(|| unsupported(format!("no node estimator for chain {}", chain_id)))?
+        }
+        "blocknative" if chain_id == 1 => {
+            let api_key = blocknative_api_key.map(String::from).ok_or_else(|| {
+                unsupported("BlockNative estimator requires BLOCKNATIVE_API_KEY".into())
+            })?;
+            blocknative(transport, api_key).await?
+        }
+        _ => return Err(unsupported(format!("unsupported gas estimator {}", name))),
+    })
+}
+
+#[cfg(feature = "tokio_")]
+async fn blocknative<T: Transport + 'static>(
+    transport: T,
+    api_key: String,
+) -> Result<Box<dyn GasPriceEstimating>> {
+    let blocknative = super::BlockNative::with_credentials(
+        transport,
+        super::ApiCredentials::header("Authorization", api_key),
+        Default::default(),
+    )
+    .await?;
+    Ok(Box::new(blocknative))
+}
+
+#[cfg(not(feature = "tokio_"))]
+async fn blocknative<T: Transport + 'static>(
+    transport: T,
+    api_key: String,
+) -> Result<Box<dyn GasPriceEstimating>> {
+    let blocknative = super::BlocknativeGasStation::with_credentials(
+        transport,
+        super::ApiCredentials::header("Authorization", api_key),
+        Default::default(),
+    )?;
+    Ok(Box::new(blocknative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    #[derive(Clone)]
+    struct NoTransport;
+
+    #[async_trait::async_trait]
+    impl Transport for NoTransport {
+        async fn get_json<T: DeserializeOwned>(
+            &self,
+            url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<T> {
+            Err(GasEstimationError::Unsupported(url.to_string()))
+        }
+    }
+
+    #[test]
+    fn defaults_skip_chains_without_node_url() {
+        let registry = ChainEstimatorRegistry::with_defaults(NoTransport, &Default::default());
+        let mut chain_ids = registry.chain_ids().collect::<Vec<_>>();
+        chain_ids.sort_unstable();
+        assert_eq!(chain_ids, vec![1, 100, 137, 80001]);
+
+        let node_urls = SUPPORTED_CHAINS
+            .iter()
+            .map(|chain_id| (*chain_id, String::new()))
+            .collect();
+        let registry = ChainEstimatorRegistry::with_defaults(NoTransport, &node_urls);
+        assert_eq!(registry.chain_ids().count(), SUPPORTED_CHAINS.len());
+        assert_eq!(registry.get(56).unwrap().source(), "rpc_percentile");
+        assert_eq!(registry.get(42161).unwrap().source(), "arbitrum");
+        assert_eq!(registry.get(324).unwrap().source(), "zksync");
+        assert_eq!(registry.get(59144).unwrap().source(), "linea");
+        assert_eq!(registry.get(534352).unwrap().source(), "scroll");
+    }
+
+    #[test]
+    fn estimates_with_registered_estimator() {
+        let mut estimator = MockGasPriceEstimating::new();
+        estimator
+            .expect_estimate_with_limits()
+            .times(1)
+            .returning(|_, _| {
+                Ok(EstimatedGasPrice {
+                    legacy: 1.0,
+                    ..Default::default()
+                })
+            });
+        let registry = ChainEstimatorRegistry::default().with_estimator(5, Box::new(estimator));
+        assert_eq!(
+            registry
+                .estimate_for_chain(5, 21000.0, Duration::from_secs(30))
+                .wait()
+                .unwrap()
+                .legacy,
+            1.0
+        );
+        assert!(matches!(
+            registry
+                .estimate_for_chain(6, 21000.0, Duration::from_secs(30))
+                .wait(),
+            Err(GasEstimationError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn env_configures_sources() {
+        let from_env = |chain_id, env| estimator_from_env(chain_id, NoTransport, env).wait();
+        let gas_estimators = |gas_estimators: &str| Env {
+            gas_estimators: Some(gas_estimators.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            from_env(100, Env::default()).unwrap().source(),
+            "gnosis_chain"
+        );
+        assert_eq!(
+            from_env(1, gas_estimators(" gasnow")).unwrap().source(),
+            "gasnow"
+        );
+        assert_eq!(
+            from_env(1, gas_estimators("GasNow, Etherscan "))
+                .unwrap()
+                .source(),
+            "priority"
+        );
+        let node = Env {
+            node_url: Some("http://localhost:8545".to_string()),
+            ..gas_estimators("Node")
+        };
+        assert_eq!(from_env(56, node).unwrap().source(), "rpc_percentile");
+        for env in [
+            gas_estimators("Node"),
+            gas_estimators("GasNow,Unknown"),
+            gas_estimators("Polygon"),
+            gas_estimators("GasNow,,"),
+            gas_estimators("GasNow, ,Etherscan"),
+        ] {
+            assert!(matches!(
+                from_env(1, env),
+                Err(GasEstimationError::Unsupported(_))
+            ));
+        }
+    }
+}
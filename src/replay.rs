@@ -0,0 +1,167 @@
+//! Replays recorded gas prices for deterministic integration tests and for backtesting bidding
+//! strategies against historical gas markets.
+
+use super::{error::Result, EstimatedGasPrice, GasEstimationError, GasPriceEstimating};
+use anyhow::anyhow;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Time of the replay. Clones share the time so the test that drives the replay keeps a clone and
+/// advances it.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock(Arc<Mutex<Duration>>);
+
+impl VirtualClock {
+    pub fn new(start: Duration) -> Self {
+        Self(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn now(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+
+    pub fn set(&self, time: Duration) {
+        *self.0.lock().unwrap() = time;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+// Returns the last recorded estimate at or before the time of the virtual clock. The time limit is
+// ignored because the recording only contains one estimate per point in time.
+pub struct ReplayEstimator {
+    // sorted by time
+    records: Vec<(Duration, EstimatedGasPrice)>,
+    clock: VirtualClock,
+}
+
+impl ReplayEstimator {
+    /// `records` are (timestamp, estimate) pairs. Timestamps can be relative to any epoch, for
+    /// example the unix epoch. The clock starts at the first timestamp.
+    pub fn new(records: impl IntoIterator<Item = (Duration, EstimatedGasPrice)>) -> Self {
+        let mut records = records.into_iter().collect::<Vec<_>>();
+        records.sort_by_key(|(time, _)| *time);
+        let start = records.first().map(|(time, _)| *time).unwrap_or_default();
+        Self {
+            records,
+            clock: VirtualClock::new(start),
+        }
+    }
+
+    /// Reads one json object per line with the timestamp in seconds and the estimate:
+    /// `{"timestamp": 1650000000.5, "price": {"legacy": 1e9, "eip1559": null}}`. Empty lines are
+    /// skipped.
+    #[cfg(feature = "serde_")]
+    pub fn from_json_lines(reader: impl std::io::BufRead) -> Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct Record {
+            timestamp: f64,
+            price: EstimatedGasPrice,
+        }
+
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(|err| anyhow!(err).context("failed to read recording"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line)?;
+            let timestamp = Duration::try_from_secs_f64(record.timestamp).map_err(|err| {
+                GasEstimationError::Decode(anyhow!(err).context("invalid timestamp"))
+            })?;
+            records.push((timestamp, record.price));
+        }
+        Ok(Self::new(records))
+    }
+
+    pub fn clock(&self) -> VirtualClock {
+        self.clock.clone()
+    }
+
+    pub fn estimate_at(&self, time: Duration) -> Result<EstimatedGasPrice> {
+        let index = self
+            .records
+            .partition_point(|(record_time, _)| *record_time <= time);
+        match index.checked_sub(1) {
+            Some(index) => Ok(self.records[index].1),
+            None => Err(GasEstimationError::Other(anyhow!(
+                "no recorded estimate at {:?}",
+                time
+            ))),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl GasPriceEstimating for ReplayEstimator {
+    fn source(&self) -> &'static str {
+        "replay"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.estimate_at(self.clock.now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replays_according_to_clock() {
+        let estimator = ReplayEstimator::new(vec![
+            (Duration::from_secs(20), price(2.0)),
+            (Duration::from_secs(10), price(1.0)),
+        ]);
+        let clock = estimator.clock();
+        assert_eq!(clock.now(), Duration::from_secs(10));
+        assert_eq!(estimator.estimate().wait().unwrap(), price(1.0));
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(estimator.estimate().wait().unwrap(), price(1.0));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(estimator.estimate().wait().unwrap(), price(2.0));
+        clock.set(Duration::from_secs(1000));
+        assert_eq!(estimator.estimate().wait().unwrap(), price(2.0));
+        clock.set(Duration::from_secs(5));
+        assert!(estimator.estimate().wait().is_err());
+    }
+
+    #[test]
+    fn empty_recording_fails() {
+        assert!(ReplayEstimator::new(vec![]).estimate().wait().is_err());
+    }
+
+    #[cfg(feature = "serde_")]
+    #[test]
+    fn reads_json_lines() {
+        let recording = r#"{"timestamp": 100.5, "price": {"legacy": 1.0, "eip1559": null}}
+
+{"timestamp": 101, "price": {"legacy": 3.0, "eip1559": {"base_fee_per_gas": 1.0, "max_fee_per_gas": 3.0, "max_priority_fee_per_gas": 1.0}}}
+"#;
+        let estimator = ReplayEstimator::from_json_lines(recording.as_bytes()).unwrap();
+        assert_eq!(estimator.clock().now(), Duration::from_secs_f64(100.5));
+        assert_eq!(estimator.estimate().wait().unwrap(), price(1.0));
+        estimator.clock().advance(Duration::from_secs(1));
+        let estimate = estimator.estimate().wait().unwrap();
+        assert_eq!(estimate.legacy, 3.0);
+        assert!(estimate.eip1559.is_some());
+
+        assert!(ReplayEstimator::from_json_lines("{".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasEstimationError,
+    GasPriceEstimating, GasPriceSchedule, TransactionCost, TxSpec,
+};
+use rand::Rng;
+use std::{future::Future, time::Duration};
+
+/// Parameters for retrying failed estimates.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // number of retries after the first attempt failed
+    pub max_retries: usize,
+    // backoff before the first retry, doubled for every following retry
+    pub initial_backoff: Duration,
+    // upper bound for the backoff between two attempts
+    pub max_backoff: Duration,
+    // fraction of the backoff that is randomly subtracted so that replicas don't retry in lockstep
+    pub jitter: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.5,
+        }
+    }
+}
+
+// Retries transient errors of the inner estimator with exponential backoff.
+pub struct RetryGasPriceEstimating<T> {
+    inner: T,
+    params: Params,
+}
+
+impl<T: GasPriceEstimating> RetryGasPriceEstimating<T> {
+    pub fn new(inner: T, params: Option<Params>) -> Self {
+        Self {
+            inner,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    async fn retry<'a, O, F, R>(&'a self, operation: O) -> Result<R>
+    where
+        O: Fn(&'a T) -> F,
+        F: Future<Output = Result<R>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation(&self.inner).await {
+                Ok(result) => return Ok(result),
+                Err(err) if attempt < self.params.max_retries && is_retryable(&err) => {
+                    let backoff = match err {
+                        GasEstimationError::RateLimited {
+                            retry_after: Some(retry_after),
+                        } => retry_after.max(backoff(&self.params, attempt)),
+                        _ => backoff(&self.params, attempt),
+                    };
+                    tracing::debug!(?err, ?backoff, "retrying failed gas estimate");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+// A response that could not be decoded will not decode on the next attempt either and unsupported
+// requests or invalid estimates will not change either. Client error responses like 401 for an
+// invalid api key or 404 for a wrong url are permanent too, 429 is `RateLimited`. An open circuit
+// breaker fails fast on purpose. Everything else is assumed to be transient.
+fn is_retryable(err: &GasEstimationError) -> bool {
+    if err
+        .http_status()
+        .is_some_and(|status| status.is_client_error())
+    {
+        return false;
+    }
+    !matches!(
+        err,
+        GasEstimationError::Decode(_)
+            | GasEstimationError::Unsupported(_)
+            | GasEstimationError::InvalidEstimate(_)
+            | GasEstimationError::CircuitOpen { .. }
+    )
+}
+
+fn backoff(params: &Params, attempt: usize) -> Duration {
+    let exponential = params
+        .initial_backoff
+        .saturating_mul(2u32.saturating_pow(attempt as u32))
+        .min(params.max_backoff);
+    let jitter = params.jitter.clamp(0.0, 1.0) * rand::thread_rng().gen::<f64>();
+    exponential.mul_f64(1.0 - jitter)
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for RetryGasPriceEstimating<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.retry(|inner| inner.estimate_with_limits(gas_limit, time_limit))
+            .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        self.retry(|inner| inner.estimate()).await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        self.retry(|inner| inner.estimate_verbose(gas_limit, time_limit))
+            .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        self.retry(|inner| inner.estimate_with_hints(gas_limit, time_limit, hints))
+            .await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        self.retry(|inner| inner.estimate_for_tx(tx)).await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        self.retry(|inner| inner.estimate_schedule()).await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        self.retry(|inner| inner.estimate_many(requests)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{error::HttpStatus, MockGasPriceEstimating};
+    use super::*;
+    use anyhow::anyhow;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn params() -> Option<Params> {
+        Some(Params {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let params = Params {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(backoff(&params, 0), Duration::from_millis(100));
+        assert_eq!(backoff(&params, 1), Duration::from_millis(200));
+        assert_eq!(backoff(&params, 3), Duration::from_millis(800));
+        assert_eq!(backoff(&params, 100), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_jitter_is_bounded() {
+        let params = Params::default();
+        for _ in 0..100 {
+            let backoff = backoff(&params, 0);
+            assert!(backoff > Duration::from_millis(50) && backoff <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn decode_errors_are_permanent() {
+        let err = serde_json::from_str::<u32>("").unwrap_err();
+        assert!(!is_retryable(&err.into()));
+        assert!(is_retryable(&GasEstimationError::Transport(anyhow!(
+            "connection reset"
+        ))));
+        assert!(is_retryable(&GasEstimationError::Timeout));
+        assert!(!is_retryable(&GasEstimationError::CircuitOpen {
+            retry_after: Some(Duration::from_secs(1))
+        }));
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let mut inner = MockGasPriceEstimating::new();
+        let mut calls = 0;
+        inner.expect_estimate().times(3).returning(move || {
+            calls += 1;
+            if calls < 3 {
+                Err(anyhow!("").into())
+            } else {
+                Ok(EstimatedGasPrice {
+                    legacy: 1.0,
+                    ..Default::default()
+                })
+            }
+        });
+        let retry = RetryGasPriceEstimating::new(inner, params());
+        assert_approx_eq!(retry.estimate().await.unwrap().legacy, 1.0);
+    }
+
+    #[tokio::test]
+    async fn retries_estimate_many() {
+        let mut inner = MockGasPriceEstimating::new();
+        let mut calls = 0;
+        inner
+            .expect_estimate_many()
+            .times(2)
+            .returning(move |requests| {
+                calls += 1;
+                if calls < 2 {
+                    Err(anyhow!("").into())
+                } else {
+                    Ok(vec![Default::default(); requests.len()])
+                }
+            });
+        let retry = RetryGasPriceEstimating::new(inner, params());
+        let requests = [(21000.0, Duration::from_secs(15)); 3];
+        assert_eq!(retry.estimate_many(&requests).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(4)
+            .returning(|| Err(anyhow!("").into()));
+        let retry = RetryGasPriceEstimating::new(inner, params());
+        assert!(retry.estimate().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_errors() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(1)
+            .returning(|| Err(serde_json::from_str::<u32>("").unwrap_err().into()));
+        let retry = RetryGasPriceEstimating::new(inner, params());
+        assert!(retry.estimate().await.is_err());
+    }
+
+    fn http_error(status: http::StatusCode) -> GasEstimationError {
+        GasEstimationError::Transport(anyhow!("error response").context(HttpStatus(status)))
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        assert!(!is_retryable(&http_error(http::StatusCode::NOT_FOUND)));
+        assert!(is_retryable(&http_error(
+            http::StatusCode::SERVICE_UNAVAILABLE
+        )));
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(1)
+            .returning(|| Err(http_error(http::StatusCode::UNAUTHORIZED)));
+        let retry = RetryGasPriceEstimating::new(inner, params());
+        assert_eq!(
+            retry.estimate().await.unwrap_err().http_status(),
+            Some(http::StatusCode::UNAUTHORIZED)
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Gas price estimation from the reward percentiles of `eth_feeHistory` for any EVM chain with a
+//! JSON-RPC node. Chains differ in whether they have a base fee and in the lowest price their
+//! validators accept, which is captured in `ChainConfig`.
+//!
+//! Unlike `native::FeeHistoryGasEstimator` this uses `Transport::post_json` instead of web3.
+
+use super::{
+    error::Result,
+    interpolation,
+    json_rpc::{self, FeeHistory},
+    EstimatedGasPrice, FeeHistoryConfig, GasEstimationError, GasPrice1559, GasPriceEstimating,
+    GasPriceWei, Transport,
+};
+use anyhow::anyhow;
+use std::{convert::TryInto, time::Duration};
+
+pub use super::chain::{ChainConfig, AVALANCHE, BSC, ETHEREUM, FANTOM};
+
+/// Parameters for the percentile estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // sampled blocks and the reward percentile used for each time limit
+    pub fee_history: FeeHistoryConfig,
+    // a coefficient to multiply base_fee_per_gas with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+    // priority fee offered when there are no recent transactions
+    pub fallback_priority_fee: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            fee_history: Default::default(),
+            base_fee_multiplier: 2.0,
+            fallback_priority_fee: 2e9,
+        }
+    }
+}
+
+pub struct RpcPercentileEstimator<T> {
+    transport: T,
+    node_url: String,
+    chain: ChainConfig,
+    params: Params,
+}
+
+impl<T: Transport> RpcPercentileEstimator<T> {
+    pub fn new(transport: T, node_url: String, chain: ChainConfig, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            chain,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    async fn fee_history(&self) -> Result<FeeHistory> {
+        json_rpc::fee_history(
+            &self.transport,
+            &self.node_url,
+            self.params.fee_history.blocks,
+            &self.params.fee_history.reward_percentiles(),
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for RpcPercentileEstimator<T> {
+    fn source(&self) -> &'static str {
+        "rpc_percentile"
+    }
+
+    fn block_time(&self) -> Duration {
+        self.chain.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let fee_history = DecodedFeeHistory::decode(&self.fee_history().await?)?;
+        estimate_with_limits(&fee_history, time_limit, &self.chain, &self.params)
+    }
+}
+
+// The fee history values the estimation uses, decoded from the json-rpc quantities or from the
+// web3 types so that the estimators of both transports share the estimation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct DecodedFeeHistory {
+    // The base fee of the pending block.
+    pub base_fee_per_gas: Option<f64>,
+    pub gas_used_ratio: Vec<f64>,
+    pub reward: Option<Vec<Vec<f64>>>,
+}
+
+impl DecodedFeeHistory {
+    fn decode(fee_history: &FeeHistory) -> Result<Self> {
+        let decode = |quantities: &[String]| {
+            quantities
+                .iter()
+                .map(|quantity| json_rpc::quantity_to_f64(quantity))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            base_fee_per_gas: fee_history
+                .base_fee_per_gas
+                .last()
+                .map(|base_fee| json_rpc::quantity_to_f64(base_fee))
+                .transpose()?,
+            gas_used_ratio: fee_history.gas_used_ratio.clone(),
+            reward: fee_history
+                .reward
+                .as_ref()
+                .map(|reward| reward.iter().map(|block| decode(block)).collect())
+                .transpose()?,
+        })
+    }
+
+    #[cfg(feature = "web3_")]
+    pub(crate) fn from_web3(fee_history: &web3::types::FeeHistory) -> Self {
+        Self {
+            base_fee_per_gas: fee_history
+                .base_fee_per_gas
+                .last()
+                .map(|base_fee| base_fee.to_f64_lossy()),
+            gas_used_ratio: fee_history.gas_used_ratio.clone(),
+            reward: fee_history.reward.as_ref().map(|reward| {
+                reward
+                    .iter()
+                    .map(|block| block.iter().map(|reward| reward.to_f64_lossy()).collect())
+                    .collect()
+            }),
+        }
+    }
+
+    // Rewards at the given index of the percentiles of the non empty blocks.
+    pub(crate) fn rewards(&self, index: usize) -> Vec<f64> {
+        self.reward
+            .iter()
+            .flatten()
+            .zip(&self.gas_used_ratio)
+            .filter(|(_, gas_used_ratio)| **gas_used_ratio > 0.0)
+            .filter_map(|(reward, _)| reward.get(index).copied())
+            .collect()
+    }
+}
+
+// Priority fee for `time_limit` interpolated between the priority fees of the configured
+// percentiles, which are raised to `min_priority_fee`. `priority_fee` is called with the index of
+// the percentile in `FeeHistoryConfig::reward_percentiles`.
+pub(crate) fn max_priority_fee(
+    config: &FeeHistoryConfig,
+    time_limit: Duration,
+    min_priority_fee: f64,
+    mut priority_fee: impl FnMut(usize) -> Result<f64>,
+) -> Result<f64> {
+    let points =
+        config.priority_fee_points(|index| Ok(priority_fee(index)?.max(min_priority_fee)))?;
+    Ok(interpolation::interpolate(
+        time_limit.as_secs_f64(),
+        points.as_slice().try_into()?,
+    ))
+}
+
+// Estimates from the fee history. The priority fee is the average reward over all non empty blocks
+// that aren't outliers.
+pub(crate) fn estimate_with_limits(
+    fee_history: &DecodedFeeHistory,
+    time_limit: Duration,
+    chain: &ChainConfig,
+    params: &Params,
+) -> Result<EstimatedGasPrice> {
+    if fee_history.reward.is_none() {
+        return Err(GasEstimationError::Decode(anyhow!(
+            "fee history is missing rewards"
+        )));
+    }
+    let max_priority_fee_per_gas = max_priority_fee(
+        &params.fee_history,
+        time_limit,
+        chain.min_priority_fee,
+        |index| {
+            Ok(params
+                .fee_history
+                .average_reward(fee_history.rewards(index))
+                .unwrap_or(params.fallback_priority_fee))
+        },
+    )?;
+
+    if !chain.supports_eip1559 {
+        return EstimatedGasPrice {
+            legacy: max_priority_fee_per_gas,
+            ..Default::default()
+        }
+        .validate();
+    }
+
+    let base_fee_per_gas = fee_history
+        .base_fee_per_gas
+        .ok_or_else(|| GasEstimationError::Decode(anyhow!("fee history is missing base fee")))?;
+    let max_fee_per_gas = base_fee_per_gas * params.base_fee_multiplier + max_priority_fee_per_gas;
+    EstimatedGasPrice {
+        legacy: max_fee_per_gas,
+        eip1559: Some(GasPrice1559 {
+            base_fee_per_gas: GasPriceWei(base_fee_per_gas),
+            max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+            max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+        }),
+    }
+    .validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use serde_json::json;
+
+    const WITHOUT_FLOOR: ChainConfig = ChainConfig {
+        min_priority_fee: 0.0,
+        ..ETHEREUM
+    };
+
+    fn fee_history() -> DecodedFeeHistory {
+        DecodedFeeHistory::decode(&FeeHistory {
+            base_fee_per_gas: vec!["0x5a".into(), "0x5f".into(), "0x64".into()],
+            gas_used_ratio: vec![0.5, 0.0],
+            reward: Some(vec![
+                vec!["0x1".into(), "0x2".into(), "0x3".into(), "0x4".into()],
+                vec!["0x0".into(), "0x0".into(), "0x0".into(), "0x0".into()],
+            ]),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn empty_blocks_are_ignored() {
+        assert_eq!(fee_history().rewards(0), vec![1.0]);
+        assert_eq!(fee_history().rewards(3), vec![4.0]);
+        assert_eq!(fee_history().rewards(4), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn estimate_maps_time_limit_to_percentile() {
+        let params = Params::default();
+        let fast = estimate_with_limits(
+            &fee_history(),
+            Duration::from_secs(15),
+            &WITHOUT_FLOOR,
+            &params,
+        )
+        .unwrap();
+        let eip1559 = fast.eip1559.unwrap();
+        assert_approx_eq!(eip1559.base_fee_per_gas.0, 100.0);
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 4.0);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 204.0);
+        assert_approx_eq!(fast.legacy, 204.0);
+
+        let slow = estimate_with_limits(
+            &fee_history(),
+            Duration::from_secs(600),
+            &WITHOUT_FLOOR,
+            &params,
+        )
+        .unwrap();
+        assert_approx_eq!(slow.eip1559.unwrap().max_priority_fee_per_gas.0, 1.0);
+    }
+
+    #[test]
+    fn mainnet_priority_fee_is_at_least_one_gwei() {
+        let price = estimate_with_limits(
+            &fee_history(),
+            Duration::from_secs(600),
+            &ETHEREUM,
+            &Default::default(),
+        )
+        .unwrap();
+        let eip1559 = price.eip1559.unwrap();
+        assert_approx_eq!(eip1559.max_priority_fee_per_gas.0, 1e9);
+        assert_approx_eq!(eip1559.max_fee_per_gas.0, 200.0 + 1e9);
+    }
+
+    #[test]
+    fn chain_without_base_fee_estimates_legacy_price() {
+        let chain = ChainConfig {
+            min_priority_fee: 2.0,
+            supports_eip1559: false,
+            ..ETHEREUM
+        };
+        let fee_history = DecodedFeeHistory {
+            base_fee_per_gas: None,
+            ..fee_history()
+        };
+        let price = estimate_with_limits(
+            &fee_history,
+            Duration::from_secs(15),
+            &chain,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_approx_eq!(price.legacy, 4.0);
+        assert!(price.eip1559.is_none());
+        // raised to the minimum
+        let price = estimate_with_limits(
+            &fee_history,
+            Duration::from_secs(600),
+            &chain,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_approx_eq!(price.legacy, 2.0);
+    }
+
+    #[test]
+    fn fallback_without_transactions() {
+        let params = Params::default();
+        let fee_history = DecodedFeeHistory {
+            gas_used_ratio: vec![0.0, 0.0],
+            ..fee_history()
+        };
+        let price = estimate_with_limits(&fee_history, Duration::from_secs(15), &ETHEREUM, &params)
+            .unwrap();
+        assert_approx_eq!(
+            price.eip1559.unwrap().max_priority_fee_per_gas.0,
+            params.fallback_priority_fee
+        );
+    }
+
+    #[test]
+    fn fails_without_base_fee_on_chain_with_base_fee() {
+        let fee_history = DecodedFeeHistory {
+            base_fee_per_gas: None,
+            ..fee_history()
+        };
+        assert!(matches!(
+            estimate_with_limits(
+                &fee_history,
+                Duration::from_secs(15),
+                &ETHEREUM,
+                &Default::default()
+            ),
+            Err(GasEstimationError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn presets_by_chain_id() {
+        assert_eq!(ChainConfig::from_chain_id(56), Some(BSC));
+        assert_eq!(ChainConfig::from_chain_id(43114), Some(AVALANCHE));
+        assert_eq!(ChainConfig::from_chain_id(250), Some(FANTOM));
+        assert_eq!(ChainConfig::from_chain_id(2), None);
+    }
+
+    #[test]
+    fn requests_fee_history() {
+        let node = FakeNode::default().with_result(
+            "eth_feeHistory",
+            json!({
+                "oldestBlock": "0x1",
+                "baseFeePerGas": ["0x5a", "0x64"],
+                "gasUsedRatio": [0.5],
+                "reward": [["0x1", "0x2", "0x3", "0x4"]],
+            }),
+        );
+        let estimator = RpcPercentileEstimator::new(node, String::new(), AVALANCHE, None);
+        let price = estimator.estimate().wait().unwrap();
+        // the rewards are below the minimum priority fee
+        assert_approx_eq!(price.eip1559.unwrap().max_priority_fee_per_gas.0, 1e9);
+    }
+
+    // NODE_URL=... cargo test rpc_percentile -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = RpcPercentileEstimator::new(
+            TestTransport::default(),
+            std::env::var("NODE_URL").unwrap(),
+            Default::default(),
+            None,
+        );
+        for time_limit in [15, 60, 180, 600] {
+            let time_limit = Duration::from_secs(time_limit);
+            println!(
+                "{:?}: {:?}",
+                time_limit,
+                estimator.estimate_with_limits(0.0, time_limit).await
+            );
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! The async runtime background tasks are spawned on and sleep with.
+//!
+//! Estimators with a background task use `default_runtime`, which is tokio with the `tokio_`
+//! feature and async-std with only the `async-std_` feature. Other runtimes can implement
+//! `Runtime` and be passed to the constructors that take one.
+
+use futures::future::BoxFuture;
+use std::{sync::Arc, time::Duration};
+
+pub trait Runtime: Send + Sync {
+    /// Runs `task` to completion in the background. The task is detached, `BackgroundTask` stops
+    /// it by dropping the future.
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+impl<T: Runtime + ?Sized> Runtime for Arc<T> {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        (**self).spawn(task)
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        (**self).sleep(duration)
+    }
+}
+
+/// Spawns on the tokio runtime of the calling thread, panics outside of one like `tokio::spawn`.
+#[cfg(feature = "tokio_")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio_")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(feature = "async-std_")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "async-std_")]
+impl Runtime for AsyncStdRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        async_std::task::spawn(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+/// Tokio if the `tokio_` feature is enabled, async-std otherwise.
+pub fn default_runtime() -> Arc<dyn Runtime> {
+    #[cfg(feature = "tokio_")]
+    let runtime = Arc::new(TokioRuntime);
+    #[cfg(not(feature = "tokio_"))]
+    let runtime = Arc::new(AsyncStdRuntime);
+    runtime
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+
+    async fn spawns_and_sleeps(runtime: &dyn Runtime) {
+        let (sender, receiver) = oneshot::channel();
+        let sleep = runtime.sleep(Duration::from_millis(10));
+        runtime.spawn(Box::pin(async move {
+            sleep.await;
+            sender.send(()).unwrap();
+        }));
+        receiver.await.unwrap();
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn tokio_runtime() {
+        spawns_and_sleeps(&TokioRuntime).await;
+    }
+
+    #[cfg(feature = "async-std_")]
+    #[test]
+    fn async_std_runtime() {
+        async_std::task::block_on(spawns_and_sleeps(&AsyncStdRuntime));
+    }
+}
@@ -0,0 +1,139 @@
+//! Scroll gas estimation.
+//!
+//! Like on the OP stack, Scroll transactions pay an L1 data fee on top of the L2 EIP-1559 gas
+//! price. The fee is computed by the L1GasPriceOracle predeploy, which has the same `getL1Fee`
+//! interface as the OP stack oracle. Documentation at
+//! https://docs.scroll.io/en/developers/transaction-fees-on-scroll/ .
+
+use super::{
+    chain,
+    cost::{TransactionCost, TxSpec},
+    error::Result,
+    json_rpc::{self, word_to_f64},
+    op_stack::{self, incompressible_calldata, Params},
+    EstimatedGasPrice, GasPriceEstimating, Transport,
+};
+use std::time::Duration;
+
+/// Address of the L1GasPriceOracle predeploy.
+pub const L1_GAS_PRICE_ORACLE: &str = "0x5300000000000000000000000000000000000002";
+
+/// Estimates gas prices through a Scroll node's JSON-RPC api. Requires a transport that supports
+/// `Transport::post_json`.
+pub struct ScrollGasEstimator<T> {
+    transport: T,
+    node_url: String,
+    params: Params,
+}
+
+impl<T: Transport> ScrollGasEstimator<T> {
+    pub fn new(transport: T, node_url: String, params: Option<Params>) -> Self {
+        Self {
+            transport,
+            node_url,
+            params: params.unwrap_or_default(),
+        }
+    }
+
+    /// L1 data fee in wei for a transaction with `calldata_size` bytes of calldata.
+    pub async fn l1_fee(&self, calldata_size: usize) -> Result<f64> {
+        let result = json_rpc::eth_call(
+            &self.transport,
+            &self.node_url,
+            L1_GAS_PRICE_ORACLE,
+            &op_stack::get_l1_fee_call(&incompressible_calldata(calldata_size)),
+        )
+        .await
+        .map_err(|err| err.context("failed to call getL1Fee"))?;
+        word_to_f64(&result, 0)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for ScrollGasEstimator<T> {
+    fn source(&self) -> &'static str {
+        "scroll"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::SCROLL.block_time
+    }
+
+    // Only the L2 gas price, use `estimate_for_tx` to include the L1 data fee.
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let (base_fee, priority_fee) = futures::try_join!(
+            op_stack::base_fee(&self.transport, &self.node_url),
+            op_stack::priority_fee(&self.transport, &self.node_url),
+        )?;
+        op_stack::estimate(base_fee, priority_fee, &self.params)
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let (gas_price, l1_fee) = futures::try_join!(
+            self.estimate_with_limits(tx.gas_limit, tx.time_limit),
+            self.l1_fee(tx.data_len()),
+        )?;
+        Ok(TransactionCost {
+            gas_price,
+            gas_limit: tx.gas_limit,
+            l1_fee,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::{tests::FakeNode, to_hex, uint_word};
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn estimates_total_fee() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({ "baseFeePerGas": "0x64" }))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"))
+            .with_result("eth_call:0x49948e0e", json!(to_hex(&uint_word(2_000_000))));
+        let estimator = ScrollGasEstimator::new(node, String::new(), None);
+        let price = estimator.estimate().wait().unwrap();
+        assert_eq!(price.eip1559.unwrap().max_fee_per_gas.0, 210.0);
+
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                gas_limit: 100_000.0,
+                calldata_len: 200,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.l1_fee, 2e6);
+        assert_eq!(cost.expected_cost(), 2e6 + 100_000.0 * 110.0);
+    }
+
+    // SCROLL_NODE_URL=... cargo test scroll -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = ScrollGasEstimator::new(
+            TestTransport::default(),
+            std::env::var("SCROLL_NODE_URL").unwrap(),
+            None,
+        );
+        println!("{:?}", estimator.estimate().await);
+        for calldata_len in [0, 100, 1000] {
+            let tx = TxSpec {
+                calldata_len,
+                ..Default::default()
+            };
+            println!(
+                "{}: {:?}",
+                calldata_len,
+                estimator.estimate_for_tx(&tx).await
+            );
+        }
+    }
+}
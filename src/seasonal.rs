@@ -0,0 +1,245 @@
+//! Typical gas prices by hour of the week, accumulated over a long running service.
+//!
+//! Gas prices follow the daily and weekly activity of users, so a price that is normal on a
+//! weekday afternoon can be suspicious on a Sunday night. Every bucket keeps the mean and variance
+//! of the logarithm of the effective gas prices recorded in that hour (UTC) of the week, which
+//! gives a band of typical prices to reject obviously broken provider values with.
+
+use super::{error::Result, EstimatedGasPrice};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HOURS_PER_WEEK: usize = 7 * 24;
+
+/// Parameters of the seasonal statistics.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // width of the typical range in standard deviations
+    pub deviations: f64,
+    // buckets with fewer samples have no typical range
+    pub min_samples: u64,
+    // Samples after this many in a bucket have the weight of 1 / max_samples so that the
+    // statistics follow slow changes of the gas prices.
+    pub max_samples: u64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            deviations: 3.0,
+            min_samples: 10,
+            max_samples: 1000,
+        }
+    }
+}
+
+/// Range of effective gas prices in wei.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypicalRange {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl TypicalRange {
+    pub fn contains(&self, price: &EstimatedGasPrice) -> bool {
+        (self.low..=self.high).contains(&price.effective_gas_price())
+    }
+}
+
+// Mean and variance of the logarithm of the prices. Up to `max_samples` every sample has the same
+// weight, after that the weights decay exponentially.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Bucket {
+    samples: u64,
+    mean: f64,
+    variance: f64,
+}
+
+impl Bucket {
+    fn record(&mut self, value: f64, max_samples: u64) {
+        self.samples += 1;
+        let weight = 1.0 / self.samples.min(max_samples.max(1)) as f64;
+        let delta = value - self.mean;
+        self.mean += weight * delta;
+        self.variance = (1.0 - weight) * (self.variance + weight * delta * delta);
+    }
+}
+
+// The file format of `SeasonalStats::save`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Stats {
+    buckets: Vec<Bucket>,
+}
+
+pub struct SeasonalStats {
+    params: Params,
+    // indexed by `hour_of_week`
+    buckets: Mutex<Vec<Bucket>>,
+}
+
+impl SeasonalStats {
+    pub fn new(params: Option<Params>) -> Self {
+        Self {
+            params: params.unwrap_or_default(),
+            buckets: Mutex::new(vec![Bucket::default(); HOURS_PER_WEEK]),
+        }
+    }
+
+    /// Records the effective gas price of `price` observed at `time`. Prices that are not positive
+    /// are ignored.
+    pub fn record(&self, time: SystemTime, price: &EstimatedGasPrice) {
+        let price = price.effective_gas_price();
+        if !(price.is_finite() && price > 0.0) {
+            return;
+        }
+        self.buckets.lock().unwrap()[hour_of_week(time)]
+            .record(price.ln(), self.params.max_samples);
+    }
+
+    /// The range of typical effective gas prices in the hour of the week of `now`. `None` while
+    /// the hour has fewer than `Params::min_samples` samples.
+    pub fn typical_range(&self, now: SystemTime) -> Option<TypicalRange> {
+        let bucket = self.buckets.lock().unwrap()[hour_of_week(now)];
+        if bucket.samples < self.params.min_samples.max(2) {
+            return None;
+        }
+        let spread = self.params.deviations.max(0.0) * bucket.variance.max(0.0).sqrt();
+        Some(TypicalRange {
+            low: (bucket.mean - spread).exp(),
+            high: (bucket.mean + spread).exp(),
+        })
+    }
+
+    /// Writes the statistics to `path` as json, replacing the file at once.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let stats = Stats {
+            buckets: self.buckets.lock().unwrap().clone(),
+        };
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&stats)?)
+            .and_then(|()| std::fs::rename(&temporary, path))
+            .with_context(|| format!("failed to write seasonal stats {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Continues the statistics saved at `path`.
+    pub fn load(path: &Path, params: Option<Params>) -> Result<Self> {
+        let stats = std::fs::read(path)
+            .with_context(|| format!("failed to read seasonal stats {}", path.display()))?;
+        let stats: Stats = serde_json::from_slice(&stats)?;
+        if stats.buckets.len() != HOURS_PER_WEEK {
+            return Err(anyhow!(
+                "seasonal stats have {} instead of {} buckets",
+                stats.buckets.len(),
+                HOURS_PER_WEEK
+            )
+            .into());
+        }
+        Ok(Self {
+            params: params.unwrap_or_default(),
+            buckets: Mutex::new(stats.buckets),
+        })
+    }
+}
+
+// Hours since monday 00:00 UTC.
+fn hour_of_week(time: SystemTime) -> usize {
+    let hours = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 3600;
+    // the unix epoch was a thursday
+    ((hours + 3 * 24) % HOURS_PER_WEEK as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::snapshot::tests::path;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use std::time::Duration;
+
+    // monday 2024-01-01 00:00 UTC
+    const MONDAY: u64 = 1_704_067_200;
+
+    fn time(hours: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(MONDAY + hours * 3600)
+    }
+
+    fn price(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hours_of_the_week() {
+        assert_eq!(hour_of_week(time(0)), 0);
+        assert_eq!(hour_of_week(time(25)), 25);
+        assert_eq!(hour_of_week(time(7 * 24 + 5)), 5);
+        assert_eq!(hour_of_week(UNIX_EPOCH), 3 * 24);
+    }
+
+    #[test]
+    fn ranges_per_hour() {
+        let stats = SeasonalStats::new(Some(Params {
+            deviations: 1.0,
+            min_samples: 2,
+            ..Default::default()
+        }));
+        assert_eq!(stats.typical_range(time(0)), None);
+        stats.record(time(0), &price(10.0));
+        assert_eq!(stats.typical_range(time(0)), None);
+        // the same hour a week later
+        stats.record(time(7 * 24), &price(1000.0));
+        stats.record(time(1), &price(0.0));
+        let range = stats.typical_range(time(0)).unwrap();
+        // mean and deviation of ln(10) and ln(1000) are ln(100) and ln(10)
+        assert_approx_eq!(range.low, 10.0, 1e-9);
+        assert_approx_eq!(range.high, 1000.0, 1e-6);
+        assert!(range.contains(&price(500.0)));
+        assert!(!range.contains(&price(5000.0)));
+        assert_eq!(stats.typical_range(time(1)), None);
+    }
+
+    #[test]
+    fn old_samples_decay() {
+        let stats = SeasonalStats::new(Some(Params {
+            max_samples: 10,
+            ..Default::default()
+        }));
+        for _ in 0..100 {
+            stats.record(time(0), &price(10.0));
+        }
+        for _ in 0..100 {
+            stats.record(time(0), &price(1000.0));
+        }
+        let range = stats.typical_range(time(0)).unwrap();
+        assert!(range.contains(&price(1000.0)));
+        assert!(!range.contains(&price(10.0)));
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = path("seasonal_saves_and_loads");
+        let stats = SeasonalStats::new(None);
+        for legacy in 1..=20 {
+            stats.record(time(3), &price(legacy as f64));
+        }
+        stats.save(&path).unwrap();
+        let loaded = SeasonalStats::load(&path, None).unwrap();
+        assert_eq!(loaded.typical_range(time(3)), stats.typical_range(time(3)));
+
+        std::fs::write(&path, r#"{"buckets": []}"#).unwrap();
+        assert!(SeasonalStats::load(&path, None).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+//! Serves the estimates of an estimator as a local http api, so that several services on a host
+//! can share one upstream polling loop and rate limit budget. The estimator is usually a
+//! `BackgroundPollingEstimator` so that requests to the server never wait for an upstream source.
+//!
+//! `GET /v1/estimate?timeLimit=30&gasLimit=21000` responds with the estimate as json, both
+//! parameters are optional and default to `DEFAULT_TIME_LIMIT` and `DEFAULT_GAS_LIMIT`. Failed
+//! estimates respond with `{"error": ...}` and 503, or 429 if the upstream source rate limited. Rate
+//! limits and an open circuit breaker add a `Retry-After` header if the delay is known.
+
+use super::{
+    error::Result, GasEstimationError, GasPriceEstimating, DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT,
+};
+use anyhow::Context;
+use http::{header, Method, Request, Response, StatusCode};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Server,
+};
+use serde::Serialize;
+use std::{convert::Infallible, future::Future, sync::Arc, time::Duration};
+
+pub const ESTIMATE_PATH: &str = "/v1/estimate";
+
+/// Serves `estimator` on `listener` until `shutdown` completes. Taking a bound listener lets
+/// callers bind port 0 and read the address from it.
+pub async fn serve<E>(
+    estimator: Arc<E>,
+    listener: std::net::TcpListener,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()>
+where
+    E: GasPriceEstimating + ?Sized + 'static,
+{
+    listener
+        .set_nonblocking(true)
+        .context("failed to configure listener")?;
+    let make_service = make_service_fn(move |_| {
+        let estimator = estimator.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let estimator = estimator.clone();
+                async move { Ok::<_, Infallible>(handle(estimator.as_ref(), request).await) }
+            }))
+        }
+    });
+    Server::from_tcp(listener)
+        .context("failed to serve on listener")?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context("gas price server failed")?;
+    Ok(())
+}
+
+async fn handle<E: GasPriceEstimating + ?Sized>(
+    estimator: &E,
+    request: Request<Body>,
+) -> Response<Body> {
+    if request.uri().path() != ESTIMATE_PATH {
+        return error(StatusCode::NOT_FOUND, "not found");
+    }
+    if request.method() != Method::GET {
+        return error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+    }
+    let (gas_limit, time_limit) = match parse_query(request.uri().query().unwrap_or_default()) {
+        Ok(limits) => limits,
+        Err(err) => return error(StatusCode::BAD_REQUEST, &err),
+    };
+    match estimator.estimate_with_limits(gas_limit, time_limit).await {
+        Ok(estimate) => json(StatusCode::OK, &estimate),
+        Err(ref err @ GasEstimationError::RateLimited { retry_after }) => with_retry_after(
+            error(StatusCode::TOO_MANY_REQUESTS, &err.to_string()),
+            retry_after,
+        ),
+        Err(ref err @ GasEstimationError::CircuitOpen { retry_after }) => with_retry_after(
+            error(StatusCode::SERVICE_UNAVAILABLE, &err.to_string()),
+            retry_after,
+        ),
+        Err(err) => {
+            tracing::warn!(?err, "failed to serve estimate");
+            error(StatusCode::SERVICE_UNAVAILABLE, &err.to_string())
+        }
+    }
+}
+
+// The gas limit and time limit of the query.
+fn parse_query(query: &str) -> Result<(f64, Duration), String> {
+    let mut gas_limit = DEFAULT_GAS_LIMIT;
+    let mut time_limit = DEFAULT_TIME_LIMIT;
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "gasLimit" => {
+                gas_limit = value
+                    .parse()
+                    .ok()
+                    .filter(|gas_limit: &f64| gas_limit.is_finite() && *gas_limit >= 0.0)
+                    .ok_or_else(|| format!("invalid gasLimit {}", value))?;
+            }
+            "timeLimit" => {
+                time_limit = value
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("invalid timeLimit {}", value))?;
+            }
+            _ => return Err(format!("unknown parameter {}", key)),
+        }
+    }
+    Ok((gas_limit, time_limit))
+}
+
+fn json(status: StatusCode, value: &impl Serialize) -> Response<Body> {
+    let body = serde_json::to_vec(value).expect("estimates serialize");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("valid response")
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+// Adds a `Retry-After` header with the delay in whole seconds, rounded up.
+fn with_retry_after(mut response: Response<Body>, retry_after: Option<Duration>) -> Response<Body> {
+    if let Some(retry_after) = retry_after {
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, seconds.into());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{testing::FixedGasPriceEstimator, EstimatedGasPrice};
+    use super::*;
+    use futures::channel::oneshot;
+
+    #[test]
+    fn parses_limits() {
+        assert_eq!(
+            parse_query("").unwrap(),
+            (DEFAULT_GAS_LIMIT, DEFAULT_TIME_LIMIT)
+        );
+        assert_eq!(
+            parse_query("timeLimit=60&gasLimit=100000").unwrap(),
+            (100_000.0, Duration::from_secs(60))
+        );
+        assert!(parse_query("timeLimit=-1").is_err());
+        assert!(parse_query("gasLimit=lots").is_err());
+        assert!(parse_query("gasLimit=inf").is_err());
+        assert!(parse_query("gasLimit=NaN").is_err());
+        assert!(parse_query("timeLimit=inf").is_err());
+        assert!(parse_query("speed=fast").is_err());
+    }
+
+    #[tokio::test]
+    async fn serves_estimates() {
+        let estimator = Arc::new(FixedGasPriceEstimator::legacy(100.0));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(estimator.clone(), listener, async {
+            let _ = stopped.await;
+        }));
+
+        let client = reqwest::Client::new();
+        let get = |path: String| {
+            let request = client.get(format!("{}{}", url, path));
+            async move { request.send().await.unwrap() }
+        };
+        let response = get(format!("{}?timeLimit=60", ESTIMATE_PATH)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let estimate: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(estimate["legacy"], 100.0);
+
+        let response = get(format!("{}?timeLimit=soon", ESTIMATE_PATH)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            get("/v2/estimate".into()).await.status(),
+            StatusCode::NOT_FOUND
+        );
+
+        estimator.push_result(Err(GasEstimationError::RateLimited { retry_after: None }));
+        let response = get(ESTIMATE_PATH.into()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+        estimator.push_result(Err(GasEstimationError::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        }));
+        let response = get(ESTIMATE_PATH.into()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+        estimator.push_result(Err(GasEstimationError::Timeout));
+        let response = get(ESTIMATE_PATH.into()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["error"], "timeout");
+        estimator.push_result(Err(GasEstimationError::CircuitOpen {
+            retry_after: Some(Duration::from_millis(1500)),
+        }));
+        let response = get(ESTIMATE_PATH.into()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+        assert_eq!(
+            estimator.estimate().await.unwrap(),
+            EstimatedGasPrice {
+                legacy: 100.0,
+                eip1559: None
+            }
+        );
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,281 @@
+//! Smoothing of estimates over time so that callers which resubmit transactions based on the
+//! latest estimate don't chase short spikes.
+
+use super::{
+    error::Result, EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasPrice1559,
+    GasPriceEstimating, GasPriceSchedule, GasPriceWei, TransactionCost, TxSpec,
+};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+pub struct Params {
+    // Weight of the newest estimate in the average, between 0 and 1. 1 disables smoothing.
+    pub alpha: f64,
+    // Maximum change of every value per estimate relative to the previous smoothed value, for
+    // example 0.125 for at most 12.5%.
+    pub max_step: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            alpha: 0.3,
+            max_step: 0.125,
+        }
+    }
+}
+
+// Exponential weighted moving average over the estimates of the inner estimator. Estimates are
+// smoothed per time limit because estimates for different urgencies are not comparable. Errors of
+// the inner estimator are returned and leave the average unchanged. When the inner estimator
+// switches between legacy and EIP-1559 estimates the average starts over.
+pub struct EwmaEstimator<T> {
+    inner: T,
+    params: Params,
+    // `None` is used for `estimate` like in `CachedGasPriceEstimating`.
+    averages: Mutex<HashMap<Option<Duration>, EstimatedGasPrice>>,
+}
+
+impl<T: GasPriceEstimating> EwmaEstimator<T> {
+    pub fn new(inner: T, params: Option<Params>) -> Self {
+        Self {
+            inner,
+            params: params.unwrap_or_default(),
+            averages: Default::default(),
+        }
+    }
+
+    fn smooth(&self, key: Option<Duration>, estimate: EstimatedGasPrice) -> EstimatedGasPrice {
+        let mut averages = self.averages.lock().unwrap();
+        let average = match averages.get(&key) {
+            Some(previous) if previous.eip1559.is_some() == estimate.eip1559.is_some() => {
+                self.step(previous, &estimate)
+            }
+            _ => estimate,
+        };
+        averages.insert(key, average);
+        average
+    }
+
+    fn step(
+        &self,
+        previous: &EstimatedGasPrice,
+        estimate: &EstimatedGasPrice,
+    ) -> EstimatedGasPrice {
+        let step = |previous: f64, value: f64| {
+            // A previous value of 0 can't be used to bound the relative step.
+            if previous <= 0.0 || !previous.is_finite() {
+                return value;
+            }
+            let max_step = self.params.max_step * previous;
+            let next = previous + self.params.alpha * (value - previous);
+            next.clamp(previous - max_step, previous + max_step)
+        };
+        let step_wei =
+            |previous: GasPriceWei, value: GasPriceWei| GasPriceWei(step(previous.0, value.0));
+        EstimatedGasPrice {
+            legacy: step(previous.legacy, estimate.legacy),
+            eip1559: previous
+                .eip1559
+                .zip(estimate.eip1559)
+                .map(|(previous, estimate)| {
+                    let max_fee_per_gas =
+                        step_wei(previous.max_fee_per_gas, estimate.max_fee_per_gas);
+                    GasPrice1559 {
+                        base_fee_per_gas: step_wei(
+                            previous.base_fee_per_gas,
+                            estimate.base_fee_per_gas,
+                        ),
+                        max_fee_per_gas,
+                        // The fields are smoothed independently so the tip could exceed the cap.
+                        max_priority_fee_per_gas: step_wei(
+                            previous.max_priority_fee_per_gas,
+                            estimate.max_priority_fee_per_gas,
+                        )
+                        .min(max_fee_per_gas),
+                    }
+                }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for EwmaEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_limits(gas_limit, time_limit)
+            .await?;
+        Ok(self.smooth(Some(time_limit), estimate))
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        let estimate = self.inner.estimate().await?;
+        Ok(self.smooth(None, estimate))
+    }
+
+    // The range is the one of the newest inner estimate, only the price is smoothed.
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let estimate = self.inner.estimate_verbose(gas_limit, time_limit).await?;
+        Ok(EstimateWithMetadata {
+            price: self.smooth(Some(time_limit), estimate.price),
+            ..estimate
+        })
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await?;
+        // Smoothing a replacement could undo its bump so replacements are passed through.
+        if hints.replacing.is_some() {
+            return Ok(estimate);
+        }
+        Ok(self.smooth(Some(time_limit), estimate))
+    }
+
+    // Only the gas price is smoothed, the l1 fee depends on the transaction.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let cost = self.inner.estimate_for_tx(tx).await?;
+        Ok(TransactionCost {
+            gas_price: self.smooth(Some(tx.time_limit), cost.gas_price),
+            ..cost
+        })
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let schedule = self.inner.estimate_schedule().await?;
+        Ok(schedule.map(|time_limit, estimate| self.smooth(Some(time_limit), estimate)))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let estimates = self.inner.estimate_many(requests).await?;
+        Ok(requests
+            .iter()
+            .zip(estimates)
+            .map(|((_, time_limit), estimate)| self.smooth(Some(*time_limit), estimate))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::FixedGasPriceEstimator;
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::GasEstimationError;
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn legacy(gas_price: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy: gas_price,
+            eip1559: None,
+        }
+    }
+
+    #[test]
+    fn averages_estimates() {
+        let estimator = EwmaEstimator::new(
+            FixedGasPriceEstimator::legacy(100.0),
+            Some(Params {
+                alpha: 0.5,
+                max_step: 1.0,
+            }),
+        );
+        assert_eq!(estimator.estimate().wait().unwrap().legacy, 100.0);
+        estimator.inner.set(legacy(200.0));
+        assert_approx_eq!(estimator.estimate().wait().unwrap().legacy, 150.0);
+        assert_approx_eq!(estimator.estimate().wait().unwrap().legacy, 175.0);
+        // Other time limits have their own average.
+        assert_eq!(
+            estimator
+                .estimate_with_limits(21000.0, Duration::from_secs(60))
+                .wait()
+                .unwrap()
+                .legacy,
+            200.0
+        );
+    }
+
+    #[test]
+    fn smooths_hinted_estimates_except_replacements() {
+        let estimator = EwmaEstimator::new(
+            FixedGasPriceEstimator::legacy(100.0),
+            Some(Params {
+                alpha: 0.5,
+                max_step: 1.0,
+            }),
+        );
+        let time_limit = Duration::from_secs(60);
+        let estimate = |hints| {
+            estimator
+                .estimate_with_hints(21000.0, time_limit, &hints)
+                .wait()
+                .unwrap()
+                .legacy
+        };
+        assert_eq!(estimate(EstimationHints::default()), 100.0);
+        estimator.inner.set(legacy(200.0));
+        assert_approx_eq!(estimate(EstimationHints::private()), 150.0);
+        assert_eq!(estimate(EstimationHints::replacing(legacy(100.0))), 200.0);
+    }
+
+    #[test]
+    fn clamps_step() {
+        let estimator = EwmaEstimator::new(FixedGasPriceEstimator::eip1559(10.0, 30.0, 2.0), None);
+        estimator.estimate().wait().unwrap();
+        // A spike of the tip to 100 is limited to 12.5% per estimate.
+        estimator.inner.set(EstimatedGasPrice {
+            legacy: 30.0,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(10.0),
+                max_fee_per_gas: GasPriceWei(30.0),
+                max_priority_fee_per_gas: GasPriceWei(100.0),
+            }),
+        });
+        let estimate = estimator.estimate().wait().unwrap();
+        assert_approx_eq!(estimate.tip(), 2.25);
+        assert_approx_eq!(estimate.cap(), 30.0);
+    }
+
+    #[test]
+    fn errors_keep_average() {
+        let estimator = EwmaEstimator::new(
+            FixedGasPriceEstimator::legacy(100.0),
+            Some(Params {
+                alpha: 0.5,
+                max_step: 1.0,
+            }),
+        );
+        estimator.estimate().wait().unwrap();
+        estimator
+            .inner
+            .push_result(Err(GasEstimationError::InvalidEstimate(
+                "spike".to_string(),
+            )));
+        assert!(estimator.estimate().wait().is_err());
+        estimator.inner.set(legacy(50.0));
+        assert_approx_eq!(estimator.estimate().wait().unwrap().legacy, 75.0);
+    }
+}
@@ -0,0 +1,149 @@
+//! Estimates saved to a small json file so that a restarted service can serve the last known
+//! estimate while its sources are unavailable instead of starting without any.
+
+use super::{error::Result, EstimatedGasPrice, GasPrice1559, GasPriceSchedule, GasPriceWei};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// The schedule types only implement `Deserialize` with the `serde_` feature so the file has its own.
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    // seconds since the unix epoch
+    saved_at: f64,
+    slow: Price,
+    standard: Price,
+    fast: Price,
+    instant: Price,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Price {
+    legacy: f64,
+    #[serde(default)]
+    eip1559: Option<Fees>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fees {
+    base_fee_per_gas: f64,
+    max_fee_per_gas: f64,
+    max_priority_fee_per_gas: f64,
+}
+
+impl From<&EstimatedGasPrice> for Price {
+    fn from(price: &EstimatedGasPrice) -> Self {
+        Self {
+            legacy: price.legacy,
+            eip1559: price.eip1559.map(|eip1559| Fees {
+                base_fee_per_gas: eip1559.base_fee_per_gas.0,
+                max_fee_per_gas: eip1559.max_fee_per_gas.0,
+                max_priority_fee_per_gas: eip1559.max_priority_fee_per_gas.0,
+            }),
+        }
+    }
+}
+
+impl Price {
+    fn estimate(&self) -> Result<EstimatedGasPrice> {
+        EstimatedGasPrice {
+            legacy: self.legacy,
+            eip1559: self.eip1559.as_ref().map(|fees| GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(fees.base_fee_per_gas),
+                max_fee_per_gas: GasPriceWei(fees.max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(fees.max_priority_fee_per_gas),
+            }),
+        }
+        .validate()
+    }
+}
+
+/// Writes the schedule to `path`. The file is replaced at once so that a crash while saving
+/// doesn't leave a partial snapshot behind.
+pub fn save(path: &Path, schedule: &GasPriceSchedule) -> Result<()> {
+    let saved_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let snapshot = Snapshot {
+        saved_at,
+        slow: (&schedule.slow).into(),
+        standard: (&schedule.standard).into(),
+        fast: (&schedule.fast).into(),
+        instant: (&schedule.instant).into(),
+    };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, serde_json::to_vec(&snapshot)?)
+        .and_then(|()| std::fs::rename(&temporary, path))
+        .with_context(|| format!("failed to write snapshot {}", path.display()))?;
+    Ok(())
+}
+
+/// Reads the schedule saved at `path` and how long ago it was saved.
+pub fn load(path: &Path) -> Result<(GasPriceSchedule, Duration)> {
+    let snapshot = std::fs::read(path)
+        .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+    let snapshot: Snapshot = serde_json::from_slice(&snapshot)?;
+    let saved_at = Duration::try_from_secs_f64(snapshot.saved_at)
+        .map_err(|_| anyhow!("invalid snapshot time {}", snapshot.saved_at))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let schedule = GasPriceSchedule {
+        slow: snapshot.slow.estimate()?,
+        standard: snapshot.standard.estimate()?,
+        fast: snapshot.fast.estimate()?,
+        instant: snapshot.instant.estimate()?,
+    };
+    Ok((schedule, now.saturating_sub(saved_at)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A path in the temporary directory that is unique to the test.
+    pub fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "gas-estimation-{}-{}.json",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn loads_saved_schedule() {
+        let path = path("loads_saved_schedule");
+        let eip1559 = EstimatedGasPrice {
+            legacy: 3.0,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(1.0),
+                max_fee_per_gas: GasPriceWei(3.0),
+                max_priority_fee_per_gas: GasPriceWei(2.0),
+            }),
+        };
+        let schedule = GasPriceSchedule {
+            slow: EstimatedGasPrice {
+                legacy: 1.0,
+                eip1559: None,
+            },
+            standard: eip1559,
+            fast: eip1559,
+            instant: eip1559,
+        };
+        save(&path, &schedule).unwrap();
+        let (loaded, age) = load(&path).unwrap();
+        assert_eq!(loaded, schedule);
+        assert!(age < Duration::from_secs(60));
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(load(&path).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Policies for what to pay given what the market costs. Estimators answer how expensive
+//! inclusion within a time limit is, a policy decides which of those prices a transaction should
+//! use based on its deadline and how much is lost if it misses it.
+
+use super::{error::Result, EstimateRange, EstimatedGasPrice, GasPriceEstimating};
+use std::time::Duration;
+
+/// What a policy knows about a transaction and the market. Fees and values in wei.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricingContext {
+    // time until the transaction has to be included
+    pub deadline: Duration,
+    // value that is lost if the transaction is not included before the deadline
+    pub value_at_risk: f64,
+    pub gas_limit: f64,
+    // estimate for inclusion before the deadline and its bounds if the source reports them
+    pub estimate: EstimatedGasPrice,
+    pub range: Option<EstimateRange>,
+    // estimate for inclusion in the next block
+    pub next_block: EstimatedGasPrice,
+}
+
+pub trait UrgencyPolicy: Send + Sync {
+    /// The gas price to send the transaction with.
+    fn choose(&self, context: &PricingContext) -> EstimatedGasPrice;
+}
+
+// Pays the estimate for the deadline. If the source reports a range the conservative bound is
+// paid instead when its extra cost is at most `max_risk_premium` times the value at risk, for
+// example 0.01 to spend up to 1% of what is at stake on making the deadline more likely.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheapestWithinDeadline {
+    pub max_risk_premium: f64,
+}
+
+impl UrgencyPolicy for CheapestWithinDeadline {
+    fn choose(&self, context: &PricingContext) -> EstimatedGasPrice {
+        match context.range {
+            Some(range) => {
+                let premium = context.gas_limit
+                    * (range.high.effective_gas_price() - context.estimate.effective_gas_price());
+                if premium <= self.max_risk_premium * context.value_at_risk {
+                    range.high
+                } else {
+                    context.estimate
+                }
+            }
+            None => context.estimate,
+        }
+    }
+}
+
+// Always pays for inclusion in the next block regardless of the deadline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AggressiveNextBlock;
+
+impl UrgencyPolicy for AggressiveNextBlock {
+    fn choose(&self, context: &PricingContext) -> EstimatedGasPrice {
+        context.next_block
+    }
+}
+
+/// Estimates the market for the deadline and the next block and lets `policy` choose.
+pub async fn choose_gas_price(
+    estimator: &dyn GasPriceEstimating,
+    policy: &dyn UrgencyPolicy,
+    gas_limit: f64,
+    deadline: Duration,
+    value_at_risk: f64,
+) -> Result<EstimatedGasPrice> {
+    let (estimate, next_block) = futures::try_join!(
+        estimator.estimate_verbose(gas_limit, deadline),
+        estimator.estimate_with_limits(gas_limit, estimator.block_time()),
+    )?;
+    Ok(policy.choose(&PricingContext {
+        deadline,
+        value_at_risk,
+        gas_limit,
+        estimate: estimate.price,
+        range: estimate.range,
+        next_block,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::{EstimateWithMetadata, MockGasPriceEstimating};
+    use super::*;
+
+    fn legacy(legacy: f64) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            eip1559: None,
+        }
+    }
+
+    fn context(range: Option<EstimateRange>) -> PricingContext {
+        PricingContext {
+            deadline: Duration::from_secs(300),
+            value_at_risk: 1e15,
+            gas_limit: 100_000.0,
+            estimate: legacy(10e9),
+            range,
+            next_block: legacy(30e9),
+        }
+    }
+
+    #[test]
+    fn cheapest_pays_conservative_bound_if_premium_is_small() {
+        let policy = CheapestWithinDeadline {
+            max_risk_premium: 0.01,
+        };
+        assert_eq!(policy.choose(&context(None)), legacy(10e9));
+        // 1e5 gas * 0.1 gwei = 1e13 wei which is 1% of the value at risk
+        let cheap_bound = EstimateRange {
+            low: legacy(9e9),
+            high: legacy(10.1e9),
+        };
+        assert_eq!(policy.choose(&context(Some(cheap_bound))), legacy(10.1e9));
+        let expensive_bound = EstimateRange {
+            low: legacy(9e9),
+            high: legacy(20e9),
+        };
+        assert_eq!(policy.choose(&context(Some(expensive_bound))), legacy(10e9));
+    }
+
+    #[test]
+    fn aggressive_pays_next_block() {
+        assert_eq!(AggressiveNextBlock.choose(&context(None)), legacy(30e9));
+    }
+
+    #[test]
+    fn estimates_deadline_and_next_block() {
+        let mut estimator = MockGasPriceEstimating::new();
+        estimator
+            .expect_block_time()
+            .returning(|| Duration::from_secs(12));
+        estimator
+            .expect_estimate_verbose()
+            .withf(|_, time_limit| *time_limit == Duration::from_secs(300))
+            .returning(|_, _| {
+                Ok(EstimateWithMetadata {
+                    price: legacy(10e9),
+                    source: "mock",
+                    observed_at: crate::time::Instant::now(),
+                    confidence: None,
+                    divergence: None,
+                    range: None,
+                })
+            });
+        estimator
+            .expect_estimate_with_limits()
+            .withf(|_, time_limit| *time_limit == Duration::from_secs(12))
+            .returning(|_, _| Ok(legacy(30e9)));
+        let choose = |policy: &dyn UrgencyPolicy| {
+            choose_gas_price(&estimator, policy, 21000.0, Duration::from_secs(300), 1e18)
+                .wait()
+                .unwrap()
+        };
+        assert_eq!(choose(&CheapestWithinDeadline::default()), legacy(10e9));
+        assert_eq!(choose(&AggressiveNextBlock), legacy(30e9));
+    }
+}
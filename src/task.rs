@@ -0,0 +1,118 @@
+//! Background tasks of the estimators that keep their estimates up to date, stopped when the
+//! estimator is shut down or dropped.
+
+use super::runtime::{self, Runtime};
+use futures::{
+    channel::oneshot,
+    future::{AbortHandle, Abortable},
+};
+use std::future::Future;
+
+pub(crate) struct BackgroundTask {
+    abort: AbortHandle,
+    // Completes when the task has finished or was dropped. `None` after the task has been awaited
+    // by `shutdown`.
+    finished: Option<oneshot::Receiver<()>>,
+}
+
+impl BackgroundTask {
+    // Only the tokio based estimators spawn on the default runtime.
+    #[cfg_attr(not(feature = "tokio_"), allow(dead_code))]
+    pub fn spawn(task: impl Future<Output = ()> + Send + 'static) -> Self {
+        Self::spawn_on(runtime::default_runtime().as_ref(), task)
+    }
+
+    pub fn spawn_on(
+        runtime: &dyn Runtime,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> Self {
+        let (abort, registration) = AbortHandle::new_pair();
+        let (sender, finished) = oneshot::channel();
+        let task = Abortable::new(task, registration);
+        runtime.spawn(Box::pin(async move {
+            let _ = task.await;
+            let _ = sender.send(());
+        }));
+        Self {
+            abort,
+            finished: Some(finished),
+        }
+    }
+
+    // Cancels the task at its next await point and waits until it has finished, which closes the
+    // connections it owns.
+    pub async fn shutdown(&mut self) {
+        self.abort.abort();
+        let finished = match self.finished.take() {
+            Some(finished) => finished,
+            None => return,
+        };
+        // The sender is dropped without sending if the task panicked.
+        if finished.await.is_err() {
+            tracing::warn!("background task panicked");
+        }
+    }
+}
+
+impl Drop for BackgroundTask {
+    fn drop(&mut self) {
+        self.abort.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, time::Duration};
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn shutdown_drops_task() {
+        let resource = Arc::new(());
+        let task_resource = resource.clone();
+        let mut task = BackgroundTask::spawn(async move {
+            let _resource = task_resource;
+            std::future::pending::<()>().await;
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(Arc::strong_count(&resource), 2);
+        task.shutdown().await;
+        assert_eq!(Arc::strong_count(&resource), 1);
+        // shutting down again is fine
+        task.shutdown().await;
+    }
+
+    #[cfg(feature = "tokio_")]
+    #[tokio::test]
+    async fn drop_cancels_task() {
+        let resource = Arc::new(());
+        let task_resource = resource.clone();
+        let task = BackgroundTask::spawn(async move {
+            let _resource = task_resource;
+            std::future::pending::<()>().await;
+        });
+        drop(task);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(Arc::strong_count(&resource), 1);
+    }
+
+    #[cfg(feature = "async-std_")]
+    #[test]
+    fn shutdown_on_async_std() {
+        let resource = Arc::new(());
+        let task_resource = resource.clone();
+        async_std::task::block_on(async {
+            let runtime = runtime::AsyncStdRuntime;
+            let mut task = BackgroundTask::spawn_on(&runtime, async move {
+                let _resource = task_resource;
+                runtime::AsyncStdRuntime
+                    .sleep(Duration::from_secs(3600))
+                    .await;
+            });
+            runtime.sleep(Duration::from_millis(10)).await;
+            assert_eq!(Arc::strong_count(&resource), 2);
+            task.shutdown().await;
+        });
+        assert_eq!(Arc::strong_count(&resource), 1);
+    }
+}
@@ -0,0 +1,339 @@
+//! Fixtures and a `Transport` that serves them, for integration tests of estimator stacks without
+//! requests to the real apis.
+
+use super::{error::Result, GasEstimationError, Transport};
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A canned response of an api at the url the estimator requests by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fixture {
+    pub url: &'static str,
+    pub body: &'static str,
+}
+
+pub mod fixtures {
+    use super::Fixture;
+
+    pub const ETHERSCAN: Fixture = Fixture {
+        url: "https://api.etherscan.io/api?module=gastracker&action=gasoracle",
+        body: r#"{
+            "status": "1",
+            "message": "OK",
+            "result": {
+                "LastBlock": "13053741",
+                "SafeGasPrice": "20",
+                "ProposeGasPrice": "22",
+                "FastGasPrice": "24",
+                "suggestBaseFee": "19.230609716",
+                "gasUsedRatio": "0.370119078777807,0.8954731,0.550911766666667"
+            }
+        }"#,
+    };
+
+    pub const ETHGASSTATION: Fixture = Fixture {
+        url: "https://ethgasstation.info/api/ethgasAPI.json",
+        body: r#"{
+            "fastest": 300.0,
+            "fast": 240.0,
+            "average": 220.0,
+            "safeLow": 200.0,
+            "fastestWait": 0.5,
+            "fastWait": 1.0,
+            "avgWait": 3.0,
+            "safeLowWait": 10.0
+        }"#,
+    };
+
+    pub const GASNOW: Fixture = Fixture {
+        url: "https://etherchain.org/api/gasnow",
+        body: r#"{
+            "code": 200,
+            "data": {
+                "rapid": 30000000000,
+                "fast": 24000000000,
+                "standard": 22000000000,
+                "slow": 20000000000,
+                "timestamp": 1630000000000
+            }
+        }"#,
+    };
+
+    pub const GNOSIS_CHAIN: Fixture = Fixture {
+        url: "https://gnosis.blockscout.com/api/v2/stats",
+        body: r#"{
+            "average_block_time": 5000.0,
+            "gas_prices": {
+                "slow": { "price": 1.1, "time": 30000.0, "base_fee": 1.0, "priority_fee": 0.1 },
+                "average": { "price": 1.5, "time": 15000.0, "base_fee": 1.0, "priority_fee": 0.5 },
+                "fast": { "price": 2.0, "time": 5000.0, "base_fee": 1.0, "priority_fee": 1.0 }
+            },
+            "total_blocks": "30000000"
+        }"#,
+    };
+
+    pub const POLYGON: Fixture = Fixture {
+        url: "https://gasstation-mainnet.matic.network/v2",
+        body: r#"{
+            "safeLow": { "maxPriorityFee": 30.7611840636, "maxFee": 30.7611840796 },
+            "standard": { "maxPriorityFee": 32.146027800733336, "maxFee": 32.14602781673334 },
+            "fast": { "maxPriorityFee": 33.284344224133335, "maxFee": 33.284344240133336 },
+            "estimatedBaseFee": 1.6e-8,
+            "blockTime": 6,
+            "blockNumber": 24962816
+        }"#,
+    };
+
+    pub const ONEINCH: Fixture = Fixture {
+        url: "https://api.1inch.dev/gas-price/v1.4/1",
+        body: r#"{
+            "baseFee": "10000000000",
+            "low": { "maxPriorityFeePerGas": "100000000", "maxFeePerGas": "12000000000" },
+            "medium": { "maxPriorityFeePerGas": "500000000", "maxFeePerGas": "13000000000" },
+            "high": { "maxPriorityFeePerGas": "1000000000", "maxFeePerGas": "15000000000" },
+            "instant": { "maxPriorityFeePerGas": "2000000000", "maxFeePerGas": "20000000000" }
+        }"#,
+    };
+
+    // At the url used with an api key secret.
+    pub const INFURA: Fixture = Fixture {
+        url: "https://gas.api.infura.io/networks/1/suggestedGasFees",
+        body: r#"{
+            "low": {
+                "suggestedMaxPriorityFeePerGas": "0.05",
+                "suggestedMaxFeePerGas": "16.334026964",
+                "minWaitTimeEstimate": 15000,
+                "maxWaitTimeEstimate": 30000
+            },
+            "medium": {
+                "suggestedMaxPriorityFeePerGas": "0.1",
+                "suggestedMaxFeePerGas": "22.083436402",
+                "minWaitTimeEstimate": 15000,
+                "maxWaitTimeEstimate": 45000
+            },
+            "high": {
+                "suggestedMaxPriorityFeePerGas": "0.3",
+                "suggestedMaxFeePerGas": "27.982845839",
+                "minWaitTimeEstimate": 15000,
+                "maxWaitTimeEstimate": 60000
+            },
+            "estimatedBaseFee": "16.284026964",
+            "networkCongestion": 0.5125
+        }"#,
+    };
+
+    pub const ALL: &[Fixture] = &[
+        ETHERSCAN,
+        ETHGASSTATION,
+        GASNOW,
+        GNOSIS_CHAIN,
+        POLYGON,
+        ONEINCH,
+        INFURA,
+    ];
+}
+
+#[derive(Default)]
+struct Route {
+    body: Option<String>,
+    latency: Duration,
+    // returned before the body, one per request
+    failures: VecDeque<GasEstimationError>,
+}
+
+// Serves fixed responses per url. A route matches every request whose url starts with the route's
+// url, so query parameters added by credentials don't need their own routes. The longest matching
+// route wins. Requests without a matching route fail with a transport error. GET and POST requests
+// are served the same way. Clones share the routes, so a test can keep a clone to change responses
+// while the estimator under test uses another.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    routes: Arc<Mutex<HashMap<String, Route>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves all fixtures of `fixtures::ALL`.
+    pub fn with_fixtures() -> Self {
+        fixtures::ALL
+            .iter()
+            .fold(Self::new(), |transport, fixture| {
+                transport.with_fixture(*fixture)
+            })
+    }
+
+    pub fn with_fixture(self, fixture: Fixture) -> Self {
+        self.with_response(fixture.url, fixture.body)
+    }
+
+    pub fn with_response(self, url: &str, body: impl Into<String>) -> Self {
+        self.set_response(url, body);
+        self
+    }
+
+    /// Requests for `url` complete after `latency`.
+    pub fn with_latency(self, url: &str, latency: Duration) -> Self {
+        self.update_route(url, |route| route.latency = latency);
+        self
+    }
+
+    /// Replaces the response, for example to simulate a price change.
+    pub fn set_response(&self, url: &str, body: impl Into<String>) {
+        let body = body.into();
+        self.update_route(url, |route| route.body = Some(body));
+    }
+
+    /// The next request for `url` fails with `error`. Failures are returned in the order they
+    /// were pushed.
+    pub fn push_failure(&self, url: &str, error: GasEstimationError) {
+        self.update_route(url, |route| route.failures.push_back(error));
+    }
+
+    /// Urls of all requests so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn update_route(&self, url: &str, update: impl FnOnce(&mut Route)) {
+        update(
+            self.routes
+                .lock()
+                .unwrap()
+                .entry(url.to_string())
+                .or_default(),
+        );
+    }
+
+    async fn respond<R: DeserializeOwned>(&self, url: &str) -> Result<R> {
+        self.requests.lock().unwrap().push(url.to_string());
+        let (latency, response) = {
+            let mut routes = self.routes.lock().unwrap();
+            let route = routes
+                .iter_mut()
+                .filter(|(route, _)| url.starts_with(route.as_str()))
+                .max_by_key(|(route, _)| route.len())
+                .map(|(_, route)| route)
+                .ok_or_else(|| {
+                    GasEstimationError::Transport(anyhow!("no mock response for {}", url))
+                })?;
+            let response = match route.failures.pop_front() {
+                Some(error) => Err(error),
+                None => route.body.clone().ok_or_else(|| {
+                    GasEstimationError::Transport(anyhow!("no mock response for {}", url))
+                }),
+            };
+            (route.latency, response)
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        Ok(serde_json::from_str(&response?)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for MockTransport {
+    async fn get_json<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        _header: http::header::HeaderMap,
+    ) -> Result<R> {
+        self.respond(url).await
+    }
+
+    async fn post_json<Req, Resp>(
+        &self,
+        url: &str,
+        _header: http::header::HeaderMap,
+        _body: &Req,
+    ) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        self.respond(url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::FutureWaitExt as _, EthGasStation, EtherscanGasStation, GasNowGasStation,
+        GasPriceEstimating, GnosisChainGasStation, InfuraGasStation, OneInchGasStation,
+        PolygonGasStation,
+    };
+    use super::*;
+
+    #[test]
+    fn estimators_parse_fixtures() {
+        let transport = MockTransport::with_fixtures();
+        let estimators: Vec<Box<dyn GasPriceEstimating>> = vec![
+            Box::new(EtherscanGasStation::with_api_key(
+                transport.clone(),
+                "key".into(),
+            )),
+            Box::new(EthGasStation::new(transport.clone())),
+            Box::new(GasNowGasStation::new(transport.clone())),
+            Box::new(GnosisChainGasStation::new(transport.clone())),
+            Box::new(PolygonGasStation::with_network_id("137", transport.clone()).unwrap()),
+            Box::new(OneInchGasStation::new(1, transport.clone(), "key")),
+            Box::new(InfuraGasStation::new(
+                1,
+                transport.clone(),
+                "key",
+                Some("secret".to_string()),
+            )),
+        ];
+        for estimator in estimators {
+            let estimate = estimator.estimate().wait();
+            assert!(estimate.is_ok(), "{}: {:?}", estimator.source(), estimate);
+        }
+        assert_eq!(transport.requests().len(), fixtures::ALL.len());
+    }
+
+    #[test]
+    fn fails_and_recovers() {
+        let transport = MockTransport::new().with_fixture(fixtures::ETHGASSTATION);
+        transport.push_failure(
+            fixtures::ETHGASSTATION.url,
+            GasEstimationError::RateLimited { retry_after: None },
+        );
+        let estimator = EthGasStation::new(transport);
+        assert!(matches!(
+            estimator.estimate().wait(),
+            Err(GasEstimationError::RateLimited { .. })
+        ));
+        assert!(estimator.estimate().wait().is_ok());
+    }
+
+    #[test]
+    fn unknown_url_fails() {
+        let transport = MockTransport::new().with_fixture(fixtures::GASNOW);
+        let result: Result<serde_json::Value> = transport
+            .get_json("https://example.com", Default::default())
+            .wait();
+        assert!(matches!(result, Err(GasEstimationError::Transport(_))));
+    }
+
+    #[tokio::test]
+    async fn delays_responses() {
+        let transport = MockTransport::new()
+            .with_fixture(fixtures::GASNOW)
+            .with_latency(fixtures::GASNOW.url, Duration::from_millis(50));
+        let start = std::time::Instant::now();
+        let result: Result<serde_json::Value> = transport
+            .get_json(fixtures::GASNOW.url, Default::default())
+            .await;
+        assert!(result.is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,124 @@
+//! Estimators for tests and development networks.
+
+use super::{error::Result, EstimatedGasPrice, GasPrice1559, GasPriceEstimating, GasPriceWei};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+// Returns the same estimate for every call regardless of the limits. Results queued with
+// `push_result` are returned first, one per call, which allows simulating failures or price
+// changes without mocks.
+pub struct FixedGasPriceEstimator {
+    estimate: Mutex<EstimatedGasPrice>,
+    overrides: Mutex<VecDeque<Result<EstimatedGasPrice>>>,
+    calls: AtomicUsize,
+}
+
+impl FixedGasPriceEstimator {
+    pub fn new(estimate: EstimatedGasPrice) -> Self {
+        Self {
+            estimate: Mutex::new(estimate),
+            overrides: Default::default(),
+            calls: Default::default(),
+        }
+    }
+
+    pub fn legacy(gas_price: f64) -> Self {
+        Self::new(EstimatedGasPrice {
+            legacy: gas_price,
+            eip1559: None,
+        })
+    }
+
+    // The legacy gas price is set to max_fee_per_gas.
+    pub fn eip1559(
+        base_fee_per_gas: f64,
+        max_fee_per_gas: f64,
+        max_priority_fee_per_gas: f64,
+    ) -> Self {
+        Self::new(EstimatedGasPrice {
+            legacy: max_fee_per_gas,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee_per_gas),
+                max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+            }),
+        })
+    }
+
+    /// Changes the estimate returned once the queued results are used up.
+    pub fn set(&self, estimate: EstimatedGasPrice) {
+        *self.estimate.lock().unwrap() = estimate;
+    }
+
+    /// Queues a result for a single call.
+    pub fn push_result(&self, result: Result<EstimatedGasPrice>) {
+        self.overrides.lock().unwrap().push_back(result);
+    }
+
+    /// Number of estimates that have been requested so far.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl GasPriceEstimating for FixedGasPriceEstimator {
+    fn source(&self) -> &'static str {
+        "fixed"
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if let Some(result) = self.overrides.lock().unwrap().pop_front() {
+            return result;
+        }
+        Ok(*self.estimate.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::super::GasEstimationError;
+    use super::*;
+
+    #[test]
+    fn returns_fixed_estimate() {
+        let estimator = FixedGasPriceEstimator::eip1559(1.0, 3.0, 2.0);
+        let estimate = estimator.estimate().wait().unwrap();
+        assert_eq!(estimate.legacy, 3.0);
+        assert_eq!(estimate.tip(), 2.0);
+        estimator.set(EstimatedGasPrice {
+            legacy: 5.0,
+            eip1559: None,
+        });
+        assert_eq!(estimator.estimate().wait().unwrap().legacy, 5.0);
+        assert_eq!(estimator.calls(), 2);
+    }
+
+    #[test]
+    fn queued_results_come_first() {
+        let estimator = FixedGasPriceEstimator::legacy(1.0);
+        estimator.push_result(Err(GasEstimationError::Timeout));
+        estimator.push_result(Ok(EstimatedGasPrice {
+            legacy: 2.0,
+            eip1559: None,
+        }));
+        assert!(matches!(
+            estimator.estimate().wait(),
+            Err(GasEstimationError::Timeout)
+        ));
+        assert_eq!(estimator.estimate().wait().unwrap().legacy, 2.0);
+        assert_eq!(estimator.estimate().wait().unwrap().legacy, 1.0);
+    }
+}
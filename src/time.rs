@@ -0,0 +1,177 @@
+//! Time for the estimators that cache or rate limit requests.
+//!
+//! `std::time::Instant::now` panics on `wasm32-unknown-unknown` so there `Instant` is measured
+//! with `Date.now()` of the javascript host instead. It has millisecond resolution and is not
+//! monotonic, which is fine for the rate limits and cache expiries the estimators use it for.
+
+#[cfg(feature = "tokio_")]
+use futures::future::BoxFuture;
+#[cfg(feature = "tokio_")]
+use std::time::Duration;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::Instant;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use wasm::Instant;
+
+/// Source of the current time. Estimators that take a clock use `SystemClock` by default, tests
+/// and hosts without a system clock can inject their own.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Waits for `duration`, on the tokio timer unless overridden. Fake clocks usually advance
+    /// their time instead so that background tasks run without waiting.
+    #[cfg(feature = "tokio_")]
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<T: Clock + ?Sized> Clock for std::sync::Arc<T> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    #[cfg(feature = "tokio_")]
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        (**self).sleep(duration)
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod wasm {
+    use std::{
+        ops::{Add, AddAssign, Sub, SubAssign},
+        time::Duration,
+    };
+
+    // Time since the unix epoch.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(Duration::from_secs_f64(
+                js_sys::Date::now().max(0.0) / 1000.0,
+            ))
+        }
+
+        pub fn duration_since(&self, earlier: Self) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub fn checked_duration_since(&self, earlier: Self) -> Option<Duration> {
+            self.0.checked_sub(earlier.0)
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Self) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Self::now().saturating_duration_since(*self)
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+            self.0.checked_add(duration).map(Self)
+        }
+
+        pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+            self.0.checked_sub(duration).map(Self)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Self;
+
+        fn add(self, duration: Duration) -> Self {
+            Self(self.0 + duration)
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, duration: Duration) {
+            self.0 += duration;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Self;
+
+        fn sub(self, duration: Duration) -> Self {
+            Self(self.0 - duration)
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, duration: Duration) {
+            self.0 -= duration;
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        fn sub(self, earlier: Self) -> Duration {
+            self.duration_since(earlier)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    // Clock that only moves when advanced.
+    #[derive(Clone)]
+    pub struct ManualClock(Arc<Mutex<Instant>>);
+
+    impl Default for ManualClock {
+        fn default() -> Self {
+            Self(Arc::new(Mutex::new(Instant::now())))
+        }
+    }
+
+    impl ManualClock {
+        pub fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+
+        // Yields so that a background task sleeping in a loop doesn't starve the runtime.
+        #[cfg(feature = "tokio_")]
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            self.advance(duration);
+            Box::pin(tokio::task::yield_now())
+        }
+    }
+
+    #[test]
+    fn manual_clock_advances() {
+        let clock = ManualClock::default();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(clock.now().duration_since(start), Duration::from_secs(5));
+        assert!(SystemClock.now() >= start);
+    }
+}
@@ -0,0 +1,199 @@
+use super::{
+    error::Result,
+    transport::{CacheValidators, Conditional},
+    EstimateWithMetadata, EstimatedGasPrice, EstimationHints, GasEstimationError,
+    GasPriceEstimating, GasPriceSchedule, TransactionCost, Transport, TxSpec,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{future::Future, time::Duration};
+
+// Fails estimates of the inner estimator that take longer than `timeout` with
+// `GasEstimationError::Timeout`. The inner future is dropped which cancels the request.
+pub struct TimeLimitedEstimator<T> {
+    inner: T,
+    timeout: Duration,
+}
+
+impl<T: GasPriceEstimating> TimeLimitedEstimator<T> {
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for TimeLimitedEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        with_timeout(
+            self.timeout,
+            self.inner.estimate_with_limits(gas_limit, time_limit),
+        )
+        .await
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        with_timeout(self.timeout, self.inner.estimate()).await
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        with_timeout(
+            self.timeout,
+            self.inner.estimate_verbose(gas_limit, time_limit),
+        )
+        .await
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        with_timeout(
+            self.timeout,
+            self.inner.estimate_with_hints(gas_limit, time_limit, hints),
+        )
+        .await
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        with_timeout(self.timeout, self.inner.estimate_for_tx(tx)).await
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        with_timeout(self.timeout, self.inner.estimate_schedule()).await
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        with_timeout(self.timeout, self.inner.estimate_many(requests)).await
+    }
+}
+
+// Enforces a deadline on every request of the inner transport. This is how the http based
+// estimators get a timeout, for example `EthGasStation::new(TimeLimitedTransport::new(..))`.
+pub struct TimeLimitedTransport<T> {
+    inner: T,
+    timeout: Duration,
+}
+
+impl<T: Transport> TimeLimitedTransport<T> {
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> Transport for TimeLimitedTransport<T> {
+    async fn get_json<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+    ) -> Result<R> {
+        with_timeout(self.timeout, self.inner.get_json(url, header)).await
+    }
+
+    async fn post_json<Req, Resp>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        body: &Req,
+    ) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        with_timeout(self.timeout, self.inner.post_json(url, header, body)).await
+    }
+
+    async fn get_json_conditional<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        validators: &CacheValidators,
+    ) -> Result<Conditional<R>> {
+        with_timeout(
+            self.timeout,
+            self.inner.get_json_conditional(url, header, validators),
+        )
+        .await
+    }
+}
+
+async fn with_timeout<R>(timeout: Duration, future: impl Future<Output = Result<R>>) -> Result<R> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or(Err(GasEstimationError::Timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::MockGasPriceEstimating;
+    use super::*;
+
+    struct PendingTransport;
+
+    #[async_trait::async_trait]
+    impl Transport for PendingTransport {
+        async fn get_json<R: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<R> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Err(GasEstimationError::Timeout)
+        }
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let transport = TimeLimitedTransport::new(PendingTransport, Duration::from_millis(1));
+        let result = transport.get_json::<u32>("", Default::default()).await;
+        assert!(matches!(result, Err(GasEstimationError::Timeout)));
+    }
+
+    struct PendingEstimator;
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for PendingEstimator {
+        async fn estimate_with_limits(
+            &self,
+            _gas_limit: f64,
+            _time_limit: Duration,
+        ) -> Result<EstimatedGasPrice> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn estimate_times_out() {
+        let estimator = TimeLimitedEstimator::new(PendingEstimator, Duration::from_millis(1));
+        let result = estimator.estimate().await;
+        assert!(matches!(result, Err(GasEstimationError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn passes_through_in_time() {
+        let mut inner = MockGasPriceEstimating::new();
+        inner
+            .expect_estimate()
+            .times(1)
+            .returning(|| Ok(Default::default()));
+        let estimator = TimeLimitedEstimator::new(inner, Duration::from_secs(1));
+        assert!(estimator.estimate().await.is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+//! Spans and events that explain how an estimate was produced. They are only emitted with the
+//! `tracing_` feature so that the default build only logs failures.
+
+use super::error::Result;
+use std::{fmt::Debug, future::Future};
+
+/// Runs `estimate` in a span with the source and url and logs the latency and the result.
+pub async fn estimate<R: Debug>(
+    source: &'static str,
+    url: Option<&str>,
+    estimate: impl Future<Output = Result<R>>,
+) -> Result<R> {
+    #[cfg(feature = "tracing_")]
+    {
+        use tracing::Instrument as _;
+        let span = tracing::debug_span!("gas_estimate", source, url = url.unwrap_or_default());
+        async move {
+            let start = crate::time::Instant::now();
+            let result = estimate.await;
+            let latency = start.elapsed();
+            match &result {
+                Ok(estimate) => tracing::debug!(?latency, ?estimate, "estimate succeeded"),
+                Err(err) => tracing::debug!(?latency, %err, "estimate failed"),
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+    #[cfg(not(feature = "tracing_"))]
+    {
+        let _ = (source, url);
+        estimate.await
+    }
+}
+
+/// Logs a decoded response of a gas price api.
+pub fn response(source: &'static str, response: &impl Debug) {
+    #[cfg(feature = "tracing_")]
+    tracing::trace!(source, ?response, "decoded response");
+    #[cfg(not(feature = "tracing_"))]
+    let _ = (source, response);
+}
+
+/// Logs the raw json body of a response, see `DebugTransport`.
+pub fn raw_response(url: &str, body: &str) {
+    #[cfg(feature = "tracing_")]
+    tracing::trace!(url, body, "raw response");
+    #[cfg(not(feature = "tracing_"))]
+    let _ = (url, body);
+}
+
+#[cfg(all(test, feature = "tracing_"))]
+mod tests {
+    use super::super::tests::FutureWaitExt as _;
+    use super::*;
+
+    #[test]
+    fn passes_through_result() {
+        let _ = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_test_writer()
+            .try_init();
+        response("test", &1);
+        raw_response("http://localhost", "1");
+        assert_eq!(
+            estimate("test", Some("http://localhost"), async { Ok(1) })
+                .wait()
+                .unwrap(),
+            1
+        );
+        assert!(
+            estimate::<()>("test", None, async { Err(anyhow::anyhow!("").into()) })
+                .wait()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Fee fields of the transaction types of web3 and ethers from an estimate, for example
+//! `TransactionParameters { to, value, ..estimate.into() }`. Fees are rounded up to whole wei.
+//!
+//! EIP-1559 estimates become type 2 transactions. Legacy estimates set the gas price, or both fees
+//! to the gas price for transaction types that only have EIP-1559 fees.
+
+use super::{EstimatedGasPrice, GasPriceWei};
+
+#[cfg(feature = "web3_")]
+impl From<EstimatedGasPrice> for web3::types::TransactionParameters {
+    fn from(gas_price: EstimatedGasPrice) -> Self {
+        match gas_price.eip1559 {
+            Some(eip1559) => Self {
+                transaction_type: Some(2.into()),
+                max_fee_per_gas: Some(eip1559.max_fee_per_gas.to_u256()),
+                max_priority_fee_per_gas: Some(eip1559.max_priority_fee_per_gas.to_u256()),
+                ..Default::default()
+            },
+            None => Self {
+                gas_price: Some(GasPriceWei(gas_price.legacy).to_u256()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// ethers uses a newer `U256` than `primitive-types` of this crate so the fees are converted
+// through u128. `Eip1559TransactionRequest::from` sets the sender, use `estimate.into()` instead.
+#[cfg(feature = "ethers_")]
+impl From<EstimatedGasPrice> for ethers_core::types::Eip1559TransactionRequest {
+    fn from(gas_price: EstimatedGasPrice) -> Self {
+        let (max_fee_per_gas, max_priority_fee_per_gas) = match gas_price.eip1559 {
+            Some(eip1559) => (eip1559.max_fee_per_gas, eip1559.max_priority_fee_per_gas),
+            None => (GasPriceWei(gas_price.legacy), GasPriceWei(gas_price.legacy)),
+        };
+        Self {
+            max_fee_per_gas: Some(max_fee_per_gas.to_u128().into()),
+            max_priority_fee_per_gas: Some(max_priority_fee_per_gas.to_u128().into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GasPrice1559;
+
+    fn estimates() -> (EstimatedGasPrice, EstimatedGasPrice) {
+        let legacy = EstimatedGasPrice {
+            legacy: 20e9 + 0.5,
+            eip1559: None,
+        };
+        let eip1559 = EstimatedGasPrice {
+            legacy: 30e9,
+            eip1559: Some(GasPrice1559::from_wei(
+                10_000_000_000,
+                30_000_000_000,
+                2_000_000_000,
+            )),
+        };
+        (legacy, eip1559)
+    }
+
+    #[cfg(feature = "web3_")]
+    #[test]
+    fn web3_transaction_parameters() {
+        use web3::types::{TransactionParameters, U256};
+        let (legacy, eip1559) = estimates();
+        let parameters = TransactionParameters::from(legacy);
+        assert_eq!(parameters.gas_price, Some(U256::from(20_000_000_001u64)));
+        assert_eq!(parameters.transaction_type, None);
+        assert_eq!(parameters.max_fee_per_gas, None);
+
+        let parameters = TransactionParameters::from(eip1559);
+        assert_eq!(parameters.gas_price, None);
+        assert_eq!(parameters.transaction_type, Some(2.into()));
+        assert_eq!(
+            parameters.max_fee_per_gas,
+            Some(U256::from(30_000_000_000u64))
+        );
+        assert_eq!(
+            parameters.max_priority_fee_per_gas,
+            Some(U256::from(2_000_000_000u64))
+        );
+    }
+
+    #[cfg(feature = "ethers_")]
+    #[test]
+    fn ethers_eip1559_request() {
+        use ethers_core::types::{Eip1559TransactionRequest, U256};
+        let (legacy, eip1559) = estimates();
+        let request: Eip1559TransactionRequest = legacy.into();
+        assert_eq!(request.max_fee_per_gas, Some(U256::from(20_000_000_001u64)));
+        assert_eq!(request.max_priority_fee_per_gas, request.max_fee_per_gas);
+
+        let request: Eip1559TransactionRequest = eip1559.into();
+        assert_eq!(request.max_fee_per_gas, Some(U256::from(30_000_000_000u64)));
+        assert_eq!(
+            request.max_priority_fee_per_gas,
+            Some(U256::from(2_000_000_000u64))
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! `Transport` implementations: an http client based on reqwest with the `reqwest_` feature and
+//! transports that record responses to a file and play them back for offline development.
+
+#[cfg(feature = "reqwest_")]
+mod http_client;
+mod recording;
+
+#[cfg(feature = "reqwest_")]
+pub use http_client::{Params, ReqwestTransport};
+pub use recording::{PlaybackTransport, RecordingTransport};
+
+use http::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+
+/// Cache validators of a response, sent back with `Transport::get_json_conditional` so that the
+/// server can answer with 304 Not Modified instead of the whole response if it has not changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheValidators {
+    pub etag: Option<HeaderValue>,
+    pub last_modified: Option<HeaderValue>,
+}
+
+impl CacheValidators {
+    /// The validators of a response.
+    pub fn from_response_headers(headers: &HeaderMap) -> Self {
+        Self {
+            etag: headers.get(ETAG).cloned(),
+            last_modified: headers.get(LAST_MODIFIED).cloned(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Adds the conditional request headers for these validators.
+    pub fn apply(&self, header: &mut HeaderMap) {
+        if let Some(etag) = &self.etag {
+            header.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            header.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+    }
+}
+
+/// Result of a conditional request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conditional<T> {
+    Modified {
+        value: T,
+        // validators for the next request, empty if the server sent none
+        validators: CacheValidators,
+    },
+    NotModified,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validators_become_conditional_headers() {
+        let mut response = HeaderMap::new();
+        assert!(CacheValidators::from_response_headers(&response).is_empty());
+        response.insert(ETAG, "\"v1\"".parse().unwrap());
+        response.insert(
+            LAST_MODIFIED,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        let validators = CacheValidators::from_response_headers(&response);
+        assert!(!validators.is_empty());
+
+        let mut request = HeaderMap::new();
+        validators.apply(&mut request);
+        assert_eq!(request[IF_NONE_MATCH], "\"v1\"");
+        assert_eq!(request[IF_MODIFIED_SINCE], "Wed, 21 Oct 2015 07:28:00 GMT");
+    }
+}
@@ -0,0 +1,460 @@
+// `Transport` implementation based on reqwest so that the http gas stations can be used without
+// writing a transport first.
+
+use super::{CacheValidators, Conditional};
+use crate::{
+    error::{HttpStatus, Result},
+    GasEstimationError, Transport,
+};
+use anyhow::anyhow;
+use http::{
+    header::{HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const DEFAULT_USER_AGENT: &str = concat!("gas-estimation/", env!("CARGO_PKG_VERSION"));
+// The gas price apis are few urls polled repeatedly so the cache only grows past this if urls
+// change between requests, for example with a timestamp in the query.
+const MAX_CACHED_RESPONSES: usize = 64;
+
+/// Parameters for the http client.
+#[derive(Debug, Clone)]
+pub struct Params {
+    // timeout of a whole request including reading the response body
+    pub timeout: Option<Duration>,
+    // timeout for establishing the connection
+    pub connect_timeout: Option<Duration>,
+    // proxy url used for all requests, for example "http://localhost:8080"
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    // maximum number of idle pooled connections kept per host
+    pub pool_max_idle_per_host: usize,
+    // Keep the last response of every url and request headers with cache validators and make GET
+    // requests conditional so that servers can answer with 304 Not Modified when polled frequently.
+    pub conditional_requests: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(10)),
+            connect_timeout: Some(Duration::from_secs(5)),
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            pool_max_idle_per_host: usize::MAX,
+            conditional_requests: false,
+        }
+    }
+}
+
+/// Http transport using a pooled reqwest client. Clones share the connection pool and the cached
+/// responses.
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+    // `None` without `Params::conditional_requests`
+    cache: Option<Arc<Mutex<HashMap<CacheKey, CachedResponse>>>>,
+}
+
+// The url and the request headers, without conditional headers the caller might have set, sorted
+// by name. Responses can depend on the headers, for example on the credentials of a paid tier.
+type CacheKey = (String, Vec<(String, Vec<u8>)>);
+
+fn cache_key(url: &str, header: &HeaderMap) -> CacheKey {
+    let mut header = header
+        .iter()
+        .filter(|(name, _)| **name != IF_NONE_MATCH && **name != IF_MODIFIED_SINCE)
+        .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+        .collect::<Vec<_>>();
+    header.sort();
+    (url.to_string(), header)
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    validators: CacheValidators,
+    body: String,
+    // the position of the response in the order of insertion, the oldest response is evicted
+    // when the cache is full
+    inserted: u64,
+}
+
+impl ReqwestTransport {
+    /// Fails if the http client cannot be built, for example because the proxy url is invalid.
+    pub fn new(params: Option<Params>) -> Result<Self> {
+        let params = params.unwrap_or_default();
+        let mut builder = reqwest::Client::builder()
+            .user_agent(params.user_agent)
+            .pool_max_idle_per_host(params.pool_max_idle_per_host);
+        if let Some(timeout) = params.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = params.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = params.proxy {
+            let proxy = reqwest::Proxy::all(&proxy)
+                .map_err(|err| anyhow!(err).context("invalid proxy url"))?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
+            .build()
+            .map_err(|err| anyhow!(err).context("failed to build http client"))?;
+        Ok(Self {
+            client,
+            cache: params.conditional_requests.then(Default::default),
+        })
+    }
+
+    /// Use an already configured client.
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            cache: None,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T> {
+        match self.fetch(request).await? {
+            Some((_, body)) => Ok(serde_json::from_str(&body)?),
+            None => Err(GasEstimationError::Decode(anyhow!(
+                "not modified response to unconditional request"
+            ))),
+        }
+    }
+
+    // The validators and body of the response, `None` for 304 Not Modified.
+    async fn fetch(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Option<(CacheValidators, String)>> {
+        let response = request.send().await.map_err(transport_error)?;
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(GasEstimationError::RateLimited {
+                retry_after: retry_after(response.headers()),
+            });
+        }
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if let Err(err) = response.error_for_status_ref() {
+            return Err(GasEstimationError::Transport(
+                anyhow::Error::new(err).context(HttpStatus(status)),
+            ));
+        }
+        let validators = CacheValidators::from_response_headers(response.headers());
+        let body = response.text().await.map_err(transport_error)?;
+        Ok(Some((validators, body)))
+    }
+
+    async fn get_cached<T: DeserializeOwned>(
+        &self,
+        cache: &Mutex<HashMap<CacheKey, CachedResponse>>,
+        url: &str,
+        mut header: HeaderMap,
+    ) -> Result<T> {
+        let key = cache_key(url, &header);
+        let cached = cache.lock().unwrap().get(&key).cloned();
+        if let Some(cached) = &cached {
+            cached.validators.apply(&mut header);
+        }
+        let body = match self.fetch(self.client.get(url).headers(header)).await? {
+            Some((validators, body)) => {
+                let mut cache = cache.lock().unwrap();
+                if validators.is_empty() {
+                    cache.remove(&key);
+                } else {
+                    insert_bounded(&mut cache, key, validators, body.clone());
+                }
+                body
+            }
+            None => match cached {
+                Some(cached) => cached.body,
+                None => {
+                    return Err(GasEstimationError::Decode(anyhow!(
+                        "not modified response without cached response"
+                    )))
+                }
+            },
+        };
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+fn insert_bounded(
+    cache: &mut HashMap<CacheKey, CachedResponse>,
+    key: CacheKey,
+    validators: CacheValidators,
+    body: String,
+) {
+    if cache.len() >= MAX_CACHED_RESPONSES && !cache.contains_key(&key) {
+        let oldest = cache
+            .iter()
+            .min_by_key(|(_, cached)| cached.inserted)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            cache.remove(&oldest);
+        }
+    }
+    let inserted = cache
+        .values()
+        .map(|cached| cached.inserted + 1)
+        .max()
+        .unwrap_or_default();
+    cache.insert(
+        key,
+        CachedResponse {
+            validators,
+            body,
+            inserted,
+        },
+    );
+}
+
+fn transport_error(err: reqwest::Error) -> GasEstimationError {
+    if err.is_timeout() {
+        return GasEstimationError::Timeout;
+    }
+    GasEstimationError::Transport(err.into())
+}
+
+// Only the delay in seconds form of the Retry-After header is supported.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(http::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[async_trait::async_trait]
+impl Transport for ReqwestTransport {
+    async fn get_json<T: DeserializeOwned>(&self, url: &str, header: HeaderMap) -> Result<T> {
+        match &self.cache {
+            Some(cache) => self.get_cached(cache, url, header).await,
+            None => self.send(self.client.get(url).headers(header)).await,
+        }
+    }
+
+    async fn get_json_conditional<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        mut header: HeaderMap,
+        validators: &CacheValidators,
+    ) -> Result<Conditional<T>> {
+        validators.apply(&mut header);
+        Ok(
+            match self.fetch(self.client.get(url).headers(header)).await? {
+                Some((validators, body)) => Conditional::Modified {
+                    value: serde_json::from_str(&body)?,
+                    validators,
+                },
+                None => Conditional::NotModified,
+            },
+        )
+    }
+
+    async fn post_json<Req, Resp>(&self, url: &str, header: HeaderMap, body: &Req) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        self.send(self.client.post(url).headers(header).json(body))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GasNowGasStation, GasPriceEstimating};
+
+    #[test]
+    fn builds_with_default_params() {
+        assert!(ReqwestTransport::new(None).is_ok());
+    }
+
+    #[test]
+    fn invalid_proxy_fails() {
+        let params = Params {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(ReqwestTransport::new(Some(params)).is_err());
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(http::header::RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(
+            http::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    // Http server on a local port that answers every request with `respond` until the test ends.
+    fn serve(respond: impl Fn(&str) -> String + Send + 'static) -> String {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    request.push_str(&line.to_lowercase());
+                }
+                stream.write_all(respond(&request).as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    fn respond_with_etag(request: &str) -> String {
+        if request.contains("if-none-match: \"v1\"") {
+            return "HTTP/1.1 304 Not Modified\r\ncontent-length: 0\r\n\r\n".to_string();
+        }
+        let body = "1";
+        format!(
+            "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn conditional_requests() {
+        let url = serve(respond_with_etag);
+        let transport = ReqwestTransport::new(None).unwrap();
+        let response = transport
+            .get_json_conditional::<u32>(&url, Default::default(), &Default::default())
+            .await
+            .unwrap();
+        let validators = match response {
+            Conditional::Modified { value, validators } => {
+                assert_eq!(value, 1);
+                validators
+            }
+            Conditional::NotModified => panic!("unconditional request not modified"),
+        };
+        assert_eq!(validators.etag.as_ref().unwrap(), "\"v1\"");
+        let response = transport
+            .get_json_conditional::<u32>(&url, Default::default(), &validators)
+            .await
+            .unwrap();
+        assert_eq!(response, Conditional::NotModified);
+    }
+
+    #[tokio::test]
+    async fn caches_responses() {
+        let url = serve(respond_with_etag);
+        let params = Params {
+            conditional_requests: true,
+            ..Default::default()
+        };
+        let transport = ReqwestTransport::new(Some(params)).unwrap();
+        for _ in 0..2 {
+            let value: u32 = transport.get_json(&url, Default::default()).await.unwrap();
+            assert_eq!(value, 1);
+        }
+        // without the cache the not modified response can't be decoded
+        let transport = ReqwestTransport::new(None).unwrap();
+        let mut header = HeaderMap::new();
+        header.insert(http::header::IF_NONE_MATCH, "\"v1\"".parse().unwrap());
+        assert!(transport.get_json::<u32>(&url, header).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn caches_responses_per_header() {
+        // answers every conditional request with not modified
+        let url = serve(|request| {
+            if request.contains("if-none-match") {
+                return "HTTP/1.1 304 Not Modified\r\ncontent-length: 0\r\n\r\n".to_string();
+            }
+            let body = if request.contains("x-api-key: b") {
+                "2"
+            } else {
+                "1"
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        });
+        let params = Params {
+            conditional_requests: true,
+            ..Default::default()
+        };
+        let transport = ReqwestTransport::new(Some(params)).unwrap();
+        let get = |key: &'static str| {
+            let mut header = HeaderMap::new();
+            header.insert("x-api-key", key.parse().unwrap());
+            transport.get_json::<u32>(&url, header)
+        };
+        for _ in 0..2 {
+            assert_eq!(get("a").await.unwrap(), 1);
+            assert_eq!(get("b").await.unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn evicts_oldest_cached_response() {
+        let mut cache = HashMap::new();
+        let validators = CacheValidators {
+            etag: Some("\"v1\"".parse().unwrap()),
+            last_modified: None,
+        };
+        let key = |i: usize| cache_key(&format!("http://localhost/{}", i), &HeaderMap::new());
+        for i in 0..=MAX_CACHED_RESPONSES {
+            insert_bounded(&mut cache, key(i), validators.clone(), i.to_string());
+        }
+        assert_eq!(cache.len(), MAX_CACHED_RESPONSES);
+        assert!(!cache.contains_key(&key(0)));
+        assert!(cache.contains_key(&key(1)));
+        // replacing a cached response does not evict another one
+        insert_bounded(&mut cache, key(1), validators, "1".to_string());
+        assert_eq!(cache.len(), MAX_CACHED_RESPONSES);
+        assert!(cache.contains_key(&key(2)));
+    }
+
+    #[tokio::test]
+    async fn keeps_error_status() {
+        let url = serve(|_| "HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\n\r\n".to_string());
+        let transport = ReqwestTransport::new(None).unwrap();
+        let err = transport
+            .get_json::<u32>(&url, Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GasEstimationError::Transport(_)));
+        assert_eq!(err.http_status(), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    // cargo test transport -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let transport = ReqwestTransport::new(None).unwrap();
+        let gasnow = GasNowGasStation::new(transport);
+        println!("{:?}", gasnow.estimate().await);
+    }
+}
@@ -0,0 +1,442 @@
+// Recording of responses as json lines with one object per request:
+// `{"timestamp": 1650000000.5, "url": "...", "request": null, "response": {...}}`. `request` is
+// the body of POST requests and null for GET requests. Failed requests are recorded with an
+// `error` message instead of a `response`. Api keys in urls are redacted, see `redact`.
+
+use super::{CacheValidators, Conditional};
+use crate::{error::Result, GasEstimationError, Transport};
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    // seconds since the unix epoch
+    timestamp: f64,
+    url: String,
+    #[serde(default)]
+    request: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Writes every request of the inner transport and its response to a json lines file.
+pub struct RecordingTransport<T> {
+    inner: T,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn new(inner: T, path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| anyhow!(err).context("failed to open recording"))?;
+        Ok(Self::with_writer(inner, file))
+    }
+
+    pub fn with_writer(inner: T, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    fn record<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        request: Option<Value>,
+        response: Result<Value>,
+    ) -> Result<R> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let redacted = redact(url);
+        let (value, error) = match &response {
+            Ok(value) => (Some(value.clone()), None),
+            // Transport errors can contain the url.
+            Err(err) => (None, Some(format!("{:?}", err).replace(url, &redacted))),
+        };
+        let record = Record {
+            timestamp,
+            url: redacted,
+            request,
+            response: value,
+            error,
+        };
+        // A failing recording should not fail the request.
+        let line = serde_json::to_string(&record)?;
+        if let Err(err) = writeln!(self.writer.lock().unwrap(), "{}", line) {
+            tracing::warn!(?err, "failed to write recording");
+        }
+        Ok(serde_json::from_value(response?)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn get_json<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+    ) -> Result<R> {
+        let response = self.inner.get_json(url, header).await;
+        self.record(url, None, response)
+    }
+
+    async fn post_json<Req, Resp>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        body: &Req,
+    ) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        let request = serde_json::to_value(body)?;
+        let response = self.inner.post_json(url, header, body).await;
+        self.record(url, Some(request), response)
+    }
+
+    // Not modified responses are not recorded. Playback repeats the last recorded response of a
+    // url, which is the response a not modified response refers to.
+    async fn get_json_conditional<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        header: http::header::HeaderMap,
+        validators: &CacheValidators,
+    ) -> Result<Conditional<R>> {
+        let (response, validators) = match self
+            .inner
+            .get_json_conditional(url, header, validators)
+            .await
+        {
+            Ok(Conditional::Modified { value, validators }) => (Ok(value), validators),
+            Ok(Conditional::NotModified) => return Ok(Conditional::NotModified),
+            Err(err) => (Err(err), Default::default()),
+        };
+        Ok(Conditional::Modified {
+            value: self.record(url, None, response)?,
+            validators,
+        })
+    }
+}
+
+// (url, request body)
+type Key = (String, Option<String>);
+
+// Serves the responses of a recording of `RecordingTransport`. Requests are matched by url and
+// request body. Responses for the same request are served in the recorded order and the last one
+// is repeated once they are used up. Recorded failures are returned as transport errors.
+pub struct PlaybackTransport {
+    records: Mutex<HashMap<Key, VecDeque<Record>>>,
+}
+
+impl PlaybackTransport {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file =
+            File::open(path).map_err(|err| anyhow!(err).context("failed to open recording"))?;
+        Self::from_json_lines(BufReader::new(file))
+    }
+
+    /// Empty lines are skipped.
+    pub fn from_json_lines(reader: impl BufRead) -> Result<Self> {
+        let mut records = HashMap::<Key, VecDeque<Record>>::new();
+        for line in reader.lines() {
+            let line = line.map_err(|err| anyhow!(err).context("failed to read recording"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line)?;
+            records
+                .entry(key(&record.url, record.request.as_ref()))
+                .or_default()
+                .push_back(record);
+        }
+        Ok(Self {
+            records: Mutex::new(records),
+        })
+    }
+
+    fn play<R: DeserializeOwned>(&self, url: &str, request: Option<&Value>) -> Result<R> {
+        let mut records = self.records.lock().unwrap();
+        let queue = records
+            .get_mut(&key(url, request))
+            .filter(|queue| !queue.is_empty())
+            .ok_or_else(|| GasEstimationError::Transport(anyhow!("no recording for {}", url)))?;
+        let record = if queue.len() > 1 {
+            queue.pop_front().unwrap()
+        } else {
+            queue[0].clone()
+        };
+        match (record.response, record.error) {
+            (Some(response), _) => Ok(serde_json::from_value(response)?),
+            (None, error) => Err(GasEstimationError::Transport(anyhow!(
+                "recorded error: {}",
+                error.unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+// Recorded urls are redacted so requests are matched by their redacted url.
+fn key(url: &str, request: Option<&Value>) -> Key {
+    (redact(url), request.map(Value::to_string))
+}
+
+// Query parameters carrying api keys, like the `apikey` of `ApiCredentials::query_param`.
+const CREDENTIAL_QUERY_PARAMS: &[&str] = &["apikey", "api_key", "key", "token", "access_token"];
+
+// (host suffix, path segment preceding the api key) of apis with the key in the path like
+// `https://mainnet.infura.io/v3/<key>`.
+const CREDENTIAL_PATH_SEGMENTS: &[(&str, &str)] = &[
+    ("infura.io", "v3"),
+    ("alchemy.com", "v2"),
+    ("alchemyapi.io", "v2"),
+];
+
+const REDACTED: &str = "REDACTED";
+
+// Replaces api keys in the query and path of `url` so recordings can be shared. Urls that can't
+// be parsed are kept as they are.
+fn redact(url: &str) -> String {
+    let mut parsed = match url::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_string(),
+    };
+    if parsed.query().is_some() {
+        let pairs: Vec<(String, String)> = parsed
+            .query_pairs()
+            .map(|(name, value)| {
+                let credential = CREDENTIAL_QUERY_PARAMS
+                    .iter()
+                    .any(|param| name.eq_ignore_ascii_case(param));
+                let value = if credential {
+                    REDACTED.to_string()
+                } else {
+                    value.into_owned()
+                };
+                (name.into_owned(), value)
+            })
+            .collect();
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    let host = parsed.host_str().unwrap_or_default().to_string();
+    let prefix = CREDENTIAL_PATH_SEGMENTS
+        .iter()
+        .find(|(suffix, _)| host.ends_with(suffix))
+        .map(|(_, prefix)| *prefix);
+    if let (Some(prefix), Some(segments)) = (prefix, parsed.path_segments()) {
+        let mut segments: Vec<&str> = segments.collect();
+        if let Some(index) = segments.iter().position(|segment| *segment == prefix) {
+            if let Some(segment) = segments.get_mut(index + 1) {
+                *segment = REDACTED;
+            }
+        }
+        let path = segments.join("/");
+        parsed.set_path(&path);
+    }
+    parsed.to_string()
+}
+
+#[async_trait::async_trait]
+impl Transport for PlaybackTransport {
+    async fn get_json<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        _header: http::header::HeaderMap,
+    ) -> Result<R> {
+        self.play(url, None)
+    }
+
+    async fn post_json<Req, Resp>(
+        &self,
+        url: &str,
+        _header: http::header::HeaderMap,
+        body: &Req,
+    ) -> Result<Resp>
+    where
+        Req: Serialize + Send + Sync,
+        Resp: DeserializeOwned,
+    {
+        self.play(url, Some(&serde_json::to_value(body)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        json_rpc::tests::FakeNode, tests::FutureWaitExt as _, GasPriceEstimating,
+        OpStackGasEstimator,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+
+    // Writer whose contents the test can read after the transport took ownership.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn plays_back_recording() {
+        let node = FakeNode::default()
+            .with_result("eth_getBlockByNumber", json!({ "baseFeePerGas": "0x64" }))
+            .with_result("eth_maxPriorityFeePerGas", json!("0xa"));
+        let buffer = Buffer::default();
+        let recording = RecordingTransport::with_writer(node, buffer.clone());
+        let estimator = OpStackGasEstimator::new(recording, "http://node".to_string(), None);
+        let recorded = estimator.estimate().wait().unwrap();
+        // fails because the fake node has no result for it
+        assert!(estimator.l1_fee(10).wait().is_err());
+
+        let lines = buffer.0.lock().unwrap().clone();
+        assert_eq!(lines.iter().filter(|byte| **byte == b'\n').count(), 3);
+        let playback = PlaybackTransport::from_json_lines(lines.as_slice()).unwrap();
+        let estimator = OpStackGasEstimator::new(playback, "http://node".to_string(), None);
+        for _ in 0..2 {
+            assert_eq!(estimator.estimate().wait().unwrap(), recorded);
+        }
+        assert!(matches!(
+            estimator.l1_fee(10).wait(),
+            Err(GasEstimationError::Transport(_))
+        ));
+        // not recorded
+        assert!(estimator.l1_fee(20).wait().is_err());
+    }
+
+    // Answers with not modified once the request has an etag.
+    struct EtagTransport;
+
+    #[async_trait::async_trait]
+    impl Transport for EtagTransport {
+        async fn get_json<R: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<R> {
+            unreachable!()
+        }
+
+        async fn get_json_conditional<R: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+            validators: &CacheValidators,
+        ) -> Result<Conditional<R>> {
+            if validators.etag.is_some() {
+                return Ok(Conditional::NotModified);
+            }
+            Ok(Conditional::Modified {
+                value: serde_json::from_value(json!(1))?,
+                validators: CacheValidators {
+                    etag: Some("\"v1\"".parse().unwrap()),
+                    last_modified: None,
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn records_modified_conditional_responses() {
+        let buffer = Buffer::default();
+        let recording = RecordingTransport::with_writer(EtagTransport, buffer.clone());
+        let get = |validators: &CacheValidators| {
+            recording
+                .get_json_conditional::<u32>("http://a", Default::default(), validators)
+                .wait()
+                .unwrap()
+        };
+        let validators = match get(&Default::default()) {
+            Conditional::Modified { value, validators } => {
+                assert_eq!(value, 1);
+                validators
+            }
+            Conditional::NotModified => panic!("unconditional request not modified"),
+        };
+        assert_eq!(get(&validators), Conditional::NotModified);
+
+        let lines = buffer.0.lock().unwrap().clone();
+        let playback = PlaybackTransport::from_json_lines(lines.as_slice()).unwrap();
+        let get = |url: &str| -> u32 { playback.get_json(url, Default::default()).wait().unwrap() };
+        assert_eq!(get("http://a"), 1);
+        assert_eq!(lines.iter().filter(|byte| **byte == b'\n').count(), 1);
+    }
+
+    struct Counter;
+
+    #[async_trait::async_trait]
+    impl Transport for Counter {
+        async fn get_json<R: DeserializeOwned>(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<R> {
+            Ok(serde_json::from_value(json!(1))?)
+        }
+    }
+
+    #[test]
+    fn redacts_api_keys() {
+        let urls = [
+            "https://api.etherscan.io/api?module=gastracker&action=gasoracle&apikey=secret",
+            "https://gas.api.infura.io/v3/secret/networks/1/suggestedGasFees",
+            "https://eth-mainnet.g.alchemy.com/v2/secret",
+        ];
+        let buffer = Buffer::default();
+        let recording = RecordingTransport::with_writer(Counter, buffer.clone());
+        for url in urls {
+            let _: u32 = recording.get_json(url, Default::default()).wait().unwrap();
+        }
+
+        let lines = buffer.0.lock().unwrap().clone();
+        let text = String::from_utf8(lines.clone()).unwrap();
+        assert!(!text.contains("secret"));
+        assert!(text.contains("module=gastracker&action=gasoracle&apikey=REDACTED"));
+        assert!(text.contains("/v3/REDACTED/networks/1/suggestedGasFees"));
+        let playback = PlaybackTransport::from_json_lines(lines.as_slice()).unwrap();
+        for url in urls {
+            let value: u32 = playback.get_json(url, Default::default()).wait().unwrap();
+            assert_eq!(value, 1);
+        }
+    }
+
+    #[test]
+    fn serves_responses_in_order() {
+        let recording = r#"
+            {"timestamp": 1.0, "url": "http://a", "request": null, "response": 1}
+
+            {"timestamp": 2.0, "url": "http://a", "request": null, "response": 2}
+            {"timestamp": 2.0, "url": "http://b", "response": 3}
+        "#;
+        let playback = PlaybackTransport::from_json_lines(recording.as_bytes()).unwrap();
+        let get = |url: &str| -> u32 { playback.get_json(url, Default::default()).wait().unwrap() };
+        assert_eq!(get("http://a"), 1);
+        assert_eq!(get("http://a"), 2);
+        assert_eq!(get("http://a"), 2);
+        assert_eq!(get("http://b"), 3);
+    }
+}
@@ -0,0 +1,326 @@
+//! Estimates from the pending transactions in the mempool of our own node.
+//!
+//! During congestion the most direct measure of the required price is the competition: to be
+//! included within `n` blocks a transaction has to outbid all but the `n` block gas limits worth
+//! of pending transactions with the highest tips. The pool comes from `txpool_content` or the
+//! lighter `txpool_inspect` which geth, erigon and nethermind implement but public rpc providers
+//! usually don't.
+
+use super::{
+    chain, error::Result, trace, EstimatedGasPrice, GasEstimationError, GasPrice1559,
+    GasPriceEstimating, GasPriceWei,
+};
+use anyhow::{anyhow, Context};
+use primitive_types::U256;
+use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+use web3::{
+    helpers::CallFuture,
+    types::{BlockId, BlockNumber},
+    Transport, Web3,
+};
+
+/// Which rpc method the pending transactions are read with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolMethod {
+    /// Full transactions including the priority fees of EIP-1559 transactions.
+    #[default]
+    Content,
+    /// Summaries with only the gas limit and the fee cap, which is much smaller on a busy node but
+    /// treats the whole fee cap above the base fee as tip.
+    Inspect,
+}
+
+/// Parameters for the txpool estimator.
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub method: PoolMethod,
+    // time between blocks, used to convert time limits into a number of blocks
+    pub block_time: Duration,
+    // a coefficient to multiply base_fee_per_gas with, in order to survive base fee increases
+    pub base_fee_multiplier: f64,
+    // lowest priority fee that is estimated, used when the pool is not congested
+    pub min_priority_fee: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            method: Default::default(),
+            block_time: chain::ETHEREUM.block_time,
+            base_fee_multiplier: 2.0,
+            min_priority_fee: chain::ETHEREUM.min_priority_fee,
+        }
+    }
+}
+
+// Estimates with the pending transactions of the node's mempool.
+pub struct TxPoolGasEstimator<T: Transport> {
+    web3: Web3<T>,
+    params: Params,
+}
+
+impl<T: Transport> TxPoolGasEstimator<T> {
+    pub fn new(web3: Web3<T>, params: Option<Params>) -> Self {
+        Self {
+            web3,
+            params: params.unwrap_or_default(),
+        }
+    }
+}
+
+// A pending transaction as far as pricing is concerned. Fees in wei.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PendingTransaction {
+    gas: f64,
+    max_fee_per_gas: f64,
+    // `None` for legacy transactions and summaries of `txpool_inspect`
+    max_priority_fee_per_gas: Option<f64>,
+}
+
+impl PendingTransaction {
+    // The tip the transaction pays at `base_fee`. Negative if it can't be included.
+    fn tip(&self, base_fee: f64) -> f64 {
+        let headroom = self.max_fee_per_gas - base_fee;
+        match self.max_priority_fee_per_gas {
+            Some(max_priority_fee_per_gas) => max_priority_fee_per_gas.min(headroom),
+            None => headroom,
+        }
+    }
+}
+
+// Both methods return the transactions by sender and nonce.
+#[derive(Debug, Deserialize)]
+struct Pool<T> {
+    #[serde(default = "HashMap::new")]
+    pending: HashMap<String, HashMap<String, T>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContentTransaction {
+    gas: U256,
+    gas_price: Option<U256>,
+    max_fee_per_gas: Option<U256>,
+    max_priority_fee_per_gas: Option<U256>,
+}
+
+impl ContentTransaction {
+    fn pending(&self) -> Option<PendingTransaction> {
+        Some(PendingTransaction {
+            gas: self.gas.to_f64_lossy(),
+            max_fee_per_gas: self.max_fee_per_gas.or(self.gas_price)?.to_f64_lossy(),
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.map(U256::to_f64_lossy),
+        })
+    }
+}
+
+// Parses the `txpool_inspect` summary `<to>: <value> wei + <gas> gas × <gas price> wei`.
+fn parse_summary(summary: &str) -> Option<PendingTransaction> {
+    let (_, gas_and_price) = summary.split_once(" + ")?;
+    let (gas, price) = gas_and_price.split_once(" gas × ")?;
+    Some(PendingTransaction {
+        gas: gas.trim().parse().ok()?,
+        max_fee_per_gas: price.trim().strip_suffix(" wei")?.trim().parse().ok()?,
+        max_priority_fee_per_gas: None,
+    })
+}
+
+// The tip that outbids the transactions with the highest tips that fill `capacity` gas. `None`
+// if the includable transactions don't fill it.
+fn required_tip(
+    mut transactions: Vec<PendingTransaction>,
+    base_fee: f64,
+    capacity: f64,
+) -> Option<f64> {
+    transactions.retain(|transaction| transaction.tip(base_fee) >= 0.0);
+    transactions.sort_by(|a, b| {
+        b.tip(base_fee)
+            .partial_cmp(&a.tip(base_fee))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut gas = 0.0;
+    for transaction in transactions {
+        gas += transaction.gas;
+        if gas >= capacity {
+            // one wei more than the transaction that just doesn't fit anymore
+            return Some(transaction.tip(base_fee) + 1.0);
+        }
+    }
+    None
+}
+
+impl<T> TxPoolGasEstimator<T>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    async fn pending_transactions(&self) -> Result<Vec<PendingTransaction>> {
+        let transport = self.web3.transport();
+        let transactions = match self.params.method {
+            PoolMethod::Content => CallFuture::<Pool<ContentTransaction>, _>::new(
+                transport.execute("txpool_content", vec![]),
+            )
+            .await
+            .context("failed to get txpool content")
+            .map_err(GasEstimationError::Transport)?
+            .pending
+            .values()
+            .flat_map(HashMap::values)
+            .filter_map(ContentTransaction::pending)
+            .collect(),
+            PoolMethod::Inspect => {
+                CallFuture::<Pool<String>, _>::new(transport.execute("txpool_inspect", vec![]))
+                    .await
+                    .context("failed to get txpool summary")
+                    .map_err(GasEstimationError::Transport)?
+                    .pending
+                    .values()
+                    .flat_map(HashMap::values)
+                    .filter_map(|summary| parse_summary(summary))
+                    .collect()
+            }
+        };
+        Ok(transactions)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> GasPriceEstimating for TxPoolGasEstimator<T>
+where
+    T: Transport + Send + Sync,
+    <T as Transport>::Out: Send,
+{
+    fn source(&self) -> &'static str {
+        "txpool"
+    }
+
+    fn block_time(&self) -> Duration {
+        self.params.block_time
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        trace::estimate(self.source(), None, async {
+            let block = async {
+                self.web3
+                    .eth()
+                    .block(BlockId::Number(BlockNumber::Latest))
+                    .await
+                    .context("failed to get latest block")
+                    .map_err(GasEstimationError::Transport)?
+                    .ok_or_else(|| GasEstimationError::from(anyhow!("latest block not found")))
+            };
+            let (block, transactions) = futures::try_join!(block, self.pending_transactions())?;
+            let base_fee = block
+                .base_fee_per_gas
+                .map(U256::to_f64_lossy)
+                .ok_or_else(|| {
+                    GasEstimationError::Unsupported("txpool estimates require EIP-1559".into())
+                })?;
+            let blocks = (time_limit.as_secs_f64() / self.params.block_time.as_secs_f64())
+                .floor()
+                .max(1.0);
+            let capacity = blocks * block.gas_limit.to_f64_lossy();
+            let max_priority_fee_per_gas = required_tip(transactions, base_fee, capacity)
+                .unwrap_or_default()
+                .max(self.params.min_priority_fee);
+            let max_fee_per_gas =
+                base_fee * self.params.base_fee_multiplier + max_priority_fee_per_gas;
+            EstimatedGasPrice {
+                legacy: max_fee_per_gas,
+                eip1559: Some(GasPrice1559 {
+                    base_fee_per_gas: GasPriceWei(base_fee),
+                    max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                    max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+                }),
+            }
+            .validate()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn transaction(gas: f64, max_fee: f64, priority_fee: Option<f64>) -> PendingTransaction {
+        PendingTransaction {
+            gas,
+            max_fee_per_gas: max_fee,
+            max_priority_fee_per_gas: priority_fee,
+        }
+    }
+
+    #[test]
+    fn outbids_transactions_filling_capacity() {
+        let transactions = vec![
+            transaction(50.0, 20.0, Some(1.0)),
+            transaction(50.0, 30.0, Some(5.0)),
+            // tip limited to 2 by the fee cap
+            transaction(50.0, 12.0, Some(9.0)),
+            // legacy, tip 8
+            transaction(50.0, 18.0, None),
+            // not includable at the base fee
+            transaction(1000.0, 9.0, Some(9.0)),
+        ];
+        let base_fee = 10.0;
+        assert_eq!(
+            required_tip(transactions.clone(), base_fee, 50.0),
+            Some(9.0)
+        );
+        assert_eq!(
+            required_tip(transactions.clone(), base_fee, 100.0),
+            Some(6.0)
+        );
+        assert_eq!(
+            required_tip(transactions.clone(), base_fee, 200.0),
+            Some(2.0)
+        );
+        assert_eq!(required_tip(transactions, base_fee, 201.0), None);
+    }
+
+    #[test]
+    fn parses_pool_responses() {
+        let content: Pool<ContentTransaction> = serde_json::from_value(serde_json::json!({
+            "pending": {
+                "0x0000000000000000000000000000000000000001": {
+                    "0": {"gas": "0x5208", "gasPrice": "0x3b9aca00", "nonce": "0x0"},
+                    "1": {
+                        "gas": "0x5208",
+                        "gasPrice": "0x77359400",
+                        "maxFeePerGas": "0x77359400",
+                        "maxPriorityFeePerGas": "0x3b9aca00"
+                    }
+                }
+            },
+            "queued": {}
+        }))
+        .unwrap();
+        let mut transactions = content.pending["0x0000000000000000000000000000000000000001"]
+            .values()
+            .filter_map(ContentTransaction::pending)
+            .collect::<Vec<_>>();
+        transactions.sort_by(|a, b| a.max_fee_per_gas.partial_cmp(&b.max_fee_per_gas).unwrap());
+        assert_eq!(
+            transactions,
+            vec![
+                transaction(21000.0, 1e9, None),
+                transaction(21000.0, 2e9, Some(1e9)),
+            ]
+        );
+
+        let summary = parse_summary(
+            "0x0000000000000000000000000000000000000002: 1000 wei + 21000 gas × 20000000000 wei",
+        )
+        .unwrap();
+        assert_eq!(summary, transaction(21000.0, 20e9, None));
+        assert_approx_eq!(summary.tip(15e9), 5e9);
+        assert_eq!(parse_summary("contract creation: 0 wei"), None);
+    }
+}
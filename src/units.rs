@@ -0,0 +1,226 @@
+//! Gas price units. Gas price sources use different units (wei, gwei, gwei * 10) so gas prices in
+//! `GasPrice1559` carry their unit in the type and have to be converted explicitly.
+//!
+//! Prices are `f64` which represents every integer wei amount up to 2^53 (about 9e15 wei or 9
+//! million gwei) exactly. Transactions need integer wei, use `to_u128` or `to_u256` for them
+//! instead of casting so that the rounding is the same everywhere.
+
+#[cfg(feature = "primitive-types")]
+use primitive_types::U256;
+use serde::Serialize;
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Div, Mul, Sub},
+};
+
+const WEI_PER_GWEI: f64 = 1e9;
+
+/// Gas price in wei.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
+#[serde(transparent)]
+pub struct GasPriceWei(pub f64);
+
+/// Gas price in gwei.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[cfg_attr(feature = "serde_", derive(serde::Deserialize))]
+#[serde(transparent)]
+pub struct GasPriceGwei(pub f64);
+
+impl GasPriceWei {
+    pub fn from_gwei(gwei: f64) -> Self {
+        GasPriceGwei(gwei).into()
+    }
+
+    pub fn to_gwei(self) -> GasPriceGwei {
+        self.into()
+    }
+
+    pub fn ceil(self) -> Self {
+        Self(self.0.ceil())
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        Self(self.0.min(other.0))
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        Self(self.0.max(other.0))
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.0.is_finite()
+    }
+
+    /// Whole wei rounded up, so a fee sent with a transaction is never below the estimate. NaN and
+    /// negative prices become 0 and prices above `u128::MAX` saturate.
+    pub fn to_u128(self) -> u128 {
+        self.0.ceil() as u128
+    }
+
+    /// Rounds to the nearest `f64` above 2^53 wei.
+    pub fn from_u128(wei: u128) -> Self {
+        Self(wei as f64)
+    }
+
+    /// Like `to_u128` with saturation at `U256::MAX`.
+    #[cfg(feature = "primitive-types")]
+    pub fn to_u256(self) -> U256 {
+        U256::from_f64_lossy(self.0.ceil())
+    }
+
+    /// Like `from_u128`.
+    #[cfg(feature = "primitive-types")]
+    pub fn from_u256(wei: U256) -> Self {
+        Self(wei.to_f64_lossy())
+    }
+}
+
+impl From<u128> for GasPriceWei {
+    fn from(wei: u128) -> Self {
+        Self::from_u128(wei)
+    }
+}
+
+#[cfg(feature = "primitive-types")]
+impl From<U256> for GasPriceWei {
+    fn from(wei: U256) -> Self {
+        Self::from_u256(wei)
+    }
+}
+
+impl GasPriceGwei {
+    pub fn to_wei(self) -> GasPriceWei {
+        self.into()
+    }
+}
+
+// Up to 9 decimals, which is whole wei, without trailing zeros: `32.1 gwei`.
+impl fmt::Display for GasPriceGwei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gwei = format!("{:.9}", self.0);
+        let gwei = match gwei.contains('.') {
+            true => gwei.trim_end_matches('0').trim_end_matches('.'),
+            false => &gwei,
+        };
+        write!(f, "{} gwei", gwei)
+    }
+}
+
+// Displayed in gwei, which is how gas prices are usually quoted.
+impl fmt::Display for GasPriceWei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_gwei().fmt(f)
+    }
+}
+
+impl From<GasPriceGwei> for GasPriceWei {
+    fn from(gwei: GasPriceGwei) -> Self {
+        Self(gwei.0 * WEI_PER_GWEI)
+    }
+}
+
+impl From<GasPriceWei> for GasPriceGwei {
+    fn from(wei: GasPriceWei) -> Self {
+        Self(wei.0 / WEI_PER_GWEI)
+    }
+}
+
+impl Add for GasPriceWei {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl Sub for GasPriceWei {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl Mul<f64> for GasPriceWei {
+    type Output = Self;
+
+    fn mul(self, factor: f64) -> Self {
+        Self(self.0 * factor)
+    }
+}
+
+impl Div<f64> for GasPriceWei {
+    type Output = Self;
+
+    fn div(self, divisor: f64) -> Self {
+        Self(self.0 / divisor)
+    }
+}
+
+impl Sum for GasPriceWei {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|wei| wei.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn converts_between_units() {
+        assert_approx_eq!(GasPriceWei::from_gwei(1.5).0, 1.5e9);
+        assert_approx_eq!(GasPriceWei(2e9).to_gwei().0, 2.0);
+        assert_approx_eq!(GasPriceGwei(3.0).to_wei().0, 3e9);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = GasPriceWei(3.0);
+        let b = GasPriceWei(1.0);
+        assert_eq!(a + b, GasPriceWei(4.0));
+        assert_eq!(a - b, GasPriceWei(2.0));
+        assert_eq!(a * 2.0, GasPriceWei(6.0));
+        assert_eq!(a / 2.0, GasPriceWei(1.5));
+        assert_eq!(a.min(b), b);
+        assert_eq!(a.max(b), a);
+        assert_eq!([a, b].into_iter().sum::<GasPriceWei>(), GasPriceWei(4.0));
+    }
+
+    #[test]
+    fn rounds_to_integer_wei() {
+        assert_eq!(GasPriceWei(1.2).to_u128(), 2);
+        assert_eq!(GasPriceWei(2.0).to_u128(), 2);
+        assert_eq!(GasPriceWei(-1.0).to_u128(), 0);
+        assert_eq!(GasPriceWei(f64::NAN).to_u128(), 0);
+        assert_eq!(GasPriceWei(f64::INFINITY).to_u128(), u128::MAX);
+        // exact up to 2^53
+        let wei = (1 << 53) - 1;
+        assert_eq!(GasPriceWei::from(wei).to_u128(), wei);
+        assert_eq!(GasPriceWei::from_u128((1 << 53) + 1).to_u128(), 1 << 53);
+    }
+
+    #[cfg(feature = "primitive-types")]
+    #[test]
+    fn converts_u256() {
+        assert_eq!(GasPriceWei(1.2).to_u256(), U256::from(2));
+        assert_eq!(GasPriceWei(f64::NAN).to_u256(), U256::zero());
+        assert_eq!(GasPriceWei::from(U256::from(5)), GasPriceWei(5.0));
+    }
+
+    #[test]
+    fn displays_gwei() {
+        assert_eq!(GasPriceGwei(32.1).to_string(), "32.1 gwei");
+        assert_eq!(GasPriceWei(60e9).to_string(), "60 gwei");
+        assert_eq!(GasPriceWei(1.0).to_string(), "0.000000001 gwei");
+        assert_eq!(GasPriceWei(f64::NAN).to_string(), "NaN gwei");
+    }
+
+    #[test]
+    fn serializes_as_number() {
+        assert_eq!(serde_json::to_string(&GasPriceWei(1.0)).unwrap(), "1.0");
+    }
+}
@@ -0,0 +1,303 @@
+use super::{
+    error::Result, EstimateRange, EstimateWithMetadata, EstimatedGasPrice, EstimationHints,
+    GasEstimationError, GasPrice1559, GasPriceEstimating, GasPriceSchedule, GasPriceWei,
+    TransactionCost, TxSpec,
+};
+use std::time::Duration;
+
+/// What to do with an estimate that violates an invariant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Adjust the offending values so that the estimate becomes valid.
+    Clamp,
+    /// Return `GasEstimationError::InvalidEstimate`.
+    Reject,
+}
+
+/// Parameters for the sanitization of estimates.
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub mode: Mode,
+    // lowest acceptable legacy gas price and max_fee_per_gas
+    pub min_gas_price: f64,
+    // highest acceptable legacy gas price and max_fee_per_gas
+    pub max_gas_price: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Clamp,
+            min_gas_price: 0.0,
+            max_gas_price: f64::INFINITY,
+        }
+    }
+}
+
+// Enforces invariants on the estimates of the inner estimator:
+// - all values, including the l1 fee of transaction costs, are finite and non negative (non
+//   finite values are always rejected)
+// - legacy and max_fee_per_gas are within the configured bounds
+// - max_fee_per_gas >= base_fee_per_gas
+// - max_priority_fee_per_gas <= max_fee_per_gas
+pub struct SanitizingEstimator<T> {
+    inner: T,
+    params: Params,
+}
+
+impl<T: GasPriceEstimating> SanitizingEstimator<T> {
+    pub fn new(inner: T, params: Option<Params>) -> Self {
+        Self {
+            inner,
+            params: params.unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GasPriceEstimating> GasPriceEstimating for SanitizingEstimator<T> {
+    fn source(&self) -> &'static str {
+        self.inner.source()
+    }
+
+    fn block_time(&self) -> Duration {
+        self.inner.block_time()
+    }
+
+    async fn estimate_with_limits(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_limits(gas_limit, time_limit)
+            .await?;
+        sanitize(estimate, &self.params)
+    }
+
+    async fn estimate(&self) -> Result<EstimatedGasPrice> {
+        let estimate = self.inner.estimate().await?;
+        sanitize(estimate, &self.params)
+    }
+
+    async fn estimate_verbose(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+    ) -> Result<EstimateWithMetadata> {
+        let estimate = self.inner.estimate_verbose(gas_limit, time_limit).await?;
+        // The range is dropped instead of failing the estimate if one of its bounds is invalid.
+        let range = estimate.range.and_then(|range| {
+            Some(EstimateRange {
+                low: sanitize(range.low, &self.params).ok()?,
+                high: sanitize(range.high, &self.params).ok()?,
+            })
+        });
+        Ok(EstimateWithMetadata {
+            price: sanitize(estimate.price, &self.params)?,
+            range,
+            ..estimate
+        })
+    }
+
+    async fn estimate_with_hints(
+        &self,
+        gas_limit: f64,
+        time_limit: Duration,
+        hints: &EstimationHints,
+    ) -> Result<EstimatedGasPrice> {
+        let estimate = self
+            .inner
+            .estimate_with_hints(gas_limit, time_limit, hints)
+            .await?;
+        sanitize(estimate, &self.params)
+    }
+
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let cost = self.inner.estimate_for_tx(tx).await?;
+        if !(cost.l1_fee.is_finite() && cost.l1_fee >= 0.0) {
+            return Err(GasEstimationError::InvalidEstimate(format!(
+                "invalid l1 fee {}",
+                cost.l1_fee
+            )));
+        }
+        Ok(TransactionCost {
+            gas_price: sanitize(cost.gas_price, &self.params)?,
+            ..cost
+        })
+    }
+
+    async fn estimate_schedule(&self) -> Result<GasPriceSchedule> {
+        let schedule = self.inner.estimate_schedule().await?;
+        schedule.try_map(|_, estimate| sanitize(estimate, &self.params))
+    }
+
+    async fn estimate_many(&self, requests: &[(f64, Duration)]) -> Result<Vec<EstimatedGasPrice>> {
+        let estimates = self.inner.estimate_many(requests).await?;
+        estimates
+            .into_iter()
+            .map(|estimate| sanitize(estimate, &self.params))
+            .collect()
+    }
+}
+
+pub fn sanitize(estimate: EstimatedGasPrice, params: &Params) -> Result<EstimatedGasPrice> {
+    let invalid =
+        || GasEstimationError::InvalidEstimate(format!("invalid gas price values: {:?}", estimate));
+    let values = std::iter::once(estimate.legacy).chain(estimate.eip1559.iter().flat_map(|x| {
+        [
+            x.base_fee_per_gas.0,
+            x.max_fee_per_gas.0,
+            x.max_priority_fee_per_gas.0,
+        ]
+    }));
+    let mut any_negative = false;
+    for value in values {
+        if !value.is_finite() {
+            return Err(invalid());
+        }
+        any_negative |= value < 0.0;
+    }
+
+    let clamped = clamp(estimate, params);
+    if params.mode == Mode::Reject && (any_negative || clamped != estimate) {
+        return Err(invalid());
+    }
+    // Clamping to `max_gas_price` can't make an estimate valid when the base fee is above it, a
+    // max_fee_per_gas below the base fee would never be mined.
+    if let Some(x) = clamped.eip1559 {
+        if x.max_fee_per_gas < x.base_fee_per_gas {
+            return Err(GasEstimationError::InvalidEstimate(format!(
+                "base fee {} is above the max gas price {}",
+                x.base_fee_per_gas.0, params.max_gas_price
+            )));
+        }
+    }
+    Ok(clamped)
+}
+
+fn clamp(estimate: EstimatedGasPrice, params: &Params) -> EstimatedGasPrice {
+    let bound = |value: f64| value.max(params.min_gas_price).min(params.max_gas_price);
+    EstimatedGasPrice {
+        legacy: bound(estimate.legacy.max(0.0)),
+        eip1559: estimate.eip1559.map(|x| {
+            let base_fee_per_gas = x.base_fee_per_gas.0.max(0.0);
+            let max_fee_per_gas = bound(x.max_fee_per_gas.0.max(base_fee_per_gas));
+            GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee_per_gas),
+                max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(
+                    x.max_priority_fee_per_gas.0.max(0.0).min(max_fee_per_gas),
+                ),
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate(
+        legacy: f64,
+        base_fee_per_gas: f64,
+        max_fee_per_gas: f64,
+        max_priority_fee_per_gas: f64,
+    ) -> EstimatedGasPrice {
+        EstimatedGasPrice {
+            legacy,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee_per_gas),
+                max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(max_priority_fee_per_gas),
+            }),
+        }
+    }
+
+    fn reject() -> Params {
+        Params {
+            mode: Mode::Reject,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_estimate_is_unchanged() {
+        let valid = estimate(3.0, 1.0, 3.0, 2.0);
+        assert_eq!(sanitize(valid, &Default::default()).unwrap(), valid);
+        assert_eq!(sanitize(valid, &reject()).unwrap(), valid);
+    }
+
+    #[test]
+    fn max_fee_below_base_fee() {
+        let invalid = estimate(3.0, 4.0, 3.0, 2.0);
+        assert_eq!(
+            sanitize(invalid, &Default::default()).unwrap(),
+            estimate(3.0, 4.0, 4.0, 2.0)
+        );
+        assert!(sanitize(invalid, &reject()).is_err());
+    }
+
+    #[test]
+    fn priority_fee_above_max_fee() {
+        let invalid = estimate(3.0, 1.0, 3.0, 5.0);
+        assert_eq!(
+            sanitize(invalid, &Default::default()).unwrap(),
+            estimate(3.0, 1.0, 3.0, 3.0)
+        );
+        assert!(sanitize(invalid, &reject()).is_err());
+    }
+
+    #[test]
+    fn negative_values() {
+        let invalid = estimate(-1.0, -1.0, 3.0, -2.0);
+        assert_eq!(
+            sanitize(invalid, &Default::default()).unwrap(),
+            estimate(0.0, 0.0, 3.0, 0.0)
+        );
+        assert!(sanitize(invalid, &reject()).is_err());
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected() {
+        let params = Params::default();
+        assert!(sanitize(estimate(f64::NAN, 1.0, 3.0, 2.0), &params).is_err());
+        assert!(sanitize(estimate(3.0, 1.0, f64::INFINITY, 2.0), &params).is_err());
+    }
+
+    #[test]
+    fn bounds() {
+        let params = Params {
+            min_gas_price: 2.0,
+            max_gas_price: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            sanitize(estimate(1.0, 1.0, 20.0, 2.0), &params).unwrap(),
+            estimate(2.0, 1.0, 10.0, 2.0)
+        );
+        let params = Params {
+            mode: Mode::Reject,
+            ..params
+        };
+        assert!(sanitize(estimate(1.0, 1.0, 5.0, 2.0), &params).is_err());
+        assert!(sanitize(estimate(5.0, 1.0, 20.0, 2.0), &params).is_err());
+    }
+
+    #[test]
+    fn base_fee_above_max_gas_price() {
+        let params = Params {
+            max_gas_price: 10.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            sanitize(estimate(5.0, 12.0, 20.0, 2.0), &params),
+            Err(GasEstimationError::InvalidEstimate(_))
+        ));
+        assert_eq!(
+            sanitize(estimate(5.0, 10.0, 20.0, 2.0), &params).unwrap(),
+            estimate(5.0, 10.0, 10.0, 2.0)
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! Long lived websocket connections for estimators that receive gas prices as a stream.
+
+use super::{
+    error::Result,
+    time::{Clock, Instant as ClockInstant, SystemClock},
+    GasEstimationError, WebSocketConnection, WebSocketTransport,
+};
+use rand::Rng;
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::time::{timeout_at, Instant};
+
+/// State of the connection reported to `Params::on_state_change`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+/// Parameters for reconnecting and keeping the connection alive.
+#[derive(Clone)]
+pub struct Params {
+    // backoff before the first reconnect, doubled for every following failed attempt
+    pub min_backoff: Duration,
+    // upper bound for the backoff between two connection attempts
+    pub max_backoff: Duration,
+    // fraction of the backoff that is randomly subtracted so that replicas don't reconnect in
+    // lockstep
+    pub jitter: f64,
+    // a ping is sent after no message has been received for this long
+    pub ping_interval: Option<Duration>,
+    // the connection is considered dead and reconnected after no message has been received for
+    // this long
+    pub idle_timeout: Option<Duration>,
+    // called whenever the state of the connection changes
+    pub on_state_change: Option<Arc<dyn Fn(ConnectionState) + Send + Sync>>,
+    // waits between reconnects and timestamps received messages, keep alive pings use the tokio
+    // timer because they race the connection
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            jitter: 0.2,
+            ping_interval: Some(Duration::from_secs(20)),
+            idle_timeout: Some(Duration::from_secs(60)),
+            on_state_change: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl Params {
+    fn report(&self, state: ConnectionState) {
+        if let Some(on_state_change) = &self.on_state_change {
+            on_state_change(state);
+        }
+    }
+}
+
+/// Connects to `url` and passes every connection to `handle` until it returns, then reconnects
+/// with exponential backoff. The backoff is reset after a connection has been established. Never
+/// returns, so it is usually spawned as a task that is aborted when the estimator is dropped.
+pub async fn run<T, H, Fut>(
+    transport: &T,
+    url: &str,
+    header: http::header::HeaderMap,
+    params: &Params,
+    handle: H,
+) where
+    T: WebSocketTransport,
+    H: FnMut(KeepAlive<T::Connection>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    run_with_failover(transport, &[url], header, params, handle).await
+}
+
+// Reconnect state of one of the urls passed to `run_with_failover`.
+struct Endpoint<'a> {
+    url: &'a str,
+    backoff: Duration,
+    // `None` until the first connection attempt.
+    retry_at: Option<ClockInstant>,
+}
+
+/// Like `run` but with several urls in order of preference. Every url has its own backoff. After
+/// a connection ends or fails the first url whose backoff has passed is connected to, so while
+/// the preferred endpoint is down the estimator rotates through the others and it returns to the
+/// preferred one once that is reachable again. Never returns, not even for an empty list.
+pub async fn run_with_failover<T, H, Fut>(
+    transport: &T,
+    urls: &[&str],
+    header: http::header::HeaderMap,
+    params: &Params,
+    mut handle: H,
+) where
+    T: WebSocketTransport,
+    H: FnMut(KeepAlive<T::Connection>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    if urls.is_empty() {
+        tracing::error!("no websocket urls configured");
+        return std::future::pending().await;
+    }
+    let mut endpoints = urls
+        .iter()
+        .map(|url| Endpoint {
+            url,
+            backoff: params.min_backoff,
+            retry_at: None,
+        })
+        .collect::<Vec<_>>();
+    loop {
+        let now = params.clock.now();
+        let available = endpoints
+            .iter()
+            .position(|endpoint| endpoint.retry_at.map_or(true, |retry_at| retry_at <= now));
+        let index = match available {
+            Some(index) => index,
+            None => {
+                // All urls are backing off, so wait for the one that is available first.
+                let (index, retry_at) = endpoints
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, endpoint)| Some((index, endpoint.retry_at?)))
+                    .min_by_key(|(_, retry_at)| *retry_at)
+                    .expect("all endpoints have been tried");
+                params
+                    .clock
+                    .sleep(retry_at.saturating_duration_since(now))
+                    .await;
+                index
+            }
+        };
+        let endpoint = &mut endpoints[index];
+        let url = endpoint.url;
+        params.report(ConnectionState::Connecting);
+        match transport.connect(url, header.clone()).await {
+            Ok(connection) => {
+                endpoint.backoff = params.min_backoff;
+                params.report(ConnectionState::Connected);
+                if let Err(err) = handle(KeepAlive::new(connection, params)).await {
+                    tracing::warn!(?err, url, "websocket connection failed");
+                }
+            }
+            Err(err) => tracing::warn!(?err, url, "failed to connect to websocket"),
+        }
+        params.report(ConnectionState::Disconnected);
+        endpoint.retry_at = Some(params.clock.now() + jittered(endpoint.backoff, params.jitter));
+        endpoint.backoff = next_backoff(endpoint.backoff, params.max_backoff);
+    }
+}
+
+fn next_backoff(backoff: Duration, max_backoff: Duration) -> Duration {
+    backoff.saturating_mul(2).min(max_backoff)
+}
+
+fn jittered(backoff: Duration, jitter: f64) -> Duration {
+    let jitter = jitter.clamp(0.0, 1.0) * rand::thread_rng().gen::<f64>();
+    backoff.mul_f64(1.0 - jitter)
+}
+
+/// Sends pings while the connection is idle and ends it with `GasEstimationError::Timeout` when
+/// no message has been received for the idle timeout. Requires the `receive` implementation of
+/// the wrapped connection to be cancel safe.
+pub struct KeepAlive<C> {
+    connection: C,
+    ping_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+}
+
+impl<C> KeepAlive<C> {
+    fn new(connection: C, params: &Params) -> Self {
+        Self {
+            connection,
+            ping_interval: params.ping_interval,
+            idle_timeout: params.idle_timeout,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: WebSocketConnection> WebSocketConnection for KeepAlive<C> {
+    async fn send(&mut self, message: String) -> Result<()> {
+        self.connection.send(message).await
+    }
+
+    async fn ping(&mut self) -> Result<()> {
+        self.connection.ping().await
+    }
+
+    async fn receive(&mut self) -> Option<Result<String>> {
+        let start = Instant::now();
+        let idle_deadline = self.idle_timeout.map(|idle_timeout| start + idle_timeout);
+        let mut next_ping = self
+            .ping_interval
+            .map(|ping_interval| start + ping_interval);
+        loop {
+            let deadline = match (next_ping, idle_deadline) {
+                (Some(next_ping), Some(idle_deadline)) => next_ping.min(idle_deadline),
+                (Some(deadline), None) | (None, Some(deadline)) => deadline,
+                (None, None) => return self.connection.receive().await,
+            };
+            if let Ok(message) = timeout_at(deadline, self.connection.receive()).await {
+                return message;
+            }
+            if idle_deadline.is_some_and(|idle_deadline| Instant::now() >= idle_deadline) {
+                return Some(Err(GasEstimationError::Timeout));
+            }
+            if let Err(err) = self.connection.ping().await {
+                return Some(Err(err));
+            }
+            next_ping = self
+                .ping_interval
+                .map(|ping_interval| Instant::now() + ping_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn backoff_is_capped() {
+        let max_backoff = Duration::from_secs(60);
+        assert_eq!(
+            next_backoff(Duration::from_secs(1), max_backoff),
+            Duration::from_secs(2)
+        );
+        assert_eq!(next_backoff(max_backoff, max_backoff), max_backoff);
+    }
+
+    #[test]
+    fn jitter_reduces_backoff() {
+        let backoff = Duration::from_secs(10);
+        assert_eq!(jittered(backoff, 0.0), backoff);
+        for _ in 0..100 {
+            let jittered = jittered(backoff, 0.5);
+            assert!(jittered <= backoff && jittered >= backoff / 2);
+        }
+    }
+
+    #[derive(Default)]
+    struct IdleConnection {
+        pings: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl WebSocketConnection for IdleConnection {
+        async fn send(&mut self, _message: String) -> Result<()> {
+            Ok(())
+        }
+
+        async fn ping(&mut self) -> Result<()> {
+            self.pings += 1;
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Option<Result<String>> {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_connection_is_pinged_and_times_out() {
+        let mut connection = KeepAlive::new(
+            IdleConnection::default(),
+            &Params {
+                ping_interval: Some(Duration::from_millis(10)),
+                idle_timeout: Some(Duration::from_millis(55)),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            connection.receive().await,
+            Some(Err(GasEstimationError::Timeout))
+        ));
+        assert!(connection.connection.pings >= 3);
+    }
+
+    struct FlakyTransport {
+        connects: Mutex<usize>,
+    }
+
+    struct ClosedConnection;
+
+    #[async_trait::async_trait]
+    impl WebSocketConnection for ClosedConnection {
+        async fn send(&mut self, _message: String) -> Result<()> {
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Option<Result<String>> {
+            None
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl WebSocketTransport for FlakyTransport {
+        type Connection = ClosedConnection;
+
+        async fn connect(
+            &self,
+            _url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<Self::Connection> {
+            let mut connects = self.connects.lock().unwrap();
+            *connects += 1;
+            // every other attempt fails
+            if *connects % 2 == 0 {
+                Err(anyhow::anyhow!("").into())
+            } else {
+                Ok(ClosedConnection)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_reports_state() {
+        let transport = FlakyTransport {
+            connects: Mutex::new(0),
+        };
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let states_clone = states.clone();
+        let params = Params {
+            min_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            on_state_change: Some(Arc::new(move |state| {
+                states_clone.lock().unwrap().push(state)
+            })),
+            ..Default::default()
+        };
+        let handled = Mutex::new(0);
+        let run = run(
+            &transport,
+            "",
+            Default::default(),
+            &params,
+            |mut connection| {
+                *handled.lock().unwrap() += 1;
+                async move {
+                    while connection.receive().await.is_some() {}
+                    Ok(())
+                }
+            },
+        );
+        tokio::time::timeout(Duration::from_millis(100), run)
+            .await
+            .unwrap_err();
+
+        let connects = *transport.connects.lock().unwrap();
+        assert!(connects >= 4);
+        assert_eq!(*handled.lock().unwrap(), connects.div_ceil(2));
+        let states = states.lock().unwrap();
+        assert_eq!(
+            &states[..5],
+            &[
+                ConnectionState::Connecting,
+                ConnectionState::Connected,
+                ConnectionState::Disconnected,
+                ConnectionState::Connecting,
+                ConnectionState::Disconnected,
+            ]
+        );
+    }
+
+    // Fails to connect to "primary" and connects to every other url.
+    #[derive(Default)]
+    struct DownPrimaryTransport {
+        connects: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl WebSocketTransport for DownPrimaryTransport {
+        type Connection = ClosedConnection;
+
+        async fn connect(
+            &self,
+            url: &str,
+            _header: http::header::HeaderMap,
+        ) -> Result<Self::Connection> {
+            self.connects.lock().unwrap().push(url.to_string());
+            if url == "primary" {
+                Err(anyhow::anyhow!("").into())
+            } else {
+                Ok(ClosedConnection)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_url() {
+        let transport = DownPrimaryTransport::default();
+        let params = Params {
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(8),
+            jitter: 0.0,
+            clock: Arc::new(crate::time::tests::ManualClock::default()),
+            ..Default::default()
+        };
+        let run = run_with_failover(
+            &transport,
+            &["primary", "secondary"],
+            Default::default(),
+            &params,
+            |mut connection| async move {
+                while connection.receive().await.is_some() {}
+                Ok(())
+            },
+        );
+        let connected = async {
+            while transport.connects.lock().unwrap().len() < 8 {
+                tokio::task::yield_now().await;
+            }
+        };
+        tokio::select! {
+            _ = run => unreachable!(),
+            _ = connected => (),
+        }
+
+        let connects = transport.connects.lock().unwrap();
+        // The secondary is used while the primary backs off for 1, 2 and 4 seconds. Its own
+        // backoff is reset by every successful connection.
+        assert_eq!(
+            &connects[..8],
+            &[
+                "primary",
+                "secondary",
+                "primary",
+                "secondary",
+                "secondary",
+                "primary",
+                "secondary",
+                "secondary",
+            ]
+        );
+    }
+}
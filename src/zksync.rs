@@ -0,0 +1,224 @@
+//! zkSync Era gas estimation.
+//!
+//! zkSync Era charges the cost of publishing state changes to L1 (pubdata) in L2 gas, so gas
+//! limits on zkSync are much higher than the execution cost on Ethereum. `zks_estimateFee` returns
+//! the fees together with the gas limit including pubdata. Documentation at
+//! https://docs.zksync.io/zksync-protocol/rollup/fee-model .
+
+use super::{
+    chain,
+    cost::{TransactionCost, TxSpec},
+    error::Result,
+    json_rpc::{self, to_hex},
+    op_stack::{self, incompressible_calldata},
+    EstimatedGasPrice, GasPrice1559, GasPriceEstimating, GasPriceWei, Transport,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Estimate for a specific zkSync Era transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ZkSyncFee {
+    pub gas_price: EstimatedGasPrice,
+    // Gas limit including the gas for publishing pubdata.
+    pub gas_limit: f64,
+    // Highest price in gas the transaction pays per byte of pubdata.
+    pub gas_per_pubdata_limit: f64,
+}
+
+impl ZkSyncFee {
+    // Unused gas is refunded so the actual fee is usually lower.
+    pub fn total_fee(&self) -> f64 {
+        self.gas_limit * self.gas_price.effective_gas_price()
+    }
+}
+
+// Quantities are hex encoded.
+#[derive(Debug, Deserialize)]
+struct Response {
+    gas_limit: String,
+    gas_per_pubdata_limit: String,
+    max_fee_per_gas: String,
+    max_priority_fee_per_gas: String,
+}
+
+/// Estimates gas prices through a zkSync Era node's JSON-RPC api. Requires a transport that
+/// supports `Transport::post_json`.
+pub struct ZkSyncGasEstimator<T> {
+    transport: T,
+    node_url: String,
+}
+
+impl<T: Transport> ZkSyncGasEstimator<T> {
+    pub fn new(transport: T, node_url: String) -> Self {
+        Self {
+            transport,
+            node_url,
+        }
+    }
+
+    /// Estimate the fee for sending `data` from `from` to `to`.
+    pub async fn estimate_fee(
+        &self,
+        from: [u8; 20],
+        to: [u8; 20],
+        data: &[u8],
+    ) -> Result<ZkSyncFee> {
+        let (response, base_fee): (Response, _) = futures::try_join!(
+            async {
+                json_rpc::call(
+                    &self.transport,
+                    &self.node_url,
+                    "zks_estimateFee",
+                    json!([{ "from": to_hex(&from), "to": to_hex(&to), "data": to_hex(data) }]),
+                )
+                .await
+                .map_err(|err| err.context("failed to call zks_estimateFee"))
+            },
+            op_stack::base_fee(&self.transport, &self.node_url),
+        )?;
+        decode_fee(&response, base_fee)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport> GasPriceEstimating for ZkSyncGasEstimator<T> {
+    fn source(&self) -> &'static str {
+        "zksync"
+    }
+
+    fn block_time(&self) -> Duration {
+        chain::ZKSYNC_ERA.block_time
+    }
+
+    // zkSync Era has no fee market for inclusion time so the time limit is ignored.
+    async fn estimate_with_limits(
+        &self,
+        _gas_limit: f64,
+        _time_limit: Duration,
+    ) -> Result<EstimatedGasPrice> {
+        Ok(self.estimate_fee([0; 20], [0; 20], &[]).await?.gas_price)
+    }
+
+    // Gas limits estimated by a zkSync node already include the pubdata gas but limits estimated
+    // for other chains do not, so the limit is at least the one of a call of the zero address with
+    // calldata of the same size.
+    async fn estimate_for_tx(&self, tx: &TxSpec) -> Result<TransactionCost> {
+        let fee = self
+            .estimate_fee([0; 20], [0; 20], &incompressible_calldata(tx.data_len()))
+            .await?;
+        Ok(TransactionCost {
+            gas_price: fee.gas_price,
+            gas_limit: tx.gas_limit.max(fee.gas_limit),
+            l1_fee: 0.0,
+        })
+    }
+}
+
+// The response has no base fee, the one of the latest block is used for the expected cost.
+fn decode_fee(response: &Response, base_fee: f64) -> Result<ZkSyncFee> {
+    let max_fee_per_gas = json_rpc::quantity_to_f64(&response.max_fee_per_gas)?;
+    let max_priority_fee_per_gas = json_rpc::quantity_to_f64(&response.max_priority_fee_per_gas)?;
+    Ok(ZkSyncFee {
+        gas_price: EstimatedGasPrice {
+            legacy: max_fee_per_gas,
+            eip1559: Some(GasPrice1559 {
+                base_fee_per_gas: GasPriceWei(base_fee.min(max_fee_per_gas)),
+                max_fee_per_gas: GasPriceWei(max_fee_per_gas),
+                max_priority_fee_per_gas: GasPriceWei(
+                    max_priority_fee_per_gas.min(max_fee_per_gas),
+                ),
+            }),
+        }
+        .validate()?,
+        gas_limit: json_rpc::quantity_to_f64(&response.gas_limit)?,
+        gas_per_pubdata_limit: json_rpc::quantity_to_f64(&response.gas_per_pubdata_limit)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json_rpc::tests::FakeNode;
+    use super::super::tests::{FutureWaitExt as _, TestTransport};
+    use super::*;
+
+    fn node() -> FakeNode {
+        FakeNode::default()
+            .with_result(
+                "eth_getBlockByNumber",
+                json!({ "baseFeePerGas": "0x2b275d0" }),
+            )
+            .with_result(
+                "zks_estimateFee",
+                json!({
+                    "gas_limit": "0x2dc6c0",
+                    "gas_per_pubdata_limit": "0xc350",
+                    "max_fee_per_gas": "0x40cb7a0",
+                    "max_priority_fee_per_gas": "0x0",
+                }),
+            )
+    }
+
+    #[test]
+    fn estimates_through_node() {
+        let estimator = ZkSyncGasEstimator::new(node(), String::new());
+        let fee = estimator
+            .estimate_fee([0; 20], [0; 20], &[])
+            .wait()
+            .unwrap();
+        assert_eq!(fee.gas_limit, 3_000_000.0);
+        assert_eq!(fee.gas_per_pubdata_limit, 50_000.0);
+        let eip1559 = fee.gas_price.eip1559.unwrap();
+        assert_eq!(eip1559.base_fee_per_gas.0, 45_250_000.0);
+        assert_eq!(eip1559.max_fee_per_gas.0, 67_942_304.0);
+        assert_eq!(eip1559.max_priority_fee_per_gas.0, 0.0);
+        assert_eq!(fee.total_fee(), 3_000_000.0 * 45_250_000.0);
+
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                gas_limit: 100_000.0,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.gas_limit, 3_000_000.0);
+        let cost = estimator
+            .estimate_for_tx(&TxSpec {
+                gas_limit: 5_000_000.0,
+                ..Default::default()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(cost.gas_limit, 5_000_000.0);
+    }
+
+    #[test]
+    fn base_fee_is_at_most_max_fee() {
+        let node = node().with_result(
+            "eth_getBlockByNumber",
+            json!({ "baseFeePerGas": "0xffffffff" }),
+        );
+        let estimator = ZkSyncGasEstimator::new(node, String::new());
+        let price = estimator.estimate().wait().unwrap();
+        let eip1559 = price.eip1559.unwrap();
+        assert_eq!(eip1559.base_fee_per_gas, eip1559.max_fee_per_gas);
+    }
+
+    // ZKSYNC_NODE_URL=... cargo test zksync -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn real_request() {
+        let estimator = ZkSyncGasEstimator::new(
+            TestTransport::default(),
+            std::env::var("ZKSYNC_NODE_URL").unwrap(),
+        );
+        println!("{:?}", estimator.estimate().await);
+        println!(
+            "{:?}",
+            estimator
+                .estimate_fee([0x11; 20], [0x22; 20], &[0xff; 1000])
+                .await
+        );
+    }
+}